@@ -0,0 +1,33 @@
+//! OGP画像用のフォント (assets/fonts/ogp.ttf) をバイナリに埋め込むための準備
+//!
+//! フォントは `OUT_DIR/ogp-font.ttf` にコピーし、`src/ogp.rs` が `include_bytes!` で埋め込む。
+//! フォントがない場合、リリースビルドはエラーにする (本番でOGP画像が静かに無効になるのを防ぐ)。
+//! 開発時のビルドは警告を出して空のフォントを埋め込む (OGP画像は `/ogp.png` へのリダイレクトになる)。
+
+use std::path::Path;
+
+const FONT_PATH: &str = "assets/fonts/ogp.ttf";
+
+fn main() {
+  println!("cargo:rerun-if-changed={}", FONT_PATH);
+  println!("cargo:rustc-check-cfg=cfg(ogp_font_bundled)");
+
+  let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("ogp-font.ttf");
+  if Path::new(FONT_PATH).is_file() {
+    std::fs::copy(FONT_PATH, &out).unwrap_or_else(|e| panic!("failed to copy {}: {}", FONT_PATH, e));
+    println!("cargo:rustc-cfg=ogp_font_bundled");
+    return;
+  }
+
+  if std::env::var("PROFILE").as_deref() == Ok("release") {
+    panic!(
+      "{} is missing: vendor the OGP font with scripts/vendor-ogp-font.sh (see assets/fonts/README.md)",
+      FONT_PATH
+    );
+  }
+  println!(
+    "cargo:warning={} is missing; OGP images are disabled in this build (see assets/fonts/README.md)",
+    FONT_PATH
+  );
+  std::fs::write(&out, []).unwrap();
+}
@@ -0,0 +1,39 @@
+#!/bin/sh
+# OGP画像用のフォント (Yuji Syuku, SIL Open Font License 1.1) とライセンスを assets/fonts/ に取り込む (メンテナ用)
+# フォントはリポジトリに含めてバイナリに埋め込むため、ビルドやDockerイメージの作成時には実行しない
+#
+# 使い方: ./scripts/vendor-ogp-font.sh <google/fonts のコミットID>
+# 取得元をコミットIDで固定し、ogp.ttf.sha256 があればその値と照合する。なければ作成する
+# 更新する場合は ogp.ttf.sha256 を削除して実行し、フォント・ライセンス・チェックサムをまとめてコミットする
+set -eu
+
+COMMIT="${1:?usage: $0 <google/fonts commit id>}"
+case "$COMMIT" in
+  *[!0-9a-f]* | "")
+    echo "commit id must be a full hex sha: $COMMIT" >&2
+    exit 1
+    ;;
+esac
+if [ "${#COMMIT}" -ne 40 ]; then
+  echo "commit id must be a full 40-character sha: $COMMIT" >&2
+  exit 1
+fi
+
+BASE_URL="https://raw.githubusercontent.com/google/fonts/$COMMIT/ofl/yujisyuku"
+DIR="$(cd "$(dirname "$0")/../assets/fonts" && pwd)"
+TMP="$(mktemp -d)"
+trap 'rm -rf "$TMP"' EXIT
+
+curl -fsSL -o "$TMP/OFL.txt" "$BASE_URL/OFL.txt"
+curl -fsSL -o "$TMP/ogp.ttf" "$BASE_URL/YujiSyuku-Regular.ttf"
+
+if [ -f "$DIR/ogp.ttf.sha256" ]; then
+  (cd "$TMP" && sha256sum -c "$DIR/ogp.ttf.sha256")
+else
+  (cd "$TMP" && sha256sum ogp.ttf OFL.txt) > "$DIR/ogp.ttf.sha256"
+fi
+
+mv "$TMP/OFL.txt" "$DIR/OFL.txt"
+mv "$TMP/ogp.ttf" "$DIR/ogp.ttf"
+echo "$COMMIT" > "$DIR/ogp.ttf.source"
+echo "Vendored Yuji Syuku ($COMMIT) to $DIR/ogp.ttf"
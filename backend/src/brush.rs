@@ -0,0 +1,246 @@
+//! 手書きの筆跡をSVG・PNGに描くモジュール
+//!
+//! 筆跡の各区間を、両端の筆圧に応じた太さの線 (端は丸める) として描く。
+//! SVGは1画ずつ書いた順・速さで再生するアニメーション (SMIL) 付きで、
+//! アニメーションに対応していない表示環境では書き終わった状態で表示される。
+
+use std::fmt::Write;
+
+use crate::models::strokes::{StrokePoint, Strokes};
+use crate::ogp::Canvas;
+use crate::svg::hex;
+use crate::tategaki::{INK, PAPER};
+
+/// 筆圧1.0のときの線の太さ (描画領域の短辺に対する比)
+const MAX_WIDTH_RATIO: f32 = 0.045;
+/// 筆圧0.0のときの線の太さ (筆圧1.0のときに対する比)
+const MIN_WIDTH_RATIO: f32 = 0.3;
+/// 再生を始めるまでの時間 (ミリ秒)
+const REPLAY_START_MS: u32 = 300;
+/// 再生時の画と画の間の最大の時間 (ミリ秒, 長く手を止めていても待たせない)
+const REPLAY_MAX_PAUSE_MS: u32 = 800;
+
+/// 再生時の1画の開始・終了時刻 (ミリ秒)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplaySpan {
+  pub start: u32,
+  pub end: u32,
+}
+
+/// 各画を再生する時刻を決める
+///
+/// 画を書く速さはそのままに、画と画の間は最大 `REPLAY_MAX_PAUSE_MS` に詰める。
+/// 点が1つだけの画も表示されるよう、長さは最低1ミリ秒とする。
+pub fn replay_timeline(strokes: &Strokes) -> Vec<ReplaySpan> {
+  let mut spans = Vec::with_capacity(strokes.strokes.len());
+  let mut cursor = REPLAY_START_MS;
+  let mut previous_end = None;
+  for stroke in &strokes.strokes {
+    let (Some(first), Some(last)) = (stroke.first(), stroke.last()) else {
+      continue;
+    };
+    let pause = previous_end.map_or(0, |end| first.t().saturating_sub(end).min(REPLAY_MAX_PAUSE_MS));
+    let start = cursor + pause;
+    let end = start + (last.t() - first.t()).max(1);
+    spans.push(ReplaySpan { start, end });
+    cursor = end;
+    previous_end = Some(last.t());
+  }
+  spans
+}
+
+/// 筆圧1.0のときの線の太さ
+fn max_width(strokes: &Strokes) -> f32 {
+  strokes.width.min(strokes.height) as f32 * MAX_WIDTH_RATIO
+}
+
+/// 点での線の太さ
+fn width_at(point: &StrokePoint, max_width: f32) -> f32 {
+  max_width * (MIN_WIDTH_RATIO + (1.0 - MIN_WIDTH_RATIO) * point.pressure().clamp(0.0, 1.0))
+}
+
+/// 画を区間 (始点, 終点) に分ける (点が1つの画は長さ0の区間になる)
+fn segments(stroke: &[StrokePoint]) -> impl Iterator<Item = (&StrokePoint, &StrokePoint)> {
+  let single = (stroke.len() == 1).then(|| (&stroke[0], &stroke[0]));
+  stroke.windows(2).map(|pair| (&pair[0], &pair[1])).chain(single)
+}
+
+/// 再生アニメーション付きのSVGを生成する
+pub fn render_svg(strokes: &Strokes) -> String {
+  let max_width = max_width(strokes);
+  let timeline = replay_timeline(strokes);
+  let mut svg = String::new();
+
+  // String への書き込みは失敗しない
+  let _ = write!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" role="img" aria-label="手書きの書き初め">"#,
+    w = strokes.width,
+    h = strokes.height,
+  );
+  let _ = write!(svg, r#"<rect width="100%" height="100%" fill="{}"/>"#, hex(PAPER));
+
+  // 1画ごとのマスク: 画の中心線を始点から順に伸ばして、書いた部分だけを見せる
+  svg.push_str("<defs>");
+  for (i, (stroke, span)) in strokes.strokes.iter().zip(&timeline).enumerate() {
+    let mut path = String::new();
+    for (j, point) in stroke.iter().enumerate() {
+      let _ = write!(path, "{}{:.1} {:.1} ", if j == 0 { 'M' } else { 'L' }, point.x(), point.y());
+    }
+    if stroke.len() == 1 {
+      // 長さ0のパスは破線の位置を決められないため、わずかに伸ばす
+      path.push_str("l0.1 0");
+    }
+    let _ = write!(
+      svg,
+      concat!(
+        r#"<mask id="s{i}" maskUnits="userSpaceOnUse">"#,
+        r##"<path d="{d}" fill="none" stroke="#fff" stroke-width="{sw:.1}" stroke-linecap="round" stroke-linejoin="round" pathLength="1" stroke-dasharray="1 1">"##,
+        r#"<animate attributeName="stroke-dashoffset" values="1;1;0" keyTimes="0;{k:.4};1" dur="{dur}ms" fill="freeze"/>"#,
+        r#"</path></mask>"#,
+      ),
+      i = i,
+      d = path.trim_end(),
+      sw = max_width * 2.0,
+      k = span.start as f32 / span.end as f32,
+      dur = span.end,
+    );
+  }
+  svg.push_str("</defs>");
+
+  let _ = write!(svg, r#"<g stroke="{}" stroke-linecap="round">"#, hex(INK));
+  for (i, stroke) in strokes.strokes.iter().enumerate() {
+    let _ = write!(svg, r#"<g mask="url(#s{})">"#, i);
+    for (a, b) in segments(stroke) {
+      let _ = write!(
+        svg,
+        r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke-width="{:.1}"/>"#,
+        a.x(),
+        a.y(),
+        b.x(),
+        b.y(),
+        (width_at(a, max_width) + width_at(b, max_width)) / 2.0,
+      );
+    }
+    svg.push_str("</g>");
+  }
+  svg.push_str("</g>");
+  svg.push_str("</svg>");
+  svg
+}
+
+/// 書き終わった状態のPNG画像を生成する (大きさは描画領域と同じ)
+pub fn render_png(strokes: &Strokes) -> Result<Vec<u8>, png::EncodingError> {
+  let (width, height) = (strokes.width, strokes.height);
+  let max_width = max_width(strokes);
+
+  // 区間ごとに塗ると重なった縁が濃くなるため、画素ごとの被覆率の最大値をとってから1回だけ重ねる
+  let mut coverage = vec![0.0f32; (width * height) as usize];
+  for stroke in &strokes.strokes {
+    for (a, b) in segments(stroke) {
+      let (r0, r1) = (width_at(a, max_width) / 2.0, width_at(b, max_width) / 2.0);
+      let reach = r0.max(r1) + 1.0;
+      let x_range = pixel_range(a.x().min(b.x()) - reach, a.x().max(b.x()) + reach, width);
+      let y_range = pixel_range(a.y().min(b.y()) - reach, a.y().max(b.y()) + reach, height);
+      let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+      let length_sq = dx * dx + dy * dy;
+      for py in y_range {
+        for px in x_range.clone() {
+          let (cx, cy) = (px as f32 + 0.5, py as f32 + 0.5);
+          // 画素の中心から区間への最短距離と、その位置での線の太さ
+          let u = if length_sq > 0.0 {
+            (((cx - a.x()) * dx + (cy - a.y()) * dy) / length_sq).clamp(0.0, 1.0)
+          } else {
+            0.0
+          };
+          let distance = (cx - (a.x() + dx * u)).hypot(cy - (a.y() + dy * u));
+          let radius = r0 + (r1 - r0) * u;
+          let value = (radius - distance + 0.5).clamp(0.0, 1.0);
+          let cell = &mut coverage[(py * width + px) as usize];
+          *cell = cell.max(value);
+        }
+      }
+    }
+  }
+
+  let mut canvas = Canvas::new(width, height, PAPER);
+  for (i, &value) in coverage.iter().enumerate() {
+    if value > 0.0 {
+      let i = i as u32;
+      canvas.blend((i % width) as f32, (i / width) as f32, INK, value);
+    }
+  }
+  canvas.encode_png()
+}
+
+/// 座標の範囲を画素の範囲 (0..size) にする
+fn pixel_range(min: f32, max: f32, size: u32) -> std::ops::Range<u32> {
+  let start = min.floor().clamp(0.0, size as f32) as u32;
+  let end = (max.ceil() + 1.0).clamp(0.0, size as f32) as u32;
+  start..end
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::models::strokes::STROKES_FORMAT_VERSION;
+
+  fn sample() -> Strokes {
+    Strokes {
+      version: STROKES_FORMAT_VERSION,
+      width: 100,
+      height: 80,
+      strokes: vec![
+        vec![StrokePoint(10.0, 10.0, 0.2, 100), StrokePoint(90.0, 10.0, 0.8, 400)],
+        // 長く手を止めてから書いた点
+        vec![StrokePoint(50.5, 40.5, 0.5, 5000)],
+      ],
+    }
+  }
+
+  /// 画を書く速さは保ち、画と画の間は詰めること
+  #[test]
+  fn test_replay_timeline() {
+    let spans = replay_timeline(&sample());
+    assert_eq!(
+      spans,
+      vec![
+        ReplaySpan { start: 300, end: 600 },
+        ReplaySpan {
+          start: 600 + REPLAY_MAX_PAUSE_MS,
+          end: 601 + REPLAY_MAX_PAUSE_MS,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_render_svg() {
+    let svg = render_svg(&sample());
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="80""#));
+    assert!(svg.ends_with("</svg>"));
+    assert_eq!(svg.matches("<mask ").count(), 2);
+    assert!(svg.contains(r#"keyTimes="0;0.5000;1" dur="600ms""#));
+    // 点が1つの画も描く
+    assert!(svg.contains(r#"<line x1="50.5" y1="40.5" x2="50.5" y2="40.5""#));
+  }
+
+  /// 描画領域と同じ大きさのPNGになり、線の上は墨色・線の外は紙の色になること
+  #[test]
+  fn test_render_png() {
+    let strokes = sample();
+    let png = render_png(&strokes).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 100);
+    assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 80);
+
+    let decoder = png::Decoder::new(std::io::Cursor::new(png));
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+    reader.next_frame(&mut pixels).unwrap();
+    let at = |x: usize, y: usize| &pixels[(y * 100 + x) * 3..(y * 100 + x) * 3 + 3];
+    assert_eq!(at(50, 10), INK);
+    assert_eq!(at(50, 40), INK);
+    assert_eq!(at(50, 70), PAPER);
+  }
+}
@@ -0,0 +1,292 @@
+//! アプリケーション設定を定義するモジュール
+//! 環境変数から読み込み、未設定の場合はデフォルト値を使用する
+
+use std::str::FromStr;
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::services::sessions::LEGACY_COOKIE_SUNSET;
+
+/// 収集したリクエスト情報を匿名化するまでの日数のデフォルト値
+const DEFAULT_RETENTION_DAYS: u32 = 90;
+/// 匿名化ジョブの実行間隔 (秒) のデフォルト値
+const DEFAULT_RETENTION_INTERVAL_SECS: u64 = 60 * 60;
+/// サイトの公開URLのデフォルト値
+const DEFAULT_PUBLIC_BASE_URL: &str = "http://localhost";
+/// アップロードされた写真を保存するデフォルトのディレクトリ
+const DEFAULT_PHOTO_DIR: &str = "data/photos";
+/// CORSで許可するメソッドのデフォルト値 (APIが使うメソッド)
+const DEFAULT_CORS_ALLOWED_METHODS: [&str; 3] = ["GET", "POST", "DELETE"];
+/// CORSのプリフライトの結果をブラウザがキャッシュする秒数のデフォルト値
+const DEFAULT_CORS_MAX_AGE_SECS: u64 = 600;
+/// リクエストボディ (JSONなど) のサイズの上限のデフォルト値 (書き初めの投稿は写真のため別の上限)
+const DEFAULT_BODY_MAX_BYTES: usize = 64 * 1024;
+/// リクエストの処理のタイムアウト (秒) のデフォルト値
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// リクエストボディの受信のタイムアウト (秒) のデフォルト値
+const DEFAULT_REQUEST_BODY_TIMEOUT_SECS: u64 = 10;
+/// リクエストヘッダーの合計サイズの上限のデフォルト値
+const DEFAULT_MAX_HEADER_BYTES: usize = 16 * 1024;
+/// トレースのサービス名のデフォルト値
+const DEFAULT_OTEL_SERVICE_NAME: &str = "puranemone-backend";
+/// トレースを記録するリクエストの割合のデフォルト値
+const DEFAULT_TRACE_SAMPLE_RATIO: f64 = 0.1;
+
+/// CORSの設定の方針
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorsProfile {
+  /// `CORS_ALLOWED_ORIGINS` に列挙したオリジンのみ許可する (本番用)
+  #[default]
+  Strict,
+  /// 全てのオリジン・メソッド・ヘッダーを、Cookie付きで許可する (開発用。本番では使わない)
+  Dev,
+}
+
+impl FromStr for CorsProfile {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "strict" => Ok(Self::Strict),
+      "dev" => Ok(Self::Dev),
+      _ => Err(()),
+    }
+  }
+}
+
+/// ログの出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+  /// 人が読むためのテキスト (開発用)
+  #[default]
+  Text,
+  /// 1行1オブジェクトのJSON (ログの収集基盤用)
+  Json,
+}
+
+impl FromStr for LogFormat {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_ascii_lowercase().as_str() {
+      "text" => Ok(Self::Text),
+      "json" => Ok(Self::Json),
+      _ => Err(()),
+    }
+  }
+}
+
+/// アプリケーション設定
+#[derive(Debug, Clone)]
+pub struct Config {
+  /// PostgreSQLのLISTEN/NOTIFYで他のレプリカと変更イベントを共有するか
+  /// 環境変数: `EVENTS_PG_NOTIFY` (デフォルト: false)
+  pub events_pg_notify: bool,
+  /// 収集したリクエスト情報 (IPアドレス等) を匿名化するまでの日数 (0なら匿名化ジョブを動かさない)
+  /// 環境変数: `RETENTION_DAYS` (デフォルト: 90, `Config::default()` では0)
+  pub retention_days: u32,
+  /// 匿名化ジョブの実行間隔 (秒)
+  /// 環境変数: `RETENTION_INTERVAL_SECS` (デフォルト: 3600)
+  pub retention_interval_secs: u64,
+  /// サイトの公開URL (フィードのリンク等の絶対URLに使う, 末尾の `/` は除く)
+  /// 環境変数: `PUBLIC_BASE_URL` (デフォルト: `http://localhost`)
+  pub public_base_url: String,
+  /// 生成したOGP画像を保存するディレクトリ (未設定ならメモリにのみキャッシュする)
+  /// 環境変数: `OGP_CACHE_DIR`
+  pub ogp_cache_dir: Option<String>,
+  /// アップロードされた写真を保存するディレクトリ (Noneなら写真を受け付けない)
+  /// 環境変数: `PHOTO_DIR` (デフォルト: `data/photos`, `Config::default()` ではNone)
+  pub photo_dir: Option<String>,
+  /// パスキー (WebAuthn) のRPのID (未設定なら公開URLのホスト名。親ドメインも指定できる)
+  /// 環境変数: `WEBAUTHN_RP_ID`
+  pub webauthn_rp_id: Option<String>,
+  /// パスキーが登録されていない書き初めのログインに返す、偽の資格情報IDを生成する鍵 (秘密にする)
+  /// 未設定なら起動ごとに生成する (複数のレプリカや再起動の前後で偽の資格情報IDが変わる)
+  /// 環境変数: `PASSKEY_DECOY_KEY`
+  pub passkey_decoy_key: Option<String>,
+  /// 状態を変更するリクエストを受け付ける、公開URL以外のオリジン (`https://example.com` の形式)
+  /// 環境変数: `CSRF_TRUSTED_ORIGINS` (カンマ区切り)
+  pub csrf_trusted_origins: Vec<String>,
+  /// CORSの設定の方針
+  /// 環境変数: `CORS_PROFILE` (`strict` または `dev`, デフォルト: `strict`)
+  pub cors_profile: CorsProfile,
+  /// 別のオリジンで配信するフロントエンドのオリジン (空ならCORSのヘッダーを付けない, CSRF対策でも許可する)
+  /// 環境変数: `CORS_ALLOWED_ORIGINS` (カンマ区切り)
+  pub cors_allowed_origins: Vec<String>,
+  /// CORSでCookieの送信を許可するか
+  /// 環境変数: `CORS_ALLOW_CREDENTIALS` (デフォルト: true)
+  pub cors_allow_credentials: bool,
+  /// CORSで許可するメソッド
+  /// 環境変数: `CORS_ALLOWED_METHODS` (カンマ区切り, デフォルト: `GET,POST,DELETE`)
+  pub cors_allowed_methods: Vec<String>,
+  /// プリフライトの結果をブラウザがキャッシュする秒数
+  /// 環境変数: `CORS_MAX_AGE_SECS` (デフォルト: 600)
+  pub cors_max_age_secs: u64,
+  /// APIのレスポンスにセキュリティ関連のヘッダー (CSP・`X-Frame-Options` 等) を付けるか
+  /// 環境変数: `SECURITY_HEADERS` (デフォルト: true)
+  pub security_headers: bool,
+  /// リクエストボディのサイズの上限 (書き初めの投稿を除く)
+  /// 環境変数: `BODY_MAX_BYTES` (デフォルト: 65536)
+  pub body_max_bytes: usize,
+  /// レスポンスを返し始めるまでのタイムアウト (秒, 0なら無制限)。超えると503
+  /// 環境変数: `REQUEST_TIMEOUT_SECS` (デフォルト: 30)
+  pub request_timeout_secs: u64,
+  /// リクエストボディを受信し終えるまでのタイムアウト (秒, 0なら無制限)。超えると408
+  /// 環境変数: `REQUEST_BODY_TIMEOUT_SECS` (デフォルト: 10)
+  pub request_body_timeout_secs: u64,
+  /// リクエストヘッダーの合計サイズ (名前と値のバイト数) の上限。超えると431
+  /// 環境変数: `MAX_HEADER_BYTES` (デフォルト: 16384)
+  pub max_header_bytes: usize,
+  /// ログの出力形式
+  /// 環境変数: `LOG_FORMAT` (`text` または `json`, デフォルト: `text`)
+  pub log_format: LogFormat,
+  /// トレースを送るOTLP (HTTP) のコレクターのURL (未設定ならエクスポートしない, 例: `http://localhost:4318`)
+  /// 環境変数: `OTEL_EXPORTER_OTLP_ENDPOINT`
+  pub otlp_endpoint: Option<String>,
+  /// トレースのサービス名
+  /// 環境変数: `OTEL_SERVICE_NAME` (デフォルト: `puranemone-backend`)
+  pub otel_service_name: String,
+  /// トレースを記録するリクエストの割合 (0.0〜1.0, 上流の `traceparent` で記録すると決まったものは常に記録する)
+  /// 環境変数: `TRACE_SAMPLE_RATIO` (デフォルト: 0.1)
+  pub trace_sample_ratio: f64,
+  /// 以前のユーザーIDのCookie (`calli_user_id`) をセッションへ移行する期限 (以降はCookieを無視する)
+  /// 環境変数: `LEGACY_COOKIE_SUNSET` (RFC 3339, デフォルト: `2027-04-01T00:00:00+09:00`)
+  pub legacy_cookie_sunset: OffsetDateTime,
+  /// APIのドキュメントのページ (`/api/docs`, Redoc) を配信するか (`/api/openapi.json` は常に配信する)
+  /// 環境変数: `API_DOCS_UI` (デフォルト: false)
+  pub api_docs_ui: bool,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      events_pg_notify: false,
+      retention_days: 0,
+      retention_interval_secs: DEFAULT_RETENTION_INTERVAL_SECS,
+      public_base_url: DEFAULT_PUBLIC_BASE_URL.to_string(),
+      ogp_cache_dir: None,
+      photo_dir: None,
+      webauthn_rp_id: None,
+      passkey_decoy_key: None,
+      csrf_trusted_origins: Vec::new(),
+      cors_profile: CorsProfile::Strict,
+      cors_allowed_origins: Vec::new(),
+      cors_allow_credentials: true,
+      cors_allowed_methods: DEFAULT_CORS_ALLOWED_METHODS.map(str::to_string).to_vec(),
+      cors_max_age_secs: DEFAULT_CORS_MAX_AGE_SECS,
+      security_headers: true,
+      body_max_bytes: DEFAULT_BODY_MAX_BYTES,
+      request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+      request_body_timeout_secs: DEFAULT_REQUEST_BODY_TIMEOUT_SECS,
+      max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+      log_format: LogFormat::Text,
+      otlp_endpoint: None,
+      otel_service_name: DEFAULT_OTEL_SERVICE_NAME.to_string(),
+      trace_sample_ratio: DEFAULT_TRACE_SAMPLE_RATIO,
+      legacy_cookie_sunset: LEGACY_COOKIE_SUNSET,
+      api_docs_ui: false,
+    }
+  }
+}
+
+impl Config {
+  /// 環境変数から設定を読み込む
+  pub fn from_env() -> Self {
+    let default = Self::default();
+    Self {
+      events_pg_notify: env_bool("EVENTS_PG_NOTIFY").unwrap_or(default.events_pg_notify),
+      retention_days: env_parse("RETENTION_DAYS").unwrap_or(DEFAULT_RETENTION_DAYS),
+      retention_interval_secs: env_parse::<u64>("RETENTION_INTERVAL_SECS")
+        .filter(|&secs| secs > 0)
+        .unwrap_or(DEFAULT_RETENTION_INTERVAL_SECS),
+      public_base_url: env_string("PUBLIC_BASE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or(default.public_base_url),
+      ogp_cache_dir: env_string("OGP_CACHE_DIR"),
+      photo_dir: Some(env_string("PHOTO_DIR").unwrap_or_else(|| DEFAULT_PHOTO_DIR.to_string())),
+      webauthn_rp_id: env_string("WEBAUTHN_RP_ID"),
+      passkey_decoy_key: env_string("PASSKEY_DECOY_KEY"),
+      csrf_trusted_origins: env_list("CSRF_TRUSTED_ORIGINS"),
+      cors_profile: env_parse("CORS_PROFILE").unwrap_or(default.cors_profile),
+      cors_allowed_origins: env_list("CORS_ALLOWED_ORIGINS"),
+      cors_allow_credentials: env_bool("CORS_ALLOW_CREDENTIALS").unwrap_or(default.cors_allow_credentials),
+      cors_allowed_methods: Some(env_list("CORS_ALLOWED_METHODS"))
+        .filter(|methods| !methods.is_empty())
+        .unwrap_or(default.cors_allowed_methods),
+      cors_max_age_secs: env_parse("CORS_MAX_AGE_SECS").unwrap_or(default.cors_max_age_secs),
+      security_headers: env_bool("SECURITY_HEADERS").unwrap_or(default.security_headers),
+      body_max_bytes: env_parse("BODY_MAX_BYTES").unwrap_or(default.body_max_bytes),
+      request_timeout_secs: env_parse("REQUEST_TIMEOUT_SECS").unwrap_or(default.request_timeout_secs),
+      request_body_timeout_secs: env_parse("REQUEST_BODY_TIMEOUT_SECS").unwrap_or(default.request_body_timeout_secs),
+      max_header_bytes: env_parse("MAX_HEADER_BYTES").unwrap_or(default.max_header_bytes),
+      log_format: env_parse("LOG_FORMAT").unwrap_or(default.log_format),
+      otlp_endpoint: env_string("OTEL_EXPORTER_OTLP_ENDPOINT").map(|url| url.trim_end_matches('/').to_string()),
+      otel_service_name: env_string("OTEL_SERVICE_NAME").unwrap_or(default.otel_service_name),
+      trace_sample_ratio: env_parse::<f64>("TRACE_SAMPLE_RATIO")
+        .filter(|ratio| (0.0..=1.0).contains(ratio))
+        .unwrap_or(default.trace_sample_ratio),
+      legacy_cookie_sunset: env_datetime("LEGACY_COOKIE_SUNSET").unwrap_or(default.legacy_cookie_sunset),
+      api_docs_ui: env_bool("API_DOCS_UI").unwrap_or(default.api_docs_ui),
+    }
+  }
+}
+
+/// 真偽値の環境変数を読み込む ("1"/"true"/"yes"/"on" を true とみなす)
+fn env_bool(key: &str) -> Option<bool> {
+  let value = std::env::var(key).ok()?;
+  match value.trim().to_ascii_lowercase().as_str() {
+    "1" | "true" | "yes" | "on" => Some(true),
+    "0" | "false" | "no" | "off" => Some(false),
+    _ => {
+      tracing::warn!("Ignoring invalid value for {}: {:?}", key, value);
+      None
+    }
+  }
+}
+
+/// 文字列の環境変数を読み込む (空文字列は未設定とみなす)
+fn env_string(key: &str) -> Option<String> {
+  std::env::var(key)
+    .ok()
+    .map(|value| value.trim().to_string())
+    .filter(|value| !value.is_empty())
+}
+
+/// 日時 (RFC 3339) の環境変数を読み込む
+fn env_datetime(key: &str) -> Option<OffsetDateTime> {
+  let value = env_string(key)?;
+  match OffsetDateTime::parse(&value, &Rfc3339) {
+    Ok(datetime) => Some(datetime),
+    Err(_) => {
+      tracing::warn!("Ignoring invalid value for {}: {:?}", key, value);
+      None
+    }
+  }
+}
+
+/// カンマ区切りの環境変数を読み込む (空の要素は除く)
+fn env_list(key: &str) -> Vec<String> {
+  env_string(key)
+    .map(|value| {
+      value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// 数値などの環境変数を読み込む
+fn env_parse<T: FromStr>(key: &str) -> Option<T> {
+  let value = std::env::var(key).ok()?;
+  match value.trim().parse() {
+    Ok(parsed) => Some(parsed),
+    Err(_) => {
+      tracing::warn!("Ignoring invalid value for {}: {:?}", key, value);
+      None
+    }
+  }
+}
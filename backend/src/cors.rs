@@ -0,0 +1,131 @@
+//! CORS (別のオリジンで配信するフロントエンドからのリクエスト)
+//!
+//! 本番 (`CORS_PROFILE=strict`) では `CORS_ALLOWED_ORIGINS` に列挙したオリジンのみ許可し、
+//! 列挙していなければCORSのヘッダーを一切付けない (同じオリジンで配信する構成)。
+//! 開発 (`CORS_PROFILE=dev`) ではリクエストのオリジン・メソッド・ヘッダーをそのまま許可する。
+
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+use crate::config::{Config, CorsProfile};
+use crate::csrf::CSRF_HEADER_NAME;
+
+/// 設定からCORSのレイヤーを作る (CORSを使わない設定ならNone)
+/// 解釈できないオリジン・メソッドは警告を出して無視する
+pub fn layer(config: &Config) -> Option<CorsLayer> {
+  let layer = match config.cors_profile {
+    CorsProfile::Dev => {
+      tracing::warn!("CORS_PROFILE=dev: accepting requests from any origin");
+      // `Any` は資格情報 (Cookie) と併用できないため、リクエストの値を返す
+      CorsLayer::new()
+        .allow_origin(AllowOrigin::mirror_request())
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(true)
+    }
+    CorsProfile::Strict => {
+      let origins = allowed_origins(config);
+      if origins.is_empty() {
+        return None;
+      }
+      CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(allowed_methods(config))
+        .allow_headers([
+          axum::http::header::CONTENT_TYPE,
+          HeaderName::from_static(CSRF_HEADER_NAME),
+        ])
+        .allow_credentials(config.cors_allow_credentials)
+    }
+  };
+  Some(
+    layer
+      // 別のオリジンのフロントエンドはCookieのCSRFトークンを読めないため、ヘッダーで渡す
+      .expose_headers([HeaderName::from_static(CSRF_HEADER_NAME)])
+      .max_age(Duration::from_secs(config.cors_max_age_secs)),
+  )
+}
+
+/// `CORS_ALLOWED_ORIGINS` のうち、ヘッダーの値にできるもの (`Origin` と比べるため末尾の `/` は除く)
+fn allowed_origins(config: &Config) -> Vec<HeaderValue> {
+  config
+    .cors_allowed_origins
+    .iter()
+    .filter_map(|origin| {
+      let value = HeaderValue::from_str(origin.trim_end_matches('/')).ok();
+      if value.is_none() {
+        tracing::warn!("Ignoring invalid origin for CORS: {:?}", origin);
+      }
+      value
+    })
+    .collect()
+}
+
+/// `CORS_ALLOWED_METHODS` のうち、HTTPメソッドとして解釈できるもの
+fn allowed_methods(config: &Config) -> Vec<Method> {
+  config
+    .cors_allowed_methods
+    .iter()
+    .filter_map(|method| {
+      let parsed = Method::from_bytes(method.to_ascii_uppercase().as_bytes()).ok();
+      if parsed.is_none() {
+        tracing::warn!("Ignoring invalid method for CORS: {:?}", method);
+      }
+      parsed
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_disabled_without_origins() {
+    assert!(layer(&Config::default()).is_none());
+    assert!(layer(&Config {
+      cors_profile: CorsProfile::Dev,
+      ..Config::default()
+    })
+    .is_some());
+  }
+
+  #[test]
+  fn test_allowed_origins() {
+    let config = Config {
+      cors_allowed_origins: vec![
+        "https://app.kakizome.example/".to_string(),
+        "http://localhost:5173".to_string(),
+        "bad\norigin".to_string(),
+      ],
+      ..Config::default()
+    };
+    assert_eq!(
+      allowed_origins(&config),
+      vec![
+        HeaderValue::from_static("https://app.kakizome.example"),
+        HeaderValue::from_static("http://localhost:5173"),
+      ]
+    );
+    assert!(layer(&config).is_some());
+  }
+
+  #[test]
+  fn test_allowed_methods() {
+    let config = Config {
+      cors_allowed_methods: vec![
+        "get".to_string(),
+        "DELETE".to_string(),
+        "NOT A METHOD".to_string(),
+      ],
+      ..Config::default()
+    };
+    assert_eq!(allowed_methods(&config), vec![Method::GET, Method::DELETE]);
+    assert_eq!(
+      allowed_methods(&Config::default()),
+      vec![Method::GET, Method::POST, Method::DELETE]
+    );
+  }
+}
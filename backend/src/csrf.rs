@@ -0,0 +1,334 @@
+//! CSRF (クロスサイトリクエストフォージェリ) 対策
+//!
+//! 状態を変更するリクエスト (GET・HEAD・OPTIONS以外) に対して、次の2つを検証する。
+//! 1. `Origin` / `Sec-Fetch-Site` ヘッダー: 別のオリジンからのリクエストは、許可したオリジンからのもの以外を拒否する
+//! 2. CSRFトークン: セッションのCookieを送ってくるリクエストは、`X-CSRF-Token` ヘッダーにトークンを付ける
+//!
+//! WebSocketのハンドシェイク (GET) は同一オリジンポリシーの対象外でCookieが送られるため、
+//! 許可したオリジンの `Origin` ヘッダーがなければ拒否する (クロスサイトWebSocketハイジャック対策)。
+//!
+//! トークンはセッショントークンから導出するため、DBには保存しない。
+//! JavaScriptから読めるよう、HttpOnlyでないCookie (`calli_csrf`) で配布する。
+//! 検証はCookieの値ではなくセッションから導出した値と比べるため、Cookieを外から書き換えられても突破されない。
+//! 別のオリジンで配信するフロントエンドはCookieを読めないため、レスポンスの `X-CSRF-Token` ヘッダーでも渡す。
+
+use std::sync::Arc;
+
+use axum::{
+  extract::{Request, State},
+  http::{header, HeaderMap, HeaderValue, Method},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tower_cookies::{Cookie, Cookies};
+use webauthn_rs::prelude::Url;
+
+use crate::config::{Config, CorsProfile};
+use crate::error::AppError;
+use crate::extractors::SESSION_COOKIE_NAME;
+use crate::services::sessions::SESSION_TTL;
+
+/// CSRFトークンのCookie名 (JavaScriptから読める)
+pub const CSRF_COOKIE_NAME: &str = "calli_csrf";
+/// CSRFトークンを送るヘッダー名
+pub const CSRF_HEADER_NAME: &str = "x-csrf-token";
+
+/// セッショントークンからCSRFトークンを導出する (SHA-256の16進数表記)
+pub fn derive_token(session_token: &str) -> String {
+  Sha256::digest(format!("csrf:{}", session_token).as_bytes())
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect()
+}
+
+/// CSRFトークンのCookieを設定する (セッションのCookieと同時に発行する)
+pub fn set_csrf_cookie(cookies: &Cookies, session_token: &str) {
+  let mut cookie = Cookie::new(CSRF_COOKIE_NAME, derive_token(session_token));
+  cookie.set_secure(true);
+  cookie.set_http_only(false); // ヘッダーに付けるため、JavaScriptから読めるようにする
+  cookie.set_path("/");
+  cookie.set_same_site(tower_cookies::cookie::SameSite::Lax);
+  cookie.set_max_age(SESSION_TTL);
+  cookies.add(cookie);
+}
+
+/// CSRFトークンのCookieを削除する
+pub fn remove_csrf_cookie(cookies: &Cookies) {
+  cookies.remove(Cookie::build(CSRF_COOKIE_NAME).path("/").into());
+}
+
+/// 状態を変更するリクエストを受け付けるオリジン
+#[derive(Clone, Debug)]
+pub struct CsrfPolicy {
+  /// `https://example.com` の形式 (末尾の `/` なし)
+  allowed_origins: Arc<Vec<String>>,
+  /// 全てのオリジンを許可するか (CORSの開発用の設定)
+  allow_any_origin: bool,
+}
+
+impl CsrfPolicy {
+  /// サイトの公開URLのオリジン・`CSRF_TRUSTED_ORIGINS`・`CORS_ALLOWED_ORIGINS` を許可する
+  /// (`CORS_PROFILE=dev` なら全てのオリジンを許可する。トークンは検証する)
+  /// 解釈できないものは警告を出して無視する
+  pub fn from_config(config: &Config) -> Self {
+    let allowed_origins = std::iter::once(&config.public_base_url)
+      .chain(&config.csrf_trusted_origins)
+      .chain(&config.cors_allowed_origins)
+      .filter_map(|url| {
+        let origin = normalize_origin(url);
+        if origin.is_none() {
+          tracing::warn!("Ignoring invalid origin for CSRF checks: {:?}", url);
+        }
+        origin
+      })
+      .collect();
+    Self {
+      allowed_origins: Arc::new(allowed_origins),
+      allow_any_origin: config.cors_profile == CorsProfile::Dev,
+    }
+  }
+
+  /// 許可したオリジンか
+  pub fn is_allowed_origin(&self, origin: &str) -> bool {
+    normalize_origin(origin).is_some_and(|origin| self.allow_any_origin || self.allowed_origins.contains(&origin))
+  }
+
+  /// リクエスト元のオリジンを検証する
+  ///
+  /// - `Sec-Fetch-Site: same-origin` はブラウザが保証するため、そのまま受け付ける
+  /// - `Origin` があれば、許可したオリジンでなければ拒否する (`null` も拒否)
+  /// - `Origin` がない場合、`Sec-Fetch-Site` が別のオリジンを示していれば拒否する
+  ///   (どちらもない場合はブラウザ以外のクライアントとみなす)
+  pub fn check_origin(&self, headers: &HeaderMap) -> Result<(), AppError> {
+    let sec_fetch_site = header_str(headers, "sec-fetch-site");
+    if sec_fetch_site == Some("same-origin") {
+      return Ok(());
+    }
+    match header_str(headers, header::ORIGIN.as_str()) {
+      Some(origin) if self.is_allowed_origin(origin) => Ok(()),
+      Some(origin) => Err(AppError::Forbidden(format!("Origin '{}' is not allowed", origin))),
+      None if matches!(sec_fetch_site, Some("cross-site") | Some("same-site")) => {
+        Err(AppError::Forbidden("Cross-site request is not allowed".to_string()))
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// WebSocketのハンドシェイクのオリジンを検証する
+  /// ブラウザは必ず `Origin` を送るため、ない場合も拒否する
+  pub fn check_websocket_origin(&self, headers: &HeaderMap) -> Result<(), AppError> {
+    match header_str(headers, header::ORIGIN.as_str()) {
+      Some(origin) if self.is_allowed_origin(origin) => Ok(()),
+      Some(origin) => Err(AppError::Forbidden(format!("Origin '{}' is not allowed", origin))),
+      None => Err(AppError::Forbidden("Missing Origin header".to_string())),
+    }
+  }
+}
+
+/// `X-CSRF-Token` ヘッダーがセッションから導出したトークンと一致するか検証する
+pub fn check_token(headers: &HeaderMap, session_token: &str) -> Result<(), AppError> {
+  let token = header_str(headers, CSRF_HEADER_NAME)
+    .ok_or_else(|| AppError::Forbidden("Missing CSRF token".to_string()))?;
+  if constant_time_eq(token.as_bytes(), derive_token(session_token).as_bytes()) {
+    Ok(())
+  } else {
+    Err(AppError::Forbidden("Invalid CSRF token".to_string()))
+  }
+}
+
+/// CSRF対策のミドルウェア (CookieManagerLayerの内側に追加する)
+///
+/// 状態を変更するリクエストはオリジンとトークンを検証する。WebSocketのハンドシェイクはオリジンを検証する
+/// (ハンドラーのエクストラクターがセッションを発行する前に拒否する)。
+/// 以前のユーザーIDのCookieだけを持つブラウザはトークンを持たないため、オリジンのみ検証する (セッションへの移行時にトークンを発行する)。
+/// レスポンスの時点でセッションのCookieに対応するトークンのCookieがなければ発行する (この機能の導入前に発行したセッション用)。
+/// トークンは `X-CSRF-Token` ヘッダーにも付ける (Cookieを読めない、別のオリジンのフロントエンド用)。
+pub async fn protect(State(policy): State<CsrfPolicy>, request: Request, next: Next) -> Response {
+  let Some(cookies) = request.extensions().get::<Cookies>().cloned() else {
+    tracing::error!("Cookies layer missing");
+    return AppError::Internal.into_response();
+  };
+
+  if is_websocket_upgrade(request.headers()) {
+    if let Err(e) = policy.check_websocket_origin(request.headers()) {
+      tracing::warn!("Rejected WebSocket {}: {}", request.uri().path(), e);
+      return e.into_response();
+    }
+  } else if !is_safe_method(request.method()) {
+    let checked = policy.check_origin(request.headers()).and_then(|()| {
+      match cookies.get(SESSION_COOKIE_NAME) {
+        Some(session) => check_token(request.headers(), session.value()),
+        None => Ok(()),
+      }
+    });
+    if let Err(e) = checked {
+      tracing::warn!("Rejected {} {}: {}", request.method(), request.uri().path(), e);
+      return e.into_response();
+    }
+  }
+
+  let mut response = next.run(request).await;
+
+  // 共有キャッシュに保存されうるレスポンスにはトークンを載せない
+  if !is_public_cacheable(&response) {
+    if let Some(session) = cookies.get(SESSION_COOKIE_NAME) {
+      let token = derive_token(session.value());
+      let issued = cookies.get(CSRF_COOKIE_NAME).is_some_and(|csrf| csrf.value() == token);
+      if !issued {
+        set_csrf_cookie(&cookies, session.value());
+      }
+      if let Ok(value) = HeaderValue::from_str(&token) {
+        response.headers_mut().insert(CSRF_HEADER_NAME, value);
+      }
+    }
+  }
+  response
+}
+
+/// 状態を変更しないメソッドか
+pub fn is_safe_method(method: &Method) -> bool {
+  matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// WebSocketのハンドシェイクか (`Upgrade: websocket`)
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+  header_str(headers, header::UPGRADE.as_str()).is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+/// `Cache-Control: public` のレスポンスか
+fn is_public_cacheable(response: &Response) -> bool {
+  header_str(response.headers(), header::CACHE_CONTROL.as_str())
+    .is_some_and(|value| value.split(',').any(|directive| directive.trim().eq_ignore_ascii_case("public")))
+}
+
+/// URLのオリジン部分 (`scheme://host[:port]`) を取り出す
+fn normalize_origin(url: &str) -> Option<String> {
+  let url = Url::parse(url.trim()).ok()?;
+  let origin = url.origin();
+  origin.is_tuple().then(|| origin.ascii_serialization())
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+  headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim)
+}
+
+/// 比較にかかる時間から一致した長さを推測されないよう、全てのバイトを比べる
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn policy() -> CsrfPolicy {
+    CsrfPolicy::from_config(&Config {
+      public_base_url: "https://kakizome.example".to_string(),
+      csrf_trusted_origins: vec!["https://admin.kakizome.example/".to_string(), "not a url".to_string()],
+      ..Config::default()
+    })
+  }
+
+  fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+      headers.insert(*name, HeaderValue::from_static(value));
+    }
+    headers
+  }
+
+  #[test]
+  fn test_derive_token() {
+    let token = derive_token("session");
+    assert_eq!(token.len(), 64);
+    assert_eq!(derive_token("session"), token);
+    assert_ne!(derive_token("other"), token);
+  }
+
+  #[test]
+  fn test_allowed_origins() {
+    let policy = policy();
+    assert!(policy.is_allowed_origin("https://kakizome.example"));
+    assert!(policy.is_allowed_origin("https://KAKIZOME.example:443"));
+    assert!(policy.is_allowed_origin("https://admin.kakizome.example"));
+    assert!(!policy.is_allowed_origin("http://kakizome.example"));
+    assert!(!policy.is_allowed_origin("https://kakizome.example:8443"));
+    assert!(!policy.is_allowed_origin("https://evil.example"));
+    assert!(!policy.is_allowed_origin("null"));
+  }
+
+  #[test]
+  fn test_check_origin() {
+    let policy = policy();
+    // 同一オリジン・許可したオリジン・ブラウザ以外のクライアント
+    assert!(policy.check_origin(&headers(&[("sec-fetch-site", "same-origin")])).is_ok());
+    assert!(policy.check_origin(&headers(&[("origin", "https://kakizome.example")])).is_ok());
+    assert!(policy
+      .check_origin(&headers(&[("origin", "https://admin.kakizome.example"), ("sec-fetch-site", "same-site")]))
+      .is_ok());
+    assert!(policy.check_origin(&HeaderMap::new()).is_ok());
+
+    // 別のオリジン
+    for rejected in [
+      headers(&[("origin", "https://evil.example")]),
+      headers(&[("origin", "https://evil.example"), ("sec-fetch-site", "cross-site")]),
+      headers(&[("origin", "null")]),
+      headers(&[("sec-fetch-site", "cross-site")]),
+      headers(&[("sec-fetch-site", "same-site")]),
+    ] {
+      assert!(matches!(policy.check_origin(&rejected), Err(AppError::Forbidden(_))));
+    }
+  }
+
+  #[test]
+  fn test_check_websocket_origin() {
+    let policy = policy();
+    assert!(policy.check_websocket_origin(&headers(&[("origin", "https://kakizome.example")])).is_ok());
+    // Sec-Fetch-Site や Origin なしでは受け付けない
+    for rejected in [
+      headers(&[("origin", "https://evil.example")]),
+      headers(&[("origin", "null")]),
+      headers(&[("sec-fetch-site", "same-origin")]),
+      HeaderMap::new(),
+    ] {
+      assert!(matches!(policy.check_websocket_origin(&rejected), Err(AppError::Forbidden(_))));
+    }
+
+    assert!(is_websocket_upgrade(&headers(&[("upgrade", "WebSocket")])));
+    assert!(!is_websocket_upgrade(&headers(&[("upgrade", "h2c")])));
+    assert!(!is_websocket_upgrade(&HeaderMap::new()));
+  }
+
+  #[test]
+  fn test_cors_origins() {
+    let policy = CsrfPolicy::from_config(&Config {
+      cors_allowed_origins: vec!["http://localhost:5173".to_string()],
+      ..Config::default()
+    });
+    assert!(policy.is_allowed_origin("http://localhost:5173"));
+    assert!(!policy.is_allowed_origin("https://evil.example"));
+
+    // 開発用の設定では全てのオリジンを許可する
+    let dev = CsrfPolicy::from_config(&Config {
+      cors_profile: CorsProfile::Dev,
+      ..Config::default()
+    });
+    assert!(dev.is_allowed_origin("https://evil.example"));
+    assert!(!dev.is_allowed_origin("null"));
+  }
+
+  #[test]
+  fn test_check_token() {
+    let token = derive_token("session");
+    let mut valid = HeaderMap::new();
+    valid.insert(CSRF_HEADER_NAME, HeaderValue::from_str(&token).unwrap());
+    assert!(check_token(&valid, "session").is_ok());
+    assert!(matches!(check_token(&valid, "other"), Err(AppError::Forbidden(_))));
+    assert!(matches!(check_token(&HeaderMap::new(), "session"), Err(AppError::Forbidden(_))));
+    assert!(matches!(
+      check_token(&headers(&[(CSRF_HEADER_NAME, "")]), "session"),
+      Err(AppError::Forbidden(_))
+    ));
+  }
+}
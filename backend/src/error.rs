@@ -1,12 +1,15 @@
-/// アプリケーション全体で使用するエラー型を定義するモジュール
+//! アプリケーション全体で使用するエラー型を定義するモジュール
 
 use axum::{
   http::StatusCode,
   response::{IntoResponse, Response},
   Json,
 };
-use serde_json::json;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::telemetry;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -15,6 +18,10 @@ pub enum AppError {
   #[error("Database error: {0}")]
   Database(#[from] sqlx::Error),
 
+  /// 許可されていないリクエスト (CSRFトークンの不一致・許可していないオリジンなど)
+  #[error("Forbidden: {0}")]
+  Forbidden(String),
+
   /// リソースが見つからない場合
   /// Repositoryは Option<T> を返すが、Service層ではこれを明示的なエラーとして扱う
   #[error("Resource not found")]
@@ -24,15 +31,105 @@ pub enum AppError {
   #[error("Validation error: {0}")]
   Validation(String),
 
+  /// リクエストボディが大きすぎる (写真のサイズ超過など)
+  #[error("Payload too large: {0}")]
+  PayloadTooLarge(String),
+
+  /// 対応していない形式 (写真の形式など)
+  #[error("Unsupported media type: {0}")]
+  UnsupportedMediaType(String),
+
+  /// 現在の状態と両立しない操作 (引き継ぎ先のブラウザに既に書き初めがあるなど)
+  #[error("Conflict: {0}")]
+  Conflict(String),
+
+  /// リクエストボディの受信が時間内に終わらない
+  #[error("Request timeout")]
+  RequestTimeout,
+
+  /// リクエストヘッダーが大きすぎる
+  #[error("Request header fields too large")]
+  RequestHeaderFieldsTooLarge,
+
   /// レート制限超過
   #[error("Too many requests")]
   TooManyRequests,
 
+  /// 処理が時間内に終わらない
+  #[error("Service unavailable")]
+  ServiceUnavailable,
+
   /// 予期しないサーバーエラー
   #[error("Internal server error")]
   Internal,
 }
 
+/// エラーの種類 (クライアントが分岐に使う、メッセージより変わりにくい値)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+  Validation,
+  Forbidden,
+  NotFound,
+  Conflict,
+  PayloadTooLarge,
+  UnsupportedMediaType,
+  RequestTimeout,
+  RequestHeaderFieldsTooLarge,
+  TooManyRequests,
+  ServiceUnavailable,
+  Internal,
+}
+
+impl ErrorCode {
+  /// 全ての値 (TypeScriptの定数の生成用)
+  pub const ALL: [ErrorCode; 11] = [
+    ErrorCode::Validation,
+    ErrorCode::Forbidden,
+    ErrorCode::NotFound,
+    ErrorCode::Conflict,
+    ErrorCode::PayloadTooLarge,
+    ErrorCode::UnsupportedMediaType,
+    ErrorCode::RequestTimeout,
+    ErrorCode::RequestHeaderFieldsTooLarge,
+    ErrorCode::TooManyRequests,
+    ErrorCode::ServiceUnavailable,
+    ErrorCode::Internal,
+  ];
+}
+
+impl AppError {
+  /// レスポンスに付けるエラーの種類
+  pub fn code(&self) -> ErrorCode {
+    match self {
+      AppError::Database(_) | AppError::Internal => ErrorCode::Internal,
+      AppError::Forbidden(_) => ErrorCode::Forbidden,
+      AppError::NotFound => ErrorCode::NotFound,
+      AppError::Validation(_) => ErrorCode::Validation,
+      AppError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+      AppError::UnsupportedMediaType(_) => ErrorCode::UnsupportedMediaType,
+      AppError::Conflict(_) => ErrorCode::Conflict,
+      AppError::RequestTimeout => ErrorCode::RequestTimeout,
+      AppError::RequestHeaderFieldsTooLarge => ErrorCode::RequestHeaderFieldsTooLarge,
+      AppError::TooManyRequests => ErrorCode::TooManyRequests,
+      AppError::ServiceUnavailable => ErrorCode::ServiceUnavailable,
+    }
+  }
+}
+
+/// エラーレスポンスのボディ
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+  /// エラーメッセージ
+  #[schema(example = "Resource Not Found")]
+  pub error: String,
+  /// エラーの種類
+  pub code: ErrorCode,
+  /// リクエストID (`X-Request-Id` と同じ値。サーバーのログと突き合わせるため)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub request_id: Option<String>,
+}
+
 // AxumのIntoResponseを実装することで、Handlerから直接 Err(AppError::...) を返せるようになる
 impl IntoResponse for AppError {
   fn into_response(self) -> Response {
@@ -42,18 +139,31 @@ impl IntoResponse for AppError {
     }
 
     // ステータスコードとエラーメッセージの決定
+    let code = self.code();
     let (status, error_message) = match self {
       AppError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string()),
+      AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
       AppError::NotFound => (StatusCode::NOT_FOUND, "Resource Not Found".to_string()),
       AppError::Validation(msg) => (StatusCode::BAD_REQUEST, msg),
+      AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
+      AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg),
+      AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+      AppError::RequestTimeout => (StatusCode::REQUEST_TIMEOUT, "Request Timeout".to_string()),
+      AppError::RequestHeaderFieldsTooLarge => (
+        StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+        "Request Header Fields Too Large".to_string(),
+      ),
       AppError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too Many Requests".to_string()),
+      AppError::ServiceUnavailable => (StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable".to_string()),
       AppError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string()),
     };
 
-    // JSONボディの作成
-    let body = Json(json!({
-			"error": error_message
-    }));
+    // JSONボディの作成 (ログと突き合わせられるよう、リクエストIDも付ける)
+    let body = Json(ErrorResponse {
+      error: error_message,
+      code,
+      request_id: telemetry::current_request_id(),
+    });
 
     (status, body).into_response()
   }
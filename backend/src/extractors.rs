@@ -7,12 +7,23 @@ use std::str::FromStr;
 
 use axum::{
   async_trait,
-  extract::FromRequestParts,
-  http::{request::Parts, StatusCode},
+  extract::{FromRequest, FromRequestParts, Multipart, Request},
+  http::{header, request::Parts, StatusCode},
+  response::{IntoResponse, Response},
+  Json,
 };
-use time::Duration;
+use bytes::{Bytes, BytesMut};
 use tower_cookies::{Cookie, Cookies};
-use uuid::Uuid; // cookieの有効期限用
+use uuid::Uuid;
+
+use crate::csrf;
+use crate::error::AppError;
+use crate::models::calligraphy::CreateCalligraphyRequest;
+use crate::photo::{PhotoError, PHOTO_MAX_BYTES};
+use crate::repositories::db_repository::CalligraphyRepositoryTrait;
+use crate::services::calligraphy::CalligraphyService;
+use crate::services::sessions::SESSION_TTL; // cookieの有効期限用
+use crate::telemetry;
 
 // ハンドラーで受け取るための型
 pub struct AuthUser {
@@ -20,49 +31,131 @@ pub struct AuthUser {
 }
 
 // 定数定義
-const COOKIE_NAME: &str = "calli_user_id";
+/// セッションのCookie名
+pub const SESSION_COOKIE_NAME: &str = "calli_session";
+/// 以前の、ユーザーIDをそのまま入れていたCookie名 (移行期限までセッションへ移行する)
+const LEGACY_COOKIE_NAME: &str = "calli_user_id";
 
 /// AuthUser用のエクストラクター実装
+///
+/// セッションの検証にDBを使うため、Stateとして書き初めサービスを要求する。
+/// セッションがない場合、状態を変更するリクエスト (GET・HEAD・OPTIONS以外) でのみセッションを発行する。
+/// 読み込みのリクエストではDBに書き込まず、このリクエスト限りの新しいユーザーとして扱う (以前のCookieの移行は除く)。
+/// 以前のCookieは、書き初めを持ちセッションがまだないユーザーのみ、移行期限まで短い有効期間のセッションに移行する。
 #[async_trait]
-impl<S> FromRequestParts<S> for AuthUser
-where
-  S: Send + Sync,
-{
-  type Rejection = (StatusCode, &'static str);
+impl<R: CalligraphyRepositoryTrait> FromRequestParts<CalligraphyService<R>> for AuthUser {
+  type Rejection = Response;
 
   /// リクエストのPartsからAuthUserを生成する
-  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-    // 1. request extensionsからCookiesを取り出す
-    // (main.rsでCookieManagerLayerを追加していないとここでpanicする)
-    let cookies = parts
-      .extensions
-      .get::<Cookies>()
-      .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Cookies layer missing"))?;
-
-    // 2. クッキーの確認
-    if let Some(cookie) = cookies.get(COOKIE_NAME) {
-      // クッキーがある場合: UUIDとしてパースを試みる
-      if let Ok(parsed_id) = Uuid::parse_str(cookie.value()) {
-        return Ok(AuthUser { id: parsed_id });
+  async fn from_request_parts(parts: &mut Parts, service: &CalligraphyService<R>) -> Result<Self, Self::Rejection> {
+    let issue = !csrf::is_safe_method(&parts.method);
+    authenticate(parts, service, issue).await
+  }
+}
+
+/// 読み込みのリクエストでもセッションを発行するAuthUser (SSE・WebSocketの購読用)
+///
+/// 購読中に届くイベントの `is_mine` は接続時のユーザーで判定するため、
+/// 接続の時点でセッションを発行し、その後の投稿と同じユーザーにする。
+pub struct SessionUser(pub AuthUser);
+
+#[async_trait]
+impl<R: CalligraphyRepositoryTrait> FromRequestParts<CalligraphyService<R>> for SessionUser {
+  type Rejection = Response;
+
+  async fn from_request_parts(parts: &mut Parts, service: &CalligraphyService<R>) -> Result<Self, Self::Rejection> {
+    authenticate(parts, service, true).await.map(SessionUser)
+  }
+}
+
+/// Cookieのセッションからユーザーを特定する
+/// セッションがなければ、`issue` の場合か以前のCookieを移行できる場合のみ新しいセッションを発行する
+async fn authenticate<R: CalligraphyRepositoryTrait>(
+  parts: &Parts,
+  service: &CalligraphyService<R>,
+  issue: bool,
+) -> Result<AuthUser, Response> {
+  // 1. request extensionsからCookiesを取り出す
+  // (main.rsでCookieManagerLayerを追加していないとここでpanicする)
+  let cookies = parts
+    .extensions
+    .get::<Cookies>()
+    .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Cookies layer missing").into_response())?;
+
+  // 2. セッションのクッキーの確認 (延長した場合はCookieの有効期限も延ばす)
+  if let Some(cookie) = cookies.get(SESSION_COOKIE_NAME) {
+    match service.resolve_session(cookie.value()).await {
+      Ok(Some((user_id, touched))) => {
+        if touched {
+          set_session_cookie(cookies, cookie.value().to_string());
+        }
+        telemetry::record_user_id(user_id);
+        return Ok(AuthUser { id: user_id });
       }
+      // 期限切れ・削除済み: セッションがないものとして扱う
+      Ok(None) => {}
+      // ログインしていないものとして続けると、別のユーザーとして操作してしまうためエラーにする
+      Err(e) => return Err(e.into_response()),
     }
+  }
 
-    // 3. クッキーがない (または不正) 場合: 新規発行
-    let new_id = Uuid::new_v4();
-    let mut cookie = Cookie::new(COOKIE_NAME, new_id.to_string());
+  // 3. 以前のユーザーIDのクッキーがあれば、そのユーザーのセッションに移行する
+  //    移行できるのは書き初めを持ちセッションがまだないユーザーのみ (一度使ったCookieは成否によらず削除する)
+  let user_agent = parts.headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+  if let Some(legacy) = cookies.get(LEGACY_COOKIE_NAME) {
+    let migrated = match Uuid::parse_str(legacy.value()) {
+      Ok(legacy_user_id) => service
+        .migrate_legacy_user(legacy_user_id, user_agent)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .map(|token| (legacy_user_id, token)),
+      Err(_) => None,
+    };
+    cookies.remove(Cookie::build(LEGACY_COOKIE_NAME).path("/").into());
+    if let Some((user_id, token)) = migrated {
+      set_session_cookie(cookies, token);
+      telemetry::record_user_id(user_id);
+      return Ok(AuthUser { id: user_id });
+    }
+  }
 
-    // クッキーのセキュリティ設定
-    cookie.set_secure(true); // HTTPS通信時のみ送信
-    cookie.set_http_only(true); // JavaScriptからアクセス不可 XSS対策
-    cookie.set_path("/");
-    cookie.set_same_site(tower_cookies::cookie::SameSite::Lax); // クロスサイトリクエスト時のCookieの送信制御 CSRF対策 Strict: 完全拒否 Lax: 一部許可 None: 制限なし
-    cookie.set_max_age(Duration::days(365)); // 1年間有効
+  // 4. 新しいユーザーIDで新規発行
+  //    読み込みだけのリクエストではセッションを発行しない
+  let user_id = Uuid::new_v4();
+  telemetry::record_user_id(user_id);
+  if !issue {
+    return Ok(AuthUser { id: user_id });
+  }
+  let token = service
+    .start_session(user_id, user_agent)
+    .await
+    .map_err(IntoResponse::into_response)?;
+  set_session_cookie(cookies, token);
 
-    // レスポンスヘッダーへの書き込み予約
-    cookies.add(cookie);
+  Ok(AuthUser { id: user_id })
+}
 
-    Ok(AuthUser { id: new_id })
-  }
+/// セッションのCookieを設定する (新規発行時・延長時・引き継ぎやパスキーでのログイン時)
+/// セッションに対応するCSRFトークンのCookieも設定する
+pub fn set_session_cookie(cookies: &Cookies, token: String) {
+  csrf::set_csrf_cookie(cookies, &token);
+  let mut cookie = Cookie::new(SESSION_COOKIE_NAME, token);
+
+  // クッキーのセキュリティ設定
+  cookie.set_secure(true); // HTTPS通信時のみ送信
+  cookie.set_http_only(true); // JavaScriptからアクセス不可 XSS対策
+  cookie.set_path("/");
+  cookie.set_same_site(tower_cookies::cookie::SameSite::Lax); // クロスサイトリクエスト時のCookieの送信制御 CSRF対策 Strict: 完全拒否 Lax: 一部許可 None: 制限なし
+  cookie.set_max_age(SESSION_TTL); // 最後に使われてから1年間有効
+
+  // レスポンスヘッダーへの書き込み予約
+  cookies.add(cookie);
+}
+
+/// セッションのCookieを削除する (ログアウト時)
+pub fn remove_session_cookie(cookies: &Cookies) {
+  cookies.remove(Cookie::build(SESSION_COOKIE_NAME).path("/").into());
+  csrf::remove_csrf_cookie(cookies);
 }
 
 /// クライアントIPアドレス抽出用エクストラクター
@@ -127,3 +220,148 @@ where
     Ok(AcceptLanguage(al))
   }
 }
+
+/// Last-Event-ID抽出用エクストラクター (SSE再接続時にブラウザが自動で付与する)
+pub struct LastEventId(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for LastEventId
+where
+  S: Send + Sync,
+{
+  type Rejection = (StatusCode, &'static str);
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    let id = parts
+      .headers
+      .get("last-event-id")
+      .and_then(|v| v.to_str().ok())
+      .map(|s| s.to_string());
+    Ok(LastEventId(id))
+  }
+}
+
+/// 条件付きGET用ヘッダー (If-None-Match / If-Modified-Since) 抽出用エクストラクター
+pub struct Preconditions {
+  pub if_none_match: Option<String>,
+  pub if_modified_since: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Preconditions
+where
+  S: Send + Sync,
+{
+  type Rejection = (StatusCode, &'static str);
+
+  async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    let header = |name: &str| {
+      parts
+        .headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+    };
+    Ok(Preconditions {
+      if_none_match: header("if-none-match"),
+      if_modified_since: header("if-modified-since"),
+    })
+  }
+}
+
+/// 書き初めの投稿で受け付けるリクエストボディの最大サイズ (写真と他の項目の分)
+pub const CALLIGRAPHY_FORM_MAX_BYTES: usize = PHOTO_MAX_BYTES + 1024 * 1024;
+
+/// 書き初めの投稿内容抽出用エクストラクター
+///
+/// JSON (`application/json`) に加え、写真を添付する場合の `multipart/form-data` を受け付ける。
+/// multipartの項目は `user_name`・`content`・`strokes` (JSON文字列, 任意)・`photo` (ファイル, 任意)。
+pub struct CalligraphyForm {
+  pub request: CreateCalligraphyRequest,
+  /// 添付した写真の内容 (未検査)
+  pub photo: Option<Bytes>,
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for CalligraphyForm
+where
+  S: Send + Sync,
+{
+  type Rejection = Response;
+
+  async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+    let is_multipart = req
+      .headers()
+      .get(header::CONTENT_TYPE)
+      .and_then(|v| v.to_str().ok())
+      .is_some_and(|v| v.trim_start().to_ascii_lowercase().starts_with("multipart/form-data"));
+    if !is_multipart {
+      let Json(request) = Json::<CreateCalligraphyRequest>::from_request(req, state)
+        .await
+        .map_err(IntoResponse::into_response)?;
+      return Ok(CalligraphyForm { request, photo: None });
+    }
+
+    let multipart = Multipart::from_request(req, state)
+      .await
+      .map_err(IntoResponse::into_response)?;
+    read_multipart(multipart).await.map_err(IntoResponse::into_response)
+  }
+}
+
+/// multipartの各項目を読み込む (同じ項目が複数ある場合・未知の項目は400)
+async fn read_multipart(mut multipart: Multipart) -> Result<CalligraphyForm, Response> {
+  let invalid = |message: String| AppError::Validation(message).into_response();
+  let mut user_name = None;
+  let mut content = None;
+  let mut strokes = None;
+  let mut photo = None;
+
+  while let Some(mut field) = multipart.next_field().await.map_err(IntoResponse::into_response)? {
+    let name = field.name().unwrap_or_default().to_string();
+    let slot_taken = match name.as_str() {
+      "user_name" => user_name.is_some(),
+      "content" => content.is_some(),
+      "strokes" => strokes.is_some(),
+      "photo" => photo.is_some(),
+      _ => return Err(invalid(format!("Unknown field '{}'", name))),
+    };
+    if slot_taken {
+      return Err(invalid(format!("Duplicate field '{}'", name)));
+    }
+
+    if name == "photo" {
+      // 写真は上限を超えた時点で読み込みをやめる
+      let mut bytes = BytesMut::new();
+      while let Some(chunk) = field.chunk().await.map_err(IntoResponse::into_response)? {
+        if bytes.len() + chunk.len() > PHOTO_MAX_BYTES {
+          return Err(AppError::from(PhotoError::TooLarge).into_response());
+        }
+        bytes.extend_from_slice(&chunk);
+      }
+      // ファイルを選ばずに送信したフォームは空の項目になる
+      photo = Some((!bytes.is_empty()).then(|| bytes.freeze()));
+      continue;
+    }
+
+    let text = field.text().await.map_err(IntoResponse::into_response)?;
+    match name.as_str() {
+      "user_name" => user_name = Some(text),
+      "content" => content = Some(text),
+      _ => {
+        let parsed = serde_json::from_str(&text).map_err(|e| invalid(format!("Invalid strokes: {}", e)))?;
+        strokes = Some(parsed);
+      }
+    }
+  }
+
+  let missing = |field: &str| invalid(format!("Missing field '{}'", field));
+  Ok(CalligraphyForm {
+    request: CreateCalligraphyRequest {
+      user_name: user_name.ok_or_else(|| missing("user_name"))?,
+      content: content.ok_or_else(|| missing("content"))?,
+      strokes,
+    },
+    photo: photo.flatten(),
+  })
+}
@@ -0,0 +1,292 @@
+//! 新着の書き初めのフィード (Atom / RSS 2.0 / JSON Feed) を生成するモジュール
+//!
+//! エントリーのIDは公開用IDのURN (`urn:uuid:...`) とし、内容を更新しても変わらない。
+//! 同じ人の書き初めは1件のエントリーとして、更新日時 (`updated_at`) とともに更新される。
+
+use std::fmt::Write;
+
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::OffsetDateTime;
+
+use crate::models::calligraphy::Calligraphy;
+use crate::models::feed::{JsonFeed, JsonFeedAuthor, JsonFeedItem, JSON_FEED_VERSION};
+
+/// フィードに含める件数 (更新日時の新しい順)
+pub const FEED_ENTRIES: usize = 50;
+
+const FEED_TITLE: &str = "書き初め";
+const FEED_DESCRIPTION: &str = "新着の書き初め";
+
+/// フィードの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+  Atom,
+  Rss,
+  Json,
+}
+
+impl FeedFormat {
+  /// URLの拡張子 (`feed.atom` 等)
+  pub fn extension(self) -> &'static str {
+    match self {
+      Self::Atom => "atom",
+      Self::Rss => "rss",
+      Self::Json => "json",
+    }
+  }
+
+  pub fn content_type(self) -> &'static str {
+    match self {
+      Self::Atom => "application/atom+xml; charset=utf-8",
+      Self::Rss => "application/rss+xml; charset=utf-8",
+      Self::Json => "application/feed+json; charset=utf-8",
+    }
+  }
+}
+
+/// フィード全体の情報
+pub struct FeedMeta<'a> {
+  /// サイトのURL (末尾の `/` なし, 例: `https://example.com`)
+  pub base_url: &'a str,
+  /// フィードの最終更新日時 (削除も含む)
+  pub updated: OffsetDateTime,
+}
+
+impl FeedMeta<'_> {
+  fn home_page_url(&self) -> String {
+    format!("{}/", self.base_url)
+  }
+
+  fn feed_url(&self, format: FeedFormat) -> String {
+    format!("{}/api/calligraphy/feed.{}", self.base_url, format.extension())
+  }
+}
+
+/// 更新日時の新しい順に `FEED_ENTRIES` 件を取り出す
+pub fn latest(entries: &[Calligraphy]) -> Vec<&Calligraphy> {
+  let mut latest: Vec<&Calligraphy> = entries.iter().collect();
+  latest.sort_by(|a, b| {
+    b.updated_at
+      .cmp(&a.updated_at)
+      .then_with(|| a.public_id.cmp(&b.public_id))
+  });
+  latest.truncate(FEED_ENTRIES);
+  latest
+}
+
+/// 指定した形式でフィードを生成する
+pub fn render(format: FeedFormat, entries: &[&Calligraphy], meta: &FeedMeta) -> String {
+  match format {
+    FeedFormat::Atom => atom(entries, meta),
+    FeedFormat::Rss => rss(entries, meta),
+    FeedFormat::Json => json(entries, meta),
+  }
+}
+
+fn entry_id(entry: &Calligraphy) -> String {
+  format!("urn:uuid:{}", entry.public_id)
+}
+
+fn entry_title(entry: &Calligraphy) -> String {
+  format!("{}の書き初め", entry.user_name)
+}
+
+fn rfc3339(t: OffsetDateTime) -> String {
+  t.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Atom 1.0 (RFC 4287)
+fn atom(entries: &[&Calligraphy], meta: &FeedMeta) -> String {
+  let mut xml = String::new();
+  xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+  xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"ja\">\n");
+  let feed_url = escape_xml(&meta.feed_url(FeedFormat::Atom));
+  let _ = writeln!(xml, "  <id>{}</id>", feed_url);
+  let _ = writeln!(xml, "  <title>{}</title>", escape_xml(FEED_TITLE));
+  let _ = writeln!(xml, "  <subtitle>{}</subtitle>", escape_xml(FEED_DESCRIPTION));
+  let _ = writeln!(xml, "  <updated>{}</updated>", rfc3339(meta.updated));
+  let _ = writeln!(xml, "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>", feed_url);
+  let _ = writeln!(
+    xml,
+    "  <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>",
+    escape_xml(&meta.home_page_url())
+  );
+  for entry in entries {
+    xml.push_str("  <entry>\n");
+    let _ = writeln!(xml, "    <id>{}</id>", entry_id(entry));
+    let _ = writeln!(xml, "    <title type=\"text\">{}</title>", escape_xml(&entry_title(entry)));
+    let _ = writeln!(xml, "    <author><name>{}</name></author>", escape_xml(&entry.user_name));
+    let _ = writeln!(xml, "    <published>{}</published>", rfc3339(entry.created_at));
+    let _ = writeln!(xml, "    <updated>{}</updated>", rfc3339(entry.updated_at));
+    let _ = writeln!(xml, "    <content type=\"text\">{}</content>", escape_xml(&entry.content));
+    xml.push_str("  </entry>\n");
+  }
+  xml.push_str("</feed>\n");
+  xml
+}
+
+/// RSS 2.0
+/// 更新日時の要素がないため、`pubDate` に更新日時を入れる
+fn rss(entries: &[&Calligraphy], meta: &FeedMeta) -> String {
+  let rfc2822 = |t: OffsetDateTime| t.format(&Rfc2822).unwrap_or_default();
+
+  let mut xml = String::new();
+  xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+  xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+  xml.push_str("  <channel>\n");
+  let _ = writeln!(xml, "    <title>{}</title>", escape_xml(FEED_TITLE));
+  let _ = writeln!(xml, "    <link>{}</link>", escape_xml(&meta.home_page_url()));
+  let _ = writeln!(xml, "    <description>{}</description>", escape_xml(FEED_DESCRIPTION));
+  xml.push_str("    <language>ja</language>\n");
+  let _ = writeln!(xml, "    <lastBuildDate>{}</lastBuildDate>", rfc2822(meta.updated));
+  let _ = writeln!(
+    xml,
+    "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>",
+    escape_xml(&meta.feed_url(FeedFormat::Rss))
+  );
+  for entry in entries {
+    xml.push_str("    <item>\n");
+    let _ = writeln!(xml, "      <title>{}</title>", escape_xml(&entry_title(entry)));
+    let _ = writeln!(xml, "      <description>{}</description>", escape_xml(&entry.content));
+    let _ = writeln!(xml, "      <guid isPermaLink=\"false\">{}</guid>", entry_id(entry));
+    let _ = writeln!(xml, "      <pubDate>{}</pubDate>", rfc2822(entry.updated_at));
+    xml.push_str("    </item>\n");
+  }
+  xml.push_str("  </channel>\n");
+  xml.push_str("</rss>\n");
+  xml
+}
+
+/// JSON Feed 1.1
+fn json(entries: &[&Calligraphy], meta: &FeedMeta) -> String {
+  let feed = JsonFeed {
+    version: JSON_FEED_VERSION,
+    title: FEED_TITLE.to_string(),
+    home_page_url: meta.home_page_url(),
+    feed_url: meta.feed_url(FeedFormat::Json),
+    language: "ja",
+    items: entries
+      .iter()
+      .map(|entry| JsonFeedItem {
+        id: entry_id(entry),
+        title: entry_title(entry),
+        content_text: entry.content.clone(),
+        date_published: entry.created_at,
+        date_modified: entry.updated_at,
+        authors: vec![JsonFeedAuthor {
+          name: entry.user_name.clone(),
+        }],
+      })
+      .collect(),
+  };
+  serde_json::to_string(&feed).unwrap_or_default()
+}
+
+/// XMLの特殊文字をエスケープする (要素の内容・属性値の両方に使える)
+/// XML 1.0 で使えない制御文字は取り除く
+pub fn escape_xml(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      '\t' | '\n' | '\r' => escaped.push(c),
+      c if c < '\u{20}' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::macros::datetime;
+  use uuid::Uuid;
+
+  fn entry(user_name: &str, content: &str, updated_at: OffsetDateTime) -> Calligraphy {
+    Calligraphy {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+      user_name: user_name.to_string(),
+      content: content.to_string(),
+      photo: None,
+      ip_address: None,
+      user_agent: None,
+      accept_language: None,
+      created_at: datetime!(2026-01-01 00:00 UTC),
+      updated_at,
+    }
+  }
+
+  fn meta() -> FeedMeta<'static> {
+    FeedMeta {
+      base_url: "https://example.com",
+      updated: datetime!(2026-01-02 03:04:05 UTC),
+    }
+  }
+
+  #[test]
+  fn test_escape_xml() {
+    assert_eq!(
+      escape_xml("<script>alert(\"x\" & 'y')</script>"),
+      "&lt;script&gt;alert(&quot;x&quot; &amp; &apos;y&apos;)&lt;/script&gt;"
+    );
+    assert_eq!(escape_xml("一\n二\u{0}\u{1b}三"), "一\n二三");
+  }
+
+  /// 更新日時の新しい順に最大件数まで取り出すこと
+  #[test]
+  fn test_latest() {
+    let base = datetime!(2026-01-01 00:00 UTC);
+    let entries: Vec<_> = (0..FEED_ENTRIES as i64 + 5)
+      .map(|i| entry("太郎", "謹賀新年", base + time::Duration::minutes(i)))
+      .collect();
+    let latest = latest(&entries);
+    assert_eq!(latest.len(), FEED_ENTRIES);
+    assert_eq!(latest[0].updated_at, base + time::Duration::minutes(FEED_ENTRIES as i64 + 4));
+    assert!(latest.windows(2).all(|w| w[0].updated_at >= w[1].updated_at));
+  }
+
+  /// Atom: エントリーのID・日時とユーザー入力のエスケープ
+  #[test]
+  fn test_atom() {
+    let e = entry("<b>太郎</b>", "一富士 & 二鷹", datetime!(2026-01-02 03:04:05 UTC));
+    let xml = render(FeedFormat::Atom, &[&e], &meta());
+
+    assert!(xml.contains("<id>https://example.com/api/calligraphy/feed.atom</id>"));
+    assert!(xml.contains(&format!("<id>urn:uuid:{}</id>", e.public_id)));
+    assert!(xml.contains("<published>2026-01-01T00:00:00Z</published>"));
+    assert!(xml.contains("<updated>2026-01-02T03:04:05Z</updated>"));
+    assert!(xml.contains("<author><name>&lt;b&gt;太郎&lt;/b&gt;</name></author>"));
+    assert!(xml.contains("<content type=\"text\">一富士 &amp; 二鷹</content>"));
+    assert!(!xml.contains("<b>"));
+  }
+
+  /// RSS: guidはパーマリンクでないこと、日時がRFC 2822形式であること
+  #[test]
+  fn test_rss() {
+    let e = entry("太郎", "<謹賀新年>", datetime!(2026-01-02 03:04:05 UTC));
+    let xml = render(FeedFormat::Rss, &[&e], &meta());
+
+    assert!(xml.contains(&format!("<guid isPermaLink=\"false\">urn:uuid:{}</guid>", e.public_id)));
+    assert!(xml.contains("<pubDate>Fri, 02 Jan 2026 03:04:05 +0000</pubDate>"));
+    assert!(xml.contains("<description>&lt;謹賀新年&gt;</description>"));
+    assert!(xml.contains("<link>https://example.com/</link>"));
+  }
+
+  #[test]
+  fn test_json_feed() {
+    let e = entry("太郎", "謹賀新年", datetime!(2026-01-02 03:04:05 UTC));
+    let json: serde_json::Value = serde_json::from_str(&render(FeedFormat::Json, &[&e], &meta())).unwrap();
+
+    assert_eq!(json["version"], JSON_FEED_VERSION);
+    assert_eq!(json["feed_url"], "https://example.com/api/calligraphy/feed.json");
+    assert_eq!(json["items"][0]["id"], format!("urn:uuid:{}", e.public_id));
+    assert_eq!(json["items"][0]["date_modified"], "2026-01-02T03:04:05Z");
+    assert_eq!(json["items"][0]["authors"][0]["name"], "太郎");
+  }
+}
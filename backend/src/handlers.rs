@@ -1 +1,13 @@
-pub mod calligraphy;
\ No newline at end of file
+pub mod calligraphy;
+pub mod conditional;
+pub mod feed;
+pub mod identity;
+pub mod ogp;
+pub mod passkeys;
+pub mod photos;
+pub mod privacy;
+pub mod sessions;
+pub mod stats;
+pub mod strokes;
+pub mod svg;
+pub mod ws;
@@ -1,29 +1,66 @@
+use std::convert::Infallible;
+
 use axum::{
-  extract::State,
-  http::StatusCode,
-  response::IntoResponse,
+  extract::{Query, State},
+  http::{header, StatusCode},
+  response::{
+    sse::{Event, KeepAlive, Sse},
+    IntoResponse, Response,
+  },
   Json,
 };
 use sqlx::types::ipnetwork::IpNetwork;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use uuid::Uuid;
 
 use crate::{
   error::AppError,
-  extractors::{AuthUser, ClientIp, UserAgent, AcceptLanguage},
-  models::calligraphy::{CreateCalligraphyRequest, CalligraphyResponse},
+  extractors::{AuthUser, CalligraphyForm, ClientIp, UserAgent, AcceptLanguage, LastEventId, Preconditions, SessionUser},
+  handlers::conditional::{no_store_headers, CacheScope, Validators},
+  models::calligraphy::CalligraphyResponse,
+  models::list_query::{ListParams, ListQuery},
+  models::search::{Highlights, SearchHitResponse, SearchParams, SearchQuery, SearchResponse},
   repositories::db_repository::CalligraphyRepositoryTrait,
-  services::calligraphy::CalligraphyService,
+  search,
+  services::{
+    calligraphy::CalligraphyService,
+    events::{EventHub, PublishedEvent, Subscription},
+  },
 };
 
 // --- Handlers ---
 
 /// 書き初め投稿・更新
+///
+/// JSON、または写真を添付する場合は multipart/form-data で受け付ける。
+#[utoipa::path(
+  post,
+  path = "/api/calligraphy",
+  tag = "calligraphy",
+  operation_id = "upsertCalligraphy",
+  request_body(
+    description = "写真を添付する場合は multipart/form-data",
+    content(
+      (crate::models::calligraphy::CreateCalligraphyRequest = "application/json"),
+      (crate::openapi::CalligraphyFormData = "multipart/form-data"),
+    ),
+  ),
+  responses(
+    (status = 200, description = "投稿・更新した書き初め", body = CalligraphyResponse),
+    (status = 400, description = "バリデーションエラー、不正なmultipartのフィールド、デコードできない写真", body = crate::error::ErrorResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 413, description = "写真またはリクエストが上限を超えている", body = crate::error::ErrorResponse),
+    (status = 415, description = "写真がJPEG / PNG / WebP以外", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
 pub async fn upsert<R: CalligraphyRepositoryTrait>(
   State(service): State<CalligraphyService<R>>, // State(service): Stateのserviceだけ取り出す
   auth_user: AuthUser,
   ClientIp(ip): ClientIp,
   UserAgent(user_agent): UserAgent,
   AcceptLanguage(accept_language): AcceptLanguage,
-  Json(payload): Json<CreateCalligraphyRequest>,
+  CalligraphyForm { request: payload, photo }: CalligraphyForm,
 ) -> Result<impl IntoResponse, AppError> {
   // IPアドレスによるレート制限チェック
   if let Some(ip_addr) = ip {
@@ -39,62 +76,223 @@ pub async fn upsert<R: CalligraphyRepositoryTrait>(
       ip_network,
       user_agent,
       accept_language,
+      payload.strokes,
+      photo,
     )
     .await?;
 
-  let response = CalligraphyResponse {
-    user_name: calligraphy.user_name,
-    content: calligraphy.content,
-    created_at: calligraphy.created_at,
-    updated_at: calligraphy.updated_at,
-    is_mine: true,
-  };
+  let response: CalligraphyResponse = calligraphy.to_response(true);
 
   Ok((StatusCode::OK, Json(response)))
 }
 
 /// 一覧取得
+///
+/// クエリパラメーターで並び順・絞り込み条件を指定できる (不正な値は400)。
+/// ETag / Last-Modified による条件付きGETに対応する。
+/// 変更がなければ一覧の取得・シリアライズを行わずに 304 Not Modified を返す。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy",
+  tag = "calligraphy",
+  operation_id = "listCalligraphy",
+  params(ListParams),
+  responses(
+    (status = 200, description = "書き初めの一覧", body = Vec<CalligraphyResponse>),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 400, description = "クエリパラメーターの値が不正", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
 pub async fn list<R: CalligraphyRepositoryTrait>(
   State(service): State<CalligraphyService<R>>,
   auth_user: AuthUser,
   ClientIp(ip): ClientIp,
-) -> Result<impl IntoResponse, AppError> {
+  preconditions: Preconditions,
+  Query(params): Query<ListParams>,
+) -> Result<Response, AppError> {
   // IPアドレスによるレート制限チェック
   if let Some(ip_addr) = ip {
     service.check_read_rate_limit(ip_addr).await?;
   }
-	// 全件取得
-  let list = service.get_all().await?;
+  let query = ListQuery::try_from(params)?;
+	// 変更の有無を軽量なクエリで確認
+	// 確認できない (DB障害等) 場合は条件付きGETを省略し、キャッシュ済みの一覧を返す
+  let validators = match service.get_board_state(auth_user.id).await {
+    Ok(state) => Some(Validators {
+      etag: state.etag(),
+      last_modified: state.last_modified(),
+      scope: CacheScope::Private,
+    }),
+    Err(e) => {
+      tracing::warn!("Skipping validators for list: {:?}", e);
+      None
+    }
+  };
+  if let Some(validators) = validators.as_ref().filter(|v| v.is_not_modified(&preconditions)) {
+    return Ok(validators.not_modified());
+  }
+	// 条件に合う一覧を取得
+  let list = service.get_list(&query, auth_user.id).await?;
 	// レスポンス用DTOに変換
   let response: Vec<CalligraphyResponse> = list
-    .into_iter()
+    .iter()
     .map(|c| {
       let is_mine = c.user_id == auth_user.id;
       c.to_response(is_mine)
     })
     .collect();
-  Ok((StatusCode::OK, Json(response)))
+  let headers = validators.map_or_else(no_store_headers, |v| v.headers());
+  Ok((StatusCode::OK, headers, Json(response)).into_response())
+}
+
+/// 全文検索
+///
+/// ユーザー名・内容を検索し、関連度の高い順にページ単位で返す。
+/// 一致箇所はフィールドごとの区間 (`highlights`) として返す。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/search",
+  tag = "calligraphy",
+  operation_id = "searchCalligraphy",
+  params(SearchParams),
+  responses(
+    (status = 200, description = "検索結果", body = SearchResponse),
+    (status = 400, description = "検索語がない、またはクエリパラメーターの値が不正", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn search<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  ClientIp(ip): ClientIp,
+  Query(params): Query<SearchParams>,
+) -> Result<Json<SearchResponse>, AppError> {
+  // 一覧と同じレート制限
+  if let Some(ip_addr) = ip {
+    service.check_read_rate_limit(ip_addr).await?;
+  }
+  let query = SearchQuery::try_from(params)?;
+  let (hits, has_more) = service.search(&query).await?;
+
+  let items = hits
+    .iter()
+    .map(|hit| SearchHitResponse {
+      entry: hit.entry.to_response(hit.entry.user_id == auth_user.id),
+      highlights: Highlights {
+        user_name: search::highlight(&hit.entry.user_name, &query.q),
+        content: search::highlight(&hit.entry.content, &query.q),
+      },
+    })
+    .collect();
+  Ok(Json(SearchResponse {
+    items,
+    page: query.page,
+    per_page: query.per_page,
+    has_more,
+  }))
+}
+
+/// 個人データのエクスポート
+///
+/// 保存している全ての情報 (通常は返さないIPアドレス・User-Agent等を含む) を
+/// JSONファイルとしてダウンロードさせる。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/me/export",
+  tag = "calligraphy",
+  operation_id = "exportPersonalData",
+  responses(
+    (status = 200, description = "保存している全ての情報 (添付ファイルとしてダウンロード)", body = crate::models::export::PersonalDataExport),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn export<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  ClientIp(ip): ClientIp,
+) -> Result<Response, AppError> {
+  if let Some(ip_addr) = ip {
+    service.check_read_rate_limit(ip_addr).await?;
+  }
+  let export = service.export(auth_user.id).await?;
+  let body = serde_json::to_vec_pretty(&export).map_err(|e| {
+    tracing::error!("Failed to serialize export: {:?}", e);
+    AppError::Internal
+  })?;
+
+  Ok(
+    (
+      StatusCode::OK,
+      [
+        (header::CONTENT_TYPE, "application/json; charset=utf-8".to_string()),
+        (
+          header::CONTENT_DISPOSITION,
+          format!("attachment; filename=\"{}\"", export.file_name()),
+        ),
+        // 個人情報を含むため、ブラウザや中間のキャッシュに残さない
+        (header::CACHE_CONTROL, "no-store".to_string()),
+      ],
+      body,
+    )
+      .into_response(),
+  )
 }
 
 /// 個別取得
+///
+/// ETag / Last-Modified による条件付きGETに対応する。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/me",
+  tag = "calligraphy",
+  operation_id = "getMyCalligraphy",
+  responses(
+    (status = 200, description = "自分の書き初め", body = CalligraphyResponse),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 404, description = "まだ書き初めを投稿していない", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
 pub async fn get<R: CalligraphyRepositoryTrait>(
   State(service): State<CalligraphyService<R>>,
   auth_user: AuthUser,
   ClientIp(ip): ClientIp,
-) -> Result<impl IntoResponse, AppError> {
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
   // IPアドレスによるレート制限チェック
   if let Some(ip_addr) = ip {
     service.check_read_rate_limit(ip_addr).await?;
   }
 	// 自分の書き初めを取得
   let calligraphy = service.get(auth_user.id).await?;
+  let validators = Validators {
+    etag: calligraphy.etag(),
+    last_modified: Some(calligraphy.updated_at),
+    scope: CacheScope::Private,
+  };
+  if validators.is_not_modified(&preconditions) {
+    return Ok(validators.not_modified());
+  }
 	// レスポンス用DTOに変換
   let response = calligraphy.to_response(true);
 
-  Ok((StatusCode::OK, Json(response)))
+  Ok((StatusCode::OK, validators.headers(), Json(response)).into_response())
 }
 
 /// 削除
+#[utoipa::path(
+  delete,
+  path = "/api/calligraphy/me",
+  tag = "calligraphy",
+  operation_id = "deleteMyCalligraphy",
+  responses(
+    (status = 204, description = "削除した"),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 404, description = "削除対象が存在しない", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
 pub async fn delete<R: CalligraphyRepositoryTrait>(
   State(service): State<CalligraphyService<R>>,
   auth_user: AuthUser,
@@ -109,22 +307,117 @@ pub async fn delete<R: CalligraphyRepositoryTrait>(
   Ok(StatusCode::NO_CONTENT)
 }
 
+/// 変更イベントの購読 (Server-Sent Events)
+///
+/// イベント名は `created` / `updated` / `deleted` / `reset`。
+/// `reset` は再送しきれないイベントがあったことを示し、クライアントは一覧を再取得する。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/stream",
+  tag = "realtime",
+  operation_id = "streamCalligraphyEvents",
+  params(
+    ("Last-Event-ID" = Option<String>, Header, description = "最後に受け取ったイベントのID (再接続時に取りこぼしを再送する)"),
+  ),
+  responses(
+    (
+      status = 200,
+      description = "イベントのストリーム (`data` は CalligraphyEventResponse のJSON)",
+      content_type = "text/event-stream",
+    ),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn stream<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  SessionUser(auth_user): SessionUser,
+  ClientIp(ip): ClientIp,
+  LastEventId(last_event_id): LastEventId,
+) -> Result<impl IntoResponse, AppError> {
+  // 接続の確立は読み込みとしてレート制限する
+  if let Some(ip_addr) = ip {
+    service.check_read_rate_limit(ip_addr).await?;
+  }
+
+  let Subscription {
+    replay,
+    reset,
+    receiver,
+  } = service.subscribe(last_event_id.as_deref());
+  let hub = service.events().clone();
+  let viewer_id = auth_user.id;
+
+  // 1. 必要ならリセット通知 2. 取りこぼしの再送 3. 以降のイベント
+  let head = reset.then(reset_event);
+  let replayed: Vec<Event> = replay
+    .into_iter()
+    .filter_map(|e| to_sse_event(&hub, viewer_id, e))
+    .collect();
+  let live = BroadcastStream::new(receiver).filter_map(move |result| match result {
+    Ok(e) => to_sse_event(&hub, viewer_id, e),
+    // 受信が追いつかずイベントを取りこぼした場合
+    Err(_) => Some(reset_event()),
+  });
+  let events = tokio_stream::iter(head.into_iter().chain(replayed))
+    .chain(live)
+    .map(Ok::<_, Infallible>);
+
+  Ok((
+    // Nginxのレスポンスバッファリングを無効化し、イベントを即時に届ける
+    [("x-accel-buffering", "no")],
+    Sse::new(events).keep_alive(KeepAlive::default()),
+  ))
+}
+
+/// 購読者ごとに is_mine を計算してSSEイベントに変換する
+fn to_sse_event(hub: &EventHub, viewer_id: Uuid, e: PublishedEvent) -> Option<Event> {
+  Event::default()
+    .id(hub.event_id(e.seq))
+    .event(e.event.name())
+    .json_data(e.event.to_response(viewer_id))
+    .ok()
+}
+
+/// 一覧の再取得を促すイベント
+fn reset_event() -> Event {
+  Event::default().event("reset").data("{}")
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::models::calligraphy::Calligraphy;
+  use crate::models::calligraphy::{BoardState, Calligraphy, CreateCalligraphyRequest};
+  use crate::models::export::{CalligraphyRecord, PasskeyCredentialRecord, SessionRecord};
+  use crate::models::list_query::ListSort;
+  use crate::models::search::{SearchHit, Segment};
   use crate::repositories::db_repository::MockCalligraphyRepositoryTrait;
-  use async_trait::async_trait;
-  use sqlx::types::ipnetwork::IpNetwork;
-  use std::sync::Arc;
+  use http_body_util::BodyExt;
   use time::OffsetDateTime;
   use uuid::Uuid;
 
+  fn no_preconditions() -> Preconditions {
+    Preconditions {
+      if_none_match: None,
+      if_modified_since: None,
+    }
+  }
+
+  fn dummy_board_state() -> BoardState {
+    BoardState {
+      count: 1,
+      max_updated_at: Some(OffsetDateTime::now_utc()),
+      last_deleted_at: None,
+      mine: None,
+    }
+  }
+
   fn create_dummy_calligraphy(user_id: Uuid, user_name: &str, content: &str) -> Calligraphy {
     Calligraphy {
       user_id,
+      public_id: Uuid::new_v4(),
       user_name: user_name.to_string(),
       content: content.to_string(),
+      photo: None,
       ip_address: None,
       user_agent: None,
       accept_language: None,
@@ -153,14 +446,23 @@ mod tests {
         mockall::predicate::always(), // ip_address
         mockall::predicate::always(), // user_agent
         mockall::predicate::always(), // accept_language
+        mockall::predicate::always(), // strokes
+        mockall::predicate::eq(None), // photo
       )
       .times(1)
-      .returning(move |_, _, _, _, _, _| Ok(returned_calligraphy.clone()));
+      .returning(move |_, _, _, _, _, _, _, _| Ok(returned_calligraphy.clone()));
 
     let service = CalligraphyService::new(mock_repo);
     let state = State(service);
     let auth_user = AuthUser { id: user_id };
-    let payload = Json(CreateCalligraphyRequest { user_name, content });
+    let payload = CalligraphyForm {
+      request: CreateCalligraphyRequest {
+        user_name,
+        content,
+        strokes: None,
+      },
+      photo: None,
+    };
     // ダミーIPアドレスを使用
     let client_ip = ClientIp(Some("127.0.0.1".parse().unwrap()));
 		let user_agent = UserAgent(None);
@@ -180,6 +482,10 @@ mod tests {
 
     let expected_calligraphy = create_dummy_calligraphy(user_id, &user_name, &content);
 
+    mock_repo
+      .expect_board_state()
+      .times(1)
+      .returning(|_| Ok(dummy_board_state()));
     mock_repo
       .expect_find_all()
       .times(1)
@@ -188,9 +494,365 @@ mod tests {
     let service = CalligraphyService::new(mock_repo);
     let state = State(service);
     let client_ip = ClientIp(Some("127.0.0.1".parse().unwrap()));
-    let response = list(state, AuthUser { id: Uuid::new_v4() }, client_ip).await;
+    let response = list(state, AuthUser { id: Uuid::new_v4() }, client_ip, no_preconditions(), Query(ListParams::default())).await;
 
     assert!(response.is_ok());
+    let response = response.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key("etag"));
+    assert_eq!(response.headers()["cache-control"], "private, no-cache");
+  }
+
+  /// listハンドラー 条件を指定した場合は絞り込み用のクエリで取得するテスト
+  #[tokio::test]
+  async fn test_list_handler_with_query() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let mine = create_dummy_calligraphy(user_id, "テストユーザー", "Mine");
+
+    mock_repo
+      .expect_board_state()
+      .times(1)
+      .returning(|_| Ok(dummy_board_state()));
+    mock_repo.expect_find_all().times(0); // 共通の一覧キャッシュは使わない
+    mock_repo
+      .expect_find_filtered()
+      .withf(move |query, viewer_id| {
+        query.mine_only && query.sort == ListSort::Oldest && *viewer_id == user_id
+      })
+      .times(1)
+      .returning(move |_, _| Ok(vec![mine.clone()]));
+
+    let service = CalligraphyService::new(mock_repo);
+    let params = ListParams {
+      sort: Some("oldest".to_string()),
+      mine_only: Some("true".to_string()),
+      ..Default::default()
+    };
+    let response = list(
+      State(service),
+      AuthUser { id: user_id },
+      ClientIp(None),
+      no_preconditions(),
+      Query(params),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 1);
+    assert_eq!(json[0]["is_mine"], true);
+  }
+
+  /// listハンドラー 未知の並び順は400を返すテスト
+  #[tokio::test]
+  async fn test_list_handler_invalid_query() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_board_state().times(0);
+
+    let service = CalligraphyService::new(mock_repo);
+    let params = ListParams {
+      sort: Some("most_reacted".to_string()),
+      ..Default::default()
+    };
+    let result = list(
+      State(service),
+      AuthUser { id: Uuid::new_v4() },
+      ClientIp(None),
+      no_preconditions(),
+      Query(params),
+    )
+    .await;
+
+    assert!(matches!(result, Err(AppError::Validation(_))));
+  }
+
+  /// listハンドラー 変更がなければ一覧を取得せずに304を返すテスト
+  #[tokio::test]
+  async fn test_list_handler_not_modified() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let state = dummy_board_state();
+    let etag = state.etag();
+
+    mock_repo
+      .expect_board_state()
+      .times(1)
+      .returning(move |_| Ok(state.clone()));
+    // 一覧は取得されない
+    mock_repo.expect_find_all().times(0);
+
+    let service = CalligraphyService::new(mock_repo);
+    let preconditions = Preconditions {
+      if_none_match: Some(etag),
+      if_modified_since: None,
+    };
+    let response = list(State(service), AuthUser { id: Uuid::new_v4() }, ClientIp(None), preconditions, Query(ListParams::default()))
+      .await
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+  }
+
+  /// listハンドラー 一度取得した後にDBが停止しても、検証子なしでキャッシュ済みの一覧を返すテスト
+  #[tokio::test]
+  async fn test_list_handler_serves_stale_when_db_down() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let entry = create_dummy_calligraphy(Uuid::new_v4(), "テストユーザー", "Stale Item");
+
+    // 1回目は成功し、2回目以降は失敗する
+    let mut seq = mockall::Sequence::new();
+    mock_repo
+      .expect_board_state()
+      .times(1)
+      .in_sequence(&mut seq)
+      .returning(|_| Ok(dummy_board_state()));
+    mock_repo
+      .expect_find_all()
+      .times(1)
+      .in_sequence(&mut seq)
+      .returning(move || Ok(vec![entry.clone()]));
+    mock_repo
+      .expect_board_state()
+      .times(1)
+      .in_sequence(&mut seq)
+      .returning(|_| Err(sqlx::Error::PoolTimedOut));
+    mock_repo
+      .expect_find_all()
+      .times(1)
+      .in_sequence(&mut seq)
+      .returning(|| Err(sqlx::Error::PoolTimedOut));
+
+    let service = CalligraphyService::new(mock_repo);
+    let response = list(State(service.clone()), AuthUser { id: Uuid::new_v4() }, ClientIp(None), no_preconditions(), Query(ListParams::default()))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 他のレプリカでの変更によりキャッシュが無効になった後、DBが停止した
+    service.events().publish_remote(crate::models::calligraphy::CalligraphyEvent::Deleted {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+    });
+    let response = list(State(service), AuthUser { id: Uuid::new_v4() }, ClientIp(None), no_preconditions(), Query(ListParams::default()))
+      .await
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("etag"));
+    assert_eq!(response.headers()["cache-control"], "no-store");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json[0]["content"], "Stale Item");
+  }
+
+  fn search_params(q: &str, per_page: Option<&str>) -> Query<SearchParams> {
+    Query(SearchParams {
+      q: Some(q.to_string()),
+      page: None,
+      per_page: per_page.map(str::to_string),
+    })
+  }
+
+  /// searchハンドラーのテスト (is_mine とハイライト)
+  #[tokio::test]
+  async fn test_search_handler() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let mine = create_dummy_calligraphy(user_id, "富士子", "謹賀新年");
+    let other = create_dummy_calligraphy(Uuid::new_v4(), "太郎", "一富士二鷹");
+
+    mock_repo
+      .expect_search()
+      .withf(|q, limit, offset| q == "富士" && *limit == 21 && *offset == 0) // 次ページ判定のため1件多く取得
+      .times(1)
+      .returning(move |_, _, _| {
+        Ok(vec![
+          SearchHit { entry: mine.clone(), score: 1.0 },
+          SearchHit { entry: other.clone(), score: 1.0 },
+        ])
+      });
+
+    let service = CalligraphyService::new(mock_repo);
+    let client_ip = ClientIp(Some("127.0.0.1".parse().unwrap()));
+    let Json(response) = search(
+      State(service),
+      AuthUser { id: user_id },
+      client_ip,
+      search_params(" 富士 ", None),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.items.len(), 2);
+    assert!(!response.has_more);
+    assert!(response.items[0].entry.is_mine);
+    assert!(!response.items[1].entry.is_mine);
+    assert_eq!(
+      response.items[0].highlights.user_name,
+      vec![Segment::matched("富士"), Segment::plain("子")]
+    );
+    assert_eq!(
+      response.items[1].highlights.content,
+      vec![Segment::plain("一"), Segment::matched("富士"), Segment::plain("二鷹")]
+    );
+  }
+
+  /// searchハンドラー 取得件数が1ページを超えたら has_more を立てるテスト
+  #[tokio::test]
+  async fn test_search_handler_has_more() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_search().times(1).returning(|_, limit, _| {
+      Ok(
+        (0..limit)
+          .map(|_| SearchHit {
+            entry: create_dummy_calligraphy(Uuid::new_v4(), "太郎", "富士"),
+            score: 1.0,
+          })
+          .collect(),
+      )
+    });
+
+    let service = CalligraphyService::new(mock_repo);
+    let Json(response) = search(
+      State(service),
+      AuthUser { id: Uuid::new_v4() },
+      ClientIp(None),
+      search_params("富士", Some("2")),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.items.len(), 2);
+    assert_eq!(response.per_page, 2);
+    assert!(response.has_more);
+  }
+
+  /// searchハンドラー 不正なパラメーターはDBに問い合わせず400を返すテスト
+  #[tokio::test]
+  async fn test_search_handler_invalid_params() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_search().times(0);
+
+    // 複数回呼び出すため、Arcでラップしてクローン可能にする
+    let service = CalligraphyService::new(mock_repo);
+    let result = search(
+      State(service.clone()),
+      AuthUser { id: Uuid::new_v4() },
+      ClientIp(None),
+      search_params("", None),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+
+    let result = search(
+      State(service),
+      AuthUser { id: Uuid::new_v4() },
+      ClientIp(None),
+      search_params("富士", Some("100")),
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+  }
+
+  /// exportハンドラーのテスト (情報収集用の列を含み、ダウンロードさせる)
+  #[tokio::test]
+  async fn test_export_handler() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+
+    mock_repo
+      .expect_export_by_id()
+      .with(mockall::predicate::eq(user_id))
+      .times(1)
+      .returning(move |_| {
+        Ok(Some(CalligraphyRecord {
+          public_id: Uuid::new_v4(),
+          user_name: "テストユーザー".to_string(),
+          content: "謹賀新年".to_string(),
+          content_length: 4,
+          ip_address: Some("192.0.2.1".to_string()),
+          user_agent: Some("TestAgent/1.0".to_string()),
+          accept_language: Some("ja".to_string()),
+          created_at: now,
+          updated_at: now,
+          anonymized_at: None,
+          strokes: None,
+          photo: None,
+        }))
+      });
+
+    mock_repo
+      .expect_export_sessions()
+      .with(mockall::predicate::eq(user_id))
+      .times(1)
+      .returning(move |_| {
+        Ok(vec![SessionRecord {
+          id: Uuid::new_v4(),
+          device: Some("Firefox / Linux".to_string()),
+          created_at: now,
+          last_seen_at: now,
+          expires_at: now,
+        }])
+      });
+    mock_repo
+      .expect_export_passkeys()
+      .with(mockall::predicate::eq(user_id))
+      .times(1)
+      .returning(move |_| {
+        Ok(vec![PasskeyCredentialRecord {
+          id: Uuid::new_v4(),
+          credential_id: "AQID".to_string(),
+          passkey: sqlx::types::Json(serde_json::json!({ "cred": {} })),
+          created_at: now,
+          last_used_at: None,
+        }])
+      });
+
+    let service = CalligraphyService::new(mock_repo);
+    let response = export(State(service), AuthUser { id: user_id }, ClientIp(None))
+      .await
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let disposition = response.headers()["content-disposition"].to_str().unwrap();
+    assert!(disposition.starts_with("attachment; filename=\"calligraphy-export-"));
+    assert_eq!(response.headers()["cache-control"], "no-store");
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["user_id"], user_id.to_string());
+    assert_eq!(json["calligraphy"]["ip_address"], "192.0.2.1");
+    assert_eq!(json["calligraphy"]["user_agent"], "TestAgent/1.0");
+    assert_eq!(json["calligraphy"]["accept_language"], "ja");
+    assert_eq!(json["sessions"][0]["device"], "Firefox / Linux");
+    assert!(json["sessions"][0].get("token_hash").is_none());
+    assert_eq!(json["passkeys"][0]["credential_id"], "AQID");
+    assert!(json["passkeys"][0]["last_used_at"].is_null());
+  }
+
+  /// exportハンドラー 投稿がなくてもユーザーIDを返すテスト
+  #[tokio::test]
+  async fn test_export_handler_without_calligraphy() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    mock_repo.expect_export_by_id().times(1).returning(|_| Ok(None));
+    mock_repo.expect_export_sessions().times(1).returning(|_| Ok(vec![]));
+    mock_repo.expect_export_passkeys().times(1).returning(|_| Ok(vec![]));
+
+    let service = CalligraphyService::new(mock_repo);
+    let response = export(State(service), AuthUser { id: user_id }, ClientIp(None))
+      .await
+      .unwrap();
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["user_id"], user_id.to_string());
+    assert!(json["calligraphy"].is_null());
+    assert_eq!(json["sessions"], serde_json::json!([]));
+    assert_eq!(json["passkeys"], serde_json::json!([]));
   }
 
   /// getハンドラーのテスト
@@ -213,11 +875,36 @@ mod tests {
     let state = State(service);
     let auth_user = AuthUser { id: user_id };
     let client_ip = ClientIp(Some("127.0.0.1".parse().unwrap()));
-    let response = get(state, auth_user, client_ip).await;
+    let response = get(state, auth_user, client_ip, no_preconditions()).await;
 
     assert!(response.is_ok());
   }
 
+  /// getハンドラー ETagが一致すれば304を返すテスト
+  #[tokio::test]
+  async fn test_get_handler_not_modified() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let calligraphy = create_dummy_calligraphy(user_id, "テストユーザー", "Get Item");
+    let etag = calligraphy.etag();
+
+    mock_repo
+      .expect_find_by_id()
+      .times(1)
+      .returning(move |_| Ok(Some(calligraphy.clone())));
+
+    let service = CalligraphyService::new(mock_repo);
+    let preconditions = Preconditions {
+      if_none_match: Some(etag),
+      if_modified_since: None,
+    };
+    let response = get(State(service), AuthUser { id: user_id }, ClientIp(None), preconditions)
+      .await
+      .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+  }
+
   /// deleteハンドラーのテスト
   #[tokio::test]
   async fn test_delete_handler() {
@@ -228,7 +915,7 @@ mod tests {
       .expect_delete()
       .with(mockall::predicate::eq(user_id))
       .times(1)
-      .returning(|_| Ok(1));
+      .returning(|_| Ok(Some(Uuid::new_v4())));
 
     let service = CalligraphyService::new(mock_repo);
     let state = State(service);
@@ -239,33 +926,6 @@ mod tests {
     assert!(response.is_ok());
   }
 
-  // Arc<MockCalligraphyRepositoryTrait> に CalligraphyRepositoryTrait を実装する
-  #[async_trait]
-  impl crate::repositories::db_repository::CalligraphyRepositoryTrait
-    for Arc<MockCalligraphyRepositoryTrait>
-  {
-    async fn create(
-      &self,
-      user_id: Uuid,
-      user_name: String,
-      content: String,
-			ip_address: Option<IpNetwork>,
-      user_agent: Option<String>,
-      accept_language: Option<String>,
-    ) -> Result<Calligraphy, sqlx::Error> {
-      self.as_ref().create(user_id, user_name, content, ip_address, user_agent, accept_language).await
-    }
-    async fn find_all(&self) -> Result<Vec<Calligraphy>, sqlx::Error> {
-      self.as_ref().find_all().await
-    }
-    async fn find_by_id(&self, id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error> {
-      self.as_ref().find_by_id(id).await
-    }
-    async fn delete(&self, id: Uuid) -> Result<u64, sqlx::Error> {
-      self.as_ref().delete(id).await
-    }
-  }
-
   /// レート制限（書き込み）のテスト
   #[tokio::test]
   async fn test_write_rate_limit() {
@@ -278,12 +938,12 @@ mod tests {
     mock_repo
       .expect_create()
       .times(2) // 2回だけ呼ばれるはず
-      .returning(move |uid, uname, c, _ip, _ua, _al| {
+      .returning(move |uid, uname, c, _ip, _ua, _al, _strokes, _photo| {
         Ok(create_dummy_calligraphy(uid, &uname, &c))
       });
 
     // MockをArcでラップしてClone可能にする
-    let service = CalligraphyService::new(Arc::new(mock_repo));
+    let service = CalligraphyService::new(mock_repo);
     let state = State(service); // serviceはCloneされるので、状態（キャッシュ）は共有され
 
     // 1回目: 成功するはず
@@ -293,10 +953,14 @@ mod tests {
       ClientIp(Some("192.168.1.1".parse().unwrap())),
 			UserAgent(None),
 			AcceptLanguage(None),
-      Json(CreateCalligraphyRequest {
-        user_name: user_name.clone(),
-        content: content.clone(),
-      }),
+      CalligraphyForm {
+        request: CreateCalligraphyRequest {
+          user_name: user_name.clone(),
+          content: content.clone(),
+          strokes: None,
+        },
+        photo: None,
+      },
     )
     .await;
     assert!(response1.is_ok());
@@ -308,17 +972,21 @@ mod tests {
       ClientIp(Some("192.168.1.1".parse().unwrap())),
 			UserAgent(None),
 			AcceptLanguage(None),
-      Json(CreateCalligraphyRequest {
-        user_name: user_name.clone(),
-        content: content.clone(),
-      }),
+      CalligraphyForm {
+        request: CreateCalligraphyRequest {
+          user_name: user_name.clone(),
+          content: content.clone(),
+          strokes: None,
+        },
+        photo: None,
+      },
     )
     .await;
 
-    match response2 {
-      Err(AppError::TooManyRequests) => assert!(true),
-      _ => assert!(false, "Should return TooManyRequests error"),
-    }
+    assert!(
+      matches!(response2, Err(AppError::TooManyRequests)),
+      "Should return TooManyRequests error"
+    );
 
     // 3回目: IP B (成功 - 別IPなので通る)
     let res3 = upsert(
@@ -327,10 +995,14 @@ mod tests {
       ClientIp(Some("192.168.1.2".parse().unwrap())), // IPを変更
 			UserAgent(None),
 			AcceptLanguage(None),
-      Json(CreateCalligraphyRequest {
-        user_name: user_name.clone(),
-        content: content.clone(),
-      }),
+      CalligraphyForm {
+        request: CreateCalligraphyRequest {
+          user_name: user_name.clone(),
+          content: content.clone(),
+          strokes: None,
+        },
+        photo: None,
+      },
     )
     .await;
     assert!(res3.is_ok());
@@ -342,23 +1014,27 @@ mod tests {
     let mut mock_repo = MockCalligraphyRepositoryTrait::new();
 
     // 1回目の呼び出しは成功するので、リポジトリが呼ばれる
+    mock_repo
+      .expect_board_state()
+      .times(1)
+      .returning(|_| Ok(dummy_board_state()));
     mock_repo
       .expect_find_all()
       .times(1)
       .returning(|| Ok(vec![]));
 
-    let service = CalligraphyService::new(Arc::new(mock_repo));
+    let service = CalligraphyService::new(mock_repo);
     let state = State(service);
 
     // 1回目: 成功
-    let response1 = list(state.clone(), AuthUser { id: Uuid::new_v4() }, ClientIp(Some("10.0.0.1".parse().unwrap()))).await;
+    let response1 = list(state.clone(), AuthUser { id: Uuid::new_v4() }, ClientIp(Some("10.0.0.1".parse().unwrap())), no_preconditions(), Query(ListParams::default())).await;
     assert!(response1.is_ok());
 
     // 2回目: 失敗 (TooManyRequests)
-    let response2 = list(state.clone(), AuthUser { id: Uuid::new_v4() }, ClientIp(Some("10.0.0.1".parse().unwrap()))).await;
-    match response2 {
-      Err(AppError::TooManyRequests) => assert!(true),
-      _ => assert!(false, "Should return TooManyRequests error"),
-    }
+    let response2 = list(state.clone(), AuthUser { id: Uuid::new_v4() }, ClientIp(Some("10.0.0.1".parse().unwrap())), no_preconditions(), Query(ListParams::default())).await;
+    assert!(
+      matches!(response2, Err(AppError::TooManyRequests)),
+      "Should return TooManyRequests error"
+    );
   }
 }
@@ -0,0 +1,207 @@
+//! 条件付きGET (ETag / Last-Modified) の共通処理
+
+use axum::{
+  http::{header, HeaderMap, HeaderValue, StatusCode},
+  response::{IntoResponse, Response},
+};
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime, PrimitiveDateTime};
+
+use crate::extractors::Preconditions;
+
+/// Cache-Control
+/// is_mine を含みCookieごとに内容が変わるため共有キャッシュには載せず (private)、
+/// ブラウザには毎回ETagで再検証させる (no-cache)
+pub const CACHE_CONTROL: &str = "private, no-cache";
+
+/// 全ユーザー共通の内容 (フィード等) のCache-Control
+/// フィードリーダーの定期的な取得に備え、共有キャッシュにも5分間載せる
+pub const PUBLIC_CACHE_CONTROL: &str = "public, max-age=300";
+
+/// 内容が変わらないURL (内容のハッシュを含むURL) のCache-Control
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// 検証子なしで返すレスポンス (DB障害時の古い内容等) のCache-Control
+/// 最新かどうか確認できない内容のため、どのキャッシュにも保存させない
+pub const NO_STORE_CACHE_CONTROL: &str = "no-store";
+
+/// HTTP-date (IMF-fixdate) 形式 例: `Sun, 06 Nov 1994 08:49:37 GMT`
+const HTTP_DATE: &[FormatItem<'static>] = format_description!(
+  "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// レスポンスのキャッシュ範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheScope {
+  /// Cookieごとに内容が変わる (`Vary: Cookie`)
+  Private,
+  /// 全ユーザー共通の内容
+  Public,
+  /// 全ユーザー共通で、同じURLの内容が変わらない
+  Immutable,
+}
+
+/// レスポンスの検証子
+pub struct Validators {
+  pub etag: String,
+  pub last_modified: Option<OffsetDateTime>,
+  pub scope: CacheScope,
+}
+
+impl Validators {
+  /// リクエストの条件に一致する (クライアントのキャッシュが最新) か判定する
+  ///
+  /// RFC 9110 に従い、If-None-Match がある場合は If-Modified-Since を無視する。
+  pub fn is_not_modified(&self, preconditions: &Preconditions) -> bool {
+    if let Some(if_none_match) = &preconditions.if_none_match {
+      return etag_matches(if_none_match, &self.etag);
+    }
+    match (&preconditions.if_modified_since, self.last_modified) {
+      (Some(since), Some(last_modified)) => parse_http_date(since)
+        // HTTP-dateは秒単位のため、秒未満を切り捨てて比較する
+        .is_some_and(|since| last_modified.unix_timestamp() <= since.unix_timestamp()),
+      _ => false,
+    }
+  }
+
+  /// ETag / Last-Modified / Cache-Control (/ Vary) ヘッダー
+  pub fn headers(&self) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+      headers.insert(header::ETAG, etag);
+    }
+    if let Some(last_modified) = self.last_modified.and_then(format_http_date) {
+      if let Ok(value) = HeaderValue::from_str(&last_modified) {
+        headers.insert(header::LAST_MODIFIED, value);
+      }
+    }
+    match self.scope {
+      CacheScope::Private => {
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL));
+        headers.insert(header::VARY, HeaderValue::from_static("Cookie"));
+      }
+      CacheScope::Public => {
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(PUBLIC_CACHE_CONTROL));
+      }
+      CacheScope::Immutable => {
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(IMMUTABLE_CACHE_CONTROL));
+      }
+    }
+    headers
+  }
+
+  /// 304 Not Modified レスポンス (ボディなし、検証子のヘッダーのみ)
+  pub fn not_modified(&self) -> Response {
+    (StatusCode::NOT_MODIFIED, self.headers()).into_response()
+  }
+}
+
+/// 検証子を確認できない場合のヘッダー (Cache-Control: no-store のみ)
+pub fn no_store_headers() -> HeaderMap {
+  let mut headers = HeaderMap::new();
+  headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(NO_STORE_CACHE_CONTROL));
+  headers
+}
+
+/// If-None-Match の値がETagに一致するか (弱い比較)
+/// `*` やカンマ区切りの複数指定にも対応する
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+  let etag = etag.trim_start_matches("W/");
+  if_none_match
+    .split(',')
+    .map(str::trim)
+    .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// HTTP-date形式に変換する
+pub fn format_http_date(t: OffsetDateTime) -> Option<String> {
+  t.to_offset(time::UtcOffset::UTC).format(HTTP_DATE).ok()
+}
+
+/// HTTP-date形式をパースする (不正な形式はNone = 条件なしとして扱う)
+fn parse_http_date(s: &str) -> Option<OffsetDateTime> {
+  PrimitiveDateTime::parse(s.trim(), HTTP_DATE)
+    .ok()
+    .map(PrimitiveDateTime::assume_utc)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::macros::datetime;
+
+  fn validators() -> Validators {
+    Validators {
+      etag: "\"list-1-2-none\"".to_string(),
+      last_modified: Some(datetime!(2025-01-01 10:00:00.5 UTC)),
+      scope: CacheScope::Private,
+    }
+  }
+
+  fn preconditions(if_none_match: Option<&str>, if_modified_since: Option<&str>) -> Preconditions {
+    Preconditions {
+      if_none_match: if_none_match.map(str::to_string),
+      if_modified_since: if_modified_since.map(str::to_string),
+    }
+  }
+
+  /// If-None-Match の判定
+  #[test]
+  fn test_if_none_match() {
+    let v = validators();
+    assert!(v.is_not_modified(&preconditions(Some("\"list-1-2-none\""), None)));
+    assert!(v.is_not_modified(&preconditions(Some("W/\"list-1-2-none\""), None)));
+    assert!(v.is_not_modified(&preconditions(Some("\"other\", \"list-1-2-none\""), None)));
+    assert!(v.is_not_modified(&preconditions(Some("*"), None)));
+    assert!(!v.is_not_modified(&preconditions(Some("\"other\""), None)));
+    assert!(!v.is_not_modified(&preconditions(None, None)));
+  }
+
+  /// If-Modified-Since の判定 (If-None-Match があればそちらを優先)
+  #[test]
+  fn test_if_modified_since() {
+    let v = validators();
+    let date = "Wed, 01 Jan 2025 10:00:00 GMT";
+    assert!(v.is_not_modified(&preconditions(None, Some(date))));
+    assert!(!v.is_not_modified(&preconditions(None, Some("Wed, 01 Jan 2025 09:59:59 GMT"))));
+    assert!(!v.is_not_modified(&preconditions(None, Some("not a date"))));
+    assert!(!v.is_not_modified(&preconditions(Some("\"other\""), Some(date))));
+  }
+
+  /// 全ユーザー共通の内容は共有キャッシュに載せ、Cookieで区別しないこと
+  #[test]
+  fn test_cache_scope_headers() {
+    let private = validators().headers();
+    assert_eq!(private[header::CACHE_CONTROL], CACHE_CONTROL);
+    assert_eq!(private[header::VARY], "Cookie");
+
+    let public = Validators {
+      scope: CacheScope::Public,
+      ..validators()
+    }
+    .headers();
+    assert_eq!(public[header::CACHE_CONTROL], PUBLIC_CACHE_CONTROL);
+    assert!(public.get(header::VARY).is_none());
+    assert!(public.get(header::ETAG).is_some());
+
+    let immutable = Validators {
+      scope: CacheScope::Immutable,
+      ..validators()
+    }
+    .headers();
+    assert_eq!(immutable[header::CACHE_CONTROL], IMMUTABLE_CACHE_CONTROL);
+    assert!(immutable.get(header::VARY).is_none());
+  }
+
+  /// HTTP-dateの変換
+  #[test]
+  fn test_http_date() {
+    assert_eq!(
+      format_http_date(datetime!(1994-11-06 08:49:37 UTC)).unwrap(),
+      "Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+    assert_eq!(
+      parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+      Some(datetime!(1994-11-06 08:49:37 UTC))
+    );
+  }
+}
@@ -0,0 +1,131 @@
+use axum::{
+  extract::State,
+  http::{header, StatusCode},
+  response::{IntoResponse, Response},
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+  error::AppError,
+  extractors::{ClientIp, Preconditions},
+  feed::{self, FeedFormat, FeedMeta},
+  handlers::conditional::{no_store_headers, CacheScope, Validators},
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// Atom フィード (`feed.atom`)
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/feed.atom",
+  tag = "feed",
+  operation_id = "getAtomFeed",
+  responses(
+    (status = 200, description = "新着の書き初めのフィード", content_type = "application/atom+xml"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn atom<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  ClientIp(ip): ClientIp,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  render(&service, ip, &preconditions, FeedFormat::Atom).await
+}
+
+/// RSS 2.0 フィード (`feed.rss`)
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/feed.rss",
+  tag = "feed",
+  operation_id = "getRssFeed",
+  responses(
+    (status = 200, description = "新着の書き初めのフィード", content_type = "application/rss+xml"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn rss<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  ClientIp(ip): ClientIp,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  render(&service, ip, &preconditions, FeedFormat::Rss).await
+}
+
+/// JSON Feed (`feed.json`)
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/feed.json",
+  tag = "feed",
+  operation_id = "getJsonFeed",
+  responses(
+    (status = 200, description = "新着の書き初めのフィード", content_type = "application/feed+json"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn json<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  ClientIp(ip): ClientIp,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  render(&service, ip, &preconditions, FeedFormat::Json).await
+}
+
+/// 新着の書き初めのフィード
+///
+/// Cookieを発行・参照せず、全ユーザー共通の内容を返す (共有キャッシュ可)。
+/// 一覧と同じく ETag / Last-Modified による条件付きGETに対応し、
+/// 変更がなければフィードを生成せずに 304 Not Modified を返す。
+async fn render<R: CalligraphyRepositoryTrait>(
+  service: &CalligraphyService<R>,
+  ip: Option<std::net::IpAddr>,
+  preconditions: &Preconditions,
+  format: FeedFormat,
+) -> Result<Response, AppError> {
+  // 一覧と同じレート制限
+  if let Some(ip_addr) = ip {
+    service.check_read_rate_limit(ip_addr).await?;
+  }
+	// 変更の有無を軽量なクエリで確認 (閲覧者によらないため、ユーザーIDは使わない)
+	// 確認できない (DB障害等) 場合は条件付きGETを省略し、キャッシュ済みの一覧から生成する
+  let state = match service.get_board_state(Uuid::nil()).await {
+    Ok(state) => Some(state),
+    Err(e) => {
+      tracing::warn!("Skipping validators for feed: {:?}", e);
+      None
+    }
+  };
+  let validators = state.as_ref().map(|state| Validators {
+    etag: state.feed_etag(format.extension()),
+    last_modified: state.last_modified(),
+    scope: CacheScope::Public,
+  });
+  if let Some(validators) = validators.as_ref().filter(|v| v.is_not_modified(preconditions)) {
+    return Ok(validators.not_modified());
+  }
+
+  let board = service.get_feed().await?;
+  let updated = match &state {
+    Some(state) => state.last_modified(),
+    None => board.iter().map(|c| c.updated_at).max(),
+  };
+  let meta = FeedMeta {
+    base_url: service.public_base_url(),
+    updated: updated.unwrap_or(OffsetDateTime::UNIX_EPOCH),
+  };
+  let body = feed::render(format, &feed::latest(&board), &meta);
+  let headers = validators.map_or_else(no_store_headers, |v| v.headers());
+  Ok(
+    (
+      StatusCode::OK,
+      headers,
+      [(header::CONTENT_TYPE, format.content_type())],
+      body,
+    )
+      .into_response(),
+  )
+}
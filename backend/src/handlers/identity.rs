@@ -0,0 +1,85 @@
+use axum::{
+  extract::State,
+  http::{header, StatusCode},
+  response::IntoResponse,
+  Extension, Json,
+};
+use tower_cookies::Cookies;
+
+use crate::{
+  error::AppError,
+  extractors::{self, AuthUser, ClientIp, UserAgent},
+  models::transfer::ClaimRequest,
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// 別のブラウザへ書き初めを引き継ぐための引き継ぎコードを発行する
+///
+/// コードは一度しか表示しないため、キャッシュさせない。
+#[utoipa::path(
+  post,
+  path = "/api/identity/transfer-code",
+  tag = "identity",
+  operation_id = "issueTransferCode",
+  responses(
+    (status = 200, description = "発行した引き継ぎコード", body = crate::models::transfer::TransferCodeResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 404, description = "書き初めを投稿していない", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn issue_transfer_code<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  ClientIp(ip): ClientIp,
+) -> Result<impl IntoResponse, AppError> {
+  if let Some(ip_addr) = ip {
+    service.check_write_rate_limit(ip_addr).await?;
+  }
+
+  let code = service.issue_transfer_code(auth_user.id).await?;
+  Ok((StatusCode::OK, [(header::CACHE_CONTROL, "no-store")], Json(code)))
+}
+
+/// 引き継ぎコードを使い、このブラウザのセッションを引き継いだ書き初めのユーザーに切り替える
+#[utoipa::path(
+  post,
+  path = "/api/identity/claim",
+  tag = "identity",
+  operation_id = "claimTransferCode",
+  request_body = ClaimRequest,
+  responses(
+    (status = 200, description = "引き継いだ書き初め", body = crate::models::calligraphy::CalligraphyResponse),
+    (status = 400, description = "コードの形式が不正、存在しない、期限切れ、または使用済み", body = crate::error::ErrorResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 409, description = "このブラウザに既に書き初めがある", body = crate::error::ErrorResponse),
+    (status = 429, description = "試行回数の上限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn claim<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  ClientIp(ip): ClientIp,
+  UserAgent(user_agent): UserAgent,
+  // tower-cookies の Cookies はこのaxumの版では直接取り出せないため、extensionから取り出す
+  Extension(cookies): Extension<Cookies>,
+  Json(payload): Json<ClaimRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  // 総当たり対策として、成否にかかわらず試行回数を制限する
+  service.check_claim_rate_limit(ip).await?;
+
+  let calligraphy = service.claim_transfer_code(auth_user.id, &payload.code).await?;
+  // AuthUser が発行・確認したこのブラウザのセッションは、引き継ぎ後のセッションに置き換える
+  let current = cookies.get(extractors::SESSION_COOKIE_NAME);
+  let token = service
+    .switch_session(current.as_ref().map(|c| c.value()), calligraphy.user_id, user_agent.as_deref())
+    .await?;
+  extractors::set_session_cookie(&cookies, token);
+
+  Ok((
+    StatusCode::OK,
+    [(header::CACHE_CONTROL, "no-store")],
+    Json(calligraphy.to_response(true)),
+  ))
+}
@@ -0,0 +1,72 @@
+use axum::{
+  extract::{Path, State},
+  http::{header, StatusCode},
+  response::{IntoResponse, Redirect, Response},
+};
+use uuid::Uuid;
+
+use crate::{
+  error::AppError,
+  extractors::Preconditions,
+  handlers::conditional::{CacheScope, Validators},
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// フォントを読み込めない場合に代わりに返すサイト共通の画像 (フロントエンドの静的ファイル)
+const FALLBACK_IMAGE: &str = "/ogp.png";
+
+/// 書き初めごとのOGP画像 (1200x630 PNG)
+///
+/// 画像は更新日時ごとにキャッシュされ、ETag / Last-Modified による条件付きGETにも対応する。
+/// 存在しない公開用IDや不正なIDは404を返す。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/{public_id}/ogp.png",
+  tag = "media",
+  operation_id = "getOgpImage",
+  params(
+    ("public_id" = Uuid, Path, description = "書き初めの公開用ID"),
+  ),
+  responses(
+    (status = 200, description = "OGP画像 (1200x630)", content_type = "image/png"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 307, description = "OGP画像を生成しない設定 (サイト共通の画像へのリダイレクト)"),
+    (status = 404, description = "書き初めが存在しない、または公開用IDが不正", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn image<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Path(public_id): Path<String>,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  let public_id = Uuid::parse_str(&public_id).map_err(|_| AppError::NotFound)?;
+  let entry = service.get_public(public_id).await?;
+  if !service.ogp().is_enabled() {
+    return Ok(Redirect::temporary(FALLBACK_IMAGE).into_response());
+  }
+
+  let validators = Validators {
+    etag: format!(
+      "\"ogp-{}-{}\"",
+      entry.public_id.simple(),
+      entry.updated_at.unix_timestamp_nanos()
+    ),
+    last_modified: Some(entry.updated_at),
+    scope: CacheScope::Public,
+  };
+  if validators.is_not_modified(&preconditions) {
+    return Ok(validators.not_modified());
+  }
+
+  let png = service.ogp().get_or_render(&entry).await?;
+  Ok(
+    (
+      StatusCode::OK,
+      validators.headers(),
+      [(header::CONTENT_TYPE, "image/png")],
+      png,
+    )
+      .into_response(),
+  )
+}
@@ -0,0 +1,198 @@
+use axum::{
+  extract::{Path, State},
+  http::StatusCode,
+  response::IntoResponse,
+  Extension, Json,
+};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::{
+  error::AppError,
+  extractors::{self, AuthUser, ClientIp, UserAgent, SESSION_COOKIE_NAME},
+  models::passkey::{
+    FinishAuthenticationRequest, FinishRegistrationRequest, PasskeyResponse, StartAuthenticationRequest,
+    StartAuthenticationResponse, StartRegistrationResponse,
+  },
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// パスキーの登録を開始する (自分の書き初めにパスキーを追加する)
+#[utoipa::path(
+  post,
+  path = "/api/passkeys/register/start",
+  tag = "passkeys",
+  operation_id = "startPasskeyRegistration",
+  responses(
+    (status = 200, description = "`navigator.credentials.create()` に渡すオプション", body = StartRegistrationResponse),
+    (status = 400, description = "パスキーを受け付けない設定", body = crate::error::ErrorResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 404, description = "書き初めを投稿していない", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn start_registration<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  ClientIp(ip): ClientIp,
+) -> Result<impl IntoResponse, AppError> {
+  if let Some(ip_addr) = ip {
+    service.check_write_rate_limit(ip_addr).await?;
+  }
+
+  let (ceremony_id, options) = service.start_passkey_registration(auth_user.id).await?;
+  Ok(Json(StartRegistrationResponse { ceremony_id, options }))
+}
+
+/// パスキーの登録を完了する
+#[utoipa::path(
+  post,
+  path = "/api/passkeys/register/finish",
+  tag = "passkeys",
+  operation_id = "finishPasskeyRegistration",
+  request_body = FinishRegistrationRequest,
+  responses(
+    (status = 201, description = "登録したパスキー", body = PasskeyResponse),
+    (status = 400, description = "`ceremony_id` が存在しない・期限切れ・使用済み、または検証に失敗した", body = crate::error::ErrorResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 404, description = "書き初めが削除された", body = crate::error::ErrorResponse),
+    (status = 409, description = "このパスキーは登録済み", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn finish_registration<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  Json(payload): Json<FinishRegistrationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let record = service
+    .finish_passkey_registration(auth_user.id, payload.ceremony_id, &payload.credential)
+    .await?;
+  Ok((StatusCode::CREATED, Json(record.to_response())))
+}
+
+/// パスキーでのログインを開始する (ログインする書き初めを公開用IDで指定する)
+///
+/// 書き初めが存在しない・パスキーが登録されていない場合も、同じ形の200を返す (完了は400になる)。
+#[utoipa::path(
+  post,
+  path = "/api/passkeys/login/start",
+  tag = "passkeys",
+  operation_id = "startPasskeyAuthentication",
+  request_body = StartAuthenticationRequest,
+  responses(
+    (status = 200, description = "`navigator.credentials.get()` に渡すオプション", body = StartAuthenticationResponse),
+    (status = 400, description = "パスキーを受け付けない設定", body = crate::error::ErrorResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn start_authentication<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  ClientIp(ip): ClientIp,
+  Json(payload): Json<StartAuthenticationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  if let Some(ip_addr) = ip {
+    service.check_write_rate_limit(ip_addr).await?;
+  }
+
+  let (ceremony_id, options) = service.start_passkey_authentication(payload.public_id).await?;
+  Ok(Json(StartAuthenticationResponse { ceremony_id, options }))
+}
+
+/// パスキーでのログインを完了し、このブラウザのセッションをログインした書き初めのユーザーに切り替える
+#[utoipa::path(
+  post,
+  path = "/api/passkeys/login/finish",
+  tag = "passkeys",
+  operation_id = "finishPasskeyAuthentication",
+  request_body = FinishAuthenticationRequest,
+  responses(
+    (status = 200, description = "ログインした書き初め", body = crate::models::calligraphy::CalligraphyResponse),
+    (status = 400, description = "`ceremony_id` が存在しない・期限切れ・使用済み、または検証に失敗した", body = crate::error::ErrorResponse),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn finish_authentication<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  UserAgent(user_agent): UserAgent,
+  Extension(cookies): Extension<Cookies>,
+  Json(payload): Json<FinishAuthenticationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+  let entry = service
+    .finish_passkey_authentication(payload.ceremony_id, &payload.credential)
+    .await?;
+
+  let current = cookies.get(SESSION_COOKIE_NAME);
+  let token = service
+    .switch_session(current.as_ref().map(|c| c.value()), entry.user_id, user_agent.as_deref())
+    .await?;
+  extractors::set_session_cookie(&cookies, token);
+
+  Ok(Json(entry.to_response(true)))
+}
+
+/// ログアウトする (このブラウザのセッションを削除する)
+#[utoipa::path(
+  post,
+  path = "/api/passkeys/logout",
+  tag = "passkeys",
+  operation_id = "logout",
+  responses(
+    (status = 204, description = "ログアウトした"),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn logout<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Extension(cookies): Extension<Cookies>,
+) -> Result<impl IntoResponse, AppError> {
+  if let Some(cookie) = cookies.get(SESSION_COOKIE_NAME) {
+    service.logout(cookie.value()).await?;
+  }
+  extractors::remove_session_cookie(&cookies);
+  Ok(StatusCode::NO_CONTENT)
+}
+
+/// 登録済みのパスキーの一覧
+#[utoipa::path(
+  get,
+  path = "/api/passkeys",
+  tag = "passkeys",
+  operation_id = "listPasskeys",
+  responses(
+    (status = 200, description = "登録済みのパスキー", body = Vec<PasskeyResponse>),
+  ),
+)]
+pub async fn list<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+) -> Result<impl IntoResponse, AppError> {
+  let records = service.list_passkeys(auth_user.id).await?;
+  let response: Vec<PasskeyResponse> = records.iter().map(|record| record.to_response()).collect();
+  Ok(Json(response))
+}
+
+/// パスキーを削除する
+#[utoipa::path(
+  delete,
+  path = "/api/passkeys/{id}",
+  tag = "passkeys",
+  operation_id = "deletePasskey",
+  params(
+    ("id" = Uuid, Path, description = "パスキーのID"),
+  ),
+  responses(
+    (status = 204, description = "削除した"),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 404, description = "パスキーが存在しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn delete<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+  service.delete_passkey(auth_user.id, id).await?;
+  Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,62 @@
+use axum::{
+  extract::{Path, State},
+  http::{header, StatusCode},
+  response::{IntoResponse, Response},
+};
+
+use crate::{
+  error::AppError,
+  extractors::Preconditions,
+  handlers::conditional::{CacheScope, Validators},
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// 書き初めに添付した写真・サムネイル (`/api/photos/{ファイル名}`)
+///
+/// ファイル名は内容のハッシュのため、同じURLの内容は変わらない。共有キャッシュにも長期間載せる。
+/// 不正なファイル名・存在しないファイルは404を返す。
+#[utoipa::path(
+  get,
+  path = "/api/photos/{key}",
+  tag = "media",
+  operation_id = "getPhoto",
+  params(
+    ("key" = String, Path, description = "写真・サムネイルのファイル名 (レスポンスの `photo.url` / `photo.thumbnail_url` の末尾)"),
+  ),
+  responses(
+    (status = 200, description = "写真 (JPEG / WebP)", content_type = "image/*"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 404, description = "ファイル名が不正、またはファイルが存在しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn image<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Path(key): Path<String>,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  let (bytes, format) = service.photos().get(&key).await?;
+
+  let validators = Validators {
+    etag: format!("\"{}\"", key),
+    last_modified: None,
+    scope: CacheScope::Immutable,
+  };
+  if validators.is_not_modified(&preconditions) {
+    return Ok(validators.not_modified());
+  }
+
+  Ok(
+    (
+      StatusCode::OK,
+      validators.headers(),
+      [
+        (header::CONTENT_TYPE, format.content_type()),
+        // 画像以外として解釈させない
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+      ],
+      bytes,
+    )
+      .into_response(),
+  )
+}
@@ -0,0 +1,25 @@
+use axum::{extract::State, Json};
+
+use crate::{
+  models::retention::RetentionPolicy,
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// 収集したリクエスト情報の保持ポリシー
+///
+/// プライバシーポリシーで保持期間を表示するために公開する。
+#[utoipa::path(
+  get,
+  path = "/api/privacy/retention",
+  tag = "board",
+  operation_id = "getRetentionPolicy",
+  responses(
+    (status = 200, description = "収集したリクエスト情報の保持ポリシー", body = RetentionPolicy),
+  ),
+)]
+pub async fn retention_policy<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+) -> Json<RetentionPolicy> {
+  Json(service.retention_policy())
+}
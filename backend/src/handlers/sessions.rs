@@ -0,0 +1,76 @@
+use axum::{
+  extract::{Path, State},
+  http::{header, StatusCode},
+  response::IntoResponse,
+  Extension, Json,
+};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::{
+  error::AppError,
+  extractors::{self, AuthUser, SESSION_COOKIE_NAME},
+  models::session::SessionResponse,
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::{calligraphy::CalligraphyService, sessions},
+};
+
+/// 自分の有効なセッション (ログインしている端末) の一覧
+#[utoipa::path(
+  get,
+  path = "/api/sessions",
+  tag = "sessions",
+  operation_id = "listSessions",
+  responses(
+    (status = 200, description = "有効なセッション", body = Vec<SessionResponse>),
+  ),
+)]
+pub async fn list<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  Extension(cookies): Extension<Cookies>,
+) -> Result<impl IntoResponse, AppError> {
+  // AuthUser が確認・発行したこのブラウザのセッションに印を付ける
+  let current_hash = cookies
+    .get(SESSION_COOKIE_NAME)
+    .map(|cookie| sessions::hash_token(cookie.value()));
+  let response: Vec<SessionResponse> = service
+    .list_sessions(auth_user.id)
+    .await?
+    .iter()
+    .map(|session| session.to_response(current_hash.as_ref() == Some(&session.token_hash)))
+    .collect();
+  Ok(([(header::CACHE_CONTROL, "no-store")], Json(response)))
+}
+
+/// セッションを削除する (他の端末をログアウトさせる)
+///
+/// このブラウザのセッションを削除した場合はCookieも削除する (ログアウトと同じ)。
+#[utoipa::path(
+  delete,
+  path = "/api/sessions/{id}",
+  tag = "sessions",
+  operation_id = "deleteSession",
+  params(
+    ("id" = Uuid, Path, description = "セッションのID"),
+  ),
+  responses(
+    (status = 204, description = "削除した"),
+    (status = 403, description = "別のオリジンからのリクエスト、CSRFトークンがない・一致しない", body = crate::error::ErrorResponse),
+    (status = 404, description = "セッションが存在しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn delete<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  auth_user: AuthUser,
+  Extension(cookies): Extension<Cookies>,
+  Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+  service.revoke_session(auth_user.id, id).await?;
+  if let Some(cookie) = cookies.get(SESSION_COOKIE_NAME) {
+    if service.resolve_session(cookie.value()).await?.is_none() {
+      extractors::remove_session_cookie(&cookies);
+    }
+  }
+  Ok(StatusCode::NO_CONTENT)
+}
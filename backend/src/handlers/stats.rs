@@ -0,0 +1,41 @@
+use axum::{
+  extract::State,
+  http::header,
+  response::{IntoResponse, Response},
+  Json,
+};
+
+use crate::{
+  error::AppError,
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// 集計結果をブラウザ・CDNにキャッシュさせる時間 (秒)
+const STATS_MAX_AGE: u32 = 60;
+
+/// ボード全体の集計結果
+///
+/// 一覧と同時に読み込まれるため、一覧のレート制限は適用しない (サーバー側でキャッシュする)。
+/// 個人を特定できる情報を含まないため、認証なしで公開キャッシュ可能とする。
+#[utoipa::path(
+  get,
+  path = "/api/stats",
+  tag = "board",
+  operation_id = "getBoardStats",
+  responses(
+    (status = 200, description = "ボード全体の集計結果", body = crate::models::stats::BoardStats),
+  ),
+)]
+pub async fn board_stats<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+) -> Result<Response, AppError> {
+  let stats = service.stats().await?;
+  Ok(
+    (
+      [(header::CACHE_CONTROL, format!("public, max-age={}", STATS_MAX_AGE))],
+      Json(stats.as_ref().clone()),
+    )
+      .into_response(),
+  )
+}
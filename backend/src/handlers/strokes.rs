@@ -0,0 +1,167 @@
+use axum::{
+  extract::{Path, State},
+  http::{header, StatusCode},
+  response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+use crate::{
+  brush,
+  error::AppError,
+  extractors::Preconditions,
+  handlers::conditional::{CacheScope, Validators},
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// 筆跡の返し方
+#[derive(Debug, Clone, Copy)]
+enum StrokesFormat {
+  /// 筆跡データ (`strokes`)
+  Json,
+  /// 1画ずつ再生するSVG (`strokes.svg`)
+  Svg,
+  /// 書き終わった状態のPNG (`strokes.png`)
+  Png,
+}
+
+impl StrokesFormat {
+  fn name(self) -> &'static str {
+    match self {
+      StrokesFormat::Json => "json",
+      StrokesFormat::Svg => "svg",
+      StrokesFormat::Png => "png",
+    }
+  }
+
+  fn content_type(self) -> &'static str {
+    match self {
+      StrokesFormat::Json => "application/json",
+      StrokesFormat::Svg => "image/svg+xml; charset=utf-8",
+      StrokesFormat::Png => "image/png",
+    }
+  }
+}
+
+/// 筆跡データ (JSON)
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/{public_id}/strokes",
+  tag = "media",
+  operation_id = "getStrokes",
+  params(
+    ("public_id" = Uuid, Path, description = "書き初めの公開用ID"),
+  ),
+  responses(
+    (status = 200, description = "筆跡データ", body = crate::models::strokes::Strokes),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 404, description = "書き初めまたは筆跡が存在しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn data<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Path(public_id): Path<String>,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  render(&service, &public_id, &preconditions, StrokesFormat::Json).await
+}
+
+/// 筆跡を1画ずつ再生するSVG
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/{public_id}/strokes.svg",
+  tag = "media",
+  operation_id = "getStrokesSvg",
+  params(
+    ("public_id" = Uuid, Path, description = "書き初めの公開用ID"),
+  ),
+  responses(
+    (status = 200, description = "1画ずつ再生するSVG", content_type = "image/svg+xml"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 404, description = "書き初めまたは筆跡が存在しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn svg<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Path(public_id): Path<String>,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  render(&service, &public_id, &preconditions, StrokesFormat::Svg).await
+}
+
+/// 書き終わった筆跡のPNG画像
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/{public_id}/strokes.png",
+  tag = "media",
+  operation_id = "getStrokesPng",
+  params(
+    ("public_id" = Uuid, Path, description = "書き初めの公開用ID"),
+  ),
+  responses(
+    (status = 200, description = "書き終わった筆跡のPNG画像", content_type = "image/png"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 404, description = "書き初めまたは筆跡が存在しない", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn png<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Path(public_id): Path<String>,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  render(&service, &public_id, &preconditions, StrokesFormat::Png).await
+}
+
+/// 書き初めの筆跡
+///
+/// Cookieを発行・参照せず共有キャッシュ可とする。書き初めまたは筆跡がなければ404を返す。
+/// 書き初めを更新すると筆跡も置き換わるため、更新日時から ETag / Last-Modified を作る。
+async fn render<R: CalligraphyRepositoryTrait>(
+  service: &CalligraphyService<R>,
+  public_id: &str,
+  preconditions: &Preconditions,
+  format: StrokesFormat,
+) -> Result<Response, AppError> {
+  let public_id = Uuid::parse_str(public_id).map_err(|_| AppError::NotFound)?;
+  let entry = service.get_public(public_id).await?;
+  let validators = Validators {
+    etag: format!(
+      "\"strokes-{}-{}-{}\"",
+      format.name(),
+      entry.public_id.simple(),
+      entry.updated_at.unix_timestamp_nanos()
+    ),
+    last_modified: Some(entry.updated_at),
+    scope: CacheScope::Public,
+  };
+  if validators.is_not_modified(preconditions) {
+    return Ok(validators.not_modified());
+  }
+
+  let strokes = service.get_strokes(&entry).await?;
+  let body = match format {
+    StrokesFormat::Json => serde_json::to_vec(strokes.as_ref()).map_err(|e| {
+      tracing::error!("Failed to serialize strokes: {}", e);
+      AppError::Internal
+    })?,
+    StrokesFormat::Svg => brush::render_svg(&strokes).into_bytes(),
+    // 描画はCPUを使うため、非同期ランタイムのスレッドを塞がないようにする
+    StrokesFormat::Png => tokio::task::spawn_blocking(move || brush::render_png(&strokes))
+      .await
+      .map_err(|e| e.to_string())
+      .and_then(|result| result.map_err(|e| e.to_string()))
+      .map_err(|e| {
+        tracing::error!("Failed to render strokes: {}", e);
+        AppError::Internal
+      })?,
+  };
+  Ok(
+    (
+      StatusCode::OK,
+      validators.headers(),
+      [(header::CONTENT_TYPE, format.content_type())],
+      body,
+    )
+      .into_response(),
+  )
+}
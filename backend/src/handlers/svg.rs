@@ -0,0 +1,74 @@
+use axum::{
+  extract::{Path, State},
+  http::{header, StatusCode},
+  response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+
+use crate::{
+  error::AppError,
+  extractors::Preconditions,
+  handlers::conditional::{CacheScope, Validators},
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::{calligraphy::CalligraphyService, stats::calligraphy_year},
+  svg,
+  tategaki::Card,
+};
+
+/// 書き初めの縦書きSVG (`{public_id}.svg`)
+///
+/// ブログへの埋め込みなどに使えるよう、Cookieを発行・参照せず共有キャッシュ可とする。
+/// ETag / Last-Modified による条件付きGETに対応する。存在しない公開用IDや不正なIDは404を返す。
+#[utoipa::path(
+  get,
+  path = "/api/calligraphy/{public_id}.svg",
+  tag = "media",
+  operation_id = "getCalligraphySvg",
+  params(
+    ("public_id" = Uuid, Path, description = "書き初めの公開用ID"),
+  ),
+  responses(
+    (status = 200, description = "縦書きのSVG", content_type = "image/svg+xml"),
+    (status = 304, description = "変更なし (条件付きGET)"),
+    (status = 404, description = "書き初めが存在しない、または公開用IDが不正", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn image<R: CalligraphyRepositoryTrait>(
+  State(service): State<CalligraphyService<R>>,
+  Path(file): Path<String>,
+  preconditions: Preconditions,
+) -> Result<Response, AppError> {
+  let public_id = file
+    .strip_suffix(".svg")
+    .and_then(|id| Uuid::parse_str(id).ok())
+    .ok_or(AppError::NotFound)?;
+  let entry = service.get_public(public_id).await?;
+
+  let validators = Validators {
+    etag: format!(
+      "\"svg-{}-{}\"",
+      entry.public_id.simple(),
+      entry.updated_at.unix_timestamp_nanos()
+    ),
+    last_modified: Some(entry.updated_at),
+    scope: CacheScope::Public,
+  };
+  if validators.is_not_modified(&preconditions) {
+    return Ok(validators.not_modified());
+  }
+
+  let body = svg::render(&Card {
+    content: &entry.content,
+    user_name: &entry.user_name,
+    year: calligraphy_year(entry.created_at),
+  });
+  Ok(
+    (
+      StatusCode::OK,
+      validators.headers(),
+      [(header::CONTENT_TYPE, "image/svg+xml; charset=utf-8")],
+      body,
+    )
+      .into_response(),
+  )
+}
@@ -0,0 +1,241 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+  extract::{
+    ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    State,
+  },
+  response::IntoResponse,
+};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+  error::AppError,
+  extractors::{ClientIp, SessionUser},
+  models::board::{ClientMessage, ServerMessage},
+  repositories::db_repository::CalligraphyRepositoryTrait,
+  services::calligraphy::CalligraphyService,
+};
+
+/// サーバーからPingを送る間隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+/// この時間クライアントから何も届かなければ切断する
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+/// クライアントから受け付けるメッセージの最大サイズ (バイト)
+const MAX_MESSAGE_SIZE: usize = 1024;
+/// 接続ごとのメッセージレート制限 (1秒あたり)
+const MESSAGES_PER_SEC: f64 = 2.0;
+/// 接続ごとのメッセージレート制限 (瞬間的な上限)
+const MESSAGE_BURST: u32 = 5;
+/// レート制限の超過がこの回数に達したら切断する
+const MAX_RATE_VIOLATIONS: u32 = 10;
+
+// --- Handlers ---
+
+/// ライブボード (WebSocket)
+///
+/// 書き初めの変更イベントと在室人数を配信し、クライアントからの「入力中」通知を受け付ける。
+/// 認証は他のエンドポイントと同じくセッションのクッキー (`calli_session`) で行う。
+/// 許可したオリジン以外からのハンドシェイクは、CSRF対策のミドルウェア (`csrf::protect`) がセッションの発行前に拒否する。
+#[utoipa::path(
+  get,
+  path = "/api/ws",
+  tag = "realtime",
+  operation_id = "connectBoard",
+  responses(
+    (status = 101, description = "WebSocketに切り替える (メッセージは ClientMessage / ServerMessage のJSON)"),
+    (status = 403, description = "Origin が許可したオリジンでない", body = crate::error::ErrorResponse),
+    (status = 429, description = "レート制限を超えた", body = crate::error::ErrorResponse),
+  ),
+)]
+pub async fn board<R: CalligraphyRepositoryTrait + 'static>(
+  State(service): State<CalligraphyService<R>>,
+  SessionUser(auth_user): SessionUser,
+  ClientIp(ip): ClientIp,
+  ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, AppError> {
+  // 接続の確立は読み込みとしてレート制限する
+  if let Some(ip_addr) = ip {
+    service.check_read_rate_limit(ip_addr).await?;
+  }
+
+  Ok(
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+      .on_upgrade(move |socket| handle_socket(socket, service, auth_user.id)),
+  )
+}
+
+/// 1接続分の処理
+/// 切断されるまで、受信・イベント配信・在室人数の配信・ハートビートを多重化する
+async fn handle_socket<R: CalligraphyRepositoryTrait>(
+  mut socket: WebSocket,
+  service: CalligraphyService<R>,
+  user_id: Uuid,
+) {
+  let presence = service.presence().clone();
+  // Dropで在室人数から外れる
+  let _guard = presence.join(user_id);
+  let mut presence_rx = presence.subscribe();
+  let mut events = service.subscribe(None).receiver;
+
+  let mut limiter = MessageRateLimiter::new(MESSAGES_PER_SEC, MESSAGE_BURST);
+  let mut violations = 0;
+  let mut last_seen = Instant::now();
+  let mut heartbeat =
+    tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+
+  // 接続直後に現在の在室人数を送る
+  let snapshot = *presence_rx.borrow_and_update();
+  let initial = ServerMessage::Presence {
+    online: snapshot.online,
+    typing: snapshot.typing,
+  };
+  if send_json(&mut socket, &initial).await.is_err() {
+    return;
+  }
+
+  loop {
+    let outgoing = tokio::select! {
+      incoming = socket.recv() => {
+        let Some(Ok(message)) = incoming else { break };
+        last_seen = Instant::now();
+        // 全てのフレームをレート制限の対象にする (バイナリやPingでも応答を生じさせられるため)
+        if !matches!(message, Message::Close(_)) && !limiter.try_acquire() {
+          violations += 1;
+          if violations >= MAX_RATE_VIOLATIONS {
+            let _ = close(&mut socket, close_code::POLICY, "Too many messages").await;
+            break;
+          }
+          continue;
+        }
+        match message {
+          Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(ClientMessage::Typing { active }) => {
+              presence.set_typing(user_id, active);
+              None
+            }
+            Ok(ClientMessage::Ping) => Some(ServerMessage::Pong),
+            Err(_) => Some(ServerMessage::Error {
+              message: "Invalid message".to_string(),
+            }),
+          },
+          Message::Binary(_) => Some(ServerMessage::Error {
+            message: "Binary messages are not supported".to_string(),
+          }),
+          // Pingへの応答 (Pong) はライブラリが自動で返す
+          Message::Ping(_) | Message::Pong(_) => None,
+          Message::Close(_) => break,
+        }
+      }
+      result = events.recv() => match result {
+        Ok(published) => Some(ServerMessage::from_event(&published.event, user_id)),
+        // 受信が追いつかずイベントを取りこぼした場合
+        Err(RecvError::Lagged(_)) => Some(ServerMessage::Reset),
+        Err(RecvError::Closed) => break,
+      },
+      changed = presence_rx.changed() => {
+        if changed.is_err() {
+          break;
+        }
+        let snapshot = *presence_rx.borrow_and_update();
+        Some(ServerMessage::Presence {
+          online: snapshot.online,
+          typing: snapshot.typing,
+        })
+      }
+      _ = heartbeat.tick() => {
+        // 期限切れの「入力中」を片付ける (人数が変われば全接続に通知される)
+        presence.sweep();
+        if last_seen.elapsed() > CLIENT_TIMEOUT {
+          let _ = close(&mut socket, close_code::AWAY, "Timed out").await;
+          break;
+        }
+        if socket.send(Message::Ping(Vec::new())).await.is_err() {
+          break;
+        }
+        None
+      }
+    };
+
+    if let Some(message) = outgoing {
+      if send_json(&mut socket, &message).await.is_err() {
+        break;
+      }
+    }
+  }
+}
+
+/// メッセージをJSONで送信する
+async fn send_json(socket: &mut WebSocket, message: &ServerMessage) -> Result<(), axum::Error> {
+  let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+  socket.send(Message::Text(text)).await
+}
+
+/// 理由を付けて接続を閉じる
+async fn close(socket: &mut WebSocket, code: u16, reason: &'static str) -> Result<(), axum::Error> {
+  socket
+    .send(Message::Close(Some(CloseFrame {
+      code,
+      reason: reason.into(),
+    })))
+    .await
+}
+
+/// 接続ごとのメッセージレート制限 (トークンバケット)
+struct MessageRateLimiter {
+  capacity: f64,
+  tokens: f64,
+  refill_per_sec: f64,
+  last_refill: Instant,
+}
+
+impl MessageRateLimiter {
+  /// `per_sec` 件/秒まで、瞬間的には `burst` 件まで許可する
+  fn new(per_sec: f64, burst: u32) -> Self {
+    Self {
+      capacity: burst as f64,
+      tokens: burst as f64,
+      refill_per_sec: per_sec,
+      last_refill: Instant::now(),
+    }
+  }
+
+  /// メッセージを1件消費する。上限を超えていればfalse
+  fn try_acquire(&mut self) -> bool {
+    self.try_acquire_at(Instant::now())
+  }
+
+  fn try_acquire_at(&mut self, now: Instant) -> bool {
+    let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+    self.last_refill = now;
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// トークンバケットのテスト
+  #[test]
+  fn test_rate_limiter() {
+    let mut limiter = MessageRateLimiter::new(2.0, 3);
+    let start = limiter.last_refill;
+
+    assert!(limiter.try_acquire_at(start));
+    assert!(limiter.try_acquire_at(start));
+    assert!(limiter.try_acquire_at(start));
+    assert!(!limiter.try_acquire_at(start));
+
+    // 0.5秒で1件回復する
+    assert!(limiter.try_acquire_at(start + Duration::from_millis(500)));
+    assert!(!limiter.try_acquire_at(start + Duration::from_millis(500)));
+  }
+}
@@ -0,0 +1,223 @@
+//! リクエストの制限とセキュリティ関連のヘッダー
+//!
+//! nginxを通さずに動かす場合 (別のプロキシの後ろ・直接公開) でも同じ防御になるよう、次をバックエンドで行う。
+//! 1. ヘッダーの合計サイズの制限 (超えると431)
+//! 2. リクエストボディの受信のタイムアウト (超えると408)
+//! 3. レスポンスを返し始めるまでのタイムアウト (超えると503)
+//! 4. セキュリティ関連のヘッダー (CSP・`X-Frame-Options`・`nosniff` 等) の付与
+//!
+//! ボディのサイズの上限は `DefaultBodyLimit` で設定する (`create_app` を参照)。
+//! タイムアウトはレスポンスのヘッダーを返すまでが対象のため、SSE・WebSocketの接続は切らない。
+
+use std::{
+  future::Future,
+  pin::Pin,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  task::{Context, Poll},
+  time::Duration,
+};
+
+use axum::{
+  body::{Body, BodyDataStream, Bytes, HttpBody},
+  extract::{Request, State},
+  http::{header, HeaderMap, HeaderName, HeaderValue},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use tokio::time::Sleep;
+use tokio_stream::Stream;
+
+use crate::config::Config;
+use crate::error::AppError;
+
+/// APIのレスポンス用のCSP (HTMLを返さないため、何も読み込ませない。SVGの直接表示のためインラインのスタイルのみ許可)
+const CONTENT_SECURITY_POLICY: &str =
+  "default-src 'none'; style-src 'unsafe-inline'; frame-ancestors 'none'; base-uri 'none'; form-action 'none'";
+/// 使わないブラウザの機能を無効にする (nginxの設定と同じ)
+const PERMISSIONS_POLICY: &str = "camera=(), microphone=(), geolocation=(), payment=(), usb=()";
+/// HTTPSで公開する場合のHSTS (1年)
+const STRICT_TRANSPORT_SECURITY: &str = "max-age=31536000";
+
+/// リクエストの制限とレスポンスに付けるヘッダー
+#[derive(Clone, Debug)]
+pub struct HardeningPolicy {
+  /// 付けるヘッダー (ハンドラーが設定したものは上書きしない)
+  headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+  max_header_bytes: usize,
+  request_timeout: Option<Duration>,
+  body_timeout: Option<Duration>,
+}
+
+impl HardeningPolicy {
+  pub fn from_config(config: &Config) -> Self {
+    let mut headers = Vec::new();
+    if config.security_headers {
+      headers.extend([
+        (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        (header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+        (header::REFERRER_POLICY, HeaderValue::from_static("strict-origin-when-cross-origin")),
+        (header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(CONTENT_SECURITY_POLICY)),
+        (HeaderName::from_static("permissions-policy"), HeaderValue::from_static(PERMISSIONS_POLICY)),
+      ]);
+      // HTTPで公開している場合に付けると、ブラウザがHTTPSに切り替えてアクセスできなくなる
+      if config.public_base_url.starts_with("https://") {
+        headers.push((header::STRICT_TRANSPORT_SECURITY, HeaderValue::from_static(STRICT_TRANSPORT_SECURITY)));
+      }
+    }
+    let timeout = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+    Self {
+      headers: Arc::new(headers),
+      max_header_bytes: config.max_header_bytes,
+      request_timeout: timeout(config.request_timeout_secs),
+      body_timeout: timeout(config.request_body_timeout_secs),
+    }
+  }
+
+  /// ヘッダーの合計サイズを検証する
+  pub fn check_headers(&self, headers: &HeaderMap) -> Result<(), AppError> {
+    let bytes: usize = headers
+      .iter()
+      .map(|(name, value)| name.as_str().len() + value.len())
+      .sum();
+    if bytes > self.max_header_bytes {
+      return Err(AppError::RequestHeaderFieldsTooLarge);
+    }
+    Ok(())
+  }
+
+  /// ハンドラーが設定していないヘッダーを付ける
+  fn apply_headers(&self, response: &mut Response) {
+    for (name, value) in self.headers.iter() {
+      response.headers_mut().entry(name).or_insert_with(|| value.clone());
+    }
+  }
+}
+
+/// リクエストの制限とヘッダーの付与を行うミドルウェア (CORSの内側・CSRF対策の外側に追加する)
+pub async fn harden(State(policy): State<HardeningPolicy>, request: Request, next: Next) -> Response {
+  if let Err(e) = policy.check_headers(request.headers()) {
+    tracing::warn!("Rejected {} {}: {}", request.method(), request.uri().path(), e);
+    let mut response = e.into_response();
+    policy.apply_headers(&mut response);
+    return response;
+  }
+
+  // ボディの受信が期限を過ぎたら、ボディの読み込みをエラーにして408を返す
+  // (エラーを受け取ったハンドラーは400を返すため、レスポンスを差し替える)
+  let body_timed_out = Arc::new(AtomicBool::new(false));
+  let request = match policy.body_timeout {
+    Some(timeout) if !request.body().is_end_stream() => {
+      let (parts, body) = request.into_parts();
+      let body = Body::from_stream(DeadlineStream {
+        inner: body.into_data_stream(),
+        deadline: Box::pin(tokio::time::sleep(timeout)),
+        timed_out: body_timed_out.clone(),
+      });
+      Request::from_parts(parts, body)
+    }
+    _ => request,
+  };
+
+  let (method, path) = (request.method().clone(), request.uri().path().to_string());
+  let response = match policy.request_timeout {
+    Some(timeout) => tokio::time::timeout(timeout, next.run(request)).await.ok(),
+    None => Some(next.run(request).await),
+  };
+  let mut response = match response {
+    _ if body_timed_out.load(Ordering::Relaxed) => {
+      tracing::warn!("Request body of {} {} timed out", method, path);
+      AppError::RequestTimeout.into_response()
+    }
+    Some(response) => response,
+    None => {
+      tracing::error!("{} {} timed out", method, path);
+      AppError::ServiceUnavailable.into_response()
+    }
+  };
+  policy.apply_headers(&mut response);
+  response
+}
+
+/// 期限を過ぎるとエラーになるリクエストボディ
+struct DeadlineStream {
+  inner: BodyDataStream,
+  deadline: Pin<Box<Sleep>>,
+  timed_out: Arc<AtomicBool>,
+}
+
+impl Stream for DeadlineStream {
+  type Item = Result<Bytes, axum::Error>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    if let Poll::Ready(item) = Pin::new(&mut self.inner).poll_next(cx) {
+      return Poll::Ready(item);
+    }
+    if self.deadline.as_mut().poll(cx).is_ready() {
+      self.timed_out.store(true, Ordering::Relaxed);
+      return Poll::Ready(Some(Err(axum::Error::new("request body timed out"))));
+    }
+    Poll::Pending
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_headers() {
+    let policy = HardeningPolicy::from_config(&Config {
+      max_header_bytes: 32,
+      ..Config::default()
+    });
+    let mut headers = HeaderMap::new();
+    headers.insert("x-short", HeaderValue::from_static("value"));
+    assert!(policy.check_headers(&headers).is_ok());
+    headers.insert("x-long", HeaderValue::from_static("a value that does not fit"));
+    assert!(matches!(
+      policy.check_headers(&headers),
+      Err(AppError::RequestHeaderFieldsTooLarge)
+    ));
+  }
+
+  #[test]
+  fn test_headers() {
+    let names = |config: Config| -> Vec<HeaderName> {
+      HardeningPolicy::from_config(&config)
+        .headers
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect()
+    };
+    let default = names(Config::default());
+    assert!(default.contains(&header::CONTENT_SECURITY_POLICY));
+    assert!(default.contains(&header::X_FRAME_OPTIONS));
+    // HTTPで公開している場合はHSTSを付けない
+    assert!(!default.contains(&header::STRICT_TRANSPORT_SECURITY));
+    assert!(names(Config {
+      public_base_url: "https://kakizome.example".to_string(),
+      ..Config::default()
+    })
+    .contains(&header::STRICT_TRANSPORT_SECURITY));
+    assert!(names(Config {
+      security_headers: false,
+      ..Config::default()
+    })
+    .is_empty());
+  }
+
+  #[test]
+  fn test_apply_headers_keeps_existing() {
+    let policy = HardeningPolicy::from_config(&Config::default());
+    let mut response = Response::new(Body::empty());
+    response
+      .headers_mut()
+      .insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"));
+    policy.apply_headers(&mut response);
+    assert_eq!(response.headers()[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+    assert_eq!(response.headers()[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+  }
+}
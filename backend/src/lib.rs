@@ -1,43 +1,231 @@
+pub mod brush;
+pub mod config;
+pub mod cors;
+pub mod csrf;
 pub mod error;
 pub mod extractors;
+pub mod feed;
+pub mod hardening;
 pub mod handlers;
 pub mod models;
+pub mod ogp;
+pub mod openapi;
+pub mod photo;
 pub mod repositories;
+pub mod search;
 pub mod services;
+pub mod svg;
+pub mod tategaki;
+pub mod telemetry;
+pub mod typescript;
+pub mod validation;
 
 use axum::{
+  extract::DefaultBodyLimit,
+  middleware,
   routing::{delete, get, post},
   Router,
 };
+use config::Config;
+use error::AppError;
+use models::retention::RetentionPolicy;
 use repositories::db_repository::CalligraphyRepository;
 use services::calligraphy::CalligraphyService;
 use sqlx::PgPool;
+use std::time::Duration;
 use tower_cookies::CookieManagerLayer;
+use uuid::Uuid;
 
-pub fn create_app(pool: PgPool) -> Router {
+pub fn create_app(pool: PgPool, config: Config) -> Router {
   // 依存関係の構築 (DI)
   // Pool -> Repository -> Service
   // 起動時に一度だけ構築し、Stateとして注入
-  let repository = CalligraphyRepository::new(pool);
-  let service = CalligraphyService::new(repository);
+  let repository = CalligraphyRepository::new(pool.clone());
+  let service = CalligraphyService::new(repository)
+    .with_retention_policy(RetentionPolicy::after_days(config.retention_days))
+    .with_public_base_url(&config.public_base_url)
+    .with_legacy_cookie_sunset(config.legacy_cookie_sunset)
+    .with_ogp_images(services::ogp::OgpImages::from_config(&config))
+    .with_photos(services::photos::Photos::from_config(&config))
+    .with_passkeys(services::passkeys::Passkeys::from_config(&config));
 
-  Router::new()
+  let csrf_policy = csrf::CsrfPolicy::from_config(&config);
+  let cors_layer = cors::layer(&config);
+  let hardening_policy = hardening::HardeningPolicy::from_config(&config);
+
+  // 保持期間を過ぎたリクエスト情報 (IPアドレス等) の匿名化と、期限切れのセッションの削除を定期的に行う
+  let interval = Duration::from_secs(config.retention_interval_secs);
+  tokio::spawn(services::retention::run_periodically(service.clone(), interval));
+
+  // 複数レプリカ構成の場合、LISTEN/NOTIFYで変更イベントを共有する
+  if config.events_pg_notify {
+    let hub = service.events().clone();
+    tokio::spawn(async move {
+      if let Err(e) = repositories::event_notifier::start(pool, hub).await {
+        tracing::error!("Failed to start event notifier: {:?}", e);
+      }
+    });
+  }
+
+  let app = Router::new()
     .route(
       "/api/calligraphy",
-      post(handlers::calligraphy::upsert::<CalligraphyRepository>),
+      post(handlers::calligraphy::upsert::<CalligraphyRepository>)
+        // 写真を添付できるよう、既定の上限 (2MB) より大きなボディを受け付ける
+        .layer(DefaultBodyLimit::max(extractors::CALLIGRAPHY_FORM_MAX_BYTES)),
     )
     .route(
       "/api/calligraphy",
       get(handlers::calligraphy::list::<CalligraphyRepository>),
     )
+    .route(
+      "/api/calligraphy/search",
+      get(handlers::calligraphy::search::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/feed.atom",
+      get(handlers::feed::atom::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/feed.rss",
+      get(handlers::feed::rss::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/feed.json",
+      get(handlers::feed::json::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/stream",
+      get(handlers::calligraphy::stream::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/:public_id/ogp.png",
+      get(handlers::ogp::image::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/:public_id/strokes",
+      get(handlers::strokes::data::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/:public_id/strokes.svg",
+      get(handlers::strokes::svg::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/calligraphy/:public_id/strokes.png",
+      get(handlers::strokes::png::<CalligraphyRepository>),
+    )
     .route(
       "/api/calligraphy/me",
       get(handlers::calligraphy::get::<CalligraphyRepository>),
     )
+    // `{public_id}.svg` (固定のパスが優先される)
+    .route(
+      "/api/calligraphy/:file",
+      get(handlers::svg::image::<CalligraphyRepository>),
+    )
     .route(
       "/api/calligraphy/me",
       delete(handlers::calligraphy::delete::<CalligraphyRepository>),
     )
+    .route(
+      "/api/calligraphy/me/export",
+      get(handlers::calligraphy::export::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/identity/transfer-code",
+      post(handlers::identity::issue_transfer_code::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/identity/claim",
+      post(handlers::identity::claim::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys",
+      get(handlers::passkeys::list::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys/:id",
+      delete(handlers::passkeys::delete::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys/register/start",
+      post(handlers::passkeys::start_registration::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys/register/finish",
+      post(handlers::passkeys::finish_registration::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys/login/start",
+      post(handlers::passkeys::start_authentication::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys/login/finish",
+      post(handlers::passkeys::finish_authentication::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/passkeys/logout",
+      post(handlers::passkeys::logout::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/sessions",
+      get(handlers::sessions::list::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/sessions/:id",
+      delete(handlers::sessions::delete::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/photos/:key",
+      get(handlers::photos::image::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/stats",
+      get(handlers::stats::board_stats::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/privacy/retention",
+      get(handlers::privacy::retention_policy::<CalligraphyRepository>),
+    )
+    .route(
+      "/api/ws",
+      get(handlers::ws::board::<CalligraphyRepository>),
+    )
+    // OpenAPIのドキュメント (`/api/openapi.json`, 設定により `/api/docs`)
+    .merge(openapi::router(&config))
     .with_state(service)	// StateとしてServiceを注入
+    .layer(DefaultBodyLimit::max(config.body_max_bytes))	// リクエストボディの上限 (ルートごとの設定が優先される)
+    .layer(middleware::from_fn_with_state(csrf_policy, csrf::protect))	// CSRF対策 (Cookieを使うためCookieManagerLayerの内側に置く)
     .layer(CookieManagerLayer::new())	// Cookie管理ミドルウェアの追加 CookieManager: レスポンスが返される直前にSet-Cookieヘッダーを追加する
+    .layer(middleware::from_fn_with_state(hardening_policy, hardening::harden));	// ヘッダーのサイズ・タイムアウトの制限、セキュリティ関連のヘッダー
+
+  // CORS (別のオリジンで配信するフロントエンド用)。プリフライトやエラーのレスポンスにもヘッダーを付けるため外側に置く
+  let app = match cors_layer {
+    Some(cors_layer) => app.layer(cors_layer),
+    None => app,
+  };
+
+  // リクエストIDとログのspan。全てのレスポンスを記録するため一番外側に置く
+  app.layer(middleware::from_fn(telemetry::trace_request))
+}
+
+/// 書き初めのユーザーの全てのセッションを無効にする (管理用コマンド `server revoke-sessions <public_id>` 用)
+/// 戻り値は無効にしたセッションの数
+pub async fn run_revoke_sessions(pool: PgPool, public_id: Uuid) -> Result<u64, AppError> {
+  CalligraphyService::new(CalligraphyRepository::new(pool))
+    .revoke_user(public_id)
+    .await
+}
+
+/// 匿名化ジョブを1回だけ実行する (管理用コマンド `server retention` 用)
+/// 期限切れのセッションも削除する。戻り値は匿名化した行数
+pub async fn run_retention_once(pool: PgPool, config: Config) -> Result<u64, AppError> {
+  let policy = RetentionPolicy::after_days(config.retention_days);
+  if !policy.enabled {
+    tracing::warn!("RETENTION_DAYS is 0; skipping anonymization");
+  }
+  let service = CalligraphyService::new(CalligraphyRepository::new(pool)).with_retention_policy(policy);
+  let rows = service.apply_retention().await?;
+  service.purge_expired_sessions().await?;
+  Ok(rows)
 }
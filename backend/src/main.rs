@@ -1,23 +1,23 @@
-use server::create_app;
+use server::{config::Config, create_app, run_retention_once, run_revoke_sessions, telemetry, typescript};
 use sqlx::postgres::PgPoolOptions;
 use std::net::SocketAddr;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 	const MAX_CONNECTIONS: u32 = 30;
 	const SERVER_PORT: u16 = 3000;
 
+  // 開発用コマンド: `server export-types <path>` でフロントエンド用のTypeScriptの型定義を書き出して終了する (DBは使わない)
+  if std::env::args().nth(1).as_deref() == Some("export-types") {
+    let path = std::env::args().nth(2).ok_or("Usage: server export-types <path>")?;
+    std::fs::write(&path, typescript::render())?;
+    println!("Wrote {}", path);
+    return Ok(());
+  }
 
-  // 構造化ログの初期化
-  // RUST_LOG環境変数でレベル制御可能 (デフォルトは debug)
-  tracing_subscriber::registry()
-    .with(
-      tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| "server=debug".into()),
-    )
-    .with(tracing_subscriber::fmt::layer())
-    .init();
+  // 構造化ログの初期化 (LOG_FORMAT=json でJSON出力)
+  let config = Config::from_env();
+  let _telemetry = telemetry::init(&config);	// dropするときに残りのトレースを送る
 
   // DB接続プールの作成
 	// poolは内部的にArc(参照カウンタ)で共有される
@@ -28,7 +28,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     .await?;
   tracing::info!("Connected to Database!");
 
-  let app = create_app(pool);
+  // 管理用コマンド: `server retention` で匿名化ジョブを1回だけ実行して終了する
+  if std::env::args().nth(1).as_deref() == Some("retention") {
+    let rows = run_retention_once(pool, config).await?;
+    tracing::info!("Retention finished: {} rows anonymized", rows);
+    return Ok(());
+  }
+
+  // 管理用コマンド: `server revoke-sessions <public_id>` で書き初めのユーザー (利用停止にするユーザー) の全セッションを無効にする
+  if std::env::args().nth(1).as_deref() == Some("revoke-sessions") {
+    let public_id = std::env::args()
+      .nth(2)
+      .and_then(|arg| uuid::Uuid::parse_str(&arg).ok())
+      .ok_or("Usage: server revoke-sessions <public_id>")?;
+    let revoked = run_revoke_sessions(pool, public_id).await?;
+    tracing::info!("Revoked {} sessions of {}", revoked, public_id);
+    return Ok(());
+  }
+
+  let app = create_app(pool, config);
 
 	// サーバー起動
 	let addr = SocketAddr::from(([0, 0, 0, 0], SERVER_PORT));
@@ -1 +1,13 @@
-pub mod calligraphy;
\ No newline at end of file
+pub mod board;
+pub mod calligraphy;
+pub mod export;
+pub mod feed;
+pub mod list_query;
+pub mod passkey;
+pub mod photo;
+pub mod retention;
+pub mod search;
+pub mod session;
+pub mod stats;
+pub mod strokes;
+pub mod transfer;
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::calligraphy::{CalligraphyEvent, CalligraphyResponse};
+
+// --- WebSocket メッセージ ---
+// いずれも `type` フィールドで種類を判別するJSON
+
+/// クライアントから受け取るメッセージ
+#[derive(Debug, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+  /// 書き初めを入力中かどうか (入力中は数秒おきに送り直す)
+  Typing { active: bool },
+  /// アプリケーションレベルの死活確認 (pongを返す)
+  Ping,
+}
+
+/// クライアントへ送るメッセージ
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+  /// 新規投稿
+  Created { entry: CalligraphyResponse },
+  /// 投稿の更新
+  Updated { entry: CalligraphyResponse },
+  /// 投稿の削除
+  Deleted { public_id: Uuid, is_mine: bool },
+  /// 取りこぼしがあったため一覧の再取得が必要
+  Reset,
+  /// 在室人数・入力中の人数
+  Presence { online: usize, typing: usize },
+  /// pingへの応答
+  Pong,
+  /// 不正なメッセージなどのエラー通知
+  Error { message: String },
+}
+
+impl ServerMessage {
+  /// 変更イベントを購読者ごとのメッセージに変換する (is_mine は購読者ごとに計算)
+  pub fn from_event(event: &CalligraphyEvent, viewer_id: Uuid) -> Self {
+    match event {
+      CalligraphyEvent::Created(c) => ServerMessage::Created {
+        entry: c.to_response(c.user_id == viewer_id),
+      },
+      CalligraphyEvent::Updated(c) => ServerMessage::Updated {
+        entry: c.to_response(c.user_id == viewer_id),
+      },
+      CalligraphyEvent::Deleted { user_id, public_id } => ServerMessage::Deleted {
+        public_id: *public_id,
+        is_mine: *user_id == viewer_id,
+      },
+    }
+  }
+}
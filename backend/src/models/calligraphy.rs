@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
 use sqlx::types::ipnetwork::IpNetwork;
+use sqlx::types::Json;
 use sqlx::FromRow;
 use time::OffsetDateTime;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::models::photo::{Photo, PhotoResponse};
+use crate::models::strokes::Strokes;
+
 /**
  * 書き初めデータ
  * DBのcalligraphiesテーブルに対応するモデル
@@ -12,10 +17,14 @@ use uuid::Uuid;
 pub struct Calligraphy {
   /// ユーザーID
   pub user_id: Uuid,
+  /// 公開用ID (user_idはCookieの値なので外部に出さない)
+  pub public_id: Uuid,
   /// ユーザー名
   pub user_name: String,
   /// 書き初め内容
   pub content: String,
+  /// 添付した写真 (なければNone)
+  pub photo: Option<Json<Photo>>,
 
   /// IPアドレス (情報収集用)
   #[serde(skip)]
@@ -37,21 +46,36 @@ pub struct Calligraphy {
 
 // --- DTOs (Data Transfer Objects) ---
 /// フロントから受け取る書き初め作成・更新用のリクエストボディ
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateCalligraphyRequest {
+  /// ユーザー名 (最大20文字)
+  #[schema(example = "富士の天然水")]
   pub user_name: String,
+  /// 書き初め内容 (最大50文字・10行)
+  #[schema(example = "今年の抱負は早起きです")]
   pub content: String,
+  /// 手書きの筆跡データ (任意)
+  #[serde(default)]
+  pub strokes: Option<Strokes>,
 }
 
 /// APIレスポンス用のDTO
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CalligraphyResponse {
+  /// 公開用ID
+  pub public_id: Uuid,
+  #[schema(example = "富士の天然水")]
   pub user_name: String,
+  #[schema(example = "今年の抱負は早起きです")]
   pub content: String,
+  /// 添付した写真 (なければnull)
+  #[schema(required = true)]
+  pub photo: Option<PhotoResponse>,
   #[serde(with = "time::serde::iso8601")]
   pub created_at: OffsetDateTime,
   #[serde(with = "time::serde::iso8601")]
   pub updated_at: OffsetDateTime,
+  /// リクエストしたユーザー自身の書き初めか
   pub is_mine: bool,
 }
 
@@ -60,11 +84,118 @@ impl Calligraphy {
 	/// CalligraphyモデルからAPIレスポンス用DTOに変換する
   pub fn to_response(&self, is_mine: bool) -> CalligraphyResponse {
     CalligraphyResponse {
+      public_id: self.public_id,
       user_name: self.user_name.clone(),
       content: self.content.clone(),
+      photo: self.photo.as_ref().map(|photo| photo.to_response()),
       created_at: self.created_at,
       updated_at: self.updated_at,
       is_mine,
     }
   }
-}
\ No newline at end of file
+
+  /// 新規作成されたばかりか (upsertでの更新でないか)
+  /// INSERT時は created_at と updated_at が同じ NOW() になる
+  pub fn is_new(&self) -> bool {
+    self.created_at == self.updated_at
+  }
+}
+
+/// ボード全体の状態 (条件付きGETのETag / Last-Modified の算出用)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardState {
+  /// 書き初めの件数
+  pub count: i64,
+  /// 最終更新日時 (作成・更新)
+  pub max_updated_at: Option<OffsetDateTime>,
+  /// 最終削除日時
+  pub last_deleted_at: Option<OffsetDateTime>,
+  /// リクエストしたユーザー自身の書き初めの公開用ID (is_mine の判定結果を表す)
+  pub mine: Option<Uuid>,
+}
+
+impl BoardState {
+  /// 一覧のETag
+  /// 件数・最終更新日時に加え、is_mine の付き方が変わるようユーザー自身の公開用IDを含める
+  pub fn etag(&self) -> String {
+    format!(
+      "\"list-{}-{}-{}\"",
+      self.count,
+      self.max_updated_at.map_or(0, |t| t.unix_timestamp_nanos()),
+      self.mine.map_or_else(|| "none".to_string(), |id| id.simple().to_string()),
+    )
+  }
+
+  /// フィードのETag
+  /// 全ユーザー共通の内容のため、ユーザー自身の公開用IDは含めない
+  pub fn feed_etag(&self, extension: &str) -> String {
+    format!(
+      "\"feed-{}-{}-{}\"",
+      extension,
+      self.count,
+      self.max_updated_at.map_or(0, |t| t.unix_timestamp_nanos()),
+    )
+  }
+
+  /// 一覧の最終更新日時 (削除も含む)
+  pub fn last_modified(&self) -> Option<OffsetDateTime> {
+    self.max_updated_at.max(self.last_deleted_at)
+  }
+}
+
+impl Calligraphy {
+  /// 個別取得のETag
+  pub fn etag(&self) -> String {
+    format!(
+      "\"me-{}-{}\"",
+      self.public_id.simple(),
+      self.updated_at.unix_timestamp_nanos()
+    )
+  }
+}
+
+// --- Events ---
+/// 書き初めの変更イベント
+/// Serviceからイベント配信 (SSE) やDBのNOTIFYに流される
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CalligraphyEvent {
+  /// 新規作成
+  Created(Calligraphy),
+  /// 更新
+  Updated(Calligraphy),
+  /// 削除
+  Deleted { user_id: Uuid, public_id: Uuid },
+}
+
+impl CalligraphyEvent {
+  /// SSEのイベント名
+  pub fn name(&self) -> &'static str {
+    match self {
+      CalligraphyEvent::Created(_) => "created",
+      CalligraphyEvent::Updated(_) => "updated",
+      CalligraphyEvent::Deleted { .. } => "deleted",
+    }
+  }
+
+  /// 購読者ごとのレスポンス用DTOに変換する
+  pub fn to_response(&self, viewer_id: Uuid) -> CalligraphyEventResponse {
+    match self {
+      CalligraphyEvent::Created(c) | CalligraphyEvent::Updated(c) => {
+        CalligraphyEventResponse::Upserted(c.to_response(c.user_id == viewer_id))
+      }
+      CalligraphyEvent::Deleted { user_id, public_id } => CalligraphyEventResponse::Deleted {
+        public_id: *public_id,
+        is_mine: *user_id == viewer_id,
+      },
+    }
+  }
+}
+
+/// イベント配信用のDTO (user_idなどの内部情報は含めない)
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum CalligraphyEventResponse {
+  Upserted(CalligraphyResponse),
+  Deleted { public_id: Uuid, is_mine: bool },
+}
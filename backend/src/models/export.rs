@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::photo::Photo;
+use crate::models::strokes::Strokes;
+
+/// エクスポート形式のバージョン (項目の追加・変更時に上げる)
+pub const EXPORT_FORMAT_VERSION: u32 = 4;
+
+/// 個人データのエクスポート (保存している全ての情報)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PersonalDataExport {
+  pub format_version: u32,
+  #[serde(with = "time::serde::iso8601")]
+  pub exported_at: OffsetDateTime,
+  /// ユーザーID (セッションが指すID。以前はCookie `calli_user_id` に保存していた値)
+  pub user_id: Uuid,
+  /// 書き初め (投稿していなければnull)
+  #[schema(required = true)]
+  pub calligraphy: Option<CalligraphyRecord>,
+  /// ブラウザのセッション (期限切れで未削除のものも含む)
+  pub sessions: Vec<SessionRecord>,
+  /// 登録したパスキー
+  pub passkeys: Vec<PasskeyCredentialRecord>,
+}
+
+/// calligraphyテーブルの1行
+/// 通常のレスポンスでは返さない情報収集用の列も含め、全ての列を持つ
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct CalligraphyRecord {
+  pub public_id: Uuid,
+  pub user_name: String,
+  pub content: String,
+  /// 内容の文字数 (書記素クラスタ単位)
+  pub content_length: i16,
+  /// IPアドレス (匿名化後はネットワークアドレス, 例: "192.0.2.0/24")
+  #[schema(required = true)]
+  pub ip_address: Option<String>,
+  #[schema(required = true)]
+  pub user_agent: Option<String>,
+  #[schema(required = true)]
+  pub accept_language: Option<String>,
+  #[serde(with = "time::serde::iso8601")]
+  pub created_at: OffsetDateTime,
+  #[serde(with = "time::serde::iso8601")]
+  pub updated_at: OffsetDateTime,
+  /// IPアドレス等を匿名化した日時 (未匿名化ならnull)
+  #[serde(with = "time::serde::iso8601::option")]
+  #[schema(required = true)]
+  pub anonymized_at: Option<OffsetDateTime>,
+  /// 手書きの筆跡データ (なければnull)
+  #[schema(value_type = Option<Strokes>, required = true)]
+  pub strokes: Option<Json<Strokes>>,
+  /// 添付した写真のファイル名・大きさ (なければnull)
+  #[schema(value_type = Option<Photo>, required = true)]
+  pub photo: Option<Json<Photo>>,
+}
+
+/// user_sessionテーブルの1行
+/// セッショントークンのハッシュはCookieの値を推測する手掛かりになるため含めない
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct SessionRecord {
+  pub id: Uuid,
+  /// ブラウザとOSの種類 (例: "Firefox / Linux")
+  #[schema(required = true)]
+  pub device: Option<String>,
+  #[serde(with = "time::serde::iso8601")]
+  pub created_at: OffsetDateTime,
+  #[serde(with = "time::serde::iso8601")]
+  pub last_seen_at: OffsetDateTime,
+  #[serde(with = "time::serde::iso8601")]
+  pub expires_at: OffsetDateTime,
+}
+
+/// passkey_credentialテーブルの1行
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PasskeyCredentialRecord {
+  pub id: Uuid,
+  /// 認証器が発行した資格情報ID (Base64URL)
+  pub credential_id: String,
+  /// 公開鍵・署名カウンタ等 (webauthn-rs の Passkey)
+  #[schema(value_type = Object)]
+  pub passkey: Json<serde_json::Value>,
+  #[serde(with = "time::serde::iso8601")]
+  pub created_at: OffsetDateTime,
+  /// 最後にログインに使った日時 (未使用ならnull)
+  #[serde(with = "time::serde::iso8601::option")]
+  #[schema(required = true)]
+  pub last_used_at: Option<OffsetDateTime>,
+}
+
+impl PersonalDataExport {
+  /// ダウンロード時のファイル名
+  pub fn file_name(&self) -> String {
+    format!("calligraphy-export-{}.json", self.exported_at.date())
+  }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+use time::OffsetDateTime;
+
+/// JSON Feed のバージョン
+pub const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// JSON Feed 1.1 (`feed.json`)
+/// https://www.jsonfeed.org/version/1.1/
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonFeed {
+  pub version: &'static str,
+  pub title: String,
+  pub home_page_url: String,
+  pub feed_url: String,
+  pub language: &'static str,
+  pub items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonFeedItem {
+  /// 公開用IDのURN (例: `urn:uuid:...`)。更新しても変わらない
+  pub id: String,
+  pub title: String,
+  pub content_text: String,
+  // JSON Feed は RFC 3339 形式を要求する
+  #[serde(with = "time::serde::rfc3339")]
+  pub date_published: OffsetDateTime,
+  #[serde(with = "time::serde::rfc3339")]
+  pub date_modified: OffsetDateTime,
+  pub authors: Vec<JsonFeedAuthor>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonFeedAuthor {
+  pub name: String,
+}
@@ -0,0 +1,250 @@
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use utoipa::IntoParams;
+
+use crate::validation::{ValidationError, CONTENT_MAX_GRAPHEMES};
+
+/// 一覧APIのクエリパラメーター (未検証)
+/// 型変換の失敗もJSONの400エラーとして返すため、全て文字列で受け取る
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+  /// 並び順 (`newest` / `oldest` / `recently_updated`, デフォルト: `newest`)
+  #[param(example = "newest")]
+  pub sort: Option<String>,
+  /// 作成日時の下限 (RFC 3339, この日時を含む)
+  #[param(value_type = Option<OffsetDateTime>)]
+  pub from: Option<String>,
+  /// 作成日時の上限 (RFC 3339, この日時を含まない)
+  #[param(value_type = Option<OffsetDateTime>)]
+  pub to: Option<String>,
+  /// 自分の書き初めのみ
+  #[param(value_type = Option<bool>)]
+  pub mine_only: Option<String>,
+  /// 内容の最小文字数 (1〜50)
+  #[param(value_type = Option<u32>, minimum = 1, maximum = 50)]
+  pub min_length: Option<String>,
+  /// 内容の最大文字数 (1〜50)
+  #[param(value_type = Option<u32>, minimum = 1, maximum = 50)]
+  pub max_length: Option<String>,
+}
+
+/// 一覧の並び順
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListSort {
+  /// 作成日時の新しい順
+  #[default]
+  Newest,
+  /// 作成日時の古い順
+  Oldest,
+  /// 更新日時の新しい順
+  RecentlyUpdated,
+}
+
+impl ListSort {
+  /// クエリパラメーターで指定できる値
+  pub const VALUES: [&'static str; 3] = ["newest", "oldest", "recently_updated"];
+
+  fn parse(value: &str) -> Result<Self, ValidationError> {
+    match value {
+      "newest" => Ok(Self::Newest),
+      "oldest" => Ok(Self::Oldest),
+      "recently_updated" => Ok(Self::RecentlyUpdated),
+      _ => Err(ValidationError::InvalidParam {
+        field: "sort",
+        reason: format!("unknown value '{}' (expected one of: {})", value, Self::VALUES.join(", ")),
+      }),
+    }
+  }
+
+  /// SQLの ORDER BY 句
+  /// ユーザー入力を埋め込まず、固定の文字列のみを返す
+  pub fn order_by(self) -> &'static str {
+    match self {
+      Self::Newest => "created_at DESC, public_id",
+      Self::Oldest => "created_at ASC, public_id",
+      Self::RecentlyUpdated => "updated_at DESC, public_id",
+    }
+  }
+}
+
+/// 検証済みの一覧の取得条件
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ListQuery {
+  pub sort: ListSort,
+  /// 作成日時の下限 (この日時を含む)
+  pub from: Option<OffsetDateTime>,
+  /// 作成日時の上限 (この日時を含まない)
+  pub to: Option<OffsetDateTime>,
+  /// 自分の書き初めのみ
+  pub mine_only: bool,
+  /// 内容の最小文字数 (書記素クラスタ単位)
+  pub min_length: Option<u32>,
+  /// 内容の最大文字数 (書記素クラスタ単位)
+  pub max_length: Option<u32>,
+}
+
+impl TryFrom<ListParams> for ListQuery {
+  type Error = ValidationError;
+
+  fn try_from(params: ListParams) -> Result<Self, Self::Error> {
+    let query = Self {
+      sort: params.sort.as_deref().map(ListSort::parse).transpose()?.unwrap_or_default(),
+      from: parse_datetime("from", params.from.as_deref())?,
+      to: parse_datetime("to", params.to.as_deref())?,
+      mine_only: parse_bool("mine_only", params.mine_only.as_deref())?,
+      min_length: parse_length("min_length", params.min_length.as_deref())?,
+      max_length: parse_length("max_length", params.max_length.as_deref())?,
+    };
+
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+      if from >= to {
+        return Err(ValidationError::InvalidParam {
+          field: "to",
+          reason: "must be later than 'from'".to_string(),
+        });
+      }
+    }
+    if let (Some(min), Some(max)) = (query.min_length, query.max_length) {
+      if min > max {
+        return Err(ValidationError::InvalidParam {
+          field: "max_length",
+          reason: "must be greater than or equal to 'min_length'".to_string(),
+        });
+      }
+    }
+    Ok(query)
+  }
+}
+
+impl ListQuery {
+  /// 条件の指定がないか (全ユーザー共通の一覧キャッシュを使える)
+  pub fn is_default(&self) -> bool {
+    *self == Self::default()
+  }
+}
+
+/// RFC 3339 形式の日時 (例: `2026-01-01T00:00:00Z`)
+fn parse_datetime(field: &'static str, value: Option<&str>) -> Result<Option<OffsetDateTime>, ValidationError> {
+  value
+    .map(|v| {
+      OffsetDateTime::parse(v, &Rfc3339).map_err(|_| ValidationError::InvalidParam {
+        field,
+        reason: "must be an RFC 3339 date-time (e.g. 2026-01-01T00:00:00Z)".to_string(),
+      })
+    })
+    .transpose()
+}
+
+fn parse_bool(field: &'static str, value: Option<&str>) -> Result<bool, ValidationError> {
+  match value {
+    None | Some("false") | Some("0") => Ok(false),
+    Some("true") | Some("1") => Ok(true),
+    Some(_) => Err(ValidationError::InvalidParam {
+      field,
+      reason: "must be 'true' or 'false'".to_string(),
+    }),
+  }
+}
+
+fn parse_length(field: &'static str, value: Option<&str>) -> Result<Option<u32>, ValidationError> {
+  let max = CONTENT_MAX_GRAPHEMES as u32;
+  value
+    .map(|v| match v.trim().parse::<u32>() {
+      Ok(n) if (1..=max).contains(&n) => Ok(n),
+      _ => Err(ValidationError::InvalidParam {
+        field,
+        reason: format!("must be an integer between 1 and {}", max),
+      }),
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::macros::datetime;
+
+  /// 指定がなければ既定値 (作成日時の新しい順・絞り込みなし)
+  #[test]
+  fn test_defaults() {
+    let query = ListQuery::try_from(ListParams::default()).unwrap();
+    assert!(query.is_default());
+    assert_eq!(query.sort, ListSort::Newest);
+  }
+
+  /// 各パラメーターが型付きの値に変換されること
+  #[test]
+  fn test_parse() {
+    let query = ListQuery::try_from(ListParams {
+      sort: Some("oldest".to_string()),
+      from: Some("2026-01-01T00:00:00+09:00".to_string()),
+      to: Some("2026-01-04T00:00:00Z".to_string()),
+      mine_only: Some("true".to_string()),
+      min_length: Some("2".to_string()),
+      max_length: Some("10".to_string()),
+    })
+    .unwrap();
+    assert_eq!(
+      query,
+      ListQuery {
+        sort: ListSort::Oldest,
+        from: Some(datetime!(2025-12-31 15:00 UTC)),
+        to: Some(datetime!(2026-01-04 00:00 UTC)),
+        mine_only: true,
+        min_length: Some(2),
+        max_length: Some(10),
+      }
+    );
+    assert!(!query.is_default());
+  }
+
+  /// 不正な値はフィールド名付きのエラーになること
+  #[test]
+  fn test_invalid_values() {
+    let cases = [
+      (ListParams { sort: Some("most_reacted".to_string()), ..Default::default() }, "sort"),
+      (ListParams { from: Some("2026-01-01".to_string()), ..Default::default() }, "from"),
+      (ListParams { mine_only: Some("yes".to_string()), ..Default::default() }, "mine_only"),
+      (ListParams { min_length: Some("0".to_string()), ..Default::default() }, "min_length"),
+      (ListParams { max_length: Some("51".to_string()), ..Default::default() }, "max_length"),
+      (
+        ListParams {
+          from: Some("2026-01-02T00:00:00Z".to_string()),
+          to: Some("2026-01-01T00:00:00Z".to_string()),
+          ..Default::default()
+        },
+        "to",
+      ),
+      (
+        ListParams {
+          min_length: Some("5".to_string()),
+          max_length: Some("4".to_string()),
+          ..Default::default()
+        },
+        "max_length",
+      ),
+    ];
+    for (params, expected) in cases {
+      match ListQuery::try_from(params) {
+        Err(ValidationError::InvalidParam { field, .. }) => assert_eq!(field, expected),
+        other => panic!("expected InvalidParam for {}, got {:?}", expected, other),
+      }
+    }
+  }
+
+  /// 未知の並び順はエラーメッセージに指定可能な値を含むこと
+  #[test]
+  fn test_unknown_sort_message() {
+    let err = ListQuery::try_from(ListParams {
+      sort: Some("popular".to_string()),
+      ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Invalid query parameter 'sort': unknown value 'popular' (expected one of: newest, oldest, recently_updated)"
+    );
+  }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+  CreationChallengeResponse, Passkey, PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse,
+};
+
+/// 登録済みのパスキー (passkey_credentialテーブルの1行)
+#[derive(Debug, Clone)]
+pub struct PasskeyRecord {
+  /// 公開用ID (資格情報IDは外部に出さない)
+  pub id: Uuid,
+  pub user_id: Uuid,
+  /// 公開鍵・署名カウンタ等
+  pub passkey: Json<Passkey>,
+  pub created_at: OffsetDateTime,
+  pub last_used_at: Option<OffsetDateTime>,
+}
+
+impl PasskeyRecord {
+  /// APIレスポンス用DTOに変換する
+  pub fn to_response(&self) -> PasskeyResponse {
+    PasskeyResponse {
+      id: self.id,
+      created_at: self.created_at,
+      last_used_at: self.last_used_at,
+    }
+  }
+}
+
+/// パスキーの登録・認証の種類 (途中の状態をDBに保存する際の区別)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CeremonyKind {
+  Registration,
+  Authentication,
+}
+
+impl CeremonyKind {
+  /// DBに保存する値
+  pub fn as_str(self) -> &'static str {
+    match self {
+      CeremonyKind::Registration => "registration",
+      CeremonyKind::Authentication => "authentication",
+    }
+  }
+}
+
+/// パスキーの登録・認証の途中の状態
+#[derive(Debug, Clone)]
+pub struct PasskeyCeremony {
+  pub id: Uuid,
+  pub kind: CeremonyKind,
+  /// 登録するユーザー / 認証されるユーザー
+  pub user_id: Uuid,
+  /// webauthn-rs の状態 (`PasskeyRegistration` / `PasskeyAuthentication`)
+  pub state: serde_json::Value,
+  pub expires_at: OffsetDateTime,
+}
+
+// --- DTOs ---
+
+/// APIレスポンス用のパスキーの情報
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PasskeyResponse {
+  pub id: Uuid,
+  #[serde(with = "time::serde::iso8601")]
+  pub created_at: OffsetDateTime,
+  #[serde(with = "time::serde::iso8601::option")]
+  #[schema(required = true)]
+  pub last_used_at: Option<OffsetDateTime>,
+}
+
+/// 登録の開始のレスポンス (`options` は `navigator.credentials.create()` にそのまま渡す)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StartRegistrationResponse {
+  pub ceremony_id: Uuid,
+  /// WebAuthnの `PublicKeyCredentialCreationOptions` (`{ "publicKey": ... }`)
+  #[schema(value_type = Object)]
+  pub options: CreationChallengeResponse,
+}
+
+/// 登録の完了のリクエストボディ
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FinishRegistrationRequest {
+  pub ceremony_id: Uuid,
+  /// `navigator.credentials.create()` の結果 (バイナリはbase64url)
+  #[schema(value_type = Object)]
+  pub credential: RegisterPublicKeyCredential,
+}
+
+/// 認証の開始のリクエストボディ (ログインする書き初めの公開用ID)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StartAuthenticationRequest {
+  pub public_id: Uuid,
+}
+
+/// 認証の開始のレスポンス (`options` は `navigator.credentials.get()` にそのまま渡す)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StartAuthenticationResponse {
+  pub ceremony_id: Uuid,
+  /// WebAuthnの `PublicKeyCredentialRequestOptions` (`{ "publicKey": ... }`)
+  #[schema(value_type = Object)]
+  pub options: RequestChallengeResponse,
+}
+
+/// 認証の完了のリクエストボディ
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FinishAuthenticationRequest {
+  pub ceremony_id: Uuid,
+  /// `navigator.credentials.get()` の結果 (バイナリはbase64url)
+  #[schema(value_type = Object)]
+  pub credential: PublicKeyCredential,
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// 写真を配信するURLの接頭辞
+pub const PHOTO_URL_PREFIX: &str = "/api/photos/";
+
+/// 書き初めに添付した写真 (DBにはJSONとして保存する)
+///
+/// ファイル名 (`key`) は再エンコードした画像の内容のハッシュ (SHA-256) と拡張子で、内容が変わらない限り同じ名前になる。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Photo {
+  /// 写真のファイル名 (例: `3a7b...e1.jpg`)
+  pub key: String,
+  pub width: u32,
+  pub height: u32,
+  /// サムネイルのファイル名
+  pub thumbnail_key: String,
+  pub thumbnail_width: u32,
+  pub thumbnail_height: u32,
+}
+
+/// APIレスポンス用の写真の情報
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PhotoResponse {
+  pub url: String,
+  pub width: u32,
+  pub height: u32,
+  pub thumbnail_url: String,
+  pub thumbnail_width: u32,
+  pub thumbnail_height: u32,
+}
+
+impl Photo {
+  /// 写真・サムネイルのファイル名
+  pub fn keys(&self) -> [&str; 2] {
+    [&self.key, &self.thumbnail_key]
+  }
+
+  pub fn to_response(&self) -> PhotoResponse {
+    PhotoResponse {
+      url: format!("{}{}", PHOTO_URL_PREFIX, self.key),
+      width: self.width,
+      height: self.height,
+      thumbnail_url: format!("{}{}", PHOTO_URL_PREFIX, self.thumbnail_key),
+      thumbnail_width: self.thumbnail_width,
+      thumbnail_height: self.thumbnail_height,
+    }
+  }
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// 匿名化前のリクエスト情報 (匿名化ジョブの対象行)
+#[derive(Debug, Clone, FromRow)]
+pub struct RequestMetadata {
+  pub user_id: Uuid,
+  /// 取得時点の更新日時 (匿名化までの間に更新された行を上書きしないための条件に使う)
+  pub updated_at: OffsetDateTime,
+  pub user_agent: Option<String>,
+  pub accept_language: Option<String>,
+}
+
+/// 匿名化後のリクエスト情報
+/// IPアドレスはDB側でネットワーク部のみに切り詰める
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnonymizedMetadata {
+  pub user_id: Uuid,
+  pub updated_at: OffsetDateTime,
+  pub user_agent: Option<String>,
+  pub accept_language: Option<String>,
+}
+
+/// 収集したリクエスト情報の保持ポリシー (プライバシーポリシーからの参照用に公開する)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RetentionPolicy {
+  /// 匿名化ジョブが有効か
+  pub enabled: bool,
+  /// 最終更新からこの日数が経過した行を匿名化する
+  pub anonymize_after_days: u32,
+  /// 匿名化後に残すIPv4アドレスのプレフィックス長
+  pub ipv4_prefix: u8,
+  /// 匿名化後に残すIPv6アドレスのプレフィックス長
+  pub ipv6_prefix: u8,
+}
+
+impl Default for RetentionPolicy {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      anonymize_after_days: 0,
+      ipv4_prefix: 24,
+      ipv6_prefix: 48,
+    }
+  }
+}
+
+impl RetentionPolicy {
+  /// 日数を指定したポリシー (0なら無効)
+  pub fn after_days(days: u32) -> Self {
+    Self {
+      enabled: days > 0,
+      anonymize_after_days: days,
+      ..Self::default()
+    }
+  }
+
+  /// この日時より前に更新された行が匿名化の対象
+  pub fn cutoff(&self, now: OffsetDateTime) -> OffsetDateTime {
+    now - time::Duration::days(i64::from(self.anonymize_after_days))
+  }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::calligraphy::{Calligraphy, CalligraphyResponse};
+use crate::validation::{self, ValidationError};
+
+/// 1ページあたりの件数の既定値
+pub const DEFAULT_PER_PAGE: u32 = 20;
+/// 1ページあたりの件数の上限
+pub const MAX_PER_PAGE: u32 = 50;
+/// 指定できるページ番号の上限 (深いOFFSETによる負荷を避ける)
+pub const MAX_PAGE: u32 = 100;
+
+/// 検索APIのクエリパラメーター (未検証)
+/// 型変換の失敗もJSONの400エラーとして返すため、全て文字列で受け取る
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+  /// 検索語 (必須)
+  #[param(example = "富士")]
+  pub q: Option<String>,
+  /// ページ番号 (1〜100, デフォルト: 1)
+  #[param(value_type = Option<u32>, minimum = 1, maximum = 100)]
+  pub page: Option<String>,
+  /// 1ページあたりの件数 (1〜50, デフォルト: 20)
+  #[param(value_type = Option<u32>, minimum = 1, maximum = 50)]
+  pub per_page: Option<String>,
+}
+
+/// 検証済みの検索条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+  /// 正規化済みの検索語
+  pub q: String,
+  /// ページ番号 (1始まり)
+  pub page: u32,
+  pub per_page: u32,
+}
+
+impl TryFrom<SearchParams> for SearchQuery {
+  type Error = ValidationError;
+
+  fn try_from(params: SearchParams) -> Result<Self, Self::Error> {
+    Ok(Self {
+      q: validation::normalize_search_query(params.q.as_deref().unwrap_or_default())?,
+      page: validation::parse_bounded_int("page", params.page.as_deref(), 1, MAX_PAGE)?,
+      per_page: validation::parse_bounded_int(
+        "per_page",
+        params.per_page.as_deref(),
+        DEFAULT_PER_PAGE,
+        MAX_PER_PAGE,
+      )?,
+    })
+  }
+}
+
+impl SearchQuery {
+  /// 取得を飛ばす件数
+  pub fn offset(&self) -> i64 {
+    i64::from(self.page - 1) * i64::from(self.per_page)
+  }
+}
+
+/// 検索結果の1件 (リポジトリの戻り値)
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+  pub entry: Calligraphy,
+  /// 関連度 (大きいほど上位)
+  pub score: f32,
+}
+
+/// ハイライト用の区間
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Segment {
+  pub text: String,
+  /// 検索語に一致した区間か
+  pub matched: bool,
+}
+
+impl Segment {
+  pub fn plain(text: &str) -> Self {
+    Self {
+      text: text.to_string(),
+      matched: false,
+    }
+  }
+
+  pub fn matched(text: &str) -> Self {
+    Self {
+      text: text.to_string(),
+      matched: true,
+    }
+  }
+}
+
+/// フィールドごとのハイライト
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Highlights {
+  pub user_name: Vec<Segment>,
+  pub content: Vec<Segment>,
+}
+
+/// 検索結果1件のレスポンス (通常の書き初めのレスポンスにハイライトを加えたもの)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchHitResponse {
+  #[serde(flatten)]
+  pub entry: CalligraphyResponse,
+  pub highlights: Highlights,
+}
+
+/// 検索APIのレスポンス
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchResponse {
+  pub items: Vec<SearchHitResponse>,
+  pub page: u32,
+  pub per_page: u32,
+  /// 次のページがあるか
+  pub has_more: bool,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(q: &str, page: Option<&str>, per_page: Option<&str>) -> SearchParams {
+    SearchParams {
+      q: Some(q.to_string()),
+      page: page.map(str::to_string),
+      per_page: per_page.map(str::to_string),
+    }
+  }
+
+  /// 既定値と正規化
+  #[test]
+  fn test_search_query_defaults() {
+    let query = SearchQuery::try_from(params(" 富士 ", None, None)).unwrap();
+    assert_eq!(query.q, "富士");
+    assert_eq!(query.page, 1);
+    assert_eq!(query.per_page, DEFAULT_PER_PAGE);
+    assert_eq!(query.offset(), 0);
+
+    let query = SearchQuery::try_from(params("富士", Some("3"), Some("10"))).unwrap();
+    assert_eq!(query.offset(), 20);
+  }
+
+  /// 不正なパラメーターはエラーになること
+  #[test]
+  fn test_search_query_invalid() {
+    assert!(SearchQuery::try_from(SearchParams::default()).is_err());
+    assert!(SearchQuery::try_from(params("  ", None, None)).is_err());
+    assert!(SearchQuery::try_from(params("富士", Some("0"), None)).is_err());
+    assert!(SearchQuery::try_from(params("富士", None, Some("51"))).is_err());
+  }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// ブラウザのセッション (user_sessionテーブルの1行)
+#[derive(Debug, Clone)]
+pub struct Session {
+  /// 公開用ID (トークンは外部に出さない)
+  pub id: Uuid,
+  /// セッショントークンのSHA-256
+  pub token_hash: Vec<u8>,
+  pub user_id: Uuid,
+  /// ブラウザとOSの種類 (例: "Firefox / Linux")
+  pub device: Option<String>,
+  pub created_at: OffsetDateTime,
+  pub last_seen_at: OffsetDateTime,
+  pub expires_at: OffsetDateTime,
+}
+
+impl Session {
+  /// APIレスポンス用DTOに変換する
+  pub fn to_response(&self, current: bool) -> SessionResponse {
+    SessionResponse {
+      id: self.id,
+      device: self.device.clone(),
+      created_at: self.created_at,
+      last_seen_at: self.last_seen_at,
+      expires_at: self.expires_at,
+      current,
+    }
+  }
+}
+
+/// APIレスポンス用のセッションの情報
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionResponse {
+  pub id: Uuid,
+  #[schema(required = true)]
+  pub device: Option<String>,
+  #[serde(with = "time::serde::iso8601")]
+  pub created_at: OffsetDateTime,
+  #[serde(with = "time::serde::iso8601")]
+  pub last_seen_at: OffsetDateTime,
+  #[serde(with = "time::serde::iso8601")]
+  pub expires_at: OffsetDateTime,
+  /// このリクエストのセッションか
+  pub current: bool,
+}
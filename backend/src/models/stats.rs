@@ -0,0 +1,54 @@
+use serde::Serialize;
+use time::Date;
+use utoipa::ToSchema;
+
+/// ボード全体の集計結果 (`GET /api/stats`)
+///
+/// 個々の行のリクエスト情報 (User-Agent・Accept-Language) は含めず、件数の集計のみを返す。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct BoardStats {
+  /// 書き初めの総数
+  pub total_entries: u64,
+  /// 年末年始の日別の投稿数
+  pub new_year: NewYearActivity,
+  /// 内容に多く使われている文字 (英数字・かな・漢字)
+  pub top_characters: Vec<CharacterCount>,
+  /// 内容に多く使われている漢字
+  pub top_kanji: Vec<CharacterCount>,
+  /// 言語別の件数 (Accept-Languageの主言語タグ)
+  pub languages: Vec<LabelCount>,
+  /// ブラウザ別の件数
+  pub browsers: Vec<LabelCount>,
+  /// OS別の件数
+  pub operating_systems: Vec<LabelCount>,
+}
+
+/// 年末年始 (日本時間) の日別の投稿数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct NewYearActivity {
+  /// 集計期間の初日 (この日を含む)
+  pub from: Date,
+  /// 集計期間の最終日 (この日を含む)
+  pub to: Date,
+  /// 期間内の全ての日の件数 (投稿がない日は0件)
+  pub daily: Vec<DailyCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DailyCount {
+  /// 日付 (日本時間)
+  pub date: Date,
+  pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct CharacterCount {
+  pub character: String,
+  pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct LabelCount {
+  pub label: String,
+  pub count: u64,
+}
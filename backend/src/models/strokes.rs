@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::schema::{ArrayBuilder, ArrayItems, ObjectBuilder, Schema, Type};
+use utoipa::openapi::RefOr;
+use utoipa::{PartialSchema, ToSchema};
+
+/// 筆跡データの形式のバージョン (形式を変えるときに上げる)
+pub const STROKES_FORMAT_VERSION: u32 = 1;
+
+/// 手書きの筆跡データ
+///
+/// ```json
+/// { "version": 1, "width": 600, "height": 800, "strokes": [[[120.5, 88, 0.42, 0], [121, 90.5, 0.5, 16]]] }
+/// ```
+///
+/// 座標は左上を原点とする `width` x `height` の描画領域上の値。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Strokes {
+  /// 形式のバージョン (現在は1)
+  pub version: u32,
+  /// 描画領域の大きさ
+  pub width: u32,
+  pub height: u32,
+  /// 書いた順の画 (1画は筆を下ろしてから離すまでの点の列)
+  /// 点は `[x, y, 筆圧, 書き始めからの経過時間 (ミリ秒)]`
+  pub strokes: Vec<Vec<StrokePoint>>,
+}
+
+/// 筆跡の1点 (`[x, y, 筆圧, 経過時間]` の配列で表す)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StrokePoint(
+  /// x座標
+  pub f32,
+  /// y座標
+  pub f32,
+  /// 筆圧 (0.0〜1.0, PointerEvent.pressure)
+  pub f32,
+  /// 書き始めからの経過時間 (ミリ秒)
+  pub u32,
+);
+
+/// OpenAPI (と生成するTypeScriptの型) では要素数4のタプルとして表す
+impl PartialSchema for StrokePoint {
+  fn schema() -> RefOr<Schema> {
+    let number = |description: &str| {
+      ObjectBuilder::new()
+        .schema_type(Type::Number)
+        .description(Some(description))
+        .build()
+    };
+    ArrayBuilder::new()
+      .description(Some("筆跡の1点 `[x, y, 筆圧 (0.0〜1.0), 書き始めからの経過時間 (ミリ秒)]`"))
+      .prefix_items([
+        number("x座標"),
+        number("y座標"),
+        number("筆圧"),
+        ObjectBuilder::new()
+          .schema_type(Type::Integer)
+          .minimum(Some(0))
+          .description(Some("書き始めからの経過時間 (ミリ秒)"))
+          .build(),
+      ])
+      .items(ArrayItems::False)
+      .min_items(Some(4))
+      .max_items(Some(4))
+      .build()
+      .into()
+  }
+}
+
+impl ToSchema for StrokePoint {}
+
+impl StrokePoint {
+  pub fn x(&self) -> f32 {
+    self.0
+  }
+  pub fn y(&self) -> f32 {
+    self.1
+  }
+  pub fn pressure(&self) -> f32 {
+    self.2
+  }
+  pub fn t(&self) -> u32 {
+    self.3
+  }
+}
+
+impl Strokes {
+  /// 全ての点の数
+  pub fn point_count(&self) -> usize {
+    self.strokes.iter().map(Vec::len).sum()
+  }
+}
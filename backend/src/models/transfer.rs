@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// 発行した引き継ぎコード (レスポンス用)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TransferCodeResponse {
+  /// 引き継ぎ先のブラウザで入力するコード (`XXXXX-XXXXX`)
+  pub code: String,
+  /// 有効期限 (これを過ぎたコードは使えない)
+  #[serde(with = "time::serde::iso8601")]
+  pub expires_at: OffsetDateTime,
+}
+
+/// 引き継ぎコードを使うリクエストのボディ
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ClaimRequest {
+  pub code: String,
+}
@@ -0,0 +1,193 @@
+//! 書き初めごとのOGP画像 (1200x630 PNG) を生成するモジュール
+//!
+//! 縦書きの配置 (`tategaki::compose`) をフォントでラスタライズ (`ab_glyph`) し、PNGにエンコード (`png`) する。
+//! 外部サービスやブラウザは使わない。
+
+use std::io;
+
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+
+use crate::tategaki::{self, Card, Color, PlacedGlyph, Scene, VerticalGlyph};
+
+/// バイナリに埋め込んだフォント (assets/fonts/ogp.ttf、build.rs を参照)
+/// フォントを配置せずに開発用にビルドした場合は空になる
+static BUNDLED_FONT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ogp-font.ttf"));
+
+/// OGP画像の描画 (フォントは起動時に1回だけ読み込む)
+pub struct OgpRenderer {
+  font: FontArc,
+}
+
+impl OgpRenderer {
+  /// バイナリに埋め込んだフォントを読み込む
+  pub fn bundled() -> io::Result<Self> {
+    if BUNDLED_FONT.is_empty() {
+      return Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "the OGP font was not bundled (assets/fonts/ogp.ttf was missing at build time)",
+      ));
+    }
+    Self::from_bytes(BUNDLED_FONT)
+  }
+
+  /// フォント (TrueType / OpenType) のデータを読み込む
+  pub fn from_bytes(data: &'static [u8]) -> io::Result<Self> {
+    let font = FontArc::try_from_slice(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Self { font })
+  }
+
+  /// PNG画像を生成する
+  pub fn render(&self, card: &Card) -> Result<Vec<u8>, png::EncodingError> {
+    let scene = tategaki::compose(card, |c| self.has_glyph(c));
+    self.render_scene(&scene)
+  }
+
+  /// 配置済みの場面をPNG画像にする
+  pub fn render_scene(&self, scene: &Scene) -> Result<Vec<u8>, png::EncodingError> {
+    let mut canvas = Canvas::new(scene.width, scene.height, scene.background);
+    canvas.frame(scene.frame.inset, scene.frame.width, scene.frame.color);
+    for glyph in &scene.glyphs {
+      self.draw_glyph(&mut canvas, glyph);
+    }
+    canvas.encode_png()
+  }
+
+  fn has_glyph(&self, c: char) -> bool {
+    self.font.glyph_id(c).0 != 0
+  }
+
+  /// 1文字を `size` x `size` の枠に描く
+  fn draw_glyph(&self, canvas: &mut Canvas, placed: &PlacedGlyph) {
+    let PlacedGlyph { glyph, x, y, size, color } = *placed;
+    let (rotated, (dx, dy)) = match glyph {
+      VerticalGlyph::Upright(_) => (false, (0.0, 0.0)),
+      VerticalGlyph::Rotated(_) => (true, (0.0, 0.0)),
+      VerticalGlyph::Shifted(_, dx, dy) => (false, (dx, dy)),
+    };
+    let c = glyph.char();
+    if !self.has_glyph(c) {
+      return;
+    }
+
+    let scaled = self.font.as_scaled(PxScale::from(size));
+    let id = self.font.glyph_id(c);
+    // 送り幅が枠より狭い文字 (英数字など) は枠の中央に寄せる
+    let glyph = id.with_scale_and_position(size, point((size - scaled.h_advance(id)) / 2.0, scaled.ascent()));
+    let Some(outlined) = self.font.outline_glyph(glyph) else {
+      return;
+    };
+    let bounds = outlined.px_bounds();
+    let center = size / 2.0;
+    outlined.draw(|gx, gy, coverage| {
+      let lx = bounds.min.x + gx as f32;
+      let ly = bounds.min.y + gy as f32;
+      // 枠の中心を軸に時計回りに90度回転する
+      let (lx, ly) = if rotated {
+        (center - (ly - center), center + (lx - center))
+      } else {
+        (lx, ly)
+      };
+      canvas.blend(x + lx + dx * size, y + ly + dy * size, color, coverage);
+    });
+  }
+}
+
+/// RGBの描画先
+pub(crate) struct Canvas {
+  width: u32,
+  height: u32,
+  pixels: Vec<u8>,
+}
+
+impl Canvas {
+  pub(crate) fn new(width: u32, height: u32, background: Color) -> Self {
+    Self {
+      width,
+      height,
+      pixels: background.repeat((width * height) as usize),
+    }
+  }
+
+  /// 色を `coverage` (0.0〜1.0) の割合で重ねる (範囲外は無視する)
+  pub(crate) fn blend(&mut self, x: f32, y: f32, color: Color, coverage: f32) {
+    if x < 0.0 || y < 0.0 {
+      return;
+    }
+    let (x, y) = (x as u32, y as u32);
+    if x >= self.width || y >= self.height {
+      return;
+    }
+    let coverage = coverage.clamp(0.0, 1.0);
+    let offset = ((y * self.width + x) * 3) as usize;
+    for (channel, &ink) in self.pixels[offset..offset + 3].iter_mut().zip(color.iter()) {
+      *channel = (f32::from(*channel) * (1.0 - coverage) + f32::from(ink) * coverage).round() as u8;
+    }
+  }
+
+  /// 外周から `inset` の位置に太さ `width` の枠線を描く
+  fn frame(&mut self, inset: u32, width: u32, color: Color) {
+    let (left, top) = (inset, inset);
+    let (right, bottom) = (self.width - inset, self.height - inset);
+    for y in top..bottom {
+      for x in left..right {
+        let on_edge = x < left + width || x >= right - width || y < top + width || y >= bottom - width;
+        if on_edge {
+          self.blend(x as f32, y as f32, color, 1.0);
+        }
+      }
+    }
+  }
+
+  pub(crate) fn encode_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&self.pixels)?;
+    writer.finish()?;
+    Ok(out)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 埋め込んだフォントで縦書きの日本語を含む1200x630のPNGが生成されること
+  #[test]
+  #[cfg_attr(not(ogp_font_bundled), ignore = "assets/fonts/ogp.ttf was not vendored at build time")]
+  fn test_render_png() {
+    let renderer = OgpRenderer::bundled().unwrap();
+    // 本文・縦書き用の句読点・署名に使う字形がフォントにあること
+    for c in "謹賀新年、初日の出。富士山二〇六山田太郎".chars() {
+      assert!(renderer.has_glyph(c), "missing glyph: {}", c);
+    }
+
+    let card = Card {
+      content: "謹賀新年\n初日の出、富士山。",
+      user_name: "山田太郎",
+      year: 2026,
+    };
+    let png = renderer.render(&card).unwrap();
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // IHDR: 幅・高さ (ビッグエンディアン)
+    assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), tategaki::WIDTH);
+    assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), tategaki::HEIGHT);
+  }
+
+  /// フォントを配置せずにビルドした場合は読み込みがエラーになること
+  #[test]
+  #[cfg(not(ogp_font_bundled))]
+  fn test_font_not_bundled() {
+    assert_eq!(OgpRenderer::bundled().err().map(|e| e.kind()), Some(io::ErrorKind::NotFound));
+  }
+
+  /// フォントでないデータはエラーになること
+  #[test]
+  fn test_invalid_font() {
+    let result = OgpRenderer::from_bytes(b"not a font");
+    assert_eq!(result.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+  }
+}
@@ -0,0 +1,237 @@
+//! OpenAPIのドキュメント
+//!
+//! ハンドラーの `#[utoipa::path]` とDTOの `ToSchema` から OpenAPI 3.1 のドキュメントを生成し、
+//! `/api/openapi.json` で配信する。`API_DOCS_UI=true` なら閲覧用のページ (Redoc) も `/api/docs` で配信する。
+//!
+//! 生成結果は `docs/openapi.json` にもコミットしておき、差分をレビューできるようにする。
+//! ハンドラーやDTOを変更したら `UPDATE_OPENAPI=1 cargo test openapi` で更新すること (更新しないとテストが失敗する)。
+
+use std::sync::OnceLock;
+
+use axum::{
+  http::{header, HeaderValue},
+  response::{Html, IntoResponse, Response},
+  routing::get,
+  Router,
+};
+use serde::Deserialize;
+use utoipa::{OpenApi, ToSchema};
+
+use crate::config::Config;
+use crate::error::ErrorResponse;
+use crate::handlers;
+use crate::models::{
+  board::{ClientMessage, ServerMessage},
+  calligraphy::CalligraphyEventResponse,
+};
+
+/// ドキュメントのページで読み込むRedocのバージョン
+const REDOC_VERSION: &str = "2.5.0";
+/// ドキュメントのページのCSP (CDNのRedocを読み込み、同じオリジンのドキュメントを取得する)
+const DOCS_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; script-src https://cdn.jsdelivr.net; style-src 'unsafe-inline'; img-src 'self' data:; connect-src 'self'; worker-src blob:; frame-ancestors 'none'; base-uri 'none'; form-action 'none'";
+
+#[derive(OpenApi)]
+#[openapi(
+  info(
+    title = "書き初め API",
+    description = "書き初めボードのバックエンドAPI。\n\n認証はセッションのCookie (`calli_session`) で行い、初回のアクセスで自動的に発行する。`GET`・`HEAD`・`OPTIONS` 以外のリクエストでは、Cookieを送る場合は `X-CSRF-Token` ヘッダーにCookie `calli_csrf` の値を付けること。\n\nエラーのレスポンスは全て `ErrorResponse` の形式。",
+  ),
+  paths(
+    handlers::calligraphy::upsert,
+    handlers::calligraphy::list,
+    handlers::calligraphy::search,
+    handlers::calligraphy::get,
+    handlers::calligraphy::delete,
+    handlers::calligraphy::export,
+    handlers::calligraphy::stream,
+    handlers::ws::board,
+    handlers::feed::atom,
+    handlers::feed::rss,
+    handlers::feed::json,
+    handlers::svg::image,
+    handlers::ogp::image,
+    handlers::strokes::data,
+    handlers::strokes::svg,
+    handlers::strokes::png,
+    handlers::photos::image,
+    handlers::identity::issue_transfer_code,
+    handlers::identity::claim,
+    handlers::passkeys::list,
+    handlers::passkeys::delete,
+    handlers::passkeys::start_registration,
+    handlers::passkeys::finish_registration,
+    handlers::passkeys::start_authentication,
+    handlers::passkeys::finish_authentication,
+    handlers::passkeys::logout,
+    handlers::sessions::list,
+    handlers::sessions::delete,
+    handlers::stats::board_stats,
+    handlers::privacy::retention_policy,
+  ),
+  // レスポンスのボディとして直接参照しないもの (SSE・WebSocketのメッセージ)
+  components(schemas(ErrorResponse, CalligraphyEventResponse, ClientMessage, ServerMessage)),
+  tags(
+    (name = "calligraphy", description = "書き初めの投稿・閲覧・削除"),
+    (name = "realtime", description = "変更イベントの配信 (SSE・WebSocket)"),
+    (name = "feed", description = "新着の書き初めのフィード"),
+    (name = "media", description = "書き初めの画像・筆跡・添付した写真"),
+    (name = "identity", description = "別のブラウザへの引き継ぎ"),
+    (name = "passkeys", description = "パスキーでのログイン"),
+    (name = "sessions", description = "ログインしている端末の管理"),
+    (name = "board", description = "ボード全体の情報"),
+  ),
+)]
+pub struct ApiDoc;
+
+/// 書き初めの投稿・更新のmultipart/form-data (ドキュメント用。実際の解析は `CalligraphyForm` で行う)
+#[derive(Deserialize, ToSchema)]
+#[allow(dead_code)]
+pub struct CalligraphyFormData {
+  /// JSONの `user_name` と同じ
+  pub user_name: String,
+  /// JSONの `content` と同じ
+  pub content: String,
+  /// 筆跡データのJSON文字列
+  pub strokes: Option<String>,
+  /// 写真ファイル (JPEG / PNG / WebP、10MiBまで)。空のファイルは添付なしとして扱う
+  #[schema(value_type = Option<String>, format = Binary)]
+  pub photo: Option<Vec<u8>>,
+}
+
+/// 配信するドキュメント (JSON)
+/// 内容は起動中に変わらないため、最初に生成したものを使い回す
+pub fn spec_json() -> &'static str {
+  static SPEC: OnceLock<String> = OnceLock::new();
+  SPEC.get_or_init(|| {
+    let mut json = ApiDoc::openapi()
+      .to_pretty_json()
+      .expect("OpenAPI document must be serializable");
+    json.push('\n');
+    json
+  })
+}
+
+/// ドキュメントのルート (Stateを使わないため、どのRouterにもmergeできる)
+pub fn router<S: Clone + Send + Sync + 'static>(config: &Config) -> Router<S> {
+  let router = Router::new().route("/api/openapi.json", get(openapi_json));
+  if config.api_docs_ui {
+    router.route("/api/docs", get(docs_page))
+  } else {
+    router
+  }
+}
+
+/// `GET /api/openapi.json`
+async fn openapi_json() -> Response {
+  (
+    [(header::CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+    spec_json(),
+  )
+    .into_response()
+}
+
+/// `GET /api/docs` (Redocでドキュメントを表示する)
+async fn docs_page() -> Response {
+  let html = format!(
+    r#"<!doctype html>
+<html lang="ja">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>書き初め API</title>
+</head>
+<body>
+<redoc spec-url="/api/openapi.json"></redoc>
+<script src="https://cdn.jsdelivr.net/npm/redoc@{}/bundles/redoc.standalone.js"></script>
+</body>
+</html>
+"#,
+    REDOC_VERSION
+  );
+  // APIのレスポンス用のCSPではスクリプトを読み込めないため、ページ用のCSPを付ける (hardeningは上書きしない)
+  (
+    [(
+      header::CONTENT_SECURITY_POLICY,
+      HeaderValue::from_static(DOCS_CONTENT_SECURITY_POLICY),
+    )],
+    Html(html),
+  )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::body::Body;
+  use axum::http::{Request, StatusCode};
+  use http_body_util::BodyExt;
+  use tower::ServiceExt;
+
+  /// コミットしてあるドキュメント
+  const SNAPSHOT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/openapi.json");
+
+  async fn get_body(router: Router, uri: &str) -> (StatusCode, String) {
+    let response = router
+      .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+  }
+
+  /// 配信するドキュメントがコミットしてあるものと一致すること
+  /// `UPDATE_OPENAPI=1` なら、コミットしてあるものを更新する
+  #[tokio::test]
+  async fn test_openapi_snapshot() {
+    let (status, served) = get_body(router(&Config::default()), "/api/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+      std::fs::write(SNAPSHOT_PATH, &served).unwrap();
+      return;
+    }
+    let snapshot = std::fs::read_to_string(SNAPSHOT_PATH).unwrap_or_default();
+    assert!(
+      served == snapshot,
+      "docs/openapi.json is out of date; run `UPDATE_OPENAPI=1 cargo test openapi` and commit the result"
+    );
+  }
+
+  /// パス引数を含むパスもOpenAPIの形式 (`{public_id}`) で載っていること
+  #[test]
+  fn test_documents_paths() {
+    let doc = ApiDoc::openapi();
+    for path in [
+      "/api/calligraphy",
+      "/api/calligraphy/me",
+      "/api/calligraphy/{public_id}.svg",
+      "/api/calligraphy/{public_id}/strokes",
+      "/api/passkeys/{id}",
+      "/api/privacy/retention",
+    ] {
+      assert!(doc.paths.paths.contains_key(path), "{} is not documented", path);
+    }
+    assert!(doc.components.unwrap().schemas.contains_key("ErrorResponse"));
+  }
+
+  /// ドキュメントのページは設定したときのみ配信し、CDNのスクリプトを許可するCSPを付けること
+  #[tokio::test]
+  async fn test_docs_page() {
+    let (status, _) = get_body(router(&Config::default()), "/api/docs").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let response = router::<()>(&Config {
+      api_docs_ui: true,
+      ..Config::default()
+    })
+    .oneshot(Request::builder().uri("/api/docs").body(Body::empty()).unwrap())
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let csp = response.headers()[header::CONTENT_SECURITY_POLICY].to_str().unwrap();
+    assert!(csp.contains("script-src https://cdn.jsdelivr.net"));
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&body).contains(r#"spec-url="/api/openapi.json""#));
+  }
+}
@@ -0,0 +1,329 @@
+//! アップロードされた写真を検査・再エンコードするモジュール
+//!
+//! 写真はクライアントが申告した Content-Type ではなく、先頭のバイト列 (マジックナンバー) で形式を判定する。
+//! 一旦画素にデコードしてから再エンコードするため、EXIF (位置情報を含む)・XMP などのメタデータは全て取り除かれる。
+//! ただし写真の向きはEXIFで指定されることが多いため、デコード時に回転を適用してから取り除く。
+
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader, Limits};
+use thiserror::Error;
+
+use crate::error::AppError;
+
+/// アップロードできる写真の最大サイズ (バイト)
+pub const PHOTO_MAX_BYTES: usize = 10 * 1024 * 1024;
+/// デコードする写真の縦横の最大画素数 (解凍爆弾の対策)
+pub const PHOTO_MAX_DIMENSION: u32 = 8192;
+/// デコード時に確保するメモリの上限 (バイト)
+const DECODE_MAX_ALLOC: u64 = 256 * 1024 * 1024;
+/// 保存する写真の長辺の最大画素数 (大きければ縮小する)
+pub const PHOTO_LONG_SIDE: u32 = 2048;
+/// サムネイルの長辺の画素数
+pub const THUMBNAIL_LONG_SIDE: u32 = 480;
+/// JPEGの品質
+const JPEG_QUALITY: u8 = 85;
+
+/// 受け付ける写真の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+  Jpeg,
+  Png,
+  WebP,
+}
+
+impl SourceFormat {
+  fn image_format(self) -> ImageFormat {
+    match self {
+      SourceFormat::Jpeg => ImageFormat::Jpeg,
+      SourceFormat::Png => ImageFormat::Png,
+      SourceFormat::WebP => ImageFormat::WebP,
+    }
+  }
+}
+
+/// 保存する画像の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodedFormat {
+  Jpeg,
+  /// 透過のある画像 (JPEGでは透過を表せないため可逆圧縮のWebPにする)
+  WebP,
+}
+
+impl EncodedFormat {
+  /// ファイルの拡張子
+  pub fn extension(self) -> &'static str {
+    match self {
+      EncodedFormat::Jpeg => "jpg",
+      EncodedFormat::WebP => "webp",
+    }
+  }
+
+  pub fn content_type(self) -> &'static str {
+    match self {
+      EncodedFormat::Jpeg => "image/jpeg",
+      EncodedFormat::WebP => "image/webp",
+    }
+  }
+
+  /// 拡張子から形式を求める
+  pub fn from_extension(extension: &str) -> Option<Self> {
+    match extension {
+      "jpg" => Some(EncodedFormat::Jpeg),
+      "webp" => Some(EncodedFormat::WebP),
+      _ => None,
+    }
+  }
+}
+
+/// 再エンコードした画像
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+  pub bytes: Vec<u8>,
+  pub format: EncodedFormat,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// 再エンコードした写真とサムネイル
+#[derive(Debug, Clone)]
+pub struct ProcessedPhoto {
+  pub photo: EncodedImage,
+  pub thumbnail: EncodedImage,
+}
+
+/// 写真のエラー
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PhotoError {
+  /// サイズ超過
+  #[error("Photo must be {} MiB or less", PHOTO_MAX_BYTES / 1024 / 1024)]
+  TooLarge,
+
+  /// JPEG / PNG / WebP 以外の形式
+  #[error("Photo must be a JPEG, PNG or WebP image")]
+  UnsupportedFormat,
+
+  /// 画素数が多すぎる
+  #[error("Photo must be {max}x{max} pixels or less", max = PHOTO_MAX_DIMENSION)]
+  TooManyPixels,
+
+  /// 壊れた画像など、デコードできない
+  #[error("Photo could not be decoded")]
+  Undecodable,
+
+  /// 再エンコードの失敗 (サーバー側の問題)
+  #[error("Failed to encode photo: {0}")]
+  Encode(String),
+}
+
+impl From<PhotoError> for AppError {
+  fn from(e: PhotoError) -> Self {
+    match e {
+      PhotoError::TooLarge => AppError::PayloadTooLarge(e.to_string()),
+      PhotoError::UnsupportedFormat => AppError::UnsupportedMediaType(e.to_string()),
+      PhotoError::TooManyPixels | PhotoError::Undecodable => AppError::Validation(e.to_string()),
+      PhotoError::Encode(_) => {
+        tracing::error!("{}", e);
+        AppError::Internal
+      }
+    }
+  }
+}
+
+/// 先頭のバイト列から形式を判定する
+pub fn sniff(bytes: &[u8]) -> Option<SourceFormat> {
+  if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+    Some(SourceFormat::Jpeg)
+  } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+    Some(SourceFormat::Png)
+  } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+    Some(SourceFormat::WebP)
+  } else {
+    None
+  }
+}
+
+/// 写真を検査し、向きを補正して再エンコードする (CPUを使うため `spawn_blocking` から呼ぶ)
+pub fn process(bytes: &[u8]) -> Result<ProcessedPhoto, PhotoError> {
+  if bytes.len() > PHOTO_MAX_BYTES {
+    return Err(PhotoError::TooLarge);
+  }
+  let format = sniff(bytes).ok_or(PhotoError::UnsupportedFormat)?;
+  let image = decode(bytes, format)?;
+
+  let photo = encode(&fit(image.clone(), PHOTO_LONG_SIDE))?;
+  let thumbnail = encode(&fit(image, THUMBNAIL_LONG_SIDE))?;
+  Ok(ProcessedPhoto { photo, thumbnail })
+}
+
+/// 画素数・メモリの上限を付けてデコードし、EXIFの向きを適用する
+fn decode(bytes: &[u8], format: SourceFormat) -> Result<DynamicImage, PhotoError> {
+  let mut limits = Limits::default();
+  limits.max_image_width = Some(PHOTO_MAX_DIMENSION);
+  limits.max_image_height = Some(PHOTO_MAX_DIMENSION);
+  limits.max_alloc = Some(DECODE_MAX_ALLOC);
+
+  let mut reader = ImageReader::with_format(Cursor::new(bytes), format.image_format());
+  reader.limits(limits);
+  let mut decoder = reader.into_decoder().map_err(decode_error)?;
+  // 向きが読めなくても写真自体は表示できるため、回転せずに続ける
+  let orientation = decoder.orientation().ok();
+  let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+  if let Some(orientation) = orientation {
+    image.apply_orientation(orientation);
+  }
+  Ok(image)
+}
+
+fn decode_error(e: image::ImageError) -> PhotoError {
+  match e {
+    image::ImageError::Limits(_) => PhotoError::TooManyPixels,
+    _ => PhotoError::Undecodable,
+  }
+}
+
+/// 長辺が `long_side` を超えていれば縮小する (拡大はしない)
+fn fit(image: DynamicImage, long_side: u32) -> DynamicImage {
+  if image.width().max(image.height()) <= long_side {
+    return image;
+  }
+  image.resize(long_side, long_side, FilterType::Lanczos3)
+}
+
+/// 透過があれば可逆圧縮のWebP、なければJPEGにする
+fn encode(image: &DynamicImage) -> Result<EncodedImage, PhotoError> {
+  let (width, height) = (image.width(), image.height());
+  let rgba = image.to_rgba8();
+  let mut bytes = Vec::new();
+  let format = if rgba.pixels().any(|pixel| pixel.0[3] < u8::MAX) {
+    WebPEncoder::new_lossless(&mut bytes)
+      .encode(&rgba, width, height, ExtendedColorType::Rgba8)
+      .map_err(|e| PhotoError::Encode(e.to_string()))?;
+    EncodedFormat::WebP
+  } else {
+    JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+      .encode_image(&image.to_rgb8())
+      .map_err(|e| PhotoError::Encode(e.to_string()))?;
+    EncodedFormat::Jpeg
+  };
+  Ok(EncodedImage {
+    bytes,
+    format,
+    width,
+    height,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+  fn png(image: &DynamicImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+    bytes
+  }
+
+  /// EXIF (GPSを含む) と向き (右に90度回転) を持つJPEGを作る
+  fn jpeg_with_exif(width: u32, height: u32) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    let image = RgbImage::from_pixel(width, height, Rgb([200, 30, 30]));
+    JpegEncoder::new_with_quality(&mut jpeg, 90).encode_image(&image).unwrap();
+
+    // TIFF (ビッグエンディアン): IFD0 に Orientation=6 と GPS IFD へのポインター、GPS IFD に緯度の南北
+    let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+    tiff.extend_from_slice(&[0x00, 0x02]);
+    tiff.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00]);
+    tiff.extend_from_slice(&[0x88, 0x25, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x26]);
+    tiff.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    tiff.extend_from_slice(&[0x00, 0x01]);
+    tiff.extend_from_slice(&[0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, b'N', 0x00, 0x00, 0x00]);
+    tiff.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    let mut app1 = b"Exif\x00\x00".to_vec();
+    app1.extend_from_slice(&tiff);
+
+    let mut output = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    output.extend_from_slice(&((app1.len() + 2) as u16).to_be_bytes());
+    output.extend_from_slice(&app1);
+    output.extend_from_slice(&jpeg[2..]);
+    output
+  }
+
+  #[test]
+  fn test_sniff() {
+    assert_eq!(sniff(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(SourceFormat::Jpeg));
+    assert_eq!(sniff(b"\x89PNG\r\n\x1a\n...."), Some(SourceFormat::Png));
+    assert_eq!(sniff(b"RIFF\x00\x00\x00\x00WEBPVP8 "), Some(SourceFormat::WebP));
+    assert_eq!(sniff(b"GIF89a"), None);
+    assert_eq!(sniff(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), None);
+    assert_eq!(sniff(b""), None);
+  }
+
+  /// EXIFの向きを適用し、EXIF自体は取り除くこと
+  #[test]
+  fn test_process_strips_exif_and_applies_orientation() {
+    let input = jpeg_with_exif(40, 20);
+    assert!(input.windows(4).any(|w| w == b"Exif"));
+
+    let processed = process(&input).unwrap();
+    let photo = &processed.photo;
+    assert_eq!(photo.format, EncodedFormat::Jpeg);
+    // 右に90度回転して縦長になる
+    assert_eq!((photo.width, photo.height), (20, 40));
+    assert!(!photo.bytes.windows(4).any(|w| w == b"Exif"));
+    let decoded = image::load_from_memory(&photo.bytes).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (20, 40));
+  }
+
+  /// 大きな写真は縮小し、サムネイルはさらに小さくすること
+  #[test]
+  fn test_process_resizes() {
+    let input = png(&DynamicImage::ImageRgb8(RgbImage::new(2200, 1100)));
+    let processed = process(&input).unwrap();
+    assert_eq!((processed.photo.width, processed.photo.height), (PHOTO_LONG_SIDE, 1024));
+    assert_eq!(
+      (processed.thumbnail.width, processed.thumbnail.height),
+      (THUMBNAIL_LONG_SIDE, 240)
+    );
+
+    // 小さな写真は拡大しない
+    let small = process(&png(&DynamicImage::ImageRgb8(RgbImage::new(300, 200)))).unwrap();
+    assert_eq!((small.photo.width, small.photo.height), (300, 200));
+    assert_eq!((small.thumbnail.width, small.thumbnail.height), (300, 200));
+  }
+
+  /// 透過のある画像はWebP、透過のない画像はアルファチャンネルがあってもJPEGにすること
+  #[test]
+  fn test_process_format() {
+    let mut transparent = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 0, 255]));
+    transparent.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+    let processed = process(&png(&DynamicImage::ImageRgba8(transparent))).unwrap();
+    assert_eq!(processed.photo.format, EncodedFormat::WebP);
+    assert_eq!(&processed.photo.bytes[8..12], b"WEBP");
+
+    let opaque = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 0, 255]));
+    let processed = process(&png(&DynamicImage::ImageRgba8(opaque))).unwrap();
+    assert_eq!(processed.photo.format, EncodedFormat::Jpeg);
+  }
+
+  #[test]
+  fn test_process_invalid() {
+    assert_eq!(process(b"GIF89a....").unwrap_err(), PhotoError::UnsupportedFormat);
+    // 形式は正しいが中身が壊れている
+    assert_eq!(
+      process(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x00").unwrap_err(),
+      PhotoError::Undecodable
+    );
+    assert_eq!(
+      process(&vec![0xFF; PHOTO_MAX_BYTES + 1]).unwrap_err(),
+      PhotoError::TooLarge
+    );
+    // 画素数の上限 (ヘッダーのみで判定し、デコードはしない)
+    let huge = png(&DynamicImage::ImageLuma8(image::GrayImage::new(PHOTO_MAX_DIMENSION + 1, 1)));
+    assert_eq!(process(&huge).unwrap_err(), PhotoError::TooManyPixels);
+  }
+}
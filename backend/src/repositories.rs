@@ -1 +1,3 @@
-pub mod db_repository;
\ No newline at end of file
+pub mod db_repository;
+pub mod event_notifier;
+pub mod photo_storage;
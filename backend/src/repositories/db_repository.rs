@@ -1,12 +1,25 @@
-use crate::models::calligraphy::Calligraphy;
+use crate::models::calligraphy::{BoardState, Calligraphy};
+use crate::models::export::{CalligraphyRecord, PasskeyCredentialRecord, SessionRecord};
+use crate::models::list_query::ListQuery;
+use crate::models::passkey::{CeremonyKind, PasskeyCeremony, PasskeyRecord};
+use crate::models::photo::Photo;
+use crate::models::retention::{AnonymizedMetadata, RequestMetadata, RetentionPolicy};
+use crate::models::search::SearchHit;
+use crate::models::session::Session;
+use crate::models::strokes::Strokes;
+use crate::validation;
 use async_trait::async_trait;
 use sqlx::types::ipnetwork::IpNetwork;
-use sqlx::PgPool;
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use time::OffsetDateTime;
 use uuid::Uuid;
+use webauthn_rs::prelude::Passkey;
 
-#[cfg_attr(test, mockall::automock)]
+/// 書き初め (ボード) の作成・取得・削除・検索
 #[async_trait] // 非同期関数を含むトレイト用のマクロ
-pub trait CalligraphyRepositoryTrait: Send + Sync {
+pub trait BoardRepository: Send + Sync {
+  #[allow(clippy::too_many_arguments)] // 1行の列をそのまま引数にとる
   async fn create(
     &self,
     user_id: Uuid,
@@ -15,10 +28,195 @@ pub trait CalligraphyRepositoryTrait: Send + Sync {
     ip_address: Option<IpNetwork>,
     user_agent: Option<String>,
     accept_language: Option<String>,
+    strokes: Option<Strokes>,
+    photo: Option<Photo>,
   ) -> Result<Calligraphy, sqlx::Error>;
   async fn find_by_id(&self, user_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error>;
+  async fn find_by_public_id(&self, public_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error>;
+  async fn find_strokes(&self, public_id: Uuid) -> Result<Option<Strokes>, sqlx::Error>;
+  async fn is_photo_in_use(&self, key: &str) -> Result<bool, sqlx::Error>;
   async fn find_all(&self) -> Result<Vec<Calligraphy>, sqlx::Error>;
-  async fn delete(&self, user_id: Uuid) -> Result<u64, sqlx::Error>;
+  async fn find_for_stats(&self) -> Result<Vec<Calligraphy>, sqlx::Error>;
+  async fn find_latest(&self, limit: i64) -> Result<Vec<Calligraphy>, sqlx::Error>;
+  async fn find_filtered(&self, query: &ListQuery, viewer_id: Uuid) -> Result<Vec<Calligraphy>, sqlx::Error>;
+  async fn delete(&self, user_id: Uuid) -> Result<Option<Uuid>, sqlx::Error>;
+  async fn board_state(&self, viewer_id: Uuid) -> Result<BoardState, sqlx::Error>;
+
+  /// 全文検索 (関連度の高い順)
+  ///
+  /// 一致条件・スコア・並び順は `search` モジュールの定義に従う。
+  /// Postgres以外のリポジトリは全件を `search::rank` に渡して絞り込める (`find_all` は100件までのため使えない)。
+  async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<SearchHit>, sqlx::Error>;
+}
+
+/// 引き継ぎコード (別のブラウザへの引き継ぎ)
+#[async_trait]
+pub trait TransferCodeRepository: Send + Sync {
+  async fn save_transfer_code(
+    &self,
+    user_id: Uuid,
+    code_hash: Vec<u8>,
+    expires_at: OffsetDateTime,
+  ) -> Result<bool, sqlx::Error>;
+  async fn claim_transfer_code(&self, code_hash: Vec<u8>, new_user_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error>;
+}
+
+/// パスキー (WebAuthn) とその登録・認証の途中状態
+#[async_trait]
+pub trait PasskeyRepository: Send + Sync {
+  async fn save_passkey_ceremony(&self, ceremony: PasskeyCeremony) -> Result<(), sqlx::Error>;
+  async fn take_passkey_ceremony(&self, id: Uuid, kind: CeremonyKind) -> Result<Option<PasskeyCeremony>, sqlx::Error>;
+  async fn add_passkey(&self, user_id: Uuid, passkey: Passkey) -> Result<Option<PasskeyRecord>, sqlx::Error>;
+  async fn find_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyRecord>, sqlx::Error>;
+  async fn find_passkeys_by_public_id(&self, public_id: Uuid) -> Result<Vec<PasskeyRecord>, sqlx::Error>;
+  async fn update_passkey(&self, passkey: Passkey) -> Result<(), sqlx::Error>;
+  async fn delete_passkey(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error>;
+}
+
+/// ブラウザのセッション
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+  async fn create_session(
+    &self,
+    token_hash: Vec<u8>,
+    user_id: Uuid,
+    device: Option<String>,
+    expires_at: OffsetDateTime,
+  ) -> Result<Session, sqlx::Error>;
+  async fn create_legacy_session(
+    &self,
+    token_hash: Vec<u8>,
+    user_id: Uuid,
+    device: Option<String>,
+    expires_at: OffsetDateTime,
+  ) -> Result<Option<Session>, sqlx::Error>;
+  async fn find_session(&self, token_hash: Vec<u8>) -> Result<Option<Session>, sqlx::Error>;
+  async fn touch_session(&self, id: Uuid, expires_at: OffsetDateTime) -> Result<(), sqlx::Error>;
+  async fn find_sessions(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error>;
+  async fn delete_session(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error>;
+  async fn delete_session_by_token(&self, token_hash: Vec<u8>) -> Result<(), sqlx::Error>;
+  async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error>;
+  async fn revoke_user(&self, public_id: Uuid, new_user_id: Uuid) -> Result<Option<u64>, sqlx::Error>;
+}
+
+/// 個人データのエクスポートと、収集したリクエスト情報の匿名化
+#[async_trait]
+pub trait PrivacyRepository: Send + Sync {
+  async fn export_by_id(&self, user_id: Uuid) -> Result<Option<CalligraphyRecord>, sqlx::Error>;
+  async fn export_sessions(&self, user_id: Uuid) -> Result<Vec<SessionRecord>, sqlx::Error>;
+  async fn export_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyCredentialRecord>, sqlx::Error>;
+  async fn find_unanonymized(&self, cutoff: OffsetDateTime, limit: i64) -> Result<Vec<RequestMetadata>, sqlx::Error>;
+  async fn anonymize_metadata(
+    &self,
+    rows: Vec<AnonymizedMetadata>,
+    policy: RetentionPolicy,
+  ) -> Result<u64, sqlx::Error>;
+}
+
+/// リポジトリ全体 (サービスが使う全てのドメインのトレイト)
+///
+/// 各ドメインのトレイトを実装した型には自動で実装される。
+pub trait CalligraphyRepositoryTrait:
+  BoardRepository + TransferCodeRepository + PasskeyRepository + SessionRepository + PrivacyRepository
+{
+}
+
+impl<T> CalligraphyRepositoryTrait for T where
+  T: BoardRepository + TransferCodeRepository + PasskeyRepository + SessionRepository + PrivacyRepository
+{
+}
+
+#[cfg(test)]
+mockall::mock! {
+  /// テスト用のリポジトリ (全てのドメインのトレイトのモック)
+  pub CalligraphyRepositoryTrait {}
+
+  #[async_trait]
+  impl BoardRepository for CalligraphyRepositoryTrait {
+    #[allow(clippy::too_many_arguments)] // 1行の列をそのまま引数にとる
+    async fn create(
+      &self,
+      user_id: Uuid,
+      user_name: String,
+      content: String,
+      ip_address: Option<IpNetwork>,
+      user_agent: Option<String>,
+      accept_language: Option<String>,
+      strokes: Option<Strokes>,
+      photo: Option<Photo>,
+    ) -> Result<Calligraphy, sqlx::Error>;
+    async fn find_by_id(&self, user_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error>;
+    async fn find_by_public_id(&self, public_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error>;
+    async fn find_strokes(&self, public_id: Uuid) -> Result<Option<Strokes>, sqlx::Error>;
+    async fn is_photo_in_use(&self, key: &str) -> Result<bool, sqlx::Error>;
+    async fn find_all(&self) -> Result<Vec<Calligraphy>, sqlx::Error>;
+    async fn find_for_stats(&self) -> Result<Vec<Calligraphy>, sqlx::Error>;
+    async fn find_latest(&self, limit: i64) -> Result<Vec<Calligraphy>, sqlx::Error>;
+    async fn find_filtered(&self, query: &ListQuery, viewer_id: Uuid) -> Result<Vec<Calligraphy>, sqlx::Error>;
+    async fn delete(&self, user_id: Uuid) -> Result<Option<Uuid>, sqlx::Error>;
+    async fn board_state(&self, viewer_id: Uuid) -> Result<BoardState, sqlx::Error>;
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<SearchHit>, sqlx::Error>;
+  }
+
+  #[async_trait]
+  impl TransferCodeRepository for CalligraphyRepositoryTrait {
+    async fn save_transfer_code(
+      &self,
+      user_id: Uuid,
+      code_hash: Vec<u8>,
+      expires_at: OffsetDateTime,
+    ) -> Result<bool, sqlx::Error>;
+    async fn claim_transfer_code(&self, code_hash: Vec<u8>, new_user_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error>;
+  }
+
+  #[async_trait]
+  impl PasskeyRepository for CalligraphyRepositoryTrait {
+    async fn save_passkey_ceremony(&self, ceremony: PasskeyCeremony) -> Result<(), sqlx::Error>;
+    async fn take_passkey_ceremony(&self, id: Uuid, kind: CeremonyKind) -> Result<Option<PasskeyCeremony>, sqlx::Error>;
+    async fn add_passkey(&self, user_id: Uuid, passkey: Passkey) -> Result<Option<PasskeyRecord>, sqlx::Error>;
+    async fn find_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyRecord>, sqlx::Error>;
+    async fn find_passkeys_by_public_id(&self, public_id: Uuid) -> Result<Vec<PasskeyRecord>, sqlx::Error>;
+    async fn update_passkey(&self, passkey: Passkey) -> Result<(), sqlx::Error>;
+    async fn delete_passkey(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error>;
+  }
+
+  #[async_trait]
+  impl SessionRepository for CalligraphyRepositoryTrait {
+    async fn create_session(
+      &self,
+      token_hash: Vec<u8>,
+      user_id: Uuid,
+      device: Option<String>,
+      expires_at: OffsetDateTime,
+    ) -> Result<Session, sqlx::Error>;
+    async fn create_legacy_session(
+      &self,
+      token_hash: Vec<u8>,
+      user_id: Uuid,
+      device: Option<String>,
+      expires_at: OffsetDateTime,
+    ) -> Result<Option<Session>, sqlx::Error>;
+    async fn find_session(&self, token_hash: Vec<u8>) -> Result<Option<Session>, sqlx::Error>;
+    async fn touch_session(&self, id: Uuid, expires_at: OffsetDateTime) -> Result<(), sqlx::Error>;
+    async fn find_sessions(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error>;
+    async fn delete_session(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error>;
+    async fn delete_session_by_token(&self, token_hash: Vec<u8>) -> Result<(), sqlx::Error>;
+    async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error>;
+    async fn revoke_user(&self, public_id: Uuid, new_user_id: Uuid) -> Result<Option<u64>, sqlx::Error>;
+  }
+
+  #[async_trait]
+  impl PrivacyRepository for CalligraphyRepositoryTrait {
+    async fn export_by_id(&self, user_id: Uuid) -> Result<Option<CalligraphyRecord>, sqlx::Error>;
+    async fn export_sessions(&self, user_id: Uuid) -> Result<Vec<SessionRecord>, sqlx::Error>;
+    async fn export_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyCredentialRecord>, sqlx::Error>;
+    async fn find_unanonymized(&self, cutoff: OffsetDateTime, limit: i64) -> Result<Vec<RequestMetadata>, sqlx::Error>;
+    async fn anonymize_metadata(
+      &self,
+      rows: Vec<AnonymizedMetadata>,
+      policy: RetentionPolicy,
+    ) -> Result<u64, sqlx::Error>;
+  }
 }
 
 /// Calligraphyテーブルへのアクセスを担当するリポジトリ
@@ -36,7 +234,7 @@ impl CalligraphyRepository {
 }
 
 #[async_trait]
-impl CalligraphyRepositoryTrait for CalligraphyRepository {
+impl BoardRepository for CalligraphyRepository {
   /// 新規書き初めの作成 (INSERT)
   ///
   /// # 引数
@@ -46,9 +244,12 @@ impl CalligraphyRepositoryTrait for CalligraphyRepository {
   /// * `ip_address` - IPアドレス
   /// * `user_agent` - User-Agent
   /// * `accept_language` - Accept-Language
+  /// * `strokes` - 手書きの筆跡データ (検証済み, 更新時に省略すると削除する)
+  /// * `photo` - 保存済みの写真 (更新時に省略すると外す)
   ///
   /// # 戻り値
   /// * `Ok(Calligraphy)` - DBにより生成されたタイムスタンプを含む完全なモデル
+  #[tracing::instrument(name = "db.create", skip_all, fields(db.system = "postgresql"))]
   async fn create(
     &self,
     user_id: Uuid,
@@ -57,31 +258,43 @@ impl CalligraphyRepositoryTrait for CalligraphyRepository {
     ip_address: Option<IpNetwork>,
     user_agent: Option<String>,
     accept_language: Option<String>,
+    strokes: Option<Strokes>,
+    photo: Option<Photo>,
   ) -> Result<Calligraphy, sqlx::Error> {
+    // 文字数での絞り込み用 (DBでは書記素クラスタを数えられないため、ここで算出する)
+    let content_length = validation::grapheme_count(&content) as i16;
+
     // query_as! マクロ:
     // コンパイル時にSQL構文と、戻り値(Calligraphy構造体)の型整合性をチェックする。
     // フィールド名とカラム名が完全に一致している必要がある。
     sqlx::query_as!(
       Calligraphy,
       r#"
-						INSERT INTO calligraphy (user_id, user_name, content, ip_address, user_agent, accept_language, updated_at)
-						VALUES ($1, $2, $3, $4, $5, $6, NOW())
+						INSERT INTO calligraphy (user_id, user_name, content, content_length, ip_address, user_agent, accept_language, strokes, photo, updated_at)
+						VALUES ($1, $2, $3, $7, $4, $5, $6, $8, $9, NOW())
 						ON CONFLICT (user_id)
 						DO UPDATE SET	-- 重複時は内容を上書き
 								user_name = EXCLUDED.user_name,
 								content = EXCLUDED.content,
+								content_length = EXCLUDED.content_length,
 								ip_address = EXCLUDED.ip_address,
 								user_agent = EXCLUDED.user_agent,
 								accept_language = EXCLUDED.accept_language,
-								updated_at = NOW()
-						RETURNING user_id, user_name, content, ip_address, user_agent, accept_language, created_at, updated_at
+								strokes = EXCLUDED.strokes,	-- 内容を書き直したら筆跡も置き換える
+								photo = EXCLUDED.photo,	-- 写真も同様
+								updated_at = NOW(),
+								anonymized_at = NULL	-- 新しいリクエスト情報は再び匿名化の対象
+						RETURNING user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", ip_address, user_agent, accept_language, created_at, updated_at
 						"#,
       user_id,
       user_name,
       content,
       ip_address,
       user_agent,
-      accept_language
+      accept_language,
+      content_length,
+      strokes.map(Json) as Option<Json<Strokes>>,
+      photo.map(Json) as Option<Json<Photo>>
     )
     .fetch_one(&self.pool)
     .await
@@ -90,11 +303,12 @@ impl CalligraphyRepositoryTrait for CalligraphyRepository {
   /// IDによる検索 (SELECT)
   ///
   /// 戻り値Option<Calligraphy>
+  #[tracing::instrument(name = "db.find_by_id", skip_all, fields(db.system = "postgresql"))]
   async fn find_by_id(&self, user_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error> {
     sqlx::query_as!(
       Calligraphy,
       r#"
-						SELECT user_id, user_name, content, NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
+						SELECT user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
 						FROM calligraphy
 						WHERE user_id = $1
 						"#,
@@ -104,14 +318,69 @@ impl CalligraphyRepositoryTrait for CalligraphyRepository {
     .await
   }
 
+  /// 公開用IDで取得する (OGP画像・SVG・筆跡用)
+  ///
+  /// 一覧 (作成日時の新しい順に100件) に含まれない古い書き初めも対象にする。
+  /// リクエスト情報は公開しないため取得しない。
+  #[tracing::instrument(name = "db.find_by_public_id", skip_all, fields(db.system = "postgresql"))]
+  async fn find_by_public_id(&self, public_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error> {
+    sqlx::query_as!(
+      Calligraphy,
+      r#"
+						SELECT user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
+						FROM calligraphy
+						WHERE public_id = $1
+						"#,
+      public_id
+    )
+    .fetch_optional(&self.pool)
+    .await
+  }
+
+  /// 公開用IDで筆跡データを取得する (筆跡のない書き初めはNone)
+  #[tracing::instrument(name = "db.find_strokes", skip_all, fields(db.system = "postgresql"))]
+  async fn find_strokes(&self, public_id: Uuid) -> Result<Option<Strokes>, sqlx::Error> {
+    let record = sqlx::query!(
+      r#"
+			SELECT strokes AS "strokes!: Json<Strokes>"
+			FROM calligraphy
+			WHERE public_id = $1 AND strokes IS NOT NULL
+			"#,
+      public_id
+    )
+    .fetch_optional(&self.pool)
+    .await?;
+
+    Ok(record.map(|r| r.strokes.0))
+  }
+
+  /// 写真のファイルが書き初めから参照されているか (写真を削除してよいかの判定用)
+  #[tracing::instrument(name = "db.is_photo_in_use", skip_all, fields(db.system = "postgresql"))]
+  async fn is_photo_in_use(&self, key: &str) -> Result<bool, sqlx::Error> {
+    let record = sqlx::query!(
+      r#"
+			SELECT EXISTS (
+				SELECT 1 FROM calligraphy
+				WHERE photo->>'key' = $1 OR photo->>'thumbnail_key' = $1
+			) AS "in_use!"
+			"#,
+      key
+    )
+    .fetch_one(&self.pool)
+    .await?;
+
+    Ok(record.in_use)
+  }
+
   /// 全件取得 (一覧表示用)
   ///
   /// 作成日時の新しい順（降順）で取得する。
+  #[tracing::instrument(name = "db.find_all", skip_all, fields(db.system = "postgresql"))]
   async fn find_all(&self) -> Result<Vec<Calligraphy>, sqlx::Error> {
     sqlx::query_as!(
       Calligraphy,
       r#"
-            SELECT user_id, user_name, content, NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
+            SELECT user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
             FROM calligraphy
             ORDER BY created_at DESC
             LIMIT 100 -- 安全のため上限を設定（必要に応じてページネーションに変更）
@@ -121,16 +390,673 @@ impl CalligraphyRepositoryTrait for CalligraphyRepository {
     .await
   }
 
+  /// 集計用の全件取得 (`GET /api/stats`)
+  ///
+  /// 言語・ブラウザ・OSの集計に使うため User-Agent / Accept-Language を含め、件数の上限は設けない。
+  /// IPアドレスと写真は集計に使わないため取得しない。
+  #[tracing::instrument(name = "db.find_for_stats", skip_all, fields(db.system = "postgresql"))]
+  async fn find_for_stats(&self) -> Result<Vec<Calligraphy>, sqlx::Error> {
+    sqlx::query_as!(
+      Calligraphy,
+      r#"
+            SELECT user_id, public_id, user_name, content, NULL::jsonb AS "photo: Json<Photo>", NULL::inet AS ip_address, user_agent, accept_language, created_at, updated_at
+            FROM calligraphy
+            "#
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// 更新日時の新しい順に取得 (フィード用)
+  #[tracing::instrument(name = "db.find_latest", skip_all, fields(db.system = "postgresql"))]
+  async fn find_latest(&self, limit: i64) -> Result<Vec<Calligraphy>, sqlx::Error> {
+    sqlx::query_as!(
+      Calligraphy,
+      r#"
+            SELECT user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
+            FROM calligraphy
+            ORDER BY updated_at DESC, public_id
+            LIMIT $1
+            "#,
+      limit
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// 条件を指定した一覧取得
+  ///
+  /// 条件に応じてWHERE句を組み立てる。値は全てバインド変数で渡し、
+  /// ORDER BY句は `ListSort` の固定の文字列のみを使うため、SQLインジェクションの余地はない。
+  #[tracing::instrument(name = "db.find_filtered", skip_all, fields(db.system = "postgresql"))]
+  async fn find_filtered(&self, query: &ListQuery, viewer_id: Uuid) -> Result<Vec<Calligraphy>, sqlx::Error> {
+    let mut builder = QueryBuilder::<Postgres>::new(
+      r#"
+			SELECT user_id, public_id, user_name, content, photo, NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
+			FROM calligraphy
+			WHERE TRUE
+			"#,
+    );
+    if let Some(from) = query.from {
+      builder.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+      builder.push(" AND created_at < ").push_bind(to);
+    }
+    if query.mine_only {
+      builder.push(" AND user_id = ").push_bind(viewer_id);
+    }
+    if let Some(min) = query.min_length {
+      builder.push(" AND content_length >= ").push_bind(min as i16);
+    }
+    if let Some(max) = query.max_length {
+      builder.push(" AND content_length <= ").push_bind(max as i16);
+    }
+    builder.push(" ORDER BY ").push(query.sort.order_by());
+    builder.push(" LIMIT 100"); // find_all と同じ上限
+
+    builder.build_query_as::<Calligraphy>().fetch_all(&self.pool).await
+  }
+
   /// 削除
-  /// 戻り値は影響を受けた行数
-  async fn delete(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
-    let result = sqlx::query!(
+  /// 戻り値は削除した行の公開用ID (対象が無ければNone)
+  #[tracing::instrument(name = "db.delete", skip_all, fields(db.system = "postgresql"))]
+  async fn delete(&self, user_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    let record = sqlx::query!(
       r#"
 			DELETE FROM calligraphy
 			WHERE user_id = $1
+			RETURNING public_id
 			"#,
       user_id
     )
+    .fetch_optional(&self.pool)
+    .await?;
+
+    Ok(record.map(|r| r.public_id))
+  }
+
+  /// ボード全体の状態 (条件付きGET用)
+  ///
+  /// 一覧を取得・シリアライズせずに、変更の有無を判定するための軽量なクエリ
+  #[tracing::instrument(name = "db.board_state", skip_all, fields(db.system = "postgresql"))]
+  async fn board_state(&self, viewer_id: Uuid) -> Result<BoardState, sqlx::Error> {
+    let record = sqlx::query!(
+      r#"
+			SELECT
+				(SELECT COUNT(*) FROM calligraphy) AS "count!",
+				(SELECT MAX(updated_at) FROM calligraphy) AS max_updated_at,
+				(SELECT last_deleted_at FROM calligraphy_board) AS last_deleted_at,
+				(SELECT public_id FROM calligraphy WHERE user_id = $1) AS mine
+			"#,
+      viewer_id
+    )
+    .fetch_one(&self.pool)
+    .await?;
+
+    Ok(BoardState {
+      count: record.count,
+      max_updated_at: record.max_updated_at,
+      last_deleted_at: record.last_deleted_at,
+      mine: record.mine,
+    })
+  }
+
+  /// 全文検索 (pg_trgm)
+  ///
+  /// 部分一致 (ILIKE) と類似度 (`%` 演算子) で絞り込み、いずれもトライグラムのGINインデックスを使う。
+  /// ただし3文字未満の検索語はトライグラムを作れないため、ILIKEは全件走査になる。
+  /// スコアの計算式は `search::score` と一致させること。
+  #[tracing::instrument(name = "db.search", skip_all, fields(db.system = "postgresql"))]
+  async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<SearchHit>, sqlx::Error> {
+    let pattern = format!("%{}%", escape_like(query));
+    let records = sqlx::query!(
+      r#"
+			SELECT user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", created_at, updated_at,
+				(
+					(user_name ILIKE $2)::int
+					+ (content ILIKE $2)::int
+					+ GREATEST(similarity(user_name, $1), similarity(content, $1))
+				)::real AS "score!"
+			FROM calligraphy
+			WHERE user_name ILIKE $2 OR content ILIKE $2 OR user_name % $1 OR content % $1
+			ORDER BY "score!" DESC, updated_at DESC, public_id
+			LIMIT $3 OFFSET $4
+			"#,
+      query,
+      pattern,
+      limit,
+      offset
+    )
+    .fetch_all(&self.pool)
+    .await?;
+
+    Ok(
+      records
+        .into_iter()
+        .map(|r| SearchHit {
+          entry: Calligraphy {
+            user_id: r.user_id,
+            public_id: r.public_id,
+            user_name: r.user_name,
+            content: r.content,
+            photo: r.photo,
+            ip_address: None,
+            user_agent: None,
+            accept_language: None,
+            created_at: r.created_at,
+            updated_at: r.updated_at,
+          },
+          score: r.score,
+        })
+        .collect(),
+    )
+  }
+}
+
+#[async_trait]
+impl TransferCodeRepository for CalligraphyRepository {
+  /// 引き継ぎコード (のハッシュ) を保存する
+  ///
+  /// 1ユーザーにつき1つのため、発行済みのコードは置き換える。期限切れのコードはここで掃除する。
+  /// 戻り値は保存したか (書き初めがなければ引き継ぐものがないため保存しない)
+  #[tracing::instrument(name = "db.save_transfer_code", skip_all, fields(db.system = "postgresql"))]
+  async fn save_transfer_code(
+    &self,
+    user_id: Uuid,
+    code_hash: Vec<u8>,
+    expires_at: OffsetDateTime,
+  ) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+      r#"
+			DELETE FROM transfer_code
+			WHERE expires_at <= NOW() AND user_id <> $1
+			"#,
+      user_id
+    )
+    .execute(&self.pool)
+    .await?;
+
+    let result = sqlx::query!(
+      r#"
+			INSERT INTO transfer_code (user_id, code_hash, expires_at)
+			SELECT user_id, $2, $3 FROM calligraphy WHERE user_id = $1
+			ON CONFLICT (user_id)
+			DO UPDATE SET
+				code_hash = EXCLUDED.code_hash,
+				expires_at = EXCLUDED.expires_at,
+				created_at = NOW()
+			"#,
+      user_id,
+      code_hash,
+      expires_at
+    )
+    .execute(&self.pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+  }
+
+  /// 引き継ぎコードを使い、書き初めのユーザーIDを `new_user_id` に付け替える
+  ///
+  /// コードの削除と付け替えを1つの文で行うため、同じコードで2回引き継ぐことはできない。
+  /// 元のユーザーID (元のブラウザのCookie) は以降どの書き初めにも対応しなくなる。
+  /// 戻り値は付け替えた書き初め (コードが存在しない・期限切れならNone)
+  #[tracing::instrument(name = "db.claim_transfer_code", skip_all, fields(db.system = "postgresql"))]
+  async fn claim_transfer_code(&self, code_hash: Vec<u8>, new_user_id: Uuid) -> Result<Option<Calligraphy>, sqlx::Error> {
+    sqlx::query_as!(
+      Calligraphy,
+      r#"
+			WITH claimed AS (
+				DELETE FROM transfer_code
+				WHERE code_hash = $1 AND expires_at > NOW()
+				RETURNING user_id
+			)
+			UPDATE calligraphy
+			SET user_id = $2
+			FROM claimed
+			WHERE calligraphy.user_id = claimed.user_id
+			RETURNING calligraphy.user_id, public_id, user_name, content, photo AS "photo: Json<Photo>", NULL::inet AS ip_address, NULL::text AS user_agent, NULL::varchar AS accept_language, created_at, updated_at
+			"#,
+      code_hash,
+      new_user_id
+    )
+    .fetch_optional(&self.pool)
+    .await
+  }
+}
+
+#[async_trait]
+impl PasskeyRepository for CalligraphyRepository {
+  /// パスキーの登録・認証の途中の状態を保存する (期限切れの状態はここで掃除する)
+  #[tracing::instrument(name = "db.save_passkey_ceremony", skip_all, fields(db.system = "postgresql"))]
+  async fn save_passkey_ceremony(&self, ceremony: PasskeyCeremony) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM passkey_ceremony WHERE expires_at <= NOW()")
+      .execute(&self.pool)
+      .await?;
+
+    sqlx::query!(
+      r#"
+			INSERT INTO passkey_ceremony (id, kind, user_id, state, expires_at)
+			VALUES ($1, $2, $3, $4, $5)
+			"#,
+      ceremony.id,
+      ceremony.kind.as_str(),
+      ceremony.user_id,
+      ceremony.state,
+      ceremony.expires_at
+    )
+    .execute(&self.pool)
+    .await?;
+    Ok(())
+  }
+
+  /// パスキーの登録・認証の途中の状態を取り出す
+  ///
+  /// 取り出すと同時に削除するため、同じチャレンジで2回完了することはできない。
+  /// 存在しない・種類が違う・期限切れならNone
+  #[tracing::instrument(name = "db.take_passkey_ceremony", skip_all, fields(db.system = "postgresql"))]
+  async fn take_passkey_ceremony(&self, id: Uuid, kind: CeremonyKind) -> Result<Option<PasskeyCeremony>, sqlx::Error> {
+    let record = sqlx::query!(
+      r#"
+			DELETE FROM passkey_ceremony
+			WHERE id = $1 AND kind = $2 AND expires_at > NOW()
+			RETURNING user_id, state, expires_at
+			"#,
+      id,
+      kind.as_str()
+    )
+    .fetch_optional(&self.pool)
+    .await?;
+
+    Ok(record.map(|r| PasskeyCeremony {
+      id,
+      kind,
+      user_id: r.user_id,
+      state: r.state,
+      expires_at: r.expires_at,
+    }))
+  }
+
+  /// パスキーを登録する
+  /// 戻り値は登録したパスキー (書き初めがなければ登録せずNone)
+  #[tracing::instrument(name = "db.add_passkey", skip_all, fields(db.system = "postgresql"))]
+  async fn add_passkey(&self, user_id: Uuid, passkey: Passkey) -> Result<Option<PasskeyRecord>, sqlx::Error> {
+    let credential_id = passkey.cred_id().to_vec();
+    sqlx::query_as!(
+      PasskeyRecord,
+      r#"
+			INSERT INTO passkey_credential (credential_id, user_id, passkey)
+			SELECT $1, user_id, $3 FROM calligraphy WHERE user_id = $2
+			RETURNING id, user_id, passkey AS "passkey: Json<Passkey>", created_at, last_used_at
+			"#,
+      credential_id,
+      user_id,
+      Json(passkey) as Json<Passkey>
+    )
+    .fetch_optional(&self.pool)
+    .await
+  }
+
+  /// ユーザーのパスキーを登録の古い順に取得する
+  #[tracing::instrument(name = "db.find_passkeys", skip_all, fields(db.system = "postgresql"))]
+  async fn find_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyRecord>, sqlx::Error> {
+    sqlx::query_as!(
+      PasskeyRecord,
+      r#"
+			SELECT id, user_id, passkey AS "passkey: Json<Passkey>", created_at, last_used_at
+			FROM passkey_credential
+			WHERE user_id = $1
+			ORDER BY created_at, id
+			"#,
+      user_id
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// 書き初めの公開用IDから、その持ち主のパスキーを取得する (ログインの開始用)
+  #[tracing::instrument(name = "db.find_passkeys_by_public_id", skip_all, fields(db.system = "postgresql"))]
+  async fn find_passkeys_by_public_id(&self, public_id: Uuid) -> Result<Vec<PasskeyRecord>, sqlx::Error> {
+    sqlx::query_as!(
+      PasskeyRecord,
+      r#"
+			SELECT p.id, p.user_id, p.passkey AS "passkey: Json<Passkey>", p.created_at, p.last_used_at
+			FROM passkey_credential p
+			JOIN calligraphy c ON c.user_id = p.user_id
+			WHERE c.public_id = $1
+			ORDER BY p.created_at, p.id
+			"#,
+      public_id
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// ログインに使ったパスキー (署名カウンタ等を更新したもの) を保存する
+  #[tracing::instrument(name = "db.update_passkey", skip_all, fields(db.system = "postgresql"))]
+  async fn update_passkey(&self, passkey: Passkey) -> Result<(), sqlx::Error> {
+    let credential_id = passkey.cred_id().to_vec();
+    sqlx::query!(
+      r#"
+			UPDATE passkey_credential
+			SET passkey = $2, last_used_at = NOW()
+			WHERE credential_id = $1
+			"#,
+      credential_id,
+      Json(passkey) as Json<Passkey>
+    )
+    .execute(&self.pool)
+    .await?;
+    Ok(())
+  }
+
+  /// パスキーを削除する (他のユーザーのパスキーは削除しない)
+  /// 戻り値は削除したか
+  #[tracing::instrument(name = "db.delete_passkey", skip_all, fields(db.system = "postgresql"))]
+  async fn delete_passkey(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+      "DELETE FROM passkey_credential WHERE id = $1 AND user_id = $2",
+      id,
+      user_id
+    )
+    .execute(&self.pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+  }
+}
+
+#[async_trait]
+impl SessionRepository for CalligraphyRepository {
+  /// セッションを作成する
+  #[tracing::instrument(name = "db.create_session", skip_all, fields(db.system = "postgresql"))]
+  async fn create_session(
+    &self,
+    token_hash: Vec<u8>,
+    user_id: Uuid,
+    device: Option<String>,
+    expires_at: OffsetDateTime,
+  ) -> Result<Session, sqlx::Error> {
+    sqlx::query_as!(
+      Session,
+      r#"
+			INSERT INTO user_session (token_hash, user_id, device, expires_at)
+			VALUES ($1, $2, $3, $4)
+			RETURNING id, token_hash, user_id, device, created_at, last_seen_at, expires_at
+			"#,
+      token_hash,
+      user_id,
+      device,
+      expires_at
+    )
+    .fetch_one(&self.pool)
+    .await
+  }
+
+  /// 以前のユーザーIDのCookieからセッションを作成する
+  ///
+  /// 書き初めを持ち、セッションが1つもない (一度も移行していない) ユーザーのみ作成する (それ以外はNone)。
+  /// コピーされたCookieや、管理者が無効にしたユーザーのCookieからはセッションを作れない。
+  #[tracing::instrument(name = "db.create_legacy_session", skip_all, fields(db.system = "postgresql"))]
+  async fn create_legacy_session(
+    &self,
+    token_hash: Vec<u8>,
+    user_id: Uuid,
+    device: Option<String>,
+    expires_at: OffsetDateTime,
+  ) -> Result<Option<Session>, sqlx::Error> {
+    sqlx::query_as!(
+      Session,
+      r#"
+			INSERT INTO user_session (token_hash, user_id, device, expires_at)
+			SELECT $1, $2, $3, $4
+			WHERE EXISTS (SELECT 1 FROM calligraphy WHERE user_id = $2)
+				AND NOT EXISTS (SELECT 1 FROM user_session WHERE user_id = $2)
+			RETURNING id, token_hash, user_id, device, created_at, last_seen_at, expires_at
+			"#,
+      token_hash,
+      user_id,
+      device,
+      expires_at
+    )
+    .fetch_optional(&self.pool)
+    .await
+  }
+
+  /// 有効なセッションを取得する (存在しない・期限切れならNone)
+  #[tracing::instrument(name = "db.find_session", skip_all, fields(db.system = "postgresql"))]
+  async fn find_session(&self, token_hash: Vec<u8>) -> Result<Option<Session>, sqlx::Error> {
+    sqlx::query_as!(
+      Session,
+      r#"
+			SELECT id, token_hash, user_id, device, created_at, last_seen_at, expires_at
+			FROM user_session
+			WHERE token_hash = $1 AND expires_at > NOW()
+			"#,
+      token_hash
+    )
+    .fetch_optional(&self.pool)
+    .await
+  }
+
+  /// セッションが使われたことを記録し、有効期限を延長する
+  #[tracing::instrument(name = "db.touch_session", skip_all, fields(db.system = "postgresql"))]
+  async fn touch_session(&self, id: Uuid, expires_at: OffsetDateTime) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+      "UPDATE user_session SET last_seen_at = NOW(), expires_at = $2 WHERE id = $1",
+      id,
+      expires_at
+    )
+    .execute(&self.pool)
+    .await?;
+    Ok(())
+  }
+
+  /// ユーザーの有効なセッションの一覧 (最近使われた順)
+  #[tracing::instrument(name = "db.find_sessions", skip_all, fields(db.system = "postgresql"))]
+  async fn find_sessions(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as!(
+      Session,
+      r#"
+			SELECT id, token_hash, user_id, device, created_at, last_seen_at, expires_at
+			FROM user_session
+			WHERE user_id = $1 AND expires_at > NOW()
+			ORDER BY last_seen_at DESC, created_at DESC
+			"#,
+      user_id
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// セッションを削除する (他のユーザーのセッションは削除しない)
+  /// 戻り値は削除したか
+  #[tracing::instrument(name = "db.delete_session", skip_all, fields(db.system = "postgresql"))]
+  async fn delete_session(&self, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM user_session WHERE id = $1 AND user_id = $2", id, user_id)
+      .execute(&self.pool)
+      .await?;
+    Ok(result.rows_affected() == 1)
+  }
+
+  /// トークンのセッションを削除する (ログアウト)
+  #[tracing::instrument(name = "db.delete_session_by_token", skip_all, fields(db.system = "postgresql"))]
+  async fn delete_session_by_token(&self, token_hash: Vec<u8>) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM user_session WHERE token_hash = $1", token_hash)
+      .execute(&self.pool)
+      .await?;
+    Ok(())
+  }
+
+  /// 期限切れのセッションを削除する (匿名化ジョブから定期的に実行する)
+  /// 戻り値は削除した行数
+  #[tracing::instrument(name = "db.delete_expired_sessions", skip_all, fields(db.system = "postgresql"))]
+  async fn delete_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM user_session WHERE expires_at <= NOW()")
+      .execute(&self.pool)
+      .await?;
+    Ok(result.rows_affected())
+  }
+
+  /// 書き初めのユーザーの全てのセッションを無効にする (管理用)
+  ///
+  /// セッションの削除だけでは、ユーザーIDのCookie (`calli_user_id`) やパスキーから新しいセッションを作れてしまう。
+  /// そのため、パスキー・引き継ぎコードも削除し、書き初めには誰も知らない新しいユーザーIDを割り当てる。
+  /// 戻り値は削除したセッションの数 (書き初めが存在しなければNone)
+  #[tracing::instrument(name = "db.revoke_user", skip_all, fields(db.system = "postgresql"))]
+  async fn revoke_user(&self, public_id: Uuid, new_user_id: Uuid) -> Result<Option<u64>, sqlx::Error> {
+    let mut tx = self.pool.begin().await?;
+
+    let Some(user_id) = sqlx::query_scalar!(
+      "SELECT user_id FROM calligraphy WHERE public_id = $1 FOR UPDATE",
+      public_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+      return Ok(None);
+    };
+
+    sqlx::query!("DELETE FROM passkey_credential WHERE user_id = $1", user_id)
+      .execute(&mut *tx)
+      .await?;
+    sqlx::query!("DELETE FROM transfer_code WHERE user_id = $1", user_id)
+      .execute(&mut *tx)
+      .await?;
+    sqlx::query!(
+      "UPDATE calligraphy SET user_id = $2 WHERE user_id = $1",
+      user_id,
+      new_user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let revoked = sqlx::query!("DELETE FROM user_session WHERE user_id = $1", user_id)
+      .execute(&mut *tx)
+      .await?
+      .rows_affected();
+
+    tx.commit().await?;
+    Ok(Some(revoked))
+  }
+}
+
+#[async_trait]
+impl PrivacyRepository for CalligraphyRepository {
+  /// 個人データのエクスポート用に、情報収集用の列を含む全ての列を取得する
+  #[tracing::instrument(name = "db.export_by_id", skip_all, fields(db.system = "postgresql"))]
+  async fn export_by_id(&self, user_id: Uuid) -> Result<Option<CalligraphyRecord>, sqlx::Error> {
+    sqlx::query_as!(
+      CalligraphyRecord,
+      r#"
+			SELECT public_id, user_name, content, content_length, abbrev(ip_address) AS ip_address, user_agent, accept_language::text AS accept_language, created_at, updated_at, anonymized_at, strokes AS "strokes: Json<Strokes>", photo AS "photo: Json<Photo>"
+			FROM calligraphy
+			WHERE user_id = $1
+			"#,
+      user_id
+    )
+    .fetch_optional(&self.pool)
+    .await
+  }
+
+  /// 個人データのエクスポート用に、ユーザーの全てのセッション (期限切れで未削除のものも含む) を取得する
+  #[tracing::instrument(name = "db.export_sessions", skip_all, fields(db.system = "postgresql"))]
+  async fn export_sessions(&self, user_id: Uuid) -> Result<Vec<SessionRecord>, sqlx::Error> {
+    sqlx::query_as!(
+      SessionRecord,
+      r#"
+			SELECT id, device, created_at, last_seen_at, expires_at
+			FROM user_session
+			WHERE user_id = $1
+			ORDER BY created_at, id
+			"#,
+      user_id
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// 個人データのエクスポート用に、ユーザーの全てのパスキーを取得する (資格情報IDはBase64URLで返す)
+  #[tracing::instrument(name = "db.export_passkeys", skip_all, fields(db.system = "postgresql"))]
+  async fn export_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyCredentialRecord>, sqlx::Error> {
+    sqlx::query_as!(
+      PasskeyCredentialRecord,
+      r#"
+			SELECT id, rtrim(translate(encode(credential_id, 'base64'), E'+/\n', '-_'), '=') AS "credential_id!", passkey AS "passkey: Json<serde_json::Value>", created_at, last_used_at
+			FROM passkey_credential
+			WHERE user_id = $1
+			ORDER BY created_at, id
+			"#,
+      user_id
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// 匿名化の対象行 (指定日時より前に更新され、まだ匿名化していない行) を取得する
+  #[tracing::instrument(name = "db.find_unanonymized", skip_all, fields(db.system = "postgresql"))]
+  async fn find_unanonymized(&self, cutoff: OffsetDateTime, limit: i64) -> Result<Vec<RequestMetadata>, sqlx::Error> {
+    sqlx::query_as!(
+      RequestMetadata,
+      r#"
+			SELECT user_id, updated_at, user_agent, accept_language::text AS accept_language
+			FROM calligraphy
+			WHERE anonymized_at IS NULL AND updated_at < $1
+			ORDER BY updated_at
+			LIMIT $2
+			"#,
+      cutoff,
+      limit
+    )
+    .fetch_all(&self.pool)
+    .await
+  }
+
+  /// リクエスト情報を匿名化する
+  ///
+  /// IPアドレスはネットワーク部のみに切り詰め、User-Agent等は匿名化済みの値で上書きする。
+  /// 取得後に書き初めが更新された行 (updated_at が変わった行) は上書きしない。
+  /// 戻り値は匿名化した行数
+  #[tracing::instrument(name = "db.anonymize_metadata", skip_all, fields(db.system = "postgresql"))]
+  async fn anonymize_metadata(
+    &self,
+    rows: Vec<AnonymizedMetadata>,
+    policy: RetentionPolicy,
+  ) -> Result<u64, sqlx::Error> {
+    let mut user_ids = Vec::with_capacity(rows.len());
+    let mut updated_ats = Vec::with_capacity(rows.len());
+    let mut user_agents = Vec::with_capacity(rows.len());
+    let mut accept_languages = Vec::with_capacity(rows.len());
+    for row in rows {
+      user_ids.push(row.user_id);
+      updated_ats.push(row.updated_at);
+      user_agents.push(row.user_agent);
+      accept_languages.push(row.accept_language);
+    }
+
+    let result = sqlx::query!(
+      r#"
+			UPDATE calligraphy AS c
+			SET
+				ip_address = network(set_masklen(
+					c.ip_address,
+					CASE WHEN family(c.ip_address) = 4 THEN $5::int ELSE $6::int END
+				))::inet,
+				user_agent = m.user_agent,
+				accept_language = m.accept_language,
+				anonymized_at = NOW()
+			FROM UNNEST($1::uuid[], $2::timestamptz[], $3::text[], $4::text[])
+				AS m(user_id, updated_at, user_agent, accept_language)
+			WHERE c.user_id = m.user_id
+				AND c.updated_at = m.updated_at
+				AND c.anonymized_at IS NULL
+			"#,
+      &user_ids,
+      &updated_ats,
+      &user_agents as &[Option<String>],
+      &accept_languages as &[Option<String>],
+      i32::from(policy.ipv4_prefix),
+      i32::from(policy.ipv6_prefix)
+    )
     .execute(&self.pool)
     .await?;
 
@@ -138,9 +1064,23 @@ impl CalligraphyRepositoryTrait for CalligraphyRepository {
   }
 }
 
+/// LIKEのワイルドカード (`%`, `_`) とエスケープ文字 (`\`) をエスケープする
+fn escape_like(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    if matches!(c, '\\' | '%' | '_') {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::models::list_query::ListSort;
+  use crate::search;
   use sqlx::postgres::PgPoolOptions;
 
   // 実際にDBに接続して動作確認を行うテスト
@@ -168,7 +1108,7 @@ mod tests {
 
     // --- Test A: 新規作成 (Create/Upsert) ---
     let created = repository
-      .create(user_id, user_name_1.clone(), content_1.to_string(), None, None, None)
+      .create(user_id, user_name_1.clone(), content_1.to_string(), None, None, None, None, None)
       .await
       .expect("Failed to create calligraphy");
 
@@ -190,11 +1130,12 @@ mod tests {
 
     // --- Test C: 更新確認 (Upsert Update) ---
     let updated = repository
-      .create(user_id, user_name_2.clone(), content_2.to_string(), None, None, None)
+      .create(user_id, user_name_2.clone(), content_2.to_string(), None, None, None, None, None)
       .await
       .expect("Failed to update calligraphy");
 
 		assert_eq!(updated.user_id, user_id);
+    assert_eq!(updated.public_id, created.public_id); // 更新しても公開用IDは変わらない
     assert_eq!(updated.user_name, user_name_2);
     assert_eq!(updated.content, content_2);
     assert!(
@@ -213,12 +1154,618 @@ mod tests {
     assert_eq!(my_data.unwrap().content, content_2); // 最新の内容であること
     println!("Test D Passed: Found in list");
 
+    // --- Test E: ボードの状態 (条件付きGET用) ---
+    let state = repository
+      .board_state(user_id)
+      .await
+      .expect("Failed to get board state");
+    assert_eq!(state.mine, Some(created.public_id));
+    assert!(state.max_updated_at >= Some(updated.updated_at));
+    let other_state = repository
+      .board_state(Uuid::new_v4())
+      .await
+      .expect("Failed to get board state");
+    assert_eq!(other_state.mine, None);
+    assert_ne!(state.etag(), other_state.etag()); // is_mine が変わるためETagも変わる
+    println!("Test E Passed: Board state");
+
+    // --- Test F: 全文検索 (pg_trgm) ---
+    let hits = repository
+      .search("やっぱり筋トレ", 100, 0)
+      .await
+      .expect("Failed to search");
+    assert!(
+      hits.iter().any(|h| h.entry.user_id == user_id),
+      "Updated entry should be found by content"
+    );
+    // メモリ上の実装 (Postgres以外のリポジトリ向け) とスコアが一致する
+    // 非ASCIIの文字の類似度はDBのロケール (LC_CTYPE) に依存するため、ASCIIの文字列で比べる
+    let ascii_user_id = Uuid::new_v4();
+    repository
+      .create(
+        ascii_user_id,
+        "Trigram Tester".to_string(),
+        "Resolution: wake up early".to_string(),
+        None,
+        None,
+        None,
+        None,
+        None,
+      )
+      .await
+      .expect("Failed to create");
+    // 部分一致 (大文字小文字を区別しない)・類似度のみでの一致
+    for query in ["early", "RESOLUTION", "Trigram Testr"] {
+      let hits = repository.search(query, 100, 0).await.expect("Failed to search");
+      let hit = hits
+        .iter()
+        .find(|h| h.entry.user_id == ascii_user_id)
+        .unwrap_or_else(|| panic!("ASCII entry should be found by {:?}", query));
+      let expected = search::score(&hit.entry, query).unwrap();
+      assert!((hit.score - expected).abs() < 1e-4, "{}: {} != {}", query, hit.score, expected);
+    }
+    repository.delete(ascii_user_id).await.expect("Failed to delete");
+    // LIKEのワイルドカードはエスケープされる
+    let hits = repository.search("%", 100, 0).await.expect("Failed to search");
+    assert!(hits.iter().all(|h| h.entry.user_name.contains('%') || h.entry.content.contains('%')));
+    println!("Test F Passed: Search");
+
+    // --- Test G: 条件を指定した一覧取得 ---
+    // content_2 "今年の抱負：やっぱり筋トレ" は13文字
+    let mine_only = ListQuery {
+      mine_only: true,
+      ..Default::default()
+    };
+    let list = repository
+      .find_filtered(&mine_only, user_id)
+      .await
+      .expect("Failed to find filtered");
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].user_id, user_id);
+
+    let by_length = |min, max| ListQuery {
+      mine_only: true,
+      min_length: Some(min),
+      max_length: Some(max),
+      ..Default::default()
+    };
+    let list = repository.find_filtered(&by_length(13, 13), user_id).await.unwrap();
+    assert_eq!(list.len(), 1);
+    let list = repository.find_filtered(&by_length(14, 50), user_id).await.unwrap();
+    assert!(list.is_empty());
+
+    let out_of_range = ListQuery {
+      mine_only: true,
+      to: Some(created.created_at),
+      sort: ListSort::RecentlyUpdated,
+      ..Default::default()
+    };
+    let list = repository.find_filtered(&out_of_range, user_id).await.unwrap();
+    assert!(list.is_empty());
+    println!("Test G Passed: Filtered list");
+
+    // --- Test H: 個人データのエクスポート ---
+    let record = repository
+      .export_by_id(user_id)
+      .await
+      .expect("Failed to export")
+      .expect("Record not found");
+    assert_eq!(record.public_id, created.public_id);
+    assert_eq!(record.content, content_2);
+    assert_eq!(record.content_length, 13);
+    assert!(repository.export_by_id(Uuid::new_v4()).await.unwrap().is_none());
+    println!("Test H Passed: Export");
+
+    // --- Test I: 写真の添付 ---
+    let hash = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let photo = Photo {
+      key: format!("{}.jpg", hash),
+      width: 1200,
+      height: 1600,
+      thumbnail_key: format!("{}.webp", hash),
+      thumbnail_width: 360,
+      thumbnail_height: 480,
+    };
+    let with_photo = repository
+      .create(user_id, user_name_2.clone(), content_2.to_string(), None, None, None, None, Some(photo.clone()))
+      .await
+      .expect("Failed to attach photo");
+    assert_eq!(with_photo.photo.as_deref(), Some(&photo));
+    let found = repository.find_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(found.photo.as_deref(), Some(&photo));
+    let list = repository.find_filtered(&mine_only, user_id).await.unwrap();
+    assert_eq!(list[0].photo.as_deref(), Some(&photo));
+    let record = repository.export_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(record.photo.as_deref(), Some(&photo));
+    assert!(repository.is_photo_in_use(&photo.key).await.unwrap());
+    assert!(repository.is_photo_in_use(&photo.thumbnail_key).await.unwrap());
+
+    // 写真なしで更新すると外れる
+    let without_photo = repository
+      .create(user_id, user_name_2.clone(), content_2.to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+    assert!(without_photo.photo.is_none());
+    assert!(!repository.is_photo_in_use(&photo.key).await.unwrap());
+    println!("Test I Passed: Photo");
+
     // --- Cleanup: テストデータの削除 (行儀よく後始末) ---
-    let deleted_count = repository
+    let deleted = repository
       .delete(user_id)
       .await
       .expect("Failed to delete calligraphy");
-    assert_eq!(deleted_count, 1);
+    assert_eq!(deleted, Some(created.public_id));
+
+    // 削除は最終削除日時に記録され、Last-Modified が進む
+    let after_delete = repository
+      .board_state(user_id)
+      .await
+      .expect("Failed to get board state");
+    assert_eq!(after_delete.mine, None);
+    assert!(after_delete.last_modified() > state.last_modified());
     println!("Cleanup Passed: Deleted test data");
   }
+
+  // 匿名化ジョブのDB側の処理 (IPアドレスの切り詰めと、更新された行の保護)
+  #[tokio::test]
+  async fn test_retention_scenario() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool.clone());
+    let policy = RetentionPolicy::after_days(90);
+
+    let user_id = Uuid::new_v4();
+    let ip: IpNetwork = "192.0.2.123".parse().unwrap();
+    repository
+      .create(
+        user_id,
+        "匿名化テスト".to_string(),
+        "内容".to_string(),
+        Some(ip),
+        Some("Mozilla/5.0 Firefox/121.0".to_string()),
+        Some("ja,en;q=0.8".to_string()),
+        None,
+        None,
+      )
+      .await
+      .expect("Failed to create calligraphy");
+
+    // 保持期間より前に更新されたことにする
+    sqlx::query("UPDATE calligraphy SET updated_at = NOW() - INTERVAL '100 days' WHERE user_id = $1")
+      .bind(user_id)
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    let cutoff = policy.cutoff(OffsetDateTime::now_utc());
+    let rows: Vec<_> = repository
+      .find_unanonymized(cutoff, 1000)
+      .await
+      .expect("Failed to find rows")
+      .into_iter()
+      .filter(|r| r.user_id == user_id)
+      .collect();
+    assert_eq!(rows.len(), 1);
+
+    // 取得後に更新された行は上書きしない
+    let stale = AnonymizedMetadata {
+      user_id,
+      updated_at: rows[0].updated_at - time::Duration::seconds(1),
+      user_agent: Some("Firefox".to_string()),
+      accept_language: Some("ja".to_string()),
+    };
+    assert_eq!(repository.anonymize_metadata(vec![stale], policy).await.unwrap(), 0);
+
+    let anonymized = AnonymizedMetadata {
+      user_id,
+      updated_at: rows[0].updated_at,
+      user_agent: Some("Firefox".to_string()),
+      accept_language: Some("ja".to_string()),
+    };
+    assert_eq!(repository.anonymize_metadata(vec![anonymized], policy).await.unwrap(), 1);
+
+    let record = repository.export_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(record.ip_address.as_deref(), Some("192.0.2.0/24"));
+    assert_eq!(record.user_agent.as_deref(), Some("Firefox"));
+    assert_eq!(record.accept_language.as_deref(), Some("ja"));
+    assert!(record.anonymized_at.is_some());
+
+    // 匿名化済みの行は対象外
+    let rows = repository.find_unanonymized(cutoff, 1000).await.unwrap();
+    assert!(rows.iter().all(|r| r.user_id != user_id));
+
+    // 更新すると新しいリクエスト情報で上書きされ、再び匿名化の対象になる
+    repository
+      .create(user_id, "匿名化テスト".to_string(), "更新".to_string(), Some(ip), None, None, None, None)
+      .await
+      .unwrap();
+    let record = repository.export_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(record.ip_address.as_deref(), Some("192.0.2.123"));
+    assert!(record.anonymized_at.is_none());
+
+    repository.delete(user_id).await.unwrap();
+  }
+
+  // 集計用の取得 (一覧の上限に関係なく、リクエスト情報を含めて全件取得する)
+  #[tokio::test]
+  async fn test_stats_scenario() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool);
+
+    // 区分ごとの件数が少ないと "Other" にまとめられるため、同じ言語・ブラウザで3件作る
+    let user_ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+    for user_id in &user_ids {
+      repository
+        .create(
+          *user_id,
+          "集計テスト".to_string(),
+          "謹賀新年".to_string(),
+          None,
+          Some("Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0".to_string()),
+          Some("ja,en;q=0.8".to_string()),
+          None,
+          None,
+        )
+        .await
+        .expect("Failed to create calligraphy");
+    }
+
+    let entries = repository.find_for_stats().await.expect("Failed to find rows");
+    let mine: Vec<_> = entries.iter().filter(|e| user_ids.contains(&e.user_id)).collect();
+    assert_eq!(mine.len(), 3);
+    assert!(mine.iter().all(|e| e.accept_language.as_deref() == Some("ja,en;q=0.8")));
+    assert!(mine.iter().all(|e| e.ip_address.is_none()));
+
+    let stats = crate::services::stats::compute(&entries, OffsetDateTime::now_utc());
+    assert_eq!(stats.total_entries, entries.len() as u64);
+    assert!(stats.languages.iter().any(|l| l.label == "ja" && l.count >= 3));
+    assert!(stats.browsers.iter().any(|b| b.label == "Firefox" && b.count >= 3));
+    assert!(stats.operating_systems.iter().any(|o| o.label == "Linux" && o.count >= 3));
+
+    for user_id in user_ids {
+      repository.delete(user_id).await.unwrap();
+    }
+  }
+
+  // 引き継ぎコードの発行と使用 (ユーザーIDの付け替え)
+  #[tokio::test]
+  async fn test_transfer_code_scenario() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool.clone());
+    let expires_at = OffsetDateTime::now_utc() + time::Duration::minutes(10);
+    let code_hash = |seed: Uuid| seed.as_bytes().repeat(2);
+
+    // 書き初めがなければ発行しない
+    assert!(!repository
+      .save_transfer_code(Uuid::new_v4(), code_hash(Uuid::new_v4()), expires_at)
+      .await
+      .unwrap());
+
+    let old_user_id = Uuid::new_v4();
+    let created = repository
+      .create(old_user_id, "引き継ぎ".to_string(), "内容".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+
+    // 再発行すると前のコードは使えなくなる
+    let first = code_hash(Uuid::new_v4());
+    let second = code_hash(Uuid::new_v4());
+    assert!(repository.save_transfer_code(old_user_id, first.clone(), expires_at).await.unwrap());
+    assert!(repository.save_transfer_code(old_user_id, second.clone(), expires_at).await.unwrap());
+    let new_user_id = Uuid::new_v4();
+    assert!(repository.claim_transfer_code(first, new_user_id).await.unwrap().is_none());
+
+    let claimed = repository
+      .claim_transfer_code(second.clone(), new_user_id)
+      .await
+      .unwrap()
+      .expect("Code should be claimable");
+    assert_eq!(claimed.user_id, new_user_id);
+    assert_eq!(claimed.public_id, created.public_id);
+    assert_eq!(claimed.updated_at, created.updated_at);
+    assert!(repository.find_by_id(old_user_id).await.unwrap().is_none());
+
+    // 使用済みのコードは使えない
+    assert!(repository.claim_transfer_code(second, Uuid::new_v4()).await.unwrap().is_none());
+
+    // 期限切れのコードは使えない
+    let expired = code_hash(Uuid::new_v4());
+    let past = OffsetDateTime::now_utc() - time::Duration::minutes(1);
+    assert!(repository.save_transfer_code(new_user_id, expired.clone(), past).await.unwrap());
+    assert!(repository.claim_transfer_code(expired, Uuid::new_v4()).await.unwrap().is_none());
+
+    // 書き初めを削除するとコードも消える
+    let pending = code_hash(Uuid::new_v4());
+    assert!(repository.save_transfer_code(new_user_id, pending.clone(), expires_at).await.unwrap());
+    repository.delete(new_user_id).await.unwrap();
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transfer_code WHERE code_hash = $1")
+      .bind(pending)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(remaining, 0);
+  }
+
+  // パスキーの登録・認証の途中の状態
+  #[tokio::test]
+  async fn test_passkey_ceremony_scenario() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool.clone());
+    let user_id = Uuid::new_v4();
+    let ceremony = |kind, expires_at| PasskeyCeremony {
+      id: Uuid::new_v4(),
+      kind,
+      user_id,
+      state: serde_json::json!({ "challenge": "test" }),
+      expires_at,
+    };
+    let future = OffsetDateTime::now_utc() + time::Duration::minutes(5);
+    let past = OffsetDateTime::now_utc() - time::Duration::minutes(1);
+
+    // 途中の状態は1回だけ取り出せる
+    let registration = ceremony(CeremonyKind::Registration, future);
+    repository.save_passkey_ceremony(registration.clone()).await.unwrap();
+    // 種類が違えば取り出せない
+    assert!(repository
+      .take_passkey_ceremony(registration.id, CeremonyKind::Authentication)
+      .await
+      .unwrap()
+      .is_none());
+    let taken = repository
+      .take_passkey_ceremony(registration.id, CeremonyKind::Registration)
+      .await
+      .unwrap()
+      .expect("Ceremony should be taken");
+    assert_eq!(taken.user_id, user_id);
+    assert_eq!(taken.state, registration.state);
+    assert!(repository
+      .take_passkey_ceremony(registration.id, CeremonyKind::Registration)
+      .await
+      .unwrap()
+      .is_none());
+
+    // 期限切れの状態は取り出せない
+    let expired = ceremony(CeremonyKind::Authentication, past);
+    repository.save_passkey_ceremony(expired.clone()).await.unwrap();
+    assert!(repository
+      .take_passkey_ceremony(expired.id, CeremonyKind::Authentication)
+      .await
+      .unwrap()
+      .is_none());
+
+    // 書き初めのないユーザーのパスキーは空
+    assert!(repository.find_passkeys(user_id).await.unwrap().is_empty());
+    assert!(repository
+      .find_passkeys_by_public_id(Uuid::new_v4())
+      .await
+      .unwrap()
+      .is_empty());
+    assert!(!repository.delete_passkey(user_id, Uuid::new_v4()).await.unwrap());
+  }
+
+  // 個人データのエクスポート: セッション (期限切れを含む) とパスキー
+  #[tokio::test]
+  async fn test_export_sessions_and_passkeys() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool.clone());
+    let user_id = Uuid::new_v4();
+    repository
+      .create(user_id, "エクスポート".to_string(), "内容".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+    let now = OffsetDateTime::now_utc();
+    let token_hash = Uuid::new_v4().as_bytes().repeat(2);
+    repository
+      .create_session(token_hash.clone(), user_id, Some("Firefox / Linux".to_string()), now + time::Duration::days(1))
+      .await
+      .unwrap();
+    repository
+      .create_session(Uuid::new_v4().as_bytes().repeat(2), user_id, None, now - time::Duration::minutes(1))
+      .await
+      .unwrap();
+    let credential_id = [0xfb, 0xff, 0xbf, 0x01, Uuid::new_v4().as_bytes()[0]];
+    sqlx::query("INSERT INTO passkey_credential (credential_id, user_id, passkey) VALUES ($1, $2, $3)")
+      .bind(&credential_id[..])
+      .bind(user_id)
+      .bind(Json(serde_json::json!({ "cred": { "counter": 1 } })))
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    let sessions = repository.export_sessions(user_id).await.unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].device.as_deref(), Some("Firefox / Linux"));
+    let passkeys = repository.export_passkeys(user_id).await.unwrap();
+    assert_eq!(passkeys.len(), 1);
+    assert!(passkeys[0].credential_id.starts_with("-_-_A")); // Base64URL (パディングなし)
+    assert!(!passkeys[0].credential_id.contains('='));
+    assert_eq!(passkeys[0].passkey.0["cred"]["counter"], 1);
+    assert!(repository.export_sessions(Uuid::new_v4()).await.unwrap().is_empty());
+
+    repository.delete(user_id).await.unwrap();
+    sqlx::query("DELETE FROM user_session WHERE user_id = $1")
+      .bind(user_id)
+      .execute(&pool)
+      .await
+      .unwrap();
+  }
+
+  // パスキーは書き初めに紐付くため、書き初めを削除すると一緒に削除される (DELETE /api/calligraphy/me)
+  // セッションは書き初めに紐付かないため残る
+  #[tokio::test]
+  async fn test_passkeys_deleted_with_calligraphy() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool.clone());
+    let user_id = Uuid::new_v4();
+    let entry = repository
+      .create(user_id, "パスキー".to_string(), "削除".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+    repository
+      .create_session(
+        Uuid::new_v4().as_bytes().repeat(2),
+        user_id,
+        None,
+        OffsetDateTime::now_utc() + time::Duration::days(1),
+      )
+      .await
+      .unwrap();
+    sqlx::query("INSERT INTO passkey_credential (credential_id, user_id, passkey) VALUES ($1, $2, $3)")
+      .bind(&Uuid::new_v4().as_bytes()[..])
+      .bind(user_id)
+      .bind(Json(serde_json::json!({})))
+      .execute(&pool)
+      .await
+      .unwrap();
+    assert_eq!(repository.export_passkeys(user_id).await.unwrap().len(), 1);
+
+    assert!(repository.delete(user_id).await.unwrap().is_some());
+    assert!(repository.export_passkeys(user_id).await.unwrap().is_empty());
+    assert!(repository.find_passkeys_by_public_id(entry.public_id).await.unwrap().is_empty());
+    assert_eq!(repository.export_sessions(user_id).await.unwrap().len(), 1);
+
+    sqlx::query("DELETE FROM user_session WHERE user_id = $1")
+      .bind(user_id)
+      .execute(&pool)
+      .await
+      .unwrap();
+  }
+
+  // セッションの作成・延長・削除と、管理者による全セッションの無効化
+  #[tokio::test]
+  async fn test_session_scenario() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+      .max_connections(1)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+    let repository = CalligraphyRepository::new(pool.clone());
+    let token_hash = || Uuid::new_v4().as_bytes().repeat(2);
+    let future = OffsetDateTime::now_utc() + time::Duration::days(1);
+    let past = OffsetDateTime::now_utc() - time::Duration::minutes(1);
+
+    let user_id = Uuid::new_v4();
+    let first_hash = token_hash();
+    let first = repository
+      .create_session(first_hash.clone(), user_id, Some("Firefox / Linux".to_string()), future)
+      .await
+      .unwrap();
+    assert_eq!(first.token_hash, first_hash);
+    assert_eq!(first.device.as_deref(), Some("Firefox / Linux"));
+    let second = repository.create_session(token_hash(), user_id, None, future).await.unwrap();
+    let found = repository.find_session(first_hash.clone()).await.unwrap().unwrap();
+    assert_eq!(found.id, first.id);
+    assert_eq!(found.user_id, user_id);
+
+    // 延長すると最後に使われた日時も更新される
+    let extended = future + time::Duration::days(1);
+    repository.touch_session(first.id, extended).await.unwrap();
+    let found = repository.find_session(first_hash.clone()).await.unwrap().unwrap();
+    assert_eq!(found.expires_at.unix_timestamp(), extended.unix_timestamp());
+    assert!(found.last_seen_at >= first.last_seen_at);
+    let sessions = repository.find_sessions(user_id).await.unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].id, first.id);
+
+    // 期限切れのセッションは使えず、一覧にも出ない
+    let expired_hash = token_hash();
+    repository.create_session(expired_hash.clone(), user_id, None, past).await.unwrap();
+    assert!(repository.find_session(expired_hash).await.unwrap().is_none());
+    assert_eq!(repository.find_sessions(user_id).await.unwrap().len(), 2);
+
+    // 期限切れのセッションは定期ジョブで削除される (有効なセッションは残る)
+    assert!(repository.delete_expired_sessions().await.unwrap() >= 1);
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_session WHERE user_id = $1")
+      .bind(user_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(remaining, 2);
+
+    // 他のユーザーのセッションは削除できない
+    assert!(!repository.delete_session(Uuid::new_v4(), second.id).await.unwrap());
+    assert!(repository.delete_session(user_id, second.id).await.unwrap());
+    repository.delete_session_by_token(first_hash.clone()).await.unwrap();
+    assert!(repository.find_session(first_hash).await.unwrap().is_none());
+    assert!(repository.find_sessions(user_id).await.unwrap().is_empty());
+
+    // 全セッションの無効化: 書き初めは新しいユーザーIDに移り、元のユーザーIDのセッションは消える
+    let banned_id = Uuid::new_v4();
+    let created = repository
+      .create(banned_id, "無効化".to_string(), "内容".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+    // 以前のCookieからは、書き初めを持ちセッションがまだないユーザーのみ一度だけ作成できる
+    assert!(repository
+      .create_legacy_session(token_hash(), user_id, None, future)
+      .await
+      .unwrap()
+      .is_none());
+    let legacy = repository
+      .create_legacy_session(token_hash(), banned_id, None, future)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(legacy.user_id, banned_id);
+    assert!(repository
+      .create_legacy_session(token_hash(), banned_id, None, future)
+      .await
+      .unwrap()
+      .is_none());
+    repository.create_session(token_hash(), banned_id, None, future).await.unwrap();
+    assert!(repository
+      .save_transfer_code(banned_id, token_hash(), future)
+      .await
+      .unwrap());
+    let rotated_id = Uuid::new_v4();
+    assert_eq!(repository.revoke_user(created.public_id, rotated_id).await.unwrap(), Some(2));
+    assert!(repository
+      .create_legacy_session(token_hash(), banned_id, None, future)
+      .await
+      .unwrap()
+      .is_none());
+    assert!(repository.find_sessions(banned_id).await.unwrap().is_empty());
+    assert!(repository.find_by_id(banned_id).await.unwrap().is_none());
+    let moved = repository.find_by_id(rotated_id).await.unwrap().unwrap();
+    assert_eq!(moved.public_id, created.public_id);
+    let codes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transfer_code WHERE user_id = $1")
+      .bind(rotated_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(codes, 0);
+    assert_eq!(repository.revoke_user(Uuid::new_v4(), Uuid::new_v4()).await.unwrap(), None);
+
+    repository.delete(rotated_id).await.unwrap();
+  }
 }
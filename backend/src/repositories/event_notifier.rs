@@ -0,0 +1,138 @@
+use crate::models::calligraphy::CalligraphyEvent;
+use crate::services::events::EventHub;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+/// LISTEN/NOTIFY のチャンネル名
+const CHANNEL: &str = "calligraphy_events";
+
+/// NOTIFYのペイロード
+#[derive(Serialize, Deserialize)]
+struct Notification {
+  /// 発行元プロセスのID (自分が発行したものは無視する)
+  origin: Uuid,
+  event: CalligraphyEvent,
+}
+
+/// PostgreSQLの LISTEN/NOTIFY を使い、複数のレプリカ間で変更イベントを共有する
+///
+/// - このプロセスで発生したイベントを NOTIFY で他のレプリカに送る
+/// - 他のレプリカからの NOTIFY を受信し、このプロセスのイベントハブに流す
+///
+/// LISTENの開始までを待ってから、送受信のタスクをバックグラウンドで起動する。
+/// LISTEN用にプールの接続を1本占有する点に注意。
+pub async fn start(pool: PgPool, hub: EventHub) -> Result<(), sqlx::Error> {
+  let mut listener = PgListener::connect_with(&pool).await?;
+  listener.listen(CHANNEL).await?;
+
+  // 送信側: 購読をここで開始し、起動直後のイベントも取りこぼさない
+  let mut receiver = hub.subscribe(None).receiver;
+  let origin = hub.instance_id();
+  tokio::spawn(async move {
+    loop {
+      let published = match receiver.recv().await {
+        Ok(published) => published,
+        Err(RecvError::Lagged(n)) => {
+          tracing::warn!("Event notifier lagged, {} events were not sent to other replicas", n);
+          continue;
+        }
+        Err(RecvError::Closed) => break,
+      };
+      // 他のレプリカから受信したイベントは送り返さない
+      if !published.local {
+        continue;
+      }
+      if let Err(e) = notify(&pool, origin, &published.event).await {
+        tracing::error!("Failed to NOTIFY calligraphy event: {:?}", e);
+      }
+    }
+  });
+
+  // 受信側
+  tokio::spawn(async move {
+    loop {
+      match listener.try_recv().await {
+        Ok(Some(notification)) => {
+          match serde_json::from_str::<Notification>(notification.payload()) {
+            Ok(n) if n.origin != origin => hub.publish_remote(n.event),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Ignoring malformed calligraphy notification: {:?}", e),
+          }
+        }
+        // 接続断: 次の呼び出しで自動的に再接続・再LISTENされる
+        Ok(None) => tracing::warn!("LISTEN connection lost, reconnecting"),
+        Err(e) => {
+          tracing::error!("Failed to receive calligraphy notification: {:?}", e);
+          tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+      }
+    }
+  });
+
+  Ok(())
+}
+
+/// イベントを NOTIFY で送信する
+async fn notify(pool: &PgPool, origin: Uuid, event: &CalligraphyEvent) -> Result<(), sqlx::Error> {
+  let payload = serde_json::to_string(&Notification {
+    origin,
+    event: event.clone(),
+  })
+  .map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+
+  // pg_notify は void を返すため、型チェックを行う query! マクロではなく query を使う
+  sqlx::query("SELECT pg_notify($1, $2)")
+    .bind(CHANNEL)
+    .bind(payload)
+    .execute(pool)
+    .await?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use sqlx::postgres::PgPoolOptions;
+  use std::time::Duration;
+
+  // 実際にDBに接続し、2つのレプリカ間でイベントが共有されることを確認するテスト
+  #[tokio::test]
+  async fn test_events_are_shared_between_replicas() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    // LISTENで2本占有するため、NOTIFY用に1本追加
+    let pool = PgPoolOptions::new()
+      .max_connections(3)
+      .connect(&database_url)
+      .await
+      .expect("Failed to connect to DB");
+
+    let hub_a = EventHub::new();
+    let hub_b = EventHub::new();
+    start(pool.clone(), hub_a.clone()).await.expect("Failed to start A");
+    start(pool.clone(), hub_b.clone()).await.expect("Failed to start B");
+
+    let mut receiver_a = hub_a.subscribe(None).receiver;
+    let mut receiver_b = hub_b.subscribe(None).receiver;
+    let public_id = Uuid::new_v4();
+    hub_a.publish(CalligraphyEvent::Deleted {
+      user_id: Uuid::new_v4(),
+      public_id,
+    });
+
+    // B はリモートのイベントとして受信する
+    let received = tokio::time::timeout(Duration::from_secs(5), receiver_b.recv())
+      .await
+      .expect("Timed out waiting for notification")
+      .unwrap();
+    assert!(!received.local);
+    assert!(matches!(*received.event, CalligraphyEvent::Deleted { public_id: p, .. } if p == public_id));
+
+    // A には自分のイベントが1回だけ届き、NOTIFY経由で二重に届かない
+    assert!(receiver_a.recv().await.unwrap().local);
+    let echoed = tokio::time::timeout(Duration::from_millis(300), receiver_a.recv()).await;
+    assert!(echoed.is_err(), "Own notification must not be re-published");
+  }
+}
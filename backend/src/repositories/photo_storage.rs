@@ -0,0 +1,103 @@
+//! 写真ファイルの保存先
+//!
+//! 保存先は `PhotoStorage` トレイトで抽象化する (S3互換のストレージなどを追加できるようにする)。
+//! ファイル名は内容のハッシュのため、同じ名前には常に同じ内容を保存する。
+
+use std::io;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use uuid::Uuid;
+
+/// 写真ファイルの保存先
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait PhotoStorage: Send + Sync {
+  /// ファイルを保存する (同じ名前のファイルがあれば上書きする)
+  async fn put(&self, key: &str, bytes: Bytes) -> io::Result<()>;
+  /// ファイルを読み込む (なければNone)
+  async fn get(&self, key: &str) -> io::Result<Option<Bytes>>;
+  /// ファイルを削除する (なければ何もしない)
+  async fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// ローカルのファイルシステムに保存する
+pub struct LocalPhotoStorage {
+  dir: PathBuf,
+}
+
+impl LocalPhotoStorage {
+  /// 保存先のディレクトリを作成する
+  pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+    let dir = dir.into();
+    std::fs::create_dir_all(&dir)?;
+    Ok(Self { dir })
+  }
+
+  /// ファイルのパス (ファイル名はサービス層で検証済みだが、ディレクトリの外を指す名前は念のため拒否する)
+  fn path(&self, key: &str) -> io::Result<PathBuf> {
+    if key.is_empty() || key.starts_with('.') || key.contains(['/', '\\']) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid photo key"));
+    }
+    Ok(self.dir.join(key))
+  }
+}
+
+#[async_trait]
+impl PhotoStorage for LocalPhotoStorage {
+  /// 一時ファイルに書き込んでから名前を変えることで、書き込み途中のファイルを読まれないようにする
+  async fn put(&self, key: &str, bytes: Bytes) -> io::Result<()> {
+    let path = self.path(key)?;
+    let temporary = self.dir.join(format!(".{}.tmp", Uuid::new_v4().simple()));
+    tokio::fs::write(&temporary, &bytes).await?;
+    if let Err(e) = tokio::fs::rename(&temporary, &path).await {
+      let _ = tokio::fs::remove_file(&temporary).await;
+      return Err(e);
+    }
+    Ok(())
+  }
+
+  async fn get(&self, key: &str) -> io::Result<Option<Bytes>> {
+    match tokio::fs::read(self.path(key)?).await {
+      Ok(bytes) => Ok(Some(Bytes::from(bytes))),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+
+  async fn delete(&self, key: &str) -> io::Result<()> {
+    match tokio::fs::remove_file(self.path(key)?).await {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_local_photo_storage() {
+    let dir = std::env::temp_dir().join(format!("photo-storage-test-{}", Uuid::new_v4().simple()));
+    let storage = LocalPhotoStorage::new(&dir).unwrap();
+
+    assert_eq!(storage.get("a.jpg").await.unwrap(), None);
+    storage.put("a.jpg", Bytes::from_static(b"jpeg")).await.unwrap();
+    assert_eq!(storage.get("a.jpg").await.unwrap(), Some(Bytes::from_static(b"jpeg")));
+    // 一時ファイルは残らない
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    storage.delete("a.jpg").await.unwrap();
+    assert_eq!(storage.get("a.jpg").await.unwrap(), None);
+    // 存在しないファイルの削除はエラーにしない
+    storage.delete("a.jpg").await.unwrap();
+
+    // ディレクトリの外を指す名前
+    assert!(storage.get("../a.jpg").await.is_err());
+    assert!(storage.put("", Bytes::new()).await.is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
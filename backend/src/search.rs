@@ -0,0 +1,250 @@
+//! 全文検索の一致判定・スコアリング・ハイライトを行うモジュール
+//!
+//! PostgreSQLでは `pg_trgm` 拡張 (トライグラム) で検索するが、
+//! Postgres以外のリポジトリ実装でも同じ結果になるよう、同等の処理をここに実装する。
+//!
+//! 日本語は単語の区切りがなく、2文字程度の検索語ではトライグラムの類似度がほぼ0になるため、
+//! 部分一致 (大文字小文字を区別しない) を主な一致条件とし、類似度はあいまい検索と並び順に使う。
+
+use std::collections::HashSet;
+
+use crate::models::calligraphy::Calligraphy;
+use crate::models::search::{SearchHit, Segment};
+
+/// 類似度による一致とみなす閾値 (`pg_trgm.similarity_threshold` の既定値と一致させること)
+pub const SIMILARITY_THRESHOLD: f32 = 0.3;
+
+/// 文字列のトライグラム集合 (`pg_trgm` の `show_trgm` 相当)
+///
+/// 英数字 (日本語の文字を含む) 以外で単語に区切り、小文字化した各単語の
+/// 前に空白2つ・後ろに空白1つを付けて3文字ずつ切り出す。
+/// `pg_trgm` は LC_CTYPE で文字を判定するため、非ASCIIの文字で一致するのはDBがUTF-8のロケールの場合に限る。
+pub fn trigrams(s: &str) -> HashSet<String> {
+  let lower = s.to_lowercase();
+  let mut set = HashSet::new();
+  for word in lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+    let padded: Vec<char> = format!("  {} ", word).chars().collect();
+    for window in padded.windows(3) {
+      set.insert(window.iter().collect());
+    }
+  }
+  set
+}
+
+/// トライグラムによる類似度 (`pg_trgm` の `similarity` 相当, 0.0〜1.0)
+pub fn similarity(a: &str, b: &str) -> f32 {
+  let a = trigrams(a);
+  let b = trigrams(b);
+  let common = a.intersection(&b).count();
+  let union = a.len() + b.len() - common;
+  if union == 0 {
+    return 0.0;
+  }
+  common as f32 / union as f32
+}
+
+/// 大文字小文字を区別しない部分一致 (`ILIKE '%query%'` 相当)
+pub fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+  haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// 検索語に対する書き初めのスコア (一致しなければNone)
+///
+/// スコア = 部分一致したフィールド数 + ユーザー名・内容の類似度の大きい方
+/// (`CalligraphyRepository::search` のSQLと一致させること)
+pub fn score(entry: &Calligraphy, query: &str) -> Option<f32> {
+  let name_contains = contains_ignore_case(&entry.user_name, query);
+  let content_contains = contains_ignore_case(&entry.content, query);
+  let name_similarity = similarity(&entry.user_name, query);
+  let content_similarity = similarity(&entry.content, query);
+
+  let matched = name_contains
+    || content_contains
+    || name_similarity >= SIMILARITY_THRESHOLD
+    || content_similarity >= SIMILARITY_THRESHOLD;
+  if !matched {
+    return None;
+  }
+  Some(name_contains as u8 as f32 + content_contains as u8 as f32 + name_similarity.max(content_similarity))
+}
+
+/// 検索語に部分一致した箇所を区別して、文字列を区間に分割する
+///
+/// HTMLタグを埋め込むとXSSの原因になるため、ハイライトは構造化したデータで返し、
+/// 描画はフロントエンドに任せる。類似度のみで一致した場合はハイライトなし (1区間) になる。
+pub fn highlight(text: &str, query: &str) -> Vec<Segment> {
+  let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+  let chars: Vec<(usize, char)> = text.char_indices().collect();
+  let mut segments = Vec::new();
+  let mut plain_start = 0; // 未一致区間の開始位置 (バイト)
+  let mut i = 0; // 現在の文字位置
+
+  while !query.is_empty() && i < chars.len() {
+    match match_len(&chars[i..], &query) {
+      Some(len) => {
+        let start = chars[i].0;
+        let end = chars.get(i + len).map_or(text.len(), |&(pos, _)| pos);
+        if plain_start < start {
+          segments.push(Segment::plain(&text[plain_start..start]));
+        }
+        segments.push(Segment::matched(&text[start..end]));
+        plain_start = end;
+        i += len;
+      }
+      None => i += 1,
+    }
+  }
+  if plain_start < text.len() {
+    segments.push(Segment::plain(&text[plain_start..]));
+  }
+  segments
+}
+
+/// 書き初めを検索語で絞り込み、関連度の高い順に並べて1ページ分を返す
+///
+/// `entries` には検索対象の全件を渡すこと (一覧用の `find_all` は100件までのため、それ以降が検索されない)。
+/// 並び順はスコアの降順・更新日時の降順・公開IDの昇順
+/// (`CalligraphyRepository::search` のSQLと一致させること)
+pub fn rank(entries: Vec<Calligraphy>, query: &str, limit: i64, offset: i64) -> Vec<SearchHit> {
+  let mut hits: Vec<SearchHit> = entries
+    .into_iter()
+    .filter_map(|entry| {
+      let score = score(&entry, query)?;
+      Some(SearchHit { entry, score })
+    })
+    .collect();
+  hits.sort_by(|a, b| {
+    b.score
+      .total_cmp(&a.score)
+      .then(b.entry.updated_at.cmp(&a.entry.updated_at))
+      .then(a.entry.public_id.cmp(&b.entry.public_id))
+  });
+  hits
+    .into_iter()
+    .skip(offset.max(0) as usize)
+    .take(limit.max(0) as usize)
+    .collect()
+}
+
+/// 先頭から (小文字化して) 検索語に一致する場合、一致した元の文字数を返す
+/// 小文字化で文字数が変わる文字 (例: 'İ') があっても元の文字境界で区切れるようにする
+fn match_len(chars: &[(usize, char)], query: &[char]) -> Option<usize> {
+  let mut matched = 0; // 一致した検索語の文字数
+  for (consumed, &(_, c)) in chars.iter().enumerate() {
+    for lower in c.to_lowercase() {
+      if query.get(matched) != Some(&lower) {
+        return None;
+      }
+      matched += 1;
+    }
+    if matched == query.len() {
+      return Some(consumed + 1);
+    }
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::OffsetDateTime;
+  use uuid::Uuid;
+
+  fn entry(user_name: &str, content: &str) -> Calligraphy {
+    Calligraphy {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+      user_name: user_name.to_string(),
+      content: content.to_string(),
+      photo: None,
+      ip_address: None,
+      user_agent: None,
+      accept_language: None,
+      created_at: OffsetDateTime::now_utc(),
+      updated_at: OffsetDateTime::now_utc(),
+    }
+  }
+
+  /// pg_trgm と同じトライグラム・類似度になること
+  #[test]
+  fn test_trigrams_match_pg_trgm() {
+    // SELECT show_trgm('Hello, World')
+    let mut hello: Vec<_> = trigrams("Hello, World").into_iter().collect();
+    hello.sort();
+    assert_eq!(
+      hello,
+      vec!["  h", "  w", " he", " wo", "ell", "hel", "ld ", "llo", "lo ", "orl", "rld", "wor"]
+    );
+    // SELECT show_trgm('一富士 二鷹') は7個
+    assert_eq!(trigrams("一富士 二鷹").len(), 7);
+    // SELECT similarity('太郎', '太郎さん') = 0.33333334
+    assert!((similarity("太郎", "太郎さん") - 1.0 / 3.0).abs() < 1e-6);
+    assert_eq!(similarity("一富士二鷹三茄子", "富士"), 0.0);
+  }
+
+  /// 部分一致と類似度による一致・スコアの順序
+  #[test]
+  fn test_score() {
+    // 日本語の短い検索語は部分一致で見つかる
+    assert!(score(&entry("太郎", "一富士二鷹三茄子"), "富士").is_some());
+    // 大文字小文字を区別しない
+    assert!(score(&entry("Taro", "Happy New Year"), "new year").is_some());
+    // 類似度のみで一致 (typo)
+    assert!(score(&entry("Yamamoto", "謹賀新年"), "Yamamota").is_some());
+    // 一致しない
+    assert!(score(&entry("太郎", "謹賀新年"), "富士").is_none());
+
+    // 両方のフィールドに一致する方が上位
+    let both = score(&entry("富士子", "富士山"), "富士").unwrap();
+    let one = score(&entry("花子", "富士山"), "富士").unwrap();
+    assert!(both > one);
+  }
+
+  /// 関連度の高い順に並び、ページングされること
+  #[test]
+  fn test_rank() {
+    let entries = vec![
+      entry("花子", "富士山"),
+      entry("太郎", "謹賀新年"),
+      entry("富士子", "富士山"),
+    ];
+
+    let hits = rank(entries.clone(), "富士", 10, 0);
+    let names: Vec<_> = hits.iter().map(|h| h.entry.user_name.as_str()).collect();
+    assert_eq!(names, vec!["富士子", "花子"]); // 両方のフィールドに一致する方が上位
+
+    let page_2 = rank(entries, "富士", 1, 1);
+    assert_eq!(page_2.len(), 1);
+    assert_eq!(page_2[0].entry.user_name, "花子");
+  }
+
+  /// 一致箇所が区間に分割されること
+  #[test]
+  fn test_highlight() {
+    assert_eq!(
+      highlight("富士山と富士川", "富士"),
+      vec![
+        Segment::matched("富士"),
+        Segment::plain("山と"),
+        Segment::matched("富士"),
+        Segment::plain("川"),
+      ]
+    );
+    // 元の大文字小文字を保つ
+    assert_eq!(
+      highlight("Happy NEW Year", "new"),
+      vec![
+        Segment::plain("Happy "),
+        Segment::matched("NEW"),
+        Segment::plain(" Year"),
+      ]
+    );
+    // 一致しなければ全体が1区間
+    assert_eq!(highlight("謹賀新年", "富士"), vec![Segment::plain("謹賀新年")]);
+    // 小文字化で文字数が変わる文字でも境界が崩れない
+    assert_eq!(
+      highlight("aİb", "i\u{307}"),
+      vec![Segment::plain("a"), Segment::matched("İ"), Segment::plain("b")]
+    );
+  }
+}
@@ -1 +1,11 @@
-pub mod calligraphy;
\ No newline at end of file
+pub mod board_cache;
+pub mod calligraphy;
+pub mod events;
+pub mod ogp;
+pub mod passkeys;
+pub mod photos;
+pub mod presence;
+pub mod retention;
+pub mod sessions;
+pub mod stats;
+pub mod transfer;
@@ -0,0 +1,148 @@
+use crate::error::AppError;
+use crate::models::calligraphy::Calligraphy;
+use moka::future::Cache;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// 一覧のスナップショットを保持する時間
+/// 他のレプリカでの変更をNOTIFYで受け取れない構成でも、この時間で必ず最新化される
+const BOARD_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// 一覧のスナップショット (全ユーザーで共有し、is_mine はハンドラーで付与する)
+pub type BoardSnapshot = Arc<Vec<Calligraphy>>;
+
+/// 公開ボード (一覧) のキャッシュ
+///
+/// キーは世代番号 (`EventHub` の通し番号)。作成・更新・削除のたびに世代が進むため、
+/// 書き込み後の読み込みは必ず新しいキーでDBから取得される (write-through な無効化)。
+/// 書き込み前に始まった読み込みが古いデータを入れても、古い世代のキーに入るだけで参照されない。
+#[derive(Clone)]
+pub struct BoardCache {
+  cache: Cache<u64, BoardSnapshot>,
+  /// 最後に取得に成功したスナップショット
+  /// DBに一時的に接続できない場合はこれを返す (stale-while-revalidate)
+  last_good: Arc<RwLock<Option<BoardSnapshot>>>,
+}
+
+impl Default for BoardCache {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl BoardCache {
+  pub fn new() -> Self {
+    Self {
+      cache: Cache::builder()
+        .max_capacity(4) // 古い世代はすぐに追い出す
+        .time_to_live(BOARD_CACHE_TTL)
+        .build(),
+      last_good: Arc::new(RwLock::new(None)),
+    }
+  }
+
+  /// 指定した世代のスナップショットを取得する
+  ///
+  /// キャッシュにない場合は `load` で取得する。同じ世代への同時アクセスは1回の `load` にまとめられる。
+  /// `load` が失敗した場合、過去に取得できたスナップショットがあればそれを返す。
+  pub async fn get_or_load<F, Fut>(&self, generation: u64, load: F) -> Result<BoardSnapshot, AppError>
+  where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<Calligraphy>, sqlx::Error>>,
+  {
+    let last_good = self.last_good.clone();
+    let result = self
+      .cache
+      .try_get_with(generation, async move {
+        let snapshot = Arc::new(load().await?);
+        *last_good.write().unwrap() = Some(snapshot.clone());
+        Ok::<_, sqlx::Error>(snapshot)
+      })
+      .await;
+
+    match result {
+      Ok(snapshot) => Ok(snapshot),
+      Err(e) => {
+        // 同時に待っていた全ての呼び出し元で共有されるエラーのため、所有権は取れない
+        tracing::error!("Database error: {:?}", e);
+        match self.last_good.read().unwrap().clone() {
+          Some(stale) => {
+            tracing::warn!("Serving stale board snapshot ({} entries)", stale.len());
+            Ok(stale)
+          }
+          None => Err(AppError::Internal),
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  /// 同じ世代は1回だけ取得され、世代が変われば再取得されること
+  #[tokio::test]
+  async fn test_cached_per_generation() {
+    let cache = BoardCache::new();
+    let loads = AtomicUsize::new(0);
+    let load = || async {
+      loads.fetch_add(1, Ordering::SeqCst);
+      Ok(vec![])
+    };
+
+    cache.get_or_load(1, load).await.unwrap();
+    cache.get_or_load(1, load).await.unwrap();
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    cache.get_or_load(2, load).await.unwrap();
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+  }
+
+  /// 同時に取得しても1回の取得にまとめられること
+  #[tokio::test]
+  async fn test_coalesces_concurrent_misses() {
+    let cache = BoardCache::new();
+    let loads = Arc::new(AtomicUsize::new(0));
+
+    let tasks = (0..20).map(|_| {
+      let cache = cache.clone();
+      let loads = loads.clone();
+      tokio::spawn(async move {
+        cache
+          .get_or_load(1, || async move {
+            loads.fetch_add(1, Ordering::SeqCst);
+            // 取得中に他のリクエストが来るよう待つ
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(vec![])
+          })
+          .await
+      })
+    });
+    for task in futures_util::future::join_all(tasks).await {
+      assert!(task.unwrap().is_ok());
+    }
+
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+  }
+
+  /// DBエラー時は最後に取得できたスナップショットを返すこと
+  #[tokio::test]
+  async fn test_serves_stale_on_error() {
+    let cache = BoardCache::new();
+
+    // 一度も取得できていなければエラー
+    let result = cache
+      .get_or_load(1, || async { Err(sqlx::Error::PoolTimedOut) })
+      .await;
+    assert!(matches!(result, Err(AppError::Internal)));
+
+    cache.get_or_load(2, || async { Ok(vec![]) }).await.unwrap();
+    let result = cache
+      .get_or_load(3, || async { Err(sqlx::Error::PoolTimedOut) })
+      .await;
+    assert!(result.is_ok());
+  }
+}
@@ -1,24 +1,105 @@
 use crate::error::AppError;
-use crate::models::calligraphy::Calligraphy;
+use crate::feed::FEED_ENTRIES;
+use crate::models::calligraphy::{BoardState, Calligraphy, CalligraphyEvent};
+use crate::models::export::{PersonalDataExport, EXPORT_FORMAT_VERSION};
+use crate::models::list_query::ListQuery;
+use crate::models::passkey::{CeremonyKind, PasskeyCeremony, PasskeyRecord};
+use crate::models::photo::Photo;
+use crate::models::retention::RetentionPolicy;
+use crate::models::search::{SearchHit, SearchQuery};
+use crate::models::session::Session;
+use crate::models::stats::BoardStats;
+use crate::models::strokes::Strokes;
+use crate::models::transfer::TransferCodeResponse;
 use crate::repositories::db_repository::CalligraphyRepositoryTrait;
+use crate::services::board_cache::{BoardCache, BoardSnapshot};
+use crate::services::events::{EventHub, Subscription};
+use crate::services::ogp::OgpImages;
+use crate::services::passkeys::Passkeys;
+use crate::services::photos::Photos;
+use crate::services::presence::PresenceHub;
+use crate::services::retention;
+use crate::services::sessions;
+use crate::services::stats;
+use crate::services::transfer;
+use crate::validation;
+use bytes::Bytes;
 use moka::future::Cache;
 use sqlx::types::ipnetwork::IpNetwork;
 use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
+use webauthn_rs::prelude::{
+  CreationChallengeResponse, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+  RegisterPublicKeyCredential, RequestChallengeResponse,
+};
 
 /// ビジネスロジックを担当するサービス
 /// データの加工、バリデーション、エラーの意味付けを行う
-#[derive(Clone)]
 pub struct CalligraphyService<R: CalligraphyRepositoryTrait> {
-  repository: R,
+  repository: Arc<R>,
   write_limit_cache: Cache<IpAddr, ()>, // 書き込み制限用
   read_limit_cache: Cache<IpAddr, ()>,  // 読み込み制限用
+  claim_attempts: Cache<IpAddr, u32>,   // 引き継ぎコードの試行回数 (総当たり対策)
+  events: EventHub,                     // 変更イベントの配信
+  presence: PresenceHub,                // ライブボードの在室状況
+  board_cache: BoardCache,              // 一覧のキャッシュ
+  feed_cache: BoardCache,               // フィード (更新日時の新しい順) のキャッシュ
+  stats_cache: Cache<u64, Arc<BoardStats>>, // 集計結果のキャッシュ (キーは世代番号)
+  retention: RetentionPolicy,           // 収集したリクエスト情報の保持ポリシー
+  public_base_url: Arc<str>,            // サイトの公開URL (フィード用)
+  ogp: OgpImages,                       // 書き初めごとのOGP画像
+  photos: Photos,                       // 添付した写真の保存先
+  passkeys: Passkeys,                                       // パスキー (WebAuthn) の設定
+  strokes_cache: Cache<(Uuid, i128), Option<Arc<Strokes>>>, // 筆跡データのキャッシュ (キーは公開用IDと更新日時)
+  public_cache: Cache<(Uuid, u64), Option<Arc<Calligraphy>>>, // 公開用IDで取得した書き初めのキャッシュ (キーは公開用IDと世代番号)
+  legacy_cookie_sunset: time::OffsetDateTime,               // 以前のユーザーIDのCookieを移行する期限
 }
 
 const WRITE_LIMIT_DURATION: Duration = Duration::from_secs(3);
 const READ_LIMIT_DURATION: Duration = Duration::from_secs(1);
+/// 引き継ぎコードの有効期間
+const TRANSFER_CODE_TTL: time::Duration = time::Duration::minutes(10);
+/// 引き継ぎコードの試行回数を数える期間 (最後の試行からの時間)
+const CLAIM_ATTEMPT_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// 上記の期間内に許す引き継ぎコードの試行回数
+const CLAIM_MAX_ATTEMPTS: u32 = 10;
+/// パスキーの登録・認証を開始してから完了するまでの期限
+const PASSKEY_CEREMONY_TTL: time::Duration = time::Duration::minutes(5);
+/// 集計結果を保持する時間 (年末年始の集計期間は日付で変わるため、変更がなくても再計算する)
+const STATS_CACHE_TTL: Duration = Duration::from_secs(60);
+/// キャッシュする筆跡データの件数
+const STROKES_CACHE_CAPACITY: u64 = 256;
+/// 公開用IDで取得した書き初めをキャッシュする件数
+const PUBLIC_CACHE_CAPACITY: u64 = 256;
+/// 匿名化ジョブが1回のクエリで処理する行数
+const RETENTION_BATCH_SIZE: i64 = 500;
 
+// リポジトリ自体は Clone でなくてもよいよう、derive ではなく手で実装する
+impl<R: CalligraphyRepositoryTrait> Clone for CalligraphyService<R> {
+  fn clone(&self) -> Self {
+    Self {
+      repository: self.repository.clone(),
+      write_limit_cache: self.write_limit_cache.clone(),
+      read_limit_cache: self.read_limit_cache.clone(),
+      claim_attempts: self.claim_attempts.clone(),
+      events: self.events.clone(),
+      presence: self.presence.clone(),
+      board_cache: self.board_cache.clone(),
+      feed_cache: self.feed_cache.clone(),
+      stats_cache: self.stats_cache.clone(),
+      retention: self.retention,
+      public_base_url: self.public_base_url.clone(),
+      ogp: self.ogp.clone(),
+      photos: self.photos.clone(),
+      passkeys: self.passkeys.clone(),
+      strokes_cache: self.strokes_cache.clone(),
+      public_cache: self.public_cache.clone(),
+      legacy_cookie_sunset: self.legacy_cookie_sunset,
+    }
+  }
+}
 
 impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
   pub fn new(repository: R) -> Self {
@@ -30,13 +111,103 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
       .time_to_live(READ_LIMIT_DURATION) // 1秒に1回まで
       .build();
     Self {
-      repository,
+      repository: Arc::new(repository),
       write_limit_cache,
       read_limit_cache,
+      claim_attempts: Cache::builder().time_to_live(CLAIM_ATTEMPT_WINDOW).build(),
+      events: EventHub::new(),
+      presence: PresenceHub::new(),
+      board_cache: BoardCache::new(),
+      feed_cache: BoardCache::new(),
+      stats_cache: Cache::builder()
+        .max_capacity(2)
+        .time_to_live(STATS_CACHE_TTL)
+        .build(),
+      retention: RetentionPolicy::default(),
+      public_base_url: Arc::from(""),
+      ogp: OgpImages::default(),
+      photos: Photos::default(),
+      passkeys: Passkeys::default(),
+      strokes_cache: Cache::builder().max_capacity(STROKES_CACHE_CAPACITY).build(),
+      public_cache: Cache::builder().max_capacity(PUBLIC_CACHE_CAPACITY).build(),
+      legacy_cookie_sunset: sessions::LEGACY_COOKIE_SUNSET,
     }
   }
 
+  /// 収集したリクエスト情報の保持ポリシーを設定する
+  pub fn with_retention_policy(mut self, policy: RetentionPolicy) -> Self {
+    self.retention = policy;
+    self
+  }
+
+  /// 収集したリクエスト情報の保持ポリシー
+  pub fn retention_policy(&self) -> RetentionPolicy {
+    self.retention
+  }
+
+  /// 以前のユーザーIDのCookieをセッションへ移行する期限を設定する
+  pub fn with_legacy_cookie_sunset(mut self, sunset: time::OffsetDateTime) -> Self {
+    self.legacy_cookie_sunset = sunset;
+    self
+  }
+
+  /// サイトの公開URLを設定する (末尾の `/` なし)
+  pub fn with_public_base_url(mut self, url: &str) -> Self {
+    self.public_base_url = Arc::from(url);
+    self
+  }
+
+  /// サイトの公開URL
+  pub fn public_base_url(&self) -> &str {
+    &self.public_base_url
+  }
+
+  /// OGP画像の生成・キャッシュを設定する
+  pub fn with_ogp_images(mut self, ogp: OgpImages) -> Self {
+    self.ogp = ogp;
+    self
+  }
+
+  /// 書き初めごとのOGP画像
+  pub fn ogp(&self) -> &OgpImages {
+    &self.ogp
+  }
+
+  /// 写真の保存先を設定する
+  pub fn with_photos(mut self, photos: Photos) -> Self {
+    self.photos = photos;
+    self
+  }
+
+  /// 添付した写真の保存先
+  pub fn photos(&self) -> &Photos {
+    &self.photos
+  }
+
+  /// パスキー (WebAuthn) の設定をする
+  pub fn with_passkeys(mut self, passkeys: Passkeys) -> Self {
+    self.passkeys = passkeys;
+    self
+  }
+
+  /// イベントハブへの参照 (他レプリカとのNOTIFY連携用)
+  pub fn events(&self) -> &EventHub {
+    &self.events
+  }
+
+  /// ライブボード (WebSocket) の在室状況
+  pub fn presence(&self) -> &PresenceHub {
+    &self.presence
+  }
+
+  /// 変更イベントの購読を開始する
+  /// `last_event_id` が指定された場合は、それ以降のイベントを再送する
+  pub fn subscribe(&self, last_event_id: Option<&str>) -> Subscription {
+    self.events.subscribe(last_event_id)
+  }
+
   /// 書き込み系（upsert, delete）のレート制限
+  #[tracing::instrument(name = "service.check_write_rate_limit", skip_all)]
   pub async fn check_write_rate_limit(&self, ip: IpAddr) -> Result<(), AppError> {
     if self.write_limit_cache.get(&ip).await.is_some() {
       return Err(AppError::TooManyRequests);
@@ -46,6 +217,7 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
   }
 
   /// 読み込み系（list, get）のレート制限
+  #[tracing::instrument(name = "service.check_read_rate_limit", skip_all)]
   pub async fn check_read_rate_limit(&self, ip: IpAddr) -> Result<(), AppError> {
     if self.read_limit_cache.get(&ip).await.is_some() {
       return Err(AppError::TooManyRequests);
@@ -54,8 +226,33 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
     Ok(())
   }
 
+  /// 引き継ぎコードの使用のレート制限 (総当たり対策)
+  ///
+  /// 成否にかかわらず試行回数を数える。コードは50ビットのため、1つのIPアドレスから
+  /// 有効期間内に当てられる見込みはない。IPアドレスが分からない場合はまとめて数える。
+  #[tracing::instrument(name = "service.check_claim_rate_limit", skip_all)]
+  pub async fn check_claim_rate_limit(&self, ip: Option<IpAddr>) -> Result<(), AppError> {
+    let key = ip.unwrap_or(IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED));
+    let attempts = self
+      .claim_attempts
+      .entry(key)
+      .and_upsert_with(|current| {
+        let attempts = current.map_or(0, |entry| *entry.value());
+        std::future::ready(attempts.saturating_add(1))
+      })
+      .await
+      .into_value();
+    if attempts > CLAIM_MAX_ATTEMPTS {
+      return Err(AppError::TooManyRequests);
+    }
+    Ok(())
+  }
+
   /// 書き初めを作成・更新する
-  /// 文字数制限などのビジネスルールがあればここで検証する
+  /// 入力値は `validation` モジュールで正規化・検証してから保存する
+  /// 写真 (`photo`) はアップロードされたファイルの内容で、再エンコードして保存する
+  #[allow(clippy::too_many_arguments)] // リポジトリの create と同じ引数をとる
+  #[tracing::instrument(name = "service.upsert", skip_all)]
   pub async fn upsert(
     &self,
     user_id: Uuid,
@@ -64,21 +261,23 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
     ip_address: Option<IpNetwork>,
     user_agent: Option<String>,
     accept_language: Option<String>,
+    strokes: Option<Strokes>,
+    photo: Option<Bytes>,
   ) -> Result<Calligraphy, AppError> {
-    // バリデーション例 (DBのCHECK制約もあるが、アプリ側でも弾く場合)
-    if content.chars().count() > 50 {
-      return Err(AppError::Validation(
-        "Content must be 50 chars or less".to_string(),
-      ));
-    }
-    if user_name.chars().count() > 20 {
-      return Err(AppError::Validation(
-        "User name must be 30 chars or less".to_string(),
-      ));
-    }
+    // 正規化 (NFC・前後の空白除去) と文字数・禁止文字の検証
+    let user_name = validation::normalize_user_name(&user_name)?;
+    let content = validation::normalize_content(&content)?;
+    let strokes = strokes.map(validation::normalize_strokes).transpose()?;
+    // 文字の検証を通ってから写真を処理・保存する
+    let photo = match photo {
+      Some(upload) => Some(self.photos.store(upload).await?),
+      None => None,
+    };
+    // 上書きで参照されなくなる写真 (写真を扱わない構成では調べない)
+    let previous_photo = self.current_photo(user_id).await?;
 
     // Repositoryの呼び出し。
-    let calligraphy = self
+    let result = self
       .repository
       .create(
         user_id,
@@ -87,13 +286,59 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
         ip_address,
         user_agent,
         accept_language,
+        strokes,
+        photo.clone(),
       )
-      .await?;
+      .await;
+    let calligraphy = match result {
+      Ok(calligraphy) => calligraphy,
+      Err(e) => {
+        // 保存できなかった書き初めの写真を残さない
+        self.remove_unused_photo(photo).await;
+        return Err(e.into());
+      }
+    };
+    if previous_photo != photo {
+      self.remove_unused_photo(previous_photo).await;
+    }
+
+    let event = if calligraphy.is_new() {
+      CalligraphyEvent::Created(calligraphy.clone())
+    } else {
+      CalligraphyEvent::Updated(calligraphy.clone())
+    };
+    self.events.publish(event);
+
     Ok(calligraphy)
   }
 
+  /// ユーザーの書き初めに添付されている写真
+  async fn current_photo(&self, user_id: Uuid) -> Result<Option<Photo>, AppError> {
+    if !self.photos.is_enabled() {
+      return Ok(None);
+    }
+    let current = self.repository.find_by_id(user_id).await?;
+    Ok(current.and_then(|c| c.photo).map(|photo| photo.0))
+  }
+
+  /// どの書き初めからも参照されていない写真のファイルを削除する
+  /// 同じ内容の写真は同じファイル名になるため、他の書き初めが参照していれば残す
+  async fn remove_unused_photo(&self, photo: Option<Photo>) {
+    let Some(photo) = photo else {
+      return;
+    };
+    for key in photo.keys() {
+      match self.repository.is_photo_in_use(key).await {
+        Ok(false) => self.photos.delete(key).await,
+        Ok(true) => {}
+        Err(e) => tracing::warn!("Failed to check whether photo {} is in use: {:?}", key, e),
+      }
+    }
+  }
+
   /// IDで取得する
   /// データが存在しない場合、AppError::NotFound を返すように変換する
+  #[tracing::instrument(name = "service.get", skip_all)]
   pub async fn get(&self, user_id: Uuid) -> Result<Calligraphy, AppError> {
     let opt = self.repository.find_by_id(user_id).await?;
 
@@ -102,20 +347,564 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
   }
 
   /// 一覧を取得する
-  pub async fn get_all(&self) -> Result<Vec<Calligraphy>, AppError> {
-    let list = self.repository.find_all().await?;
-    Ok(list)
+  ///
+  /// 全ユーザー共通のスナップショットをキャッシュから返す。
+  /// 作成・更新・削除のイベントで世代が進むため、書き込み直後でも古い一覧は返らない。
+  #[tracing::instrument(name = "service.get_all", skip_all)]
+  pub async fn get_all(&self) -> Result<BoardSnapshot, AppError> {
+    let generation = self.events.generation();
+    self
+      .board_cache
+      .get_or_load(generation, || self.repository.find_all())
+      .await
+  }
+
+  /// フィード用に、更新日時の新しい順に `FEED_ENTRIES` 件を取得する
+  ///
+  /// 一覧 (作成日時の新しい順に100件) とは対象が異なるため、別のクエリで取得し一覧と同じくキャッシュする。
+  #[tracing::instrument(name = "service.get_feed", skip_all)]
+  pub async fn get_feed(&self) -> Result<BoardSnapshot, AppError> {
+    let generation = self.events.generation();
+    self
+      .feed_cache
+      .get_or_load(generation, || self.repository.find_latest(FEED_ENTRIES as i64))
+      .await
+  }
+
+  /// 公開用IDで取得する (OGP画像・SVG・筆跡用)
+  ///
+  /// 一覧は新しい100件に限られるため、一覧からは探さず公開用IDで取得する。
+  /// 一覧と同じ世代番号を含めた (公開用ID, 世代番号) をキーにキャッシュし、変更があれば取得し直す。
+  #[tracing::instrument(name = "service.get_public", skip_all)]
+  pub async fn get_public(&self, public_id: Uuid) -> Result<Calligraphy, AppError> {
+    let key = (public_id, self.events.generation());
+    let entry = self
+      .public_cache
+      .try_get_with(key, async move {
+        Ok::<_, sqlx::Error>(self.repository.find_by_public_id(public_id).await?.map(Arc::new))
+      })
+      .await
+      .map_err(|e| {
+        tracing::error!("Database error: {:?}", e);
+        AppError::Internal
+      })?;
+    entry.map(|entry| (*entry).clone()).ok_or(AppError::NotFound)
+  }
+
+  /// 書き初めの筆跡データを取得する (筆跡がなければNotFound)
+  ///
+  /// 書き初めを更新すると筆跡も置き換わるため、(公開用ID, 更新日時) をキーにキャッシュする。
+  #[tracing::instrument(name = "service.get_strokes", skip_all)]
+  pub async fn get_strokes(&self, entry: &Calligraphy) -> Result<Arc<Strokes>, AppError> {
+    let public_id = entry.public_id;
+    let key = (public_id, entry.updated_at.unix_timestamp_nanos());
+    let strokes = self
+      .strokes_cache
+      .try_get_with(key, async move {
+        Ok::<_, sqlx::Error>(self.repository.find_strokes(public_id).await?.map(Arc::new))
+      })
+      .await
+      .map_err(|e| {
+        tracing::error!("Database error: {:?}", e);
+        AppError::Internal
+      })?;
+    strokes.ok_or(AppError::NotFound)
+  }
+
+  /// ボード全体の集計結果を取得する
+  ///
+  /// 一覧 (上限100件、リクエスト情報なし) とは別に全件を取得して集計する。
+  /// 一覧と同じ世代番号をキーにキャッシュし、変更があるまで再計算しない。
+  #[tracing::instrument(name = "service.stats", skip_all)]
+  pub async fn stats(&self) -> Result<Arc<BoardStats>, AppError> {
+    let generation = self.events.generation();
+    self
+      .stats_cache
+      .try_get_with(generation, async move {
+        let entries = self.repository.find_for_stats().await?;
+        Ok::<_, sqlx::Error>(Arc::new(stats::compute(&entries, time::OffsetDateTime::now_utc())))
+      })
+      .await
+      .map_err(|e| {
+        tracing::error!("Database error: {:?}", e);
+        AppError::Internal
+      })
+  }
+
+  /// 保持期間を過ぎたリクエスト情報 (IPアドレス等) を匿名化する
+  /// 戻り値は匿名化した行数
+  #[tracing::instrument(name = "service.apply_retention", skip_all)]
+  pub async fn apply_retention(&self) -> Result<u64, AppError> {
+    let policy = self.retention;
+    if !policy.enabled {
+      return Ok(0);
+    }
+    let cutoff = policy.cutoff(time::OffsetDateTime::now_utc());
+
+    let mut total = 0;
+    loop {
+      let rows = self
+        .repository
+        .find_unanonymized(cutoff, RETENTION_BATCH_SIZE)
+        .await?;
+      let fetched = rows.len() as i64;
+      if fetched == 0 {
+        break;
+      }
+      let anonymized = rows.into_iter().map(retention::anonymize).collect();
+      let updated = self.repository.anonymize_metadata(anonymized, policy).await?;
+      total += updated;
+      // 全て取得直後に更新された場合などに、同じ行を取得し続けないようにする
+      if fetched < RETENTION_BATCH_SIZE || updated == 0 {
+        break;
+      }
+    }
+
+    tracing::info!(
+      "Anonymized request metadata of {} rows (last updated before {})",
+      total,
+      cutoff
+    );
+    Ok(total)
+  }
+
+  /// 個人データをエクスポートする (書き初め・セッション・パスキー)
+  /// 書き初めを投稿していない場合も、ユーザーIDとセッションを含むデータを返す
+  #[tracing::instrument(name = "service.export", skip_all)]
+  pub async fn export(&self, user_id: Uuid) -> Result<PersonalDataExport, AppError> {
+    let calligraphy = self.repository.export_by_id(user_id).await?;
+    let sessions = self.repository.export_sessions(user_id).await?;
+    let passkeys = self.repository.export_passkeys(user_id).await?;
+    Ok(PersonalDataExport {
+      format_version: EXPORT_FORMAT_VERSION,
+      exported_at: time::OffsetDateTime::now_utc(),
+      user_id,
+      calligraphy,
+      sessions,
+      passkeys,
+    })
+  }
+
+  /// 条件を指定して一覧を取得する
+  /// 条件の指定がなければ、全ユーザー共通のキャッシュされた一覧を返す
+  #[tracing::instrument(name = "service.get_list", skip_all)]
+  pub async fn get_list(&self, query: &ListQuery, viewer_id: Uuid) -> Result<BoardSnapshot, AppError> {
+    if query.is_default() {
+      return self.get_all().await;
+    }
+    let list = self.repository.find_filtered(query, viewer_id).await?;
+    Ok(Arc::new(list))
+  }
+
+  /// 全文検索
+  /// 戻り値は (検索結果, 次のページがあるか)。次のページの有無を判定するため1件多く取得する
+  #[tracing::instrument(name = "service.search", skip_all)]
+  pub async fn search(&self, query: &SearchQuery) -> Result<(Vec<SearchHit>, bool), AppError> {
+    let limit = i64::from(query.per_page);
+    let mut hits = self
+      .repository
+      .search(&query.q, limit + 1, query.offset())
+      .await?;
+    let has_more = hits.len() as i64 > limit;
+    hits.truncate(query.per_page as usize);
+    Ok((hits, has_more))
+  }
+
+  /// ボード全体の状態を取得する (条件付きGET用)
+  #[tracing::instrument(name = "service.get_board_state", skip_all)]
+  pub async fn get_board_state(&self, viewer_id: Uuid) -> Result<BoardState, AppError> {
+    let state = self.repository.board_state(viewer_id).await?;
+    Ok(state)
+  }
+
+  /// 別のブラウザへ書き初めを引き継ぐための引き継ぎコードを発行する
+  ///
+  /// 発行済みのコードは無効になる。書き初めがなければ引き継ぐものがないためNotFound
+  #[tracing::instrument(name = "service.issue_transfer_code", skip_all)]
+  pub async fn issue_transfer_code(&self, user_id: Uuid) -> Result<TransferCodeResponse, AppError> {
+    let code = transfer::generate_code();
+    let code_hash = transfer::hash_code(&transfer::normalize_code(&code)?);
+    let expires_at = time::OffsetDateTime::now_utc() + TRANSFER_CODE_TTL;
+    if !self
+      .repository
+      .save_transfer_code(user_id, code_hash, expires_at)
+      .await?
+    {
+      return Err(AppError::NotFound);
+    }
+    Ok(TransferCodeResponse { code, expires_at })
+  }
+
+  /// 引き継ぎコードを使い、書き初めをこのブラウザ (`claimer_id`) に引き継ぐ
+  ///
+  /// 書き初めには新しいユーザーIDを割り当てるため、元のブラウザのCookieは以降使えなくなる。
+  /// 戻り値の `user_id` が新しいユーザーIDで、呼び出し側でCookieに設定する。
+  /// このブラウザに既に書き初めがある場合は、それが失われないよう引き継がない (Conflict)。
+  #[tracing::instrument(name = "service.claim_transfer_code", skip_all)]
+  pub async fn claim_transfer_code(&self, claimer_id: Uuid, code: &str) -> Result<Calligraphy, AppError> {
+    let code_hash = transfer::hash_code(&transfer::normalize_code(code)?);
+    if self.repository.find_by_id(claimer_id).await?.is_some() {
+      return Err(AppError::Conflict(
+        "This browser already has an entry; delete it before claiming another".to_string(),
+      ));
+    }
+
+    let calligraphy = self
+      .repository
+      .claim_transfer_code(code_hash, Uuid::new_v4())
+      .await?
+      .ok_or_else(transfer::invalid_code)?;
+
+    // 一覧のキャッシュの世代を進め、is_mine の付き方を新しいユーザーIDに合わせる
+    self.events.publish(CalligraphyEvent::Updated(calligraphy.clone()));
+
+    Ok(calligraphy)
+  }
+
+  /// パスキーの登録を開始する
+  ///
+  /// パスキーは書き初めの持ち主として登録するため、書き初めがなければNotFound。
+  /// 戻り値は (登録のID, `navigator.credentials.create()` に渡すオプション)
+  #[tracing::instrument(name = "service.start_passkey_registration", skip_all)]
+  pub async fn start_passkey_registration(&self, user_id: Uuid) -> Result<(Uuid, CreationChallengeResponse), AppError> {
+    let webauthn = self.passkeys.webauthn()?;
+    let entry = self.repository.find_by_id(user_id).await?.ok_or(AppError::NotFound)?;
+    // 同じ認証器に2つ目のパスキーを作らせない
+    let registered = self
+      .repository
+      .find_passkeys(user_id)
+      .await?
+      .into_iter()
+      .map(|record| record.passkey.0.cred_id().clone())
+      .collect();
+
+    let (options, state) = webauthn
+      .start_passkey_registration(
+        user_id,
+        &entry.public_id.to_string(),
+        &entry.user_name,
+        Some(registered),
+      )
+      .map_err(|e| {
+        tracing::error!("Failed to start passkey registration: {:?}", e);
+        AppError::Internal
+      })?;
+    let ceremony_id = self.save_ceremony(CeremonyKind::Registration, user_id, &state).await?;
+    Ok((ceremony_id, options))
+  }
+
+  /// パスキーの登録を完了する (開始したユーザーと同じユーザーでなければならない)
+  #[tracing::instrument(name = "service.finish_passkey_registration", skip_all)]
+  pub async fn finish_passkey_registration(
+    &self,
+    user_id: Uuid,
+    ceremony_id: Uuid,
+    credential: &RegisterPublicKeyCredential,
+  ) -> Result<PasskeyRecord, AppError> {
+    let webauthn = self.passkeys.webauthn()?;
+    let state: PasskeyRegistration = self
+      .take_ceremony(ceremony_id, CeremonyKind::Registration, Some(user_id))
+      .await?
+      .1;
+    let passkey = webauthn.finish_passkey_registration(credential, &state).map_err(|e| {
+      tracing::info!("Passkey registration rejected: {:?}", e);
+      AppError::Validation("Passkey registration failed".to_string())
+    })?;
+
+    match self.repository.add_passkey(user_id, passkey).await {
+      Ok(Some(record)) => Ok(record),
+      // 登録の途中で書き初めが削除された
+      Ok(None) => Err(AppError::NotFound),
+      Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+        Err(AppError::Conflict("This passkey is already registered".to_string()))
+      }
+      Err(e) => Err(e.into()),
+    }
+  }
+
+  /// パスキーでのログインを開始する
+  ///
+  /// ユーザー名はないため、ログインする書き初めを公開用IDで指定する。
+  /// 書き初めがない・パスキーが登録されていない場合も、偽の資格情報IDで同じ形のオプションを返す
+  /// (完了は検証に失敗する)。公開用IDからパスキーの有無を推測させないため。
+  /// 戻り値は (認証のID, `navigator.credentials.get()` に渡すオプション)
+  #[tracing::instrument(name = "service.start_passkey_authentication", skip_all)]
+  pub async fn start_passkey_authentication(
+    &self,
+    public_id: Uuid,
+  ) -> Result<(Uuid, RequestChallengeResponse), AppError> {
+    let webauthn = self.passkeys.webauthn()?;
+    let records = self.repository.find_passkeys_by_public_id(public_id).await?;
+    // パスキーがなければ誰のものでもない認証として保存する (完了時に一致するパスキーはない)
+    let user_id = records.first().map_or(Uuid::nil(), |record| record.user_id);
+    let passkeys: Vec<_> = records.into_iter().map(|record| record.passkey.0).collect();
+
+    let (mut options, state) = webauthn.start_passkey_authentication(&passkeys).map_err(|e| {
+      tracing::error!("Failed to start passkey authentication: {:?}", e);
+      AppError::Internal
+    })?;
+    self.passkeys.disguise(public_id, &mut options)?;
+    let ceremony_id = self
+      .save_ceremony(CeremonyKind::Authentication, user_id, &state)
+      .await?;
+    Ok((ceremony_id, options))
+  }
+
+  /// パスキーでのログインを完了する
+  /// 戻り値はログインした書き初め (セッションは呼び出し側で `switch_session` により切り替える)
+  #[tracing::instrument(name = "service.finish_passkey_authentication", skip_all)]
+  pub async fn finish_passkey_authentication(
+    &self,
+    ceremony_id: Uuid,
+    credential: &PublicKeyCredential,
+  ) -> Result<Calligraphy, AppError> {
+    let webauthn = self.passkeys.webauthn()?;
+    let (user_id, state): (Uuid, PasskeyAuthentication) = self
+      .take_ceremony(ceremony_id, CeremonyKind::Authentication, None)
+      .await?;
+    let result = webauthn
+      .finish_passkey_authentication(credential, &state)
+      .map_err(|e| {
+        tracing::info!("Passkey authentication rejected: {:?}", e);
+        AppError::Validation("Passkey authentication failed".to_string())
+      })?;
+
+    // 署名カウンタ等を更新する (認証の途中で削除されたパスキーではログインさせない)
+    let mut passkey = self
+      .repository
+      .find_passkeys(user_id)
+      .await?
+      .into_iter()
+      .map(|record| record.passkey.0)
+      .find(|passkey| passkey.cred_id() == result.cred_id())
+      .ok_or_else(|| AppError::Validation("Passkey authentication failed".to_string()))?;
+    passkey.update_credential(&result);
+    self.repository.update_passkey(passkey).await?;
+
+    let entry = self.repository.find_by_id(user_id).await?.ok_or(AppError::NotFound)?;
+    Ok(entry)
+  }
+
+  /// 新しいセッションを作成する (引き継ぎ・パスキーでのログイン用)
+  /// 戻り値はCookieに入れるセッショントークン
+  #[tracing::instrument(name = "service.create_session", skip_all)]
+  pub async fn create_session(&self, user_id: Uuid, user_agent: Option<&str>) -> Result<String, AppError> {
+    self.insert_session(user_id, user_agent, sessions::SESSION_TTL).await
+  }
+
+  /// 新しいユーザーのセッションを作成する
+  ///
+  /// Cookieを保存しないクライアントのセッションが残り続けないよう、有効期間は短くする。
+  /// 同じセッションで次のリクエストが来た時点で通常の有効期間に延長される。
+  #[tracing::instrument(name = "service.start_session", skip_all)]
+  pub async fn start_session(&self, user_id: Uuid, user_agent: Option<&str>) -> Result<String, AppError> {
+    self.insert_session(user_id, user_agent, sessions::UNUSED_SESSION_TTL).await
+  }
+
+  /// 以前のユーザーIDのCookieのユーザーのセッションを作成する
+  ///
+  /// 移行期限までに、書き初めを持ちセッションがまだないユーザーのみ作成する (それ以外はNone)。
+  /// Cookieの値だけで作れるため、新しいユーザーと同じく有効期間は短くする。
+  /// 戻り値はCookieに入れるセッショントークン
+  #[tracing::instrument(name = "service.migrate_legacy_user", skip_all)]
+  pub async fn migrate_legacy_user(&self, user_id: Uuid, user_agent: Option<&str>) -> Result<Option<String>, AppError> {
+    let now = time::OffsetDateTime::now_utc();
+    if now >= self.legacy_cookie_sunset {
+      return Ok(None);
+    }
+    let token = sessions::generate_token();
+    let session = self
+      .repository
+      .create_legacy_session(
+        sessions::hash_token(&token),
+        user_id,
+        user_agent.map(sessions::device_label),
+        now + sessions::UNUSED_SESSION_TTL,
+      )
+      .await?;
+    Ok(session.map(|_| token))
+  }
+
+  async fn insert_session(&self, user_id: Uuid, user_agent: Option<&str>, ttl: time::Duration) -> Result<String, AppError> {
+    let token = sessions::generate_token();
+    self
+      .repository
+      .create_session(
+        sessions::hash_token(&token),
+        user_id,
+        user_agent.map(sessions::device_label),
+        time::OffsetDateTime::now_utc() + ttl,
+      )
+      .await?;
+    Ok(token)
+  }
+
+  /// 期限切れのセッションを削除する (匿名化ジョブと合わせて定期的に実行する)
+  /// 戻り値は削除した行数
+  #[tracing::instrument(name = "service.purge_expired_sessions", skip_all)]
+  pub async fn purge_expired_sessions(&self) -> Result<u64, AppError> {
+    let deleted = self.repository.delete_expired_sessions().await?;
+    tracing::info!("Deleted {} expired sessions", deleted);
+    Ok(deleted)
+  }
+
+  /// トークンのセッションのユーザーID (存在しない・期限切れならNone)
+  ///
+  /// 前回の延長から一定時間が経っていれば有効期限を延長する。
+  /// 戻り値の2つ目は延長したか (延長した場合はCookieの有効期限も設定し直す)
+  #[tracing::instrument(name = "service.resolve_session", skip_all)]
+  pub async fn resolve_session(&self, token: &str) -> Result<Option<(Uuid, bool)>, AppError> {
+    let Some(session) = self.repository.find_session(sessions::hash_token(token)).await? else {
+      return Ok(None);
+    };
+    let now = time::OffsetDateTime::now_utc();
+    let touched = sessions::needs_touch(&session, now);
+    if touched {
+      self
+        .repository
+        .touch_session(session.id, now + sessions::SESSION_TTL)
+        .await?;
+    }
+    Ok(Some((session.user_id, touched)))
+  }
+
+  /// このブラウザのセッションを別のユーザーのセッションに切り替える (引き継ぎ・パスキーでのログイン)
+  /// 戻り値は新しいセッショントークン
+  #[tracing::instrument(name = "service.switch_session", skip_all)]
+  pub async fn switch_session(
+    &self,
+    current_token: Option<&str>,
+    user_id: Uuid,
+    user_agent: Option<&str>,
+  ) -> Result<String, AppError> {
+    if let Some(token) = current_token {
+      self.logout(token).await?;
+    }
+    self.create_session(user_id, user_agent).await
+  }
+
+  /// ログアウトする (このブラウザのセッションを削除する)
+  #[tracing::instrument(name = "service.logout", skip_all)]
+  pub async fn logout(&self, token: &str) -> Result<(), AppError> {
+    self
+      .repository
+      .delete_session_by_token(sessions::hash_token(token))
+      .await?;
+    Ok(())
+  }
+
+  /// ユーザーの有効なセッションの一覧 (最近使われた順)
+  #[tracing::instrument(name = "service.list_sessions", skip_all)]
+  pub async fn list_sessions(&self, user_id: Uuid) -> Result<Vec<Session>, AppError> {
+    let sessions = self.repository.find_sessions(user_id).await?;
+    Ok(sessions)
+  }
+
+  /// セッションを削除する (他の端末をログアウトさせる。自分のセッションでなければNotFound)
+  #[tracing::instrument(name = "service.revoke_session", skip_all)]
+  pub async fn revoke_session(&self, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+    if !self.repository.delete_session(user_id, id).await? {
+      return Err(AppError::NotFound);
+    }
+    Ok(())
+  }
+
+  /// 書き初めのユーザーの全てのセッションを無効にする (管理用コマンド `server revoke-sessions` 用)
+  ///
+  /// 書き初めには新しいユーザーIDを割り当て、パスキー・引き継ぎコードも削除するため、
+  /// ユーザーはどの端末からも書き初めを編集・削除できなくなる。戻り値は無効にしたセッションの数
+  #[tracing::instrument(name = "service.revoke_user", skip_all)]
+  pub async fn revoke_user(&self, public_id: Uuid) -> Result<u64, AppError> {
+    let revoked = self
+      .repository
+      .revoke_user(public_id, Uuid::new_v4())
+      .await?
+      .ok_or(AppError::NotFound)?;
+    Ok(revoked)
+  }
+
+  /// 登録済みのパスキーの一覧
+  #[tracing::instrument(name = "service.list_passkeys", skip_all)]
+  pub async fn list_passkeys(&self, user_id: Uuid) -> Result<Vec<PasskeyRecord>, AppError> {
+    let records = self.repository.find_passkeys(user_id).await?;
+    Ok(records)
+  }
+
+  /// パスキーを削除する (自分のパスキーでなければNotFound)
+  #[tracing::instrument(name = "service.delete_passkey", skip_all)]
+  pub async fn delete_passkey(&self, user_id: Uuid, id: Uuid) -> Result<(), AppError> {
+    if !self.repository.delete_passkey(user_id, id).await? {
+      return Err(AppError::NotFound);
+    }
+    Ok(())
+  }
+
+  /// パスキーの登録・認証の途中の状態をDBに保存する (複数のレプリカで完了できるようにする)
+  async fn save_ceremony<T: serde::Serialize>(
+    &self,
+    kind: CeremonyKind,
+    user_id: Uuid,
+    state: &T,
+  ) -> Result<Uuid, AppError> {
+    let state = serde_json::to_value(state).map_err(|e| {
+      tracing::error!("Failed to serialize passkey state: {}", e);
+      AppError::Internal
+    })?;
+    let id = Uuid::new_v4();
+    self
+      .repository
+      .save_passkey_ceremony(PasskeyCeremony {
+        id,
+        kind,
+        user_id,
+        state,
+        expires_at: time::OffsetDateTime::now_utc() + PASSKEY_CEREMONY_TTL,
+      })
+      .await?;
+    Ok(id)
+  }
+
+  /// パスキーの登録・認証の途中の状態を取り出す
+  /// 存在しない・期限切れ・別のユーザーが開始したものは、いずれも同じ400エラーにする
+  /// 戻り値は (ユーザーID, 状態)
+  async fn take_ceremony<T: serde::de::DeserializeOwned>(
+    &self,
+    id: Uuid,
+    kind: CeremonyKind,
+    expected_user: Option<Uuid>,
+  ) -> Result<(Uuid, T), AppError> {
+    let invalid = || AppError::Validation("Passkey challenge expired or not found".to_string());
+    let ceremony = self
+      .repository
+      .take_passkey_ceremony(id, kind)
+      .await?
+      .ok_or_else(invalid)?;
+    if expected_user.is_some_and(|user_id| user_id != ceremony.user_id) {
+      return Err(invalid());
+    }
+    let state = serde_json::from_value(ceremony.state).map_err(|e| {
+      tracing::error!("Failed to deserialize passkey state: {}", e);
+      AppError::Internal
+    })?;
+    Ok((ceremony.user_id, state))
   }
 
   /// 削除する
   /// 削除対象が存在しなかった場合もエラーとみなす設計にする
+  #[tracing::instrument(name = "service.delete", skip_all)]
   pub async fn delete(&self, user_id: Uuid) -> Result<(), AppError> {
-    let count = self.repository.delete(user_id).await?;
+    let photo = self.current_photo(user_id).await?;
+    // 削除しようとしたが無い = NotFound
+    let public_id = self
+      .repository
+      .delete(user_id)
+      .await?
+      .ok_or(AppError::NotFound)?;
+    self.remove_unused_photo(photo).await;
 
-    if count == 0 {
-      // 削除しようとしたが無い = NotFound
-      return Err(AppError::NotFound);
-    }
+    self
+      .events
+      .publish(CalligraphyEvent::Deleted { user_id, public_id });
 
     Ok(())
   }
@@ -124,7 +913,10 @@ impl<R: CalligraphyRepositoryTrait> CalligraphyService<R> {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::models::retention::{AnonymizedMetadata, RequestMetadata};
   use crate::repositories::db_repository::MockCalligraphyRepositoryTrait;
+  use crate::repositories::photo_storage::MockPhotoStorage;
+  use sqlx::types::Json;
   use sqlx::types::ipnetwork::IpNetwork;
   use std::net::IpAddr;
   use time::OffsetDateTime;
@@ -139,9 +931,11 @@ mod tests {
     let ip_address = Some(IpNetwork::from(IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1))));
     let expected_calligraphy = Calligraphy {
       user_id,
+      public_id: Uuid::new_v4(),
       user_name: user_name.clone(),
       content: content.clone(),
-      ip_address: ip_address.clone(),
+      photo: None,
+      ip_address,
       user_agent: None,
       accept_language: None,
       created_at: OffsetDateTime::now_utc(),
@@ -158,12 +952,14 @@ mod tests {
         mockall::predicate::eq(ip_address),
         mockall::predicate::eq(None),
         mockall::predicate::eq(None),
+        mockall::predicate::eq(None),
+        mockall::predicate::eq(None),
       )
       .times(1)
-      .returning(move |_, _, _, _, _, _| Ok(returned_calligraphy.clone()));
+      .returning(move |_, _, _, _, _, _, _, _| Ok(returned_calligraphy.clone()));
     let service = CalligraphyService::new(mock_repo);
     let result = service
-      .upsert(user_id, user_name.clone(), content, ip_address, None, None)
+      .upsert(user_id, user_name.clone(), content, ip_address, None, None, None, None)
       .await;
 
     assert!(result.is_ok());
@@ -171,6 +967,70 @@ mod tests {
     assert_eq!(result.unwrap().content, "Happy New Year");
   }
 
+  /// 書き込み 正規化された値がリポジトリに渡されることのテスト
+  #[tokio::test]
+  async fn test_upsert_normalizes_input() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+
+    mock_repo
+      .expect_create()
+      .with(
+        mockall::predicate::eq(user_id),
+        mockall::predicate::eq("太郎".to_string()),
+        mockall::predicate::eq("一富士\n二鷹".to_string()),
+        mockall::predicate::always(),
+        mockall::predicate::always(),
+        mockall::predicate::always(),
+        mockall::predicate::always(),
+        mockall::predicate::always(),
+      )
+      .times(1)
+      .returning(|uid, uname, c, _, _, _, _, _| {
+        Ok(Calligraphy {
+          user_id: uid,
+          public_id: Uuid::new_v4(),
+          user_name: uname,
+          content: c,
+          photo: None,
+          ip_address: None,
+          user_agent: None,
+          accept_language: None,
+          created_at: OffsetDateTime::now_utc(),
+          updated_at: OffsetDateTime::now_utc(),
+        })
+      });
+
+    let service = CalligraphyService::new(mock_repo);
+    let result = service
+      .upsert(
+        user_id,
+        "　太郎 ".to_string(),
+        "一富士\r\n二鷹\n".to_string(),
+        None,
+        None,
+        None,
+        None,
+        None,
+      )
+      .await;
+
+    assert!(result.is_ok());
+  }
+
+  /// 書き込み 空白のみのユーザー名はバリデーションエラーになることのテスト
+  #[tokio::test]
+  async fn test_upsert_blank_user_name_error() {
+    let mock_repo = MockCalligraphyRepositoryTrait::new();
+    let service = CalligraphyService::new(mock_repo);
+
+    let result = service
+      .upsert(Uuid::new_v4(), " 　".to_string(), "内容".to_string(), None, None, None, None, None)
+      .await;
+
+    assert!(matches!(result, Err(AppError::Validation(_))));
+  }
+
   /// 書き込み バリデーションエラーのテスト
   #[tokio::test]
   async fn test_upsert_validation_error() {
@@ -182,7 +1042,7 @@ mod tests {
     let ip_address = Some(IpNetwork::from(IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1))));
 
     let result = service
-      .upsert(user_id, user_name, long_content, ip_address, None, None)
+      .upsert(user_id, user_name, long_content, ip_address, None, None, None, None)
       .await;
 
     assert!(matches!(result, Err(AppError::Validation(_))));
@@ -199,7 +1059,7 @@ mod tests {
     let ip_address = Some(IpNetwork::from(IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1))));
 
     let result = service
-      .upsert(user_id, long_user_name, content, ip_address, None, None)
+      .upsert(user_id, long_user_name, content, ip_address, None, None, None, None)
       .await;
 
     assert!(matches!(result, Err(AppError::Validation(_))));
@@ -214,8 +1074,10 @@ mod tests {
     let content = "Found".to_string();
     let expected_calligraphy = Calligraphy {
       user_id,
+      public_id: Uuid::new_v4(),
       user_name: user_name.clone(),
       content: content.clone(),
+      photo: None,
       ip_address: Some(IpNetwork::from(IpAddr::V4(std::net::Ipv4Addr::new(
         127, 0, 0, 1,
       )))),
@@ -268,7 +1130,7 @@ mod tests {
       .expect_delete()
       .with(mockall::predicate::eq(user_id))
       .times(1)
-      .returning(|_| Ok(1));
+      .returning(|_| Ok(Some(Uuid::new_v4())));
 
     let service = CalligraphyService::new(mock_repo);
     let result = service.delete(user_id).await;
@@ -286,11 +1148,652 @@ mod tests {
       .expect_delete()
       .with(mockall::predicate::eq(user_id))
       .times(1)
-      .returning(|_| Ok(0));
+      .returning(|_| Ok(None));
 
     let service = CalligraphyService::new(mock_repo);
     let result = service.delete(user_id).await;
 
     assert!(matches!(result, Err(AppError::NotFound)));
   }
+
+  /// 作成・削除でイベントが配信されることのテスト
+  #[tokio::test]
+  async fn test_events_published() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let public_id = Uuid::new_v4();
+
+    mock_repo
+      .expect_create()
+      .times(1)
+      .returning(move |uid, uname, c, _, _, _, _, _| {
+        let now = OffsetDateTime::now_utc();
+        Ok(Calligraphy {
+          user_id: uid,
+          public_id,
+          user_name: uname,
+          content: c,
+          photo: None,
+          ip_address: None,
+          user_agent: None,
+          accept_language: None,
+          created_at: now,
+          updated_at: now,
+        })
+      });
+    mock_repo
+      .expect_delete()
+      .times(1)
+      .returning(move |_| Ok(Some(public_id)));
+
+    let service = CalligraphyService::new(mock_repo);
+    let mut sub = service.subscribe(None);
+
+    service
+      .upsert(user_id, "名前".to_string(), "内容".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+    service.delete(user_id).await.unwrap();
+
+    let created = sub.receiver.recv().await.unwrap();
+    assert!(matches!(*created.event, CalligraphyEvent::Created(ref c) if c.public_id == public_id));
+    let deleted = sub.receiver.recv().await.unwrap();
+    assert!(matches!(
+      *deleted.event,
+      CalligraphyEvent::Deleted { user_id: u, public_id: p } if u == user_id && p == public_id
+    ));
+  }
+
+  fn dummy_photo(hash: char) -> Photo {
+    Photo {
+      key: format!("{}.jpg", hash.to_string().repeat(64)),
+      width: 600,
+      height: 800,
+      thumbnail_key: format!("{}.jpg", hash.to_string().repeat(63) + "0"),
+      thumbnail_width: 360,
+      thumbnail_height: 480,
+    }
+  }
+
+  fn with_photo(user_id: Uuid, photo: Option<Photo>) -> Calligraphy {
+    let now = OffsetDateTime::now_utc();
+    Calligraphy {
+      user_id,
+      public_id: Uuid::new_v4(),
+      user_name: "名前".to_string(),
+      content: "内容".to_string(),
+      photo: photo.map(Json),
+      ip_address: None,
+      user_agent: None,
+      accept_language: None,
+      created_at: now,
+      updated_at: now,
+    }
+  }
+
+  /// 写真なしで上書きすると、参照されなくなった写真のファイルを削除することのテスト
+  #[tokio::test]
+  async fn test_upsert_removes_replaced_photo() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let old = dummy_photo('a');
+
+    let current = with_photo(user_id, Some(old.clone()));
+    mock_repo
+      .expect_find_by_id()
+      .times(1)
+      .returning(move |_| Ok(Some(current.clone())));
+    mock_repo
+      .expect_create()
+      .withf(|_, _, _, _, _, _, _, photo| photo.is_none())
+      .times(1)
+      .returning(move |uid, _, _, _, _, _, _, _| Ok(with_photo(uid, None)));
+    // サムネイルは他の書き初めが同じ内容の写真を使っているため残す
+    let (key, thumbnail_key) = (old.key.clone(), old.thumbnail_key.clone());
+    mock_repo
+      .expect_is_photo_in_use()
+      .returning(move |k| Ok(k == thumbnail_key));
+
+    let mut storage = MockPhotoStorage::new();
+    storage
+      .expect_delete()
+      .withf(move |k| k == key)
+      .times(1)
+      .returning(|_| Ok(()));
+
+    let service = CalligraphyService::new(mock_repo).with_photos(Photos::new(Some(Arc::new(storage))));
+    service
+      .upsert(user_id, "名前".to_string(), "内容".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+  }
+
+  /// 削除すると写真のファイルも削除することのテスト
+  #[tokio::test]
+  async fn test_delete_removes_photo() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+
+    let current = with_photo(user_id, Some(dummy_photo('b')));
+    mock_repo
+      .expect_find_by_id()
+      .times(1)
+      .returning(move |_| Ok(Some(current.clone())));
+    mock_repo
+      .expect_delete()
+      .times(1)
+      .returning(|_| Ok(Some(Uuid::new_v4())));
+    mock_repo.expect_is_photo_in_use().times(2).returning(|_| Ok(false));
+
+    let mut storage = MockPhotoStorage::new();
+    storage.expect_delete().times(2).returning(|_| Ok(()));
+
+    let service = CalligraphyService::new(mock_repo).with_photos(Photos::new(Some(Arc::new(storage))));
+    service.delete(user_id).await.unwrap();
+  }
+
+  /// 引き継ぎコードはハッシュのみを保存し、書き初めがなければ発行しないことのテスト
+  #[tokio::test]
+  async fn test_issue_transfer_code() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let saved = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let saved_hashes = saved.clone();
+    mock_repo
+      .expect_save_transfer_code()
+      .times(2)
+      .returning(move |uid, hash, _| {
+        saved_hashes.lock().unwrap().push(hash);
+        Ok(uid == user_id)
+      });
+
+    let service = CalligraphyService::new(mock_repo);
+    let issued = service.issue_transfer_code(user_id).await.unwrap();
+    assert!(issued.expires_at > OffsetDateTime::now_utc());
+    let normalized = transfer::normalize_code(&issued.code).unwrap();
+    assert_eq!(saved.lock().unwrap()[0], transfer::hash_code(&normalized));
+
+    let result = service.issue_transfer_code(Uuid::new_v4()).await;
+    assert!(matches!(result, Err(AppError::NotFound)));
+  }
+
+  /// 引き継ぐと新しいユーザーIDの書き初めとして返り、変更イベントが配信されることのテスト
+  #[tokio::test]
+  async fn test_claim_transfer_code() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let claimer_id = Uuid::new_v4();
+    let expected_hash = transfer::hash_code("ABCDE1GHJK");
+
+    mock_repo
+      .expect_find_by_id()
+      .with(mockall::predicate::eq(claimer_id))
+      .times(1)
+      .returning(|_| Ok(None));
+    mock_repo
+      .expect_claim_transfer_code()
+      .withf(move |hash, new_user_id| *hash == expected_hash && *new_user_id != claimer_id)
+      .times(1)
+      .returning(|_, new_user_id| Ok(Some(with_photo(new_user_id, None))));
+
+    let service = CalligraphyService::new(mock_repo);
+    let mut sub = service.subscribe(None);
+    // 小文字・区切りなし・読み間違えやすい文字も受け付ける
+    let claimed = service.claim_transfer_code(claimer_id, "abcdeighjk").await.unwrap();
+    assert_ne!(claimed.user_id, claimer_id);
+
+    let event = sub.receiver.recv().await.unwrap();
+    assert!(matches!(&*event.event, CalligraphyEvent::Updated(c) if c.user_id == claimed.user_id));
+  }
+
+  /// 不正・使用済みのコード、書き初めのあるブラウザへの引き継ぎを拒否することのテスト
+  #[tokio::test]
+  async fn test_claim_transfer_code_errors() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let claimer_id = Uuid::new_v4();
+    let owner_id = Uuid::new_v4();
+
+    mock_repo
+      .expect_find_by_id()
+      .returning(move |uid| Ok((uid == owner_id).then(|| with_photo(uid, None))));
+    mock_repo.expect_claim_transfer_code().times(1).returning(|_, _| Ok(None));
+
+    let service = CalligraphyService::new(mock_repo);
+    // 形式が不正なコードはDBに問い合わせない
+    let result = service.claim_transfer_code(claimer_id, "ABCDE").await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+    // 存在しない・期限切れ・使用済み
+    let result = service.claim_transfer_code(claimer_id, "ABCDE-FGHJK").await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+    // 既に書き初めがあるブラウザにはコードを消費せずに拒否する
+    let result = service.claim_transfer_code(owner_id, "ABCDE-FGHJK").await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+  }
+
+  /// 引き継ぎコードの試行回数の制限のテスト
+  #[tokio::test]
+  async fn test_claim_rate_limit() {
+    let service = CalligraphyService::new(MockCalligraphyRepositoryTrait::new());
+    let ip: IpAddr = "192.0.2.1".parse().unwrap();
+    for _ in 0..CLAIM_MAX_ATTEMPTS {
+      service.check_claim_rate_limit(Some(ip)).await.unwrap();
+    }
+    assert!(matches!(
+      service.check_claim_rate_limit(Some(ip)).await,
+      Err(AppError::TooManyRequests)
+    ));
+    // 他のIPアドレスには影響しない
+    service.check_claim_rate_limit(Some("192.0.2.2".parse().unwrap())).await.unwrap();
+  }
+
+  /// パスキーの登録は書き初めがあるユーザーのみ開始でき、開始したユーザーのみ完了できることのテスト
+  #[tokio::test]
+  async fn test_passkey_registration_ceremony() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let owner_id = Uuid::new_v4();
+    let other_id = Uuid::new_v4();
+    let saved = std::sync::Arc::new(std::sync::Mutex::new(None::<PasskeyCeremony>));
+
+    mock_repo
+      .expect_find_by_id()
+      .returning(move |uid| Ok((uid == owner_id).then(|| with_photo(uid, None))));
+    mock_repo.expect_find_passkeys().times(1).returning(|_| Ok(vec![]));
+    let saved_clone = saved.clone();
+    mock_repo
+      .expect_save_passkey_ceremony()
+      .withf(move |ceremony| ceremony.kind == CeremonyKind::Registration && ceremony.user_id == owner_id)
+      .times(1)
+      .returning(move |ceremony| {
+        *saved_clone.lock().unwrap() = Some(ceremony);
+        Ok(())
+      });
+    let saved_clone = saved.clone();
+    mock_repo
+      .expect_take_passkey_ceremony()
+      .times(1)
+      .returning(move |_, _| Ok(saved_clone.lock().unwrap().take()));
+
+    let service =
+      CalligraphyService::new(mock_repo).with_passkeys(Passkeys::from_config(&crate::config::Config::default()));
+    // 書き初めがなければ登録できない
+    let result = service.start_passkey_registration(other_id).await;
+    assert!(matches!(result, Err(AppError::NotFound)));
+
+    let (ceremony_id, _) = service.start_passkey_registration(owner_id).await.unwrap();
+    assert_eq!(saved.lock().unwrap().as_ref().unwrap().id, ceremony_id);
+
+    // 別のユーザーは他人の登録を完了できない (検証の前に拒否する)
+    let credential: RegisterPublicKeyCredential = serde_json::from_value(serde_json::json!({
+      "id": "AA",
+      "rawId": "AA",
+      "response": { "attestationObject": "AA", "clientDataJSON": "AA" },
+      "type": "public-key",
+      "extensions": {}
+    }))
+    .unwrap();
+    let result = service
+      .finish_passkey_registration(other_id, ceremony_id, &credential)
+      .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+  }
+
+  /// パスキーの有無によらず同じ形のログインのオプションを返すことのテスト
+  #[tokio::test]
+  async fn test_passkey_authentication_without_passkeys() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_find_passkeys_by_public_id().returning(|_| Ok(vec![]));
+    mock_repo
+      .expect_save_passkey_ceremony()
+      .withf(|ceremony| ceremony.kind == CeremonyKind::Authentication && ceremony.user_id.is_nil())
+      .times(3)
+      .returning(|_| Ok(()));
+    let service =
+      CalligraphyService::new(mock_repo).with_passkeys(Passkeys::from_config(&crate::config::Config {
+        passkey_decoy_key: Some("decoy".to_string()),
+        ..crate::config::Config::default()
+      }));
+
+    let public_id = Uuid::new_v4();
+    let (first_id, first) = service.start_passkey_authentication(public_id).await.unwrap();
+    let (second_id, second) = service.start_passkey_authentication(public_id).await.unwrap();
+    let (_, other) = service.start_passkey_authentication(Uuid::new_v4()).await.unwrap();
+    let allowed =
+      |options: &RequestChallengeResponse| serde_json::to_value(options).unwrap()["publicKey"]["allowCredentials"].clone();
+
+    assert_ne!(first_id, second_id);
+    // 偽の資格情報IDは1件以上で、同じ公開用IDには同じIDを返す
+    assert!(!allowed(&first).as_array().unwrap().is_empty());
+    assert_eq!(allowed(&first), allowed(&second));
+    assert_ne!(allowed(&first), allowed(&other));
+    assert_eq!(first.public_key.user_verification, second.public_key.user_verification);
+  }
+
+  /// パスキーの設定が無効な場合は受け付けないことのテスト
+  #[tokio::test]
+  async fn test_passkeys_disabled() {
+    let service = CalligraphyService::new(MockCalligraphyRepositoryTrait::new());
+    let result = service.start_passkey_registration(Uuid::new_v4()).await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+    let result = service.start_passkey_authentication(Uuid::new_v4()).await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+  }
+
+  fn session(user_id: Uuid, token: &str, last_seen_at: OffsetDateTime) -> Session {
+    Session {
+      id: Uuid::new_v4(),
+      token_hash: sessions::hash_token(token),
+      user_id,
+      device: None,
+      created_at: last_seen_at,
+      last_seen_at,
+      expires_at: last_seen_at + sessions::SESSION_TTL,
+    }
+  }
+
+  /// セッションはトークンのハッシュで検索され、一定時間ごとに延長されることのテスト
+  #[tokio::test]
+  async fn test_resolve_session() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let recent = session(user_id, "recent", now);
+    let stale = session(user_id, "stale", now - sessions::SESSION_TOUCH_INTERVAL);
+    let stale_id = stale.id;
+
+    mock_repo.expect_find_session().returning(move |token_hash| {
+      Ok([&recent, &stale].into_iter().find(|s| s.token_hash == token_hash).cloned())
+    });
+    // 最近延長したセッションは延長しない
+    mock_repo
+      .expect_touch_session()
+      .withf(move |id, expires_at| *id == stale_id && *expires_at > now + sessions::SESSION_TTL - Duration::from_secs(60))
+      .times(1)
+      .returning(|_, _| Ok(()));
+
+    let service = CalligraphyService::new(mock_repo);
+    assert_eq!(service.resolve_session("recent").await.unwrap(), Some((user_id, false)));
+    assert_eq!(service.resolve_session("stale").await.unwrap(), Some((user_id, true)));
+    assert_eq!(service.resolve_session("unknown").await.unwrap(), None);
+  }
+
+  /// 新しいセッションには端末の種類のみを保存し、切り替え時は元のセッションを削除することのテスト
+  #[tokio::test]
+  async fn test_switch_session() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let user_agent = "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0";
+
+    mock_repo
+      .expect_delete_session_by_token()
+      .with(mockall::predicate::eq(sessions::hash_token("old")))
+      .times(1)
+      .returning(|_| Ok(()));
+    mock_repo
+      .expect_create_session()
+      .withf(move |_, uid, device, _| *uid == user_id && device.as_deref() == Some("Firefox / Linux"))
+      .times(1)
+      .returning(|token_hash, user_id, _, _| {
+        let mut created = session(user_id, "", OffsetDateTime::now_utc());
+        created.token_hash = token_hash;
+        Ok(created)
+      });
+
+    let service = CalligraphyService::new(mock_repo);
+    let token = service.switch_session(Some("old"), user_id, Some(user_agent)).await.unwrap();
+    assert_eq!(token.len(), 64);
+  }
+
+  /// 新しいユーザーのセッションは短い有効期限で発行されることのテスト
+  #[tokio::test]
+  async fn test_start_session() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+
+    mock_repo
+      .expect_create_session()
+      .withf(move |_, uid, _, expires_at| *uid == user_id && *expires_at <= now + sessions::UNUSED_SESSION_TTL + Duration::from_secs(60))
+      .times(1)
+      .returning(|token_hash, user_id, _, expires_at| {
+        let mut created = session(user_id, "", OffsetDateTime::now_utc());
+        created.token_hash = token_hash;
+        created.expires_at = expires_at;
+        Ok(created)
+      });
+    mock_repo.expect_delete_expired_sessions().times(1).returning(|| Ok(3));
+
+    let service = CalligraphyService::new(mock_repo);
+    let token = service.start_session(user_id, None).await.unwrap();
+    assert_eq!(token.len(), 64);
+    assert_eq!(service.purge_expired_sessions().await.unwrap(), 3);
+  }
+
+  /// 以前のCookieは、リポジトリが作成した場合のみ短い有効期間で移行し、移行期限後は移行しないことのテスト
+  #[tokio::test]
+  async fn test_migrate_legacy_user() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let owner = Uuid::new_v4();
+    let stranger = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+
+    mock_repo
+      .expect_create_legacy_session()
+      .withf(move |_, uid, _, expires_at| *uid == owner && *expires_at <= now + sessions::UNUSED_SESSION_TTL + Duration::from_secs(60))
+      .times(1)
+      .returning(|token_hash, user_id, _, expires_at| {
+        let mut created = session(user_id, "", OffsetDateTime::now_utc());
+        created.token_hash = token_hash;
+        created.expires_at = expires_at;
+        Ok(Some(created))
+      });
+    mock_repo
+      .expect_create_legacy_session()
+      .withf(move |_, uid, _, _| *uid == stranger)
+      .times(1)
+      .returning(|_, _, _, _| Ok(None));
+
+    let service = CalligraphyService::new(mock_repo);
+    let token = service.migrate_legacy_user(owner, None).await.unwrap();
+    assert_eq!(token.map(|t| t.len()), Some(64));
+    assert_eq!(service.migrate_legacy_user(stranger, None).await.unwrap(), None);
+
+    // 移行期限を過ぎるとリポジトリを呼ばない
+    let service = service.with_legacy_cookie_sunset(now);
+    assert_eq!(service.migrate_legacy_user(owner, None).await.unwrap(), None);
+  }
+
+  /// 他のユーザーのセッション・存在しない書き初めはNotFoundになることのテスト
+  #[tokio::test]
+  async fn test_revoke_not_found() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_delete_session().times(1).returning(|_, _| Ok(false));
+    mock_repo.expect_revoke_user().times(1).returning(|_, _| Ok(None));
+
+    let service = CalligraphyService::new(mock_repo);
+    let result = service.revoke_session(Uuid::new_v4(), Uuid::new_v4()).await;
+    assert!(matches!(result, Err(AppError::NotFound)));
+    let result = service.revoke_user(Uuid::new_v4()).await;
+    assert!(matches!(result, Err(AppError::NotFound)));
+  }
+
+  /// 一覧はキャッシュされ、書き込み後は再取得されることのテスト
+  #[tokio::test]
+  async fn test_get_all_cached_until_write() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+
+    // 削除前に1回、削除後に1回だけ取得される
+    mock_repo.expect_find_all().times(2).returning(|| Ok(vec![]));
+    mock_repo
+      .expect_delete()
+      .times(1)
+      .returning(|_| Ok(Some(Uuid::new_v4())));
+
+    let service = CalligraphyService::new(mock_repo);
+    service.get_all().await.unwrap();
+    service.get_all().await.unwrap();
+
+    service.delete(user_id).await.unwrap();
+    service.get_all().await.unwrap();
+    service.get_all().await.unwrap();
+  }
+
+  /// フィードは一覧とは別に、更新日時の新しい順の取得結果をキャッシュすることのテスト
+  #[tokio::test]
+  async fn test_get_feed_uses_latest_query() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_find_all().times(0);
+    mock_repo
+      .expect_find_latest()
+      .with(mockall::predicate::eq(FEED_ENTRIES as i64))
+      .times(1)
+      .returning(|_| Ok(vec![]));
+
+    let service = CalligraphyService::new(mock_repo);
+    service.get_feed().await.unwrap();
+    service.get_feed().await.unwrap();
+  }
+
+  /// 公開用IDでの取得は一覧を使わず、変更があるまでキャッシュされることのテスト
+  #[tokio::test]
+  async fn test_get_public_uses_public_id_query() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let public_id = Uuid::new_v4();
+    let missing_id = Uuid::new_v4();
+    mock_repo.expect_find_all().times(0);
+    mock_repo
+      .expect_find_by_public_id()
+      .with(mockall::predicate::eq(public_id))
+      .times(2)
+      .returning(move |_| {
+        let now = OffsetDateTime::now_utc();
+        Ok(Some(Calligraphy {
+          user_id: Uuid::new_v4(),
+          public_id,
+          user_name: "名前".to_string(),
+          content: "謹賀新年".to_string(),
+          photo: None,
+          ip_address: None,
+          user_agent: None,
+          accept_language: None,
+          created_at: now,
+          updated_at: now,
+        }))
+      });
+    mock_repo
+      .expect_find_by_public_id()
+      .with(mockall::predicate::eq(missing_id))
+      .times(1)
+      .returning(|_| Ok(None));
+
+    let service = CalligraphyService::new(mock_repo);
+    assert_eq!(service.get_public(public_id).await.unwrap().public_id, public_id);
+    assert_eq!(service.get_public(public_id).await.unwrap().public_id, public_id);
+    assert!(matches!(service.get_public(missing_id).await, Err(AppError::NotFound)));
+    service.events().publish_remote(CalligraphyEvent::Deleted {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+    });
+    service.get_public(public_id).await.unwrap();
+  }
+
+  /// 他のレプリカからのイベントでもキャッシュが無効になることのテスト
+  #[tokio::test]
+  async fn test_get_all_invalidated_by_remote_event() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_find_all().times(2).returning(|| Ok(vec![]));
+
+    let service = CalligraphyService::new(mock_repo);
+    service.get_all().await.unwrap();
+    service.events().publish_remote(CalligraphyEvent::Deleted {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+    });
+    service.get_all().await.unwrap();
+  }
+
+  /// 集計結果は一覧と同じく、変更があるまで再計算されないことのテスト
+  /// (一覧用のキャッシュは使わず、集計用の全件取得を使う)
+  #[tokio::test]
+  async fn test_stats_cached_until_change() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let now = OffsetDateTime::now_utc();
+    mock_repo.expect_find_for_stats().times(2).returning(move || {
+      Ok(vec![Calligraphy {
+        user_id: Uuid::new_v4(),
+        public_id: Uuid::new_v4(),
+        user_name: "名前".to_string(),
+        content: "謹賀新年".to_string(),
+        photo: None,
+        ip_address: None,
+        user_agent: None,
+        accept_language: None,
+        created_at: now,
+        updated_at: now,
+      }])
+    });
+
+    let service = CalligraphyService::new(mock_repo);
+    let first = service.stats().await.unwrap();
+    let second = service.stats().await.unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(first.total_entries, 1);
+
+    service.events().publish_remote(CalligraphyEvent::Deleted {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+    });
+    let third = service.stats().await.unwrap();
+    assert!(!Arc::ptr_eq(&first, &third));
+  }
+
+  /// 匿名化ジョブ: 対象行を匿名化してからバッチ単位で処理することのテスト
+  #[tokio::test]
+  async fn test_apply_retention() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    let user_id = Uuid::new_v4();
+    let updated_at = OffsetDateTime::now_utc() - time::Duration::days(100);
+
+    mock_repo
+      .expect_find_unanonymized()
+      .withf(|cutoff, limit| {
+        *limit == RETENTION_BATCH_SIZE
+          && *cutoff < OffsetDateTime::now_utc() - time::Duration::days(89)
+      })
+      .times(1)
+      .returning(move |_, _| {
+        Ok(vec![RequestMetadata {
+          user_id,
+          updated_at,
+          user_agent: Some("Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0".to_string()),
+          accept_language: Some("ja,en;q=0.8".to_string()),
+        }])
+      });
+    mock_repo
+      .expect_anonymize_metadata()
+      .withf(move |rows, policy| {
+        rows
+          == &vec![AnonymizedMetadata {
+            user_id,
+            updated_at,
+            user_agent: Some("Firefox".to_string()),
+            accept_language: Some("ja".to_string()),
+          }]
+          && policy.ipv4_prefix == 24
+      })
+      .times(1)
+      .returning(|rows, _| Ok(rows.len() as u64));
+
+    let service = CalligraphyService::new(mock_repo).with_retention_policy(RetentionPolicy::after_days(90));
+    assert_eq!(service.apply_retention().await.unwrap(), 1);
+  }
+
+  /// 匿名化ジョブ: 無効なポリシーではDBに触れないことのテスト
+  #[tokio::test]
+  async fn test_apply_retention_disabled() {
+    let mut mock_repo = MockCalligraphyRepositoryTrait::new();
+    mock_repo.expect_find_unanonymized().times(0);
+
+    let service = CalligraphyService::new(mock_repo);
+    assert_eq!(service.apply_retention().await.unwrap(), 0);
+  }
 }
@@ -0,0 +1,238 @@
+use crate::models::calligraphy::CalligraphyEvent;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// 再接続 (Last-Event-ID) 時に再送できるよう保持しておくイベント数
+const REPLAY_BUFFER_SIZE: usize = 256;
+/// broadcastチャンネルの容量 (これを超えて遅れた購読者はLaggedになる)
+const CHANNEL_CAPACITY: usize = 256;
+
+/// 配信されるイベント
+/// `seq` はこのプロセス内で単調増加する通し番号
+#[derive(Debug, Clone)]
+pub struct PublishedEvent {
+  pub seq: u64,
+  pub event: Arc<CalligraphyEvent>,
+  /// このプロセスで発生したイベントか (falseなら他のレプリカからNOTIFYで受信したもの)
+  pub local: bool,
+}
+
+/// 購読開始時の情報
+pub struct Subscription {
+  /// 再送が必要なイベント (Last-Event-ID より後のもの)
+  pub replay: Vec<PublishedEvent>,
+  /// 再送しきれないため、クライアントに一覧の再取得を促す必要があるか
+  pub reset: bool,
+  /// 以降のイベントの受信口
+  pub receiver: broadcast::Receiver<PublishedEvent>,
+}
+
+/// 書き初めの変更イベントをプロセス内で配信するハブ
+/// Serviceと同様にClone可能で、状態は全てのクローンで共有される
+#[derive(Clone)]
+pub struct EventHub {
+  inner: Arc<Inner>,
+}
+
+struct Inner {
+  /// プロセス (起動) ごとのID
+  /// イベントIDに含め、別プロセスが発行したLast-Event-IDを判別する
+  instance_id: Uuid,
+  sender: broadcast::Sender<PublishedEvent>,
+  /// 通し番号と再送用バッファ
+  /// 採番・バッファへの追加・送信を同じロック内で行い、順序の一貫性を保つ
+  state: Mutex<State>,
+}
+
+struct State {
+  next_seq: u64,
+  buffer: VecDeque<PublishedEvent>,
+}
+
+impl Default for EventHub {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl EventHub {
+  pub fn new() -> Self {
+    let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+    Self {
+      inner: Arc::new(Inner {
+        instance_id: Uuid::new_v4(),
+        sender,
+        state: Mutex::new(State {
+          next_seq: 1,
+          buffer: VecDeque::with_capacity(REPLAY_BUFFER_SIZE),
+        }),
+      }),
+    }
+  }
+
+  /// このプロセスで発生したイベントを配信する
+  pub fn publish(&self, event: CalligraphyEvent) {
+    self.push(event, true);
+  }
+
+  /// 他のレプリカから受信したイベントを配信する (NOTIFYへは再送しない)
+  pub fn publish_remote(&self, event: CalligraphyEvent) {
+    self.push(event, false);
+  }
+
+  fn push(&self, event: CalligraphyEvent, local: bool) {
+    let mut state = self.inner.state.lock().unwrap();
+    let published = PublishedEvent {
+      seq: state.next_seq,
+      event: Arc::new(event),
+      local,
+    };
+    state.next_seq += 1;
+    if state.buffer.len() == REPLAY_BUFFER_SIZE {
+      state.buffer.pop_front();
+    }
+    state.buffer.push_back(published.clone());
+    // 購読者がいない場合はErrになるが、問題ない
+    let _ = self.inner.sender.send(published);
+  }
+
+  /// イベントの購読を開始する
+  ///
+  /// `last_event_id` はSSEの `Last-Event-ID` ヘッダーの値。
+  /// 同じプロセスが発行したIDで、再送用バッファに残っていれば続きから再送する。
+  /// 別プロセスのIDやバッファから溢れたIDの場合は `reset` を立てる。
+  pub fn subscribe(&self, last_event_id: Option<&str>) -> Subscription {
+    let state = self.inner.state.lock().unwrap();
+    let receiver = self.inner.sender.subscribe();
+
+    let Some(last_event_id) = last_event_id else {
+      return Subscription {
+        replay: Vec::new(),
+        reset: false,
+        receiver,
+      };
+    };
+
+    let oldest_seq = state.buffer.front().map_or(state.next_seq, |e| e.seq);
+    match self.parse_event_id(last_event_id) {
+      // 欠落なく再送できる (last_seq の次のイベントがバッファに残っている)
+      Some(last_seq) if last_seq < state.next_seq && last_seq + 1 >= oldest_seq => Subscription {
+        replay: state
+          .buffer
+          .iter()
+          .filter(|e| e.seq > last_seq)
+          .cloned()
+          .collect(),
+        reset: false,
+        receiver,
+      },
+      _ => Subscription {
+        replay: Vec::new(),
+        reset: true,
+        receiver,
+      },
+    }
+  }
+
+  /// 現在の世代番号 (次に採番される通し番号)
+  /// ローカル・リモートを問わずイベントが配信されるたびに進むため、キャッシュのキーに使える
+  pub fn generation(&self) -> u64 {
+    self.inner.state.lock().unwrap().next_seq
+  }
+
+  /// プロセス (起動) ごとのID
+  pub fn instance_id(&self) -> Uuid {
+    self.inner.instance_id
+  }
+
+  /// SSEのイベントID (`{instance_id}-{seq}`) を生成する
+  pub fn event_id(&self, seq: u64) -> String {
+    format!("{}-{}", self.inner.instance_id, seq)
+  }
+
+  /// イベントIDから通し番号を取り出す (別プロセスのIDならNone)
+  fn parse_event_id(&self, id: &str) -> Option<u64> {
+    let (instance, seq) = id.rsplit_once('-')?;
+    if instance != self.inner.instance_id.to_string() {
+      return None;
+    }
+    seq.parse().ok()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn deleted_event() -> CalligraphyEvent {
+    CalligraphyEvent::Deleted {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+    }
+  }
+
+  /// 購読後に配信されたイベントを受信できること
+  #[tokio::test]
+  async fn test_publish_and_receive() {
+    let hub = EventHub::new();
+    let mut sub = hub.subscribe(None);
+    hub.publish(deleted_event());
+
+    let received = sub.receiver.recv().await.unwrap();
+    assert_eq!(received.seq, 1);
+    assert!(received.local);
+    assert!(sub.replay.is_empty());
+    assert!(!sub.reset);
+  }
+
+  /// Last-Event-ID 以降のイベントが再送されること
+  #[test]
+  fn test_resume_from_last_event_id() {
+    let hub = EventHub::new();
+    for _ in 0..3 {
+      hub.publish(deleted_event());
+    }
+
+    let sub = hub.subscribe(Some(&hub.event_id(1)));
+    assert!(!sub.reset);
+    assert_eq!(
+      sub.replay.iter().map(|e| e.seq).collect::<Vec<_>>(),
+      vec![2, 3]
+    );
+
+    // 最新まで受信済みなら再送なし
+    let sub = hub.subscribe(Some(&hub.event_id(3)));
+    assert!(!sub.reset);
+    assert!(sub.replay.is_empty());
+  }
+
+  /// イベントが配信されるたびに世代が進むこと
+  #[test]
+  fn test_generation_advances() {
+    let hub = EventHub::new();
+    let before = hub.generation();
+    hub.publish(deleted_event());
+    hub.publish_remote(deleted_event());
+    assert_eq!(hub.generation(), before + 2);
+  }
+
+  /// 再送できないIDの場合はリセットが必要になること
+  #[test]
+  fn test_resume_requires_reset() {
+    let hub = EventHub::new();
+    for _ in 0..(REPLAY_BUFFER_SIZE + 2) {
+      hub.publish(deleted_event());
+    }
+
+    // バッファから溢れたID
+    assert!(hub.subscribe(Some(&hub.event_id(1))).reset);
+    // 別プロセス (再起動前や別レプリカ) のID
+    assert!(hub.subscribe(Some(&EventHub::new().event_id(3))).reset);
+    // 不正な形式
+    assert!(hub.subscribe(Some("garbage")).reset);
+    // 未来のID
+    assert!(hub.subscribe(Some(&hub.event_id(10_000))).reset);
+  }
+}
@@ -0,0 +1,174 @@
+//! 書き初めごとのOGP画像のキャッシュ
+//!
+//! 画像は (公開用ID, 更新日時) をキーにメモリと (設定されていれば) ディスクにキャッシュする。
+//! 書き初めを更新すると更新日時が変わるため、古い画像は参照されなくなる。
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use moka::future::Cache;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::models::calligraphy::Calligraphy;
+use crate::ogp::OgpRenderer;
+use crate::services::stats::calligraphy_year;
+use crate::tategaki::Card;
+
+/// メモリに保持する画像の合計サイズ (バイト)
+const MEMORY_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// キャッシュのキー (公開用ID, 更新日時のUNIX時間 (ナノ秒))
+type OgpKey = (Uuid, i128);
+
+/// OGP画像の生成とキャッシュ
+#[derive(Clone)]
+pub struct OgpImages {
+  /// フォントを読み込めなかった場合はNone (静的な画像で代替する)
+  renderer: Option<Arc<OgpRenderer>>,
+  memory: Cache<OgpKey, Bytes>,
+  disk_dir: Option<PathBuf>,
+}
+
+impl Default for OgpImages {
+  fn default() -> Self {
+    Self::new(None, None)
+  }
+}
+
+impl OgpImages {
+  pub fn new(renderer: Option<OgpRenderer>, disk_dir: Option<PathBuf>) -> Self {
+    Self {
+      renderer: renderer.map(Arc::new),
+      memory: Cache::builder()
+        .weigher(|_key, png: &Bytes| png.len().try_into().unwrap_or(u32::MAX))
+        .max_capacity(MEMORY_CACHE_BYTES)
+        .build(),
+      disk_dir,
+    }
+  }
+
+  /// 埋め込んだフォントを読み込み、設定からキャッシュ用のディレクトリを作成する
+  /// フォントはリリースビルドでは必ず埋め込まれる (build.rs)。開発用のビルドでフォントがない場合はエラーを記録して続行する
+  pub fn from_config(config: &Config) -> Self {
+    let renderer = OgpRenderer::bundled()
+      .inspect_err(|e| tracing::error!("OGP images disabled: {}", e))
+      .ok();
+    let disk_dir = config.ogp_cache_dir.as_ref().map(PathBuf::from).filter(|dir| {
+      std::fs::create_dir_all(dir)
+        .inspect_err(|e| tracing::warn!("OGP disk cache disabled: {}: {}", dir.display(), e))
+        .is_ok()
+    });
+    Self::new(renderer, disk_dir)
+  }
+
+  /// 画像を生成できるか (フォントを読み込めたか)
+  pub fn is_enabled(&self) -> bool {
+    self.renderer.is_some()
+  }
+
+  /// 書き初めのOGP画像 (PNG) を取得する
+  ///
+  /// メモリ → ディスク → 生成 の順に探す。同じ画像への同時アクセスは1回の生成にまとめられる。
+  pub async fn get_or_render(&self, entry: &Calligraphy) -> Result<Bytes, AppError> {
+    let renderer = self.renderer.clone().ok_or(AppError::NotFound)?;
+    let key = (entry.public_id, entry.updated_at.unix_timestamp_nanos());
+    let disk_path = self
+      .disk_dir
+      .as_ref()
+      .map(|dir| dir.join(format!("{}-{}.png", key.0.simple(), key.1)));
+    let entry = entry.clone();
+
+    self
+      .memory
+      .try_get_with(key, async move {
+        if let Some(png) = read_disk(disk_path.as_deref()).await {
+          return Ok(png);
+        }
+        // 描画はCPUを使うため、非同期ランタイムのスレッドを塞がないようにする
+        let png = tokio::task::spawn_blocking(move || {
+          renderer.render(&Card {
+            content: &entry.content,
+            user_name: &entry.user_name,
+            year: calligraphy_year(entry.created_at),
+          })
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+        let png = Bytes::from(png);
+        write_disk(disk_path.as_deref(), &png).await;
+        Ok::<_, String>(png)
+      })
+      .await
+      .map_err(|e| {
+        tracing::error!("Failed to render OGP image: {}", e);
+        AppError::Internal
+      })
+  }
+}
+
+async fn read_disk(path: Option<&Path>) -> Option<Bytes> {
+  tokio::fs::read(path?).await.ok().map(Bytes::from)
+}
+
+/// ディスクキャッシュへの書き込み (失敗しても画像は返す)
+async fn write_disk(path: Option<&Path>, png: &[u8]) {
+  let Some(path) = path else {
+    return;
+  };
+  // 書き込み途中のファイルを読まないよう、一時ファイルに書いてから移動する
+  let tmp = path.with_extension("png.tmp");
+  let result = async {
+    tokio::fs::write(&tmp, png).await?;
+    tokio::fs::rename(&tmp, path).await
+  }
+  .await;
+  if let Err(e) = result {
+    tracing::warn!("Failed to write OGP cache {}: {}", path.display(), e);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// フォントがなければ無効 (NotFound) になること
+  #[tokio::test]
+  async fn test_disabled_without_font() {
+    let images = OgpImages::default();
+    assert!(!images.is_enabled());
+
+    let now = time::OffsetDateTime::now_utc();
+    let entry = Calligraphy {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+      user_name: "太郎".to_string(),
+      content: "謹賀新年".to_string(),
+      photo: None,
+      ip_address: None,
+      user_agent: None,
+      accept_language: None,
+      created_at: now,
+      updated_at: now,
+    };
+    assert!(matches!(images.get_or_render(&entry).await, Err(AppError::NotFound)));
+  }
+
+  /// ディスクキャッシュの読み書き
+  #[tokio::test]
+  async fn test_disk_cache_roundtrip() {
+    let dir = std::env::temp_dir().join(format!("ogp-cache-test-{}", Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("entry.png");
+
+    assert_eq!(read_disk(Some(&path)).await, None);
+    write_disk(Some(&path), b"png").await;
+    assert_eq!(read_disk(Some(&path)).await, Some(Bytes::from_static(b"png")));
+    assert!(!path.with_extension("png.tmp").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,156 @@
+//! パスキー (WebAuthn) の設定とログインセッションのトークン
+//!
+//! 登録・認証の検証は webauthn-rs が行う。Relying Party (RP) のIDとオリジンはサイトの公開URLから決める。
+//! ログインに成功したブラウザには、ログインした書き初めのユーザーのセッション (`services/sessions.rs`) を発行する。
+//! パスキーが登録されていない書き初めのログインには、偽の資格情報IDを入れた同じ形のオプションを返す (登録の有無を推測させない)。
+
+use std::sync::Arc;
+
+use uuid::Uuid;
+use webauthn_rs::fake::{FakePasskeyDistribution, WebauthnFakeCredentialGenerator};
+use webauthn_rs::prelude::{RequestChallengeResponse, Url};
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+use webauthn_rs_proto::AllowCredentials;
+
+use crate::config::Config;
+use crate::error::AppError;
+
+/// 認証器に表示するサイト名
+const RP_NAME: &str = "書き初め";
+
+/// 偽の資格情報IDの生成 (鍵と公開用IDから決まるため、同じ書き初めには同じIDを返す)
+type DecoyGenerator = WebauthnFakeCredentialGenerator<FakePasskeyDistribution>;
+
+/// パスキーの設定
+#[derive(Clone, Default)]
+pub struct Passkeys {
+  /// RPの設定が不正ならNone (パスキーを受け付けない)
+  webauthn: Option<Arc<Webauthn>>,
+  decoys: Option<Arc<DecoyGenerator>>,
+}
+
+impl Passkeys {
+  /// RPのID (省略時は公開URLのホスト名) と公開URLから設定する
+  /// 設定が不正な場合は警告を出して続行する (パスキー以外の機能には影響させない)
+  pub fn from_config(config: &Config) -> Self {
+    match build(config).and_then(|webauthn| Ok((webauthn, build_decoys(config)?))) {
+      Ok((webauthn, decoys)) => Self {
+        webauthn: Some(Arc::new(webauthn)),
+        decoys: Some(Arc::new(decoys)),
+      },
+      Err(e) => {
+        tracing::warn!("Passkeys disabled: {}", e);
+        Self::default()
+      }
+    }
+  }
+
+  /// パスキーを受け付けるか
+  pub fn is_enabled(&self) -> bool {
+    self.webauthn.is_some()
+  }
+
+  pub fn webauthn(&self) -> Result<&Webauthn, AppError> {
+    self
+      .webauthn
+      .as_deref()
+      .ok_or_else(|| AppError::Validation("Passkeys are not available".to_string()))
+  }
+
+  /// ログインのオプションを、パスキーの有無で区別できない形にする
+  ///
+  /// パスキーがなければ、公開用IDから決まる偽の資格情報IDを `allowCredentials` に入れる。
+  /// 偽のIDには転送方法 (`transports`) の情報がないため、本物のIDからも取り除く。
+  pub fn disguise(&self, public_id: Uuid, options: &mut RequestChallengeResponse) -> Result<(), AppError> {
+    let credentials = &mut options.public_key.allow_credentials;
+    if credentials.is_empty() {
+      *credentials = self
+        .decoy_credential_ids(public_id)?
+        .into_iter()
+        .map(|id| AllowCredentials {
+          type_: "public-key".to_string(),
+          id: id.as_ref().to_vec().into(),
+          transports: None,
+        })
+        .collect();
+    }
+    for credential in credentials.iter_mut() {
+      credential.transports = None;
+    }
+    Ok(())
+  }
+
+  /// 偽の資格情報ID (1件以上)
+  /// 生成器は資格情報が0件の分布も含むため、0件なら入力を変えて生成し直す (本物の書き初めは必ず1件以上)
+  fn decoy_credential_ids(&self, public_id: Uuid) -> Result<Vec<webauthn_rs::prelude::CredentialID>, AppError> {
+    let decoys = self
+      .decoys
+      .as_deref()
+      .ok_or_else(|| AppError::Validation("Passkeys are not available".to_string()))?;
+    let mut input = public_id.as_bytes().to_vec();
+    loop {
+      let ids = decoys.generate(&input).map_err(|e| {
+        tracing::error!("Failed to generate decoy credentials: {:?}", e);
+        AppError::Internal
+      })?;
+      if !ids.is_empty() {
+        return Ok(ids);
+      }
+      input.push(0);
+    }
+  }
+}
+
+fn build(config: &Config) -> Result<Webauthn, String> {
+  let origin = Url::parse(&config.public_base_url).map_err(|e| format!("{}: {}", config.public_base_url, e))?;
+  let rp_id = match &config.webauthn_rp_id {
+    Some(rp_id) => rp_id.clone(),
+    None => origin
+      .host_str()
+      .ok_or_else(|| format!("{}: no host", config.public_base_url))?
+      .to_string(),
+  };
+  WebauthnBuilder::new(&rp_id, &origin)
+    .and_then(|builder| builder.rp_name(RP_NAME).build())
+    .map_err(|e| format!("{} ({}): {}", rp_id, origin, e))
+}
+
+/// 偽の資格情報IDの生成器 (鍵が未設定なら起動ごとに生成する)
+fn build_decoys(config: &Config) -> Result<DecoyGenerator, String> {
+  let key = match &config.passkey_decoy_key {
+    Some(key) => key.as_bytes().to_vec(),
+    None => DecoyGenerator::new_hmac_key().map_err(|e| format!("decoy key: {:?}", e))?,
+  };
+  DecoyGenerator::new(&key).map_err(|e| format!("decoy key: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_config() {
+    let passkeys = Passkeys::from_config(&Config::default());
+    assert!(passkeys.is_enabled());
+
+    // RPのIDが公開URLのホストと一致しない
+    let config = Config {
+      public_base_url: "https://kakizome.example".to_string(),
+      webauthn_rp_id: Some("other.example".to_string()),
+      ..Config::default()
+    };
+    assert!(!Passkeys::from_config(&config).is_enabled());
+    assert!(matches!(
+      Passkeys::from_config(&config).webauthn(),
+      Err(AppError::Validation(_))
+    ));
+
+    // 親ドメインはRPのIDにできる
+    let config = Config {
+      public_base_url: "https://kakizome.example".to_string(),
+      webauthn_rp_id: Some("example".to_string()),
+      ..Config::default()
+    };
+    assert!(Passkeys::from_config(&config).is_enabled());
+  }
+}
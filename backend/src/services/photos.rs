@@ -0,0 +1,227 @@
+//! 書き初めに添付する写真の保存・配信
+//!
+//! アップロードされた写真は `photo::process` で再エンコードし、内容のハッシュをファイル名として保存する。
+//! 同じファイル名の内容は変わらないため、配信時は長期間キャッシュさせ、メモリにもキャッシュする。
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use moka::future::Cache;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::models::photo::Photo;
+use crate::photo::{self, EncodedFormat, EncodedImage};
+use crate::repositories::photo_storage::{LocalPhotoStorage, PhotoStorage};
+
+/// メモリに保持する写真の合計サイズ (バイト)
+const MEMORY_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// 写真の保存・配信
+#[derive(Clone)]
+pub struct Photos {
+  /// 保存先が設定されていなければNone (写真のアップロードを受け付けない)
+  storage: Option<Arc<dyn PhotoStorage>>,
+  memory: Cache<String, Bytes>,
+}
+
+impl Default for Photos {
+  fn default() -> Self {
+    Self::new(None)
+  }
+}
+
+impl Photos {
+  pub fn new(storage: Option<Arc<dyn PhotoStorage>>) -> Self {
+    Self {
+      storage,
+      memory: Cache::builder()
+        .weigher(|_key, bytes: &Bytes| bytes.len().try_into().unwrap_or(u32::MAX))
+        .max_capacity(MEMORY_CACHE_BYTES)
+        .build(),
+    }
+  }
+
+  /// 設定された保存先のディレクトリを作成する
+  /// 作成できない場合は警告を出して続行する (写真以外の機能には影響させない)
+  pub fn from_config(config: &Config) -> Self {
+    let storage = config.photo_dir.as_ref().and_then(|dir| match LocalPhotoStorage::new(dir) {
+      Ok(storage) => Some(Arc::new(storage) as Arc<dyn PhotoStorage>),
+      Err(e) => {
+        tracing::warn!("Photo uploads disabled: {}: {}", dir, e);
+        None
+      }
+    });
+    Self::new(storage)
+  }
+
+  /// 写真を受け付けるか (保存先が設定されているか)
+  pub fn is_enabled(&self) -> bool {
+    self.storage.is_some()
+  }
+
+  fn storage(&self) -> Result<&Arc<dyn PhotoStorage>, AppError> {
+    self
+      .storage
+      .as_ref()
+      .ok_or_else(|| AppError::Validation("Photo uploads are not available".to_string()))
+  }
+
+  /// アップロードされた写真を再エンコードし、写真とサムネイルを保存する
+  pub async fn store(&self, upload: Bytes) -> Result<Photo, AppError> {
+    let storage = self.storage()?;
+    // デコード・縮小はCPUを使うため、非同期ランタイムのスレッドを塞がないようにする
+    let processed = tokio::task::spawn_blocking(move || photo::process(&upload))
+      .await
+      .map_err(|e| {
+        tracing::error!("Photo processing task failed: {}", e);
+        AppError::Internal
+      })??;
+
+    let (key, width, height) = self.put(storage, processed.photo).await?;
+    let (thumbnail_key, thumbnail_width, thumbnail_height) = self.put(storage, processed.thumbnail).await?;
+    Ok(Photo {
+      key,
+      width,
+      height,
+      thumbnail_key,
+      thumbnail_width,
+      thumbnail_height,
+    })
+  }
+
+  /// 画像を内容のハッシュをファイル名として保存する
+  /// 戻り値は (ファイル名, 幅, 高さ)
+  async fn put(&self, storage: &Arc<dyn PhotoStorage>, image: EncodedImage) -> Result<(String, u32, u32), AppError> {
+    let key = content_key(&image.bytes, image.format);
+    let bytes = Bytes::from(image.bytes);
+    storage.put(&key, bytes.clone()).await.map_err(|e| {
+      tracing::error!("Failed to store photo {}: {}", key, e);
+      AppError::Internal
+    })?;
+    self.memory.insert(key.clone(), bytes).await;
+    Ok((key, image.width, image.height))
+  }
+
+  /// 写真を取得する
+  /// 戻り値は (内容, 形式)。ファイル名が不正・存在しない場合はNotFound
+  pub async fn get(&self, key: &str) -> Result<(Bytes, EncodedFormat), AppError> {
+    let format = parse_key(key).ok_or(AppError::NotFound)?;
+    let storage = self.storage.clone().ok_or(AppError::NotFound)?;
+    let owned_key = key.to_string();
+    let bytes = self
+      .memory
+      .try_get_with(key.to_string(), async move {
+        storage.get(&owned_key).await?.ok_or_else(|| std::io::ErrorKind::NotFound.into())
+      })
+      .await
+      .map_err(|e: Arc<std::io::Error>| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+          AppError::NotFound
+        } else {
+          tracing::error!("Failed to read photo {}: {}", key, e);
+          AppError::Internal
+        }
+      })?;
+    Ok((bytes, format))
+  }
+
+  /// 写真を削除する (失敗しても書き初めの操作は成功させるため、ログのみ残す)
+  pub async fn delete(&self, key: &str) {
+    let Some(storage) = &self.storage else {
+      return;
+    };
+    self.memory.invalidate(key).await;
+    if let Err(e) = storage.delete(key).await {
+      tracing::warn!("Failed to delete photo {}: {}", key, e);
+    }
+  }
+}
+
+/// 内容のハッシュ (SHA-256の16進数) と拡張子からなるファイル名
+fn content_key(bytes: &[u8], format: EncodedFormat) -> String {
+  let digest = Sha256::digest(bytes);
+  let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+  format!("{}.{}", hex, format.extension())
+}
+
+/// ファイル名を検証し、形式を求める
+pub fn parse_key(key: &str) -> Option<EncodedFormat> {
+  let (hash, extension) = key.split_once('.')?;
+  let is_hash = hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+  if !is_hash {
+    return None;
+  }
+  EncodedFormat::from_extension(extension)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::repositories::photo_storage::MockPhotoStorage;
+  use image::{DynamicImage, ImageFormat, RgbImage};
+
+  fn png(width: u32, height: u32) -> Bytes {
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+      .write_to(&mut std::io::Cursor::new(&mut bytes), ImageFormat::Png)
+      .unwrap();
+    Bytes::from(bytes)
+  }
+
+  #[test]
+  fn test_parse_key() {
+    let key = content_key(b"jpeg", EncodedFormat::Jpeg);
+    assert_eq!(key.len(), 64 + 4);
+    assert_eq!(parse_key(&key), Some(EncodedFormat::Jpeg));
+    assert_eq!(parse_key(&format!("{}.webp", "0".repeat(64))), Some(EncodedFormat::WebP));
+    assert_eq!(parse_key(&format!("{}.png", "0".repeat(64))), None);
+    assert_eq!(parse_key(&format!("{}.jpg", "A".repeat(64))), None);
+    assert_eq!(parse_key("../secret.jpg"), None);
+    assert_eq!(parse_key("abc.jpg"), None);
+  }
+
+  /// 写真とサムネイルを内容のハッシュで保存し、保存した内容を取得できること
+  #[tokio::test]
+  async fn test_store_and_get() {
+    let mut storage = MockPhotoStorage::new();
+    storage
+      .expect_put()
+      .withf(|key, _| parse_key(key) == Some(EncodedFormat::Jpeg))
+      .times(2)
+      .returning(|_, _| Ok(()));
+    // 保存直後はメモリから返すため、保存先からは読まない
+    storage.expect_get().times(0);
+    let photos = Photos::new(Some(Arc::new(storage)));
+
+    let photo = photos.store(png(600, 300)).await.unwrap();
+    assert_eq!((photo.width, photo.height), (600, 300));
+    assert_eq!((photo.thumbnail_width, photo.thumbnail_height), (480, 240));
+    assert_ne!(photo.key, photo.thumbnail_key);
+
+    let (bytes, format) = photos.get(&photo.key).await.unwrap();
+    assert_eq!(format, EncodedFormat::Jpeg);
+    assert_eq!(content_key(&bytes, format), photo.key);
+  }
+
+  #[tokio::test]
+  async fn test_get_not_found() {
+    let mut storage = MockPhotoStorage::new();
+    storage.expect_get().times(1).returning(|_| Ok(None));
+    let photos = Photos::new(Some(Arc::new(storage)));
+
+    let key = content_key(b"missing", EncodedFormat::Jpeg);
+    assert!(matches!(photos.get(&key).await, Err(AppError::NotFound)));
+    // 不正なファイル名は保存先に問い合わせない
+    assert!(matches!(photos.get("../a.jpg").await, Err(AppError::NotFound)));
+  }
+
+  /// 保存先がなければアップロードを受け付けないこと
+  #[tokio::test]
+  async fn test_disabled() {
+    let photos = Photos::default();
+    assert!(!photos.is_enabled());
+    assert!(matches!(photos.store(png(10, 10)).await, Err(AppError::Validation(_))));
+  }
+}
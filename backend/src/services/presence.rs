@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// 「入力中」表示を維持する時間 (クライアントはこれより短い間隔で送り直す)
+pub const TYPING_TTL: Duration = Duration::from_secs(5);
+
+/// ボードの在室状況
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PresenceSnapshot {
+  /// 接続中のユーザー数 (同じユーザーの複数タブは1人と数える)
+  pub online: usize,
+  /// 入力中のユーザー数
+  pub typing: usize,
+}
+
+/// WebSocket接続中のユーザーを管理するハブ
+/// 人数が変わるたびに `watch` チャンネルで最新の状況を通知する
+/// (個々のユーザーIDは外部に出さず、人数のみを配信する)
+#[derive(Clone)]
+pub struct PresenceHub {
+  inner: Arc<Inner>,
+}
+
+struct Inner {
+  state: Mutex<State>,
+  sender: watch::Sender<PresenceSnapshot>,
+}
+
+#[derive(Default)]
+struct State {
+  /// ユーザーIDごとの接続数
+  connections: HashMap<Uuid, usize>,
+  /// ユーザーIDごとの「入力中」の期限
+  typing: HashMap<Uuid, Instant>,
+}
+
+impl State {
+  fn snapshot(&self) -> PresenceSnapshot {
+    PresenceSnapshot {
+      online: self.connections.len(),
+      typing: self.typing.len(),
+    }
+  }
+}
+
+/// 接続中であることを表すガード
+/// Dropされると (接続が切れると) 在室人数から外れる
+pub struct PresenceGuard {
+  hub: PresenceHub,
+  user_id: Uuid,
+}
+
+impl Drop for PresenceGuard {
+  fn drop(&mut self) {
+    self.hub.leave(self.user_id);
+  }
+}
+
+impl Default for PresenceHub {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl PresenceHub {
+  pub fn new() -> Self {
+    let (sender, _) = watch::channel(PresenceSnapshot::default());
+    Self {
+      inner: Arc::new(Inner {
+        state: Mutex::new(State::default()),
+        sender,
+      }),
+    }
+  }
+
+  /// 在室状況の変化を購読する
+  pub fn subscribe(&self) -> watch::Receiver<PresenceSnapshot> {
+    self.inner.sender.subscribe()
+  }
+
+  /// 現在の在室状況
+  pub fn snapshot(&self) -> PresenceSnapshot {
+    *self.inner.sender.borrow()
+  }
+
+  /// 接続を登録する
+  pub fn join(&self, user_id: Uuid) -> PresenceGuard {
+    self.update(|state| {
+      *state.connections.entry(user_id).or_insert(0) += 1;
+    });
+    PresenceGuard {
+      hub: self.clone(),
+      user_id,
+    }
+  }
+
+  fn leave(&self, user_id: Uuid) {
+    self.update(|state| {
+      if let Some(count) = state.connections.get_mut(&user_id) {
+        *count -= 1;
+        if *count == 0 {
+          state.connections.remove(&user_id);
+          state.typing.remove(&user_id);
+        }
+      }
+    });
+  }
+
+  /// 「入力中」状態を更新する
+  pub fn set_typing(&self, user_id: Uuid, active: bool) {
+    self.update(|state| {
+      if active {
+        state.typing.insert(user_id, Instant::now() + TYPING_TTL);
+      } else {
+        state.typing.remove(&user_id);
+      }
+    });
+  }
+
+  /// 期限切れの「入力中」状態を取り除く
+  /// 各接続のハートビートから定期的に呼ばれる
+  pub fn sweep(&self) {
+    let now = Instant::now();
+    self.update(|state| state.typing.retain(|_, expires_at| *expires_at > now));
+  }
+
+  /// 状態を更新し、人数が変わった場合のみ通知する
+  fn update(&self, f: impl FnOnce(&mut State)) {
+    let mut state = self.inner.state.lock().unwrap();
+    f(&mut state);
+    let snapshot = state.snapshot();
+    self.inner.sender.send_if_modified(|current| {
+      if *current == snapshot {
+        return false;
+      }
+      *current = snapshot;
+      true
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 同じユーザーの複数接続は1人と数え、全て切断されると0人になること
+  #[test]
+  fn test_online_count_per_user() {
+    let hub = PresenceHub::new();
+    let alice = Uuid::new_v4();
+    let bob = Uuid::new_v4();
+
+    let a1 = hub.join(alice);
+    let a2 = hub.join(alice);
+    let b1 = hub.join(bob);
+    assert_eq!(hub.snapshot().online, 2);
+
+    drop(a1);
+    assert_eq!(hub.snapshot().online, 2);
+    drop(a2);
+    assert_eq!(hub.snapshot().online, 1);
+    drop(b1);
+    assert_eq!(hub.snapshot().online, 0);
+  }
+
+  /// 入力中の状態は切断・解除で消えること
+  #[test]
+  fn test_typing() {
+    let hub = PresenceHub::new();
+    let alice = Uuid::new_v4();
+    let guard = hub.join(alice);
+
+    hub.set_typing(alice, true);
+    assert_eq!(hub.snapshot().typing, 1);
+    hub.set_typing(alice, false);
+    assert_eq!(hub.snapshot().typing, 0);
+
+    hub.set_typing(alice, true);
+    drop(guard);
+    assert_eq!(hub.snapshot(), PresenceSnapshot::default());
+  }
+
+  /// 人数が変わったときだけ通知されること
+  #[tokio::test]
+  async fn test_notifies_only_on_change() {
+    let hub = PresenceHub::new();
+    let mut rx = hub.subscribe();
+    let user = Uuid::new_v4();
+
+    let _g1 = hub.join(user);
+    assert!(rx.has_changed().unwrap());
+    rx.borrow_and_update();
+
+    let _g2 = hub.join(user); // 同じユーザーなので人数は変わらない
+    assert!(!rx.has_changed().unwrap());
+  }
+}
@@ -0,0 +1,150 @@
+//! 収集したリクエスト情報 (IPアドレス・User-Agent・Accept-Language) の匿名化
+//!
+//! 最終更新から一定期間が経過した行について、
+//! - IPアドレス: ネットワーク部のみに切り詰める (IPv4は/24, IPv6は/48, DB側で処理)
+//! - User-Agent: ブラウザの種類のみにする (例: "Chrome")
+//! - Accept-Language: 先頭の言語の主言語タグのみにする (例: "ja")
+//!
+//! 書き初めを更新すると、新しいリクエスト情報で上書きされ再び匿名化の対象になる。
+
+use std::time::Duration;
+
+use crate::models::retention::{AnonymizedMetadata, RequestMetadata};
+use crate::repositories::db_repository::CalligraphyRepositoryTrait;
+use crate::services::calligraphy::CalligraphyService;
+
+/// User-Agentに含まれる文字列とブラウザの種類の対応
+///
+/// 多くのブラウザが他のブラウザの名前を含めるため (EdgeやOperaは "Chrome/" を含む)、
+/// 判定順序に意味がある。
+const BROWSER_FAMILIES: [(&str, &str); 9] = [
+  ("Edg", "Edge"),
+  ("OPR/", "Opera"),
+  ("Opera", "Opera"),
+  ("SamsungBrowser/", "Samsung Internet"),
+  ("Firefox/", "Firefox"),
+  ("FxiOS/", "Firefox"),
+  ("CriOS/", "Chrome"),
+  ("Chrome/", "Chrome"),
+  ("Safari/", "Safari"),
+];
+
+/// User-Agentをブラウザの種類に縮約する
+pub fn browser_family(user_agent: &str) -> &'static str {
+  if user_agent.to_ascii_lowercase().contains("bot") {
+    return "Bot";
+  }
+  BROWSER_FAMILIES
+    .iter()
+    .find(|(token, _)| user_agent.contains(token))
+    .map_or("Other", |&(_, family)| family)
+}
+
+/// 匿名化済みの値 (`browser_family` の戻り値) か
+pub fn is_browser_family(value: &str) -> bool {
+  value == "Bot" || value == "Other" || BROWSER_FAMILIES.iter().any(|&(_, family)| family == value)
+}
+
+/// Accept-Languageを先頭の言語の主言語タグに縮約する
+/// 例: "ja,en-US;q=0.9" -> "ja", "en-GB" -> "en"
+pub fn primary_language(accept_language: &str) -> Option<String> {
+  let first = accept_language.split(',').next()?;
+  let tag = first.split(';').next()?.trim();
+  let primary = tag.split('-').next()?;
+  if (2..=8).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic()) {
+    Some(primary.to_ascii_lowercase())
+  } else {
+    None
+  }
+}
+
+/// 1行分のリクエスト情報を匿名化する
+pub fn anonymize(row: RequestMetadata) -> AnonymizedMetadata {
+  AnonymizedMetadata {
+    user_id: row.user_id,
+    updated_at: row.updated_at,
+    user_agent: row.user_agent.as_deref().map(|ua| browser_family(ua).to_string()),
+    accept_language: row.accept_language.as_deref().and_then(primary_language),
+  }
+}
+
+/// 匿名化ジョブを定期的に実行する (バックグラウンドタスク用)
+/// 期限切れのセッションの削除も合わせて行う (匿名化が無効な場合もセッションは削除する)
+pub async fn run_periodically<R: CalligraphyRepositoryTrait>(
+  service: CalligraphyService<R>,
+  interval: Duration,
+) {
+  let policy = service.retention_policy();
+  if policy.enabled {
+    tracing::info!(
+      "Retention job started: anonymizing request metadata older than {} days every {:?}",
+      policy.anonymize_after_days,
+      interval
+    );
+  } else {
+    tracing::info!("Retention job started: deleting expired sessions every {:?}", interval);
+  }
+  let mut ticker = tokio::time::interval(interval);
+  ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+  loop {
+    ticker.tick().await;
+    // 失敗しても次回に再試行する
+    if let Err(e) = service.apply_retention().await {
+      tracing::error!("Retention job failed: {:?}", e);
+    }
+    if let Err(e) = service.purge_expired_sessions().await {
+      tracing::error!("Expired session cleanup failed: {:?}", e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::OffsetDateTime;
+  use uuid::Uuid;
+
+  /// 主要ブラウザのUser-Agentがブラウザの種類に縮約されること
+  #[test]
+  fn test_browser_family() {
+    let cases = [
+      ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36", "Chrome"),
+      ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36 Edg/120.0.0.0", "Edge"),
+      ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Safari/605.1.15", "Safari"),
+      ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) CriOS/120.0.6099.119 Mobile/15E148 Safari/604.1", "Chrome"),
+      ("Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0", "Firefox"),
+      ("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36 OPR/106.0.0.0", "Opera"),
+      ("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", "Bot"),
+      ("curl/8.5.0", "Other"),
+    ];
+    for (ua, expected) in cases {
+      assert_eq!(browser_family(ua), expected, "{}", ua);
+    }
+  }
+
+  /// Accept-Languageが主言語タグに縮約されること
+  #[test]
+  fn test_primary_language() {
+    assert_eq!(primary_language("ja,en-US;q=0.9,en;q=0.8"), Some("ja".to_string()));
+    assert_eq!(primary_language("en-GB"), Some("en".to_string()));
+    assert_eq!(primary_language(" ZH-Hant-TW ;q=1"), Some("zh".to_string()));
+    assert_eq!(primary_language("*"), None);
+    assert_eq!(primary_language(""), None);
+  }
+
+  /// 匿名化で行の識別情報 (user_id, updated_at) は保たれること
+  #[test]
+  fn test_anonymize() {
+    let row = RequestMetadata {
+      user_id: Uuid::new_v4(),
+      updated_at: OffsetDateTime::now_utc(),
+      user_agent: Some("Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0".to_string()),
+      accept_language: None,
+    };
+    let anonymized = anonymize(row.clone());
+    assert_eq!(anonymized.user_id, row.user_id);
+    assert_eq!(anonymized.updated_at, row.updated_at);
+    assert_eq!(anonymized.user_agent.as_deref(), Some("Firefox"));
+    assert_eq!(anonymized.accept_language, None);
+  }
+}
@@ -0,0 +1,93 @@
+//! ブラウザのセッション
+//!
+//! Cookie (`calli_session`) にはランダムなトークンを入れ、DBにはトークンのハッシュのみを保存する。
+//! セッションは使われるたびに有効期限を延長し (スライディング方式)、最後に使われた日時を記録する。
+//! 毎リクエストでDBに書き込まないよう、延長は前回から一定時間が経った場合のみ行う。
+//! Cookieを保存しないクライアントのセッションが残り続けないよう、発行直後の有効期間は短くし、
+//! 同じセッションで次のリクエストが来た時点で通常の有効期間に延長する。
+//! 以前のユーザーIDのCookie (`calli_user_id`) は、書き初めを持ちセッションがまだないユーザーのみ、
+//! 移行期限までに一度だけセッションへ移行できる。
+
+use sha2::{Digest, Sha256};
+use time::macros::datetime;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::models::session::Session;
+use crate::services::{retention, stats};
+
+/// 最後に使われてからセッションが失効するまでの期間
+pub const SESSION_TTL: Duration = Duration::days(365);
+/// 有効期限の延長 (と最後に使われた日時の記録) の間隔
+pub const SESSION_TOUCH_INTERVAL: Duration = Duration::minutes(5);
+/// 発行してから一度も使われていないセッションの有効期間
+pub const UNUSED_SESSION_TTL: Duration = Duration::days(1);
+/// 以前のユーザーIDのCookieをセッションへ移行する期限のデフォルト値 (以降はCookieを無視する)
+pub const LEGACY_COOKIE_SUNSET: OffsetDateTime = datetime!(2027-04-01 00:00 +09:00);
+
+/// 新しいセッショントークンを生成する (UUID v4 2つ分の乱数を16進数で表す)
+pub fn generate_token() -> String {
+  format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// DBに保存するセッショントークンのハッシュ (SHA-256)
+pub fn hash_token(token: &str) -> Vec<u8> {
+  Sha256::digest(token.as_bytes()).to_vec()
+}
+
+/// セッションの一覧に表示する端末の種類 (User-Agentそのものは保存しない)
+pub fn device_label(user_agent: &str) -> String {
+  format!("{} / {}", retention::browser_family(user_agent), stats::os_family(user_agent))
+}
+
+/// 有効期限を延長する時期か
+/// (発行後に初めて使われたセッションは、有効期限が短いため必ず延長する)
+pub fn needs_touch(session: &Session, now: OffsetDateTime) -> bool {
+  now - session.last_seen_at >= SESSION_TOUCH_INTERVAL || session.expires_at - now <= UNUSED_SESSION_TTL
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_token() {
+    let token = generate_token();
+    assert_eq!(token.len(), 64);
+    assert_ne!(generate_token(), token);
+    assert_eq!(hash_token(&token), hash_token(&token));
+    assert_eq!(hash_token(&token).len(), 32);
+  }
+
+  #[test]
+  fn test_device_label() {
+    let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0";
+    assert_eq!(device_label(firefox), "Firefox / Linux");
+    let safari = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Mobile/15E148 Safari/604.1";
+    assert_eq!(device_label(safari), "Safari / iOS");
+  }
+
+  #[test]
+  fn test_needs_touch() {
+    let now = OffsetDateTime::now_utc();
+    let session = Session {
+      id: Uuid::new_v4(),
+      token_hash: hash_token("token"),
+      user_id: Uuid::new_v4(),
+      device: None,
+      created_at: now,
+      last_seen_at: now - Duration::minutes(1),
+      expires_at: now + SESSION_TTL,
+    };
+    assert!(!needs_touch(&session, now));
+    assert!(needs_touch(&session, now + SESSION_TOUCH_INTERVAL));
+
+    // 発行直後のセッションは次に使われた時に延長する
+    let unused = Session {
+      last_seen_at: now,
+      expires_at: now + UNUSED_SESSION_TTL,
+      ..session
+    };
+    assert!(needs_touch(&unused, now + Duration::seconds(1)));
+  }
+}
@@ -0,0 +1,318 @@
+//! ボード全体の集計 (`GET /api/stats`)
+//!
+//! 集計はサーバー側で行い、個々の行のリクエスト情報は返さない。
+//! 件数が少ない区分 (言語・ブラウザ・OS) は、少数の利用者の特定につながらないよう "Other" にまとめる。
+
+use std::collections::HashMap;
+
+use time::macros::offset;
+use time::{Date, Duration, Month, OffsetDateTime, UtcOffset};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::models::calligraphy::Calligraphy;
+use crate::models::stats::{BoardStats, CharacterCount, DailyCount, LabelCount, NewYearActivity};
+use crate::services::retention::{browser_family, is_browser_family, primary_language};
+
+/// 日別の集計に使うタイムゾーン (日本時間)
+const JST: UtcOffset = offset!(+9);
+/// 年末年始の集計期間 (元日の前後の日数)
+const NEW_YEAR_DAYS_BEFORE: i64 = 7;
+const NEW_YEAR_DAYS_AFTER: i64 = 6;
+/// よく使われている文字として返す件数
+const TOP_CHARACTERS: usize = 20;
+/// これより件数が少ない区分は "Other" にまとめる
+const MIN_GROUP_SIZE: u64 = 3;
+
+const OTHER: &str = "Other";
+const UNKNOWN: &str = "Unknown";
+
+/// 書き初めの一覧から集計結果を作る
+pub fn compute(entries: &[Calligraphy], now: OffsetDateTime) -> BoardStats {
+  let contents = || entries.iter().map(|e| e.content.as_str());
+  let user_agents = || entries.iter().map(|e| e.user_agent.as_deref());
+
+  BoardStats {
+    total_entries: entries.len() as u64,
+    new_year: new_year_activity(entries, now.to_offset(JST).date()),
+    top_characters: top_characters(contents(), |_| true),
+    top_kanji: top_characters(contents(), is_kanji),
+    languages: breakdown(entries.iter().map(|e| {
+      e.accept_language
+        .as_deref()
+        .and_then(primary_language)
+        .unwrap_or_else(|| UNKNOWN.to_string())
+    })),
+    browsers: breakdown(user_agents().map(|ua| match ua {
+      // 匿名化済みの行はブラウザの種類のみが残っている
+      Some(ua) if is_browser_family(ua) => ua.to_string(),
+      Some(ua) => browser_family(ua).to_string(),
+      None => UNKNOWN.to_string(),
+    })),
+    operating_systems: breakdown(user_agents().map(|ua| match ua {
+      Some(ua) if !is_browser_family(ua) => os_family(ua).to_string(),
+      _ => UNKNOWN.to_string(),
+    })),
+  }
+}
+
+/// User-AgentをOSの種類に縮約する
+///
+/// iOSは "like Mac OS X"、Androidは "Linux" を含むため、判定順序に意味がある。
+pub fn os_family(user_agent: &str) -> &'static str {
+  const FAMILIES: [(&str, &str); 8] = [
+    ("Windows", "Windows"),
+    ("CrOS", "ChromeOS"),
+    ("Android", "Android"),
+    ("iPhone", "iOS"),
+    ("iPad", "iOS"),
+    ("iPod", "iOS"),
+    ("Mac OS X", "macOS"),
+    ("Linux", "Linux"),
+  ];
+  FAMILIES
+    .iter()
+    .find(|(token, _)| user_agent.contains(token))
+    .map_or(OTHER, |&(_, family)| family)
+}
+
+/// 集計対象の年末年始の期間 (前後の日を含む)
+/// 7月以降は次の正月、6月までは直前の正月を対象にする
+pub fn new_year_window(today: Date) -> (Date, Date) {
+  let year = if today.month() >= Month::July {
+    today.year() + 1
+  } else {
+    today.year()
+  };
+  let new_year = Date::from_calendar_date(year, Month::January, 1).expect("January 1st is a valid date");
+  (
+    new_year - Duration::days(NEW_YEAR_DAYS_BEFORE),
+    new_year + Duration::days(NEW_YEAR_DAYS_AFTER),
+  )
+}
+
+/// 書き初めの年 (日本時間で7月以降の投稿は翌年の書き初めとみなす)
+pub fn calligraphy_year(created_at: OffsetDateTime) -> i32 {
+  new_year_window(created_at.to_offset(JST).date()).1.year()
+}
+
+/// 年末年始の日別の投稿数 (作成日時を日本時間の日付で集計する)
+fn new_year_activity(entries: &[Calligraphy], today: Date) -> NewYearActivity {
+  let (from, to) = new_year_window(today);
+  let mut counts: HashMap<Date, u64> = HashMap::new();
+  for entry in entries {
+    let date = entry.created_at.to_offset(JST).date();
+    if (from..=to).contains(&date) {
+      *counts.entry(date).or_default() += 1;
+    }
+  }
+
+  let mut daily = Vec::new();
+  let mut date = from;
+  while date <= to {
+    daily.push(DailyCount {
+      date,
+      count: counts.get(&date).copied().unwrap_or(0),
+    });
+    date = date.next_day().expect("date within the supported range");
+  }
+  NewYearActivity { from, to, daily }
+}
+
+/// 内容に多く使われている文字 (書記素クラスタ単位)
+/// 空白・記号・絵文字は除き、`filter` を満たす文字のみを数える
+fn top_characters<'a>(contents: impl Iterator<Item = &'a str>, filter: fn(char) -> bool) -> Vec<CharacterCount> {
+  let mut counts: HashMap<&str, u64> = HashMap::new();
+  for content in contents {
+    for grapheme in content.graphemes(true) {
+      let Some(first) = grapheme.chars().next() else {
+        continue;
+      };
+      if first.is_alphanumeric() && filter(first) {
+        *counts.entry(grapheme).or_default() += 1;
+      }
+    }
+  }
+
+  let mut top: Vec<CharacterCount> = counts
+    .into_iter()
+    .map(|(character, count)| CharacterCount {
+      character: character.to_string(),
+      count,
+    })
+    .collect();
+  top.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.character.cmp(&b.character)));
+  top.truncate(TOP_CHARACTERS);
+  top
+}
+
+/// CJK統合漢字 (拡張A・互換漢字・拡張B以降を含む)
+fn is_kanji(c: char) -> bool {
+  matches!(
+    c,
+    '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{3FFFF}'
+  )
+}
+
+/// 区分ごとの件数 (件数の多い順, "Other" は末尾)
+/// 件数が `MIN_GROUP_SIZE` 未満の区分は "Other" にまとめる
+fn breakdown(labels: impl Iterator<Item = String>) -> Vec<LabelCount> {
+  let mut counts: HashMap<String, u64> = HashMap::new();
+  for label in labels {
+    *counts.entry(label).or_default() += 1;
+  }
+
+  let mut other = 0;
+  let mut groups: Vec<LabelCount> = Vec::new();
+  for (label, count) in counts {
+    if label == OTHER || count < MIN_GROUP_SIZE {
+      other += count;
+    } else {
+      groups.push(LabelCount { label, count });
+    }
+  }
+  groups.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+  if other > 0 {
+    groups.push(LabelCount {
+      label: OTHER.to_string(),
+      count: other,
+    });
+  }
+  groups
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::macros::{date, datetime};
+  use uuid::Uuid;
+
+  const CHROME_WINDOWS: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+  const SAFARI_IOS: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Mobile/15E148 Safari/604.1";
+  const FIREFOX_LINUX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0";
+
+  fn entry(content: &str, created_at: OffsetDateTime, user_agent: Option<&str>, accept_language: Option<&str>) -> Calligraphy {
+    Calligraphy {
+      user_id: Uuid::new_v4(),
+      public_id: Uuid::new_v4(),
+      user_name: "太郎".to_string(),
+      content: content.to_string(),
+      photo: None,
+      ip_address: None,
+      user_agent: user_agent.map(str::to_string),
+      accept_language: accept_language.map(str::to_string),
+      created_at,
+      updated_at: created_at,
+    }
+  }
+
+  fn label(label: &str, count: u64) -> LabelCount {
+    LabelCount {
+      label: label.to_string(),
+      count,
+    }
+  }
+
+  /// 主要なOSのUser-AgentがOSの種類に縮約されること
+  #[test]
+  fn test_os_family() {
+    let cases = [
+      (CHROME_WINDOWS, "Windows"),
+      (SAFARI_IOS, "iOS"),
+      ("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Safari/605.1.15", "macOS"),
+      ("Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36", "Android"),
+      ("Mozilla/5.0 (X11; CrOS x86_64 14541.0.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36", "ChromeOS"),
+      (FIREFOX_LINUX, "Linux"),
+      ("curl/8.5.0", "Other"),
+    ];
+    for (ua, expected) in cases {
+      assert_eq!(os_family(ua), expected, "{}", ua);
+    }
+  }
+
+  /// 年の後半は次の正月、前半は直前の正月が対象になること
+  #[test]
+  fn test_new_year_window() {
+    assert_eq!(new_year_window(date!(2025 - 12 - 20)), (date!(2025 - 12 - 25), date!(2026 - 01 - 07)));
+    assert_eq!(new_year_window(date!(2026 - 01 - 02)), (date!(2025 - 12 - 25), date!(2026 - 01 - 07)));
+    assert_eq!(new_year_window(date!(2026 - 06 - 30)), (date!(2025 - 12 - 25), date!(2026 - 01 - 07)));
+    assert_eq!(new_year_window(date!(2026 - 07 - 01)), (date!(2026 - 12 - 25), date!(2027 - 01 - 07)));
+  }
+
+  /// 年の変わり目は日本時間で判定すること
+  #[test]
+  fn test_calligraphy_year() {
+    assert_eq!(calligraphy_year(datetime!(2025-12-31 14:59 UTC)), 2026);
+    assert_eq!(calligraphy_year(datetime!(2026-01-01 03:00 UTC)), 2026);
+    assert_eq!(calligraphy_year(datetime!(2026-06-30 14:59 UTC)), 2026);
+    assert_eq!(calligraphy_year(datetime!(2026-06-30 15:00 UTC)), 2027);
+  }
+
+  /// 日別の件数は日本時間の日付で集計され、期間外の投稿は含まれないこと
+  #[test]
+  fn test_new_year_activity() {
+    let entries = [
+      // 日本時間では 1/1 00:30
+      entry("謹賀新年", datetime!(2025-12-31 15:30 UTC), None, None),
+      entry("一富士二鷹", datetime!(2026-01-01 03:00 UTC), None, None),
+      entry("今年もよろしく", datetime!(2026-01-02 00:00 UTC), None, None),
+      // 期間外
+      entry("春", datetime!(2026-03-01 00:00 UTC), None, None),
+    ];
+    let stats = compute(&entries, datetime!(2026-01-03 00:00 UTC));
+
+    assert_eq!(stats.total_entries, 4);
+    assert_eq!(stats.new_year.daily.len(), 14);
+    let count_on = |date: Date| stats.new_year.daily.iter().find(|d| d.date == date).unwrap().count;
+    assert_eq!(count_on(date!(2025 - 12 - 31)), 0);
+    assert_eq!(count_on(date!(2026 - 01 - 01)), 2);
+    assert_eq!(count_on(date!(2026 - 01 - 02)), 1);
+    assert_eq!(stats.new_year.daily.iter().map(|d| d.count).sum::<u64>(), 3);
+  }
+
+  /// 文字は書記素クラスタ単位で数え、記号・空白は除くこと
+  #[test]
+  fn test_top_characters() {
+    let now = datetime!(2026-01-03 00:00 UTC);
+    let entries = [
+      entry("謹賀新年！", now, None, None),
+      entry("新年 あけおめ", now, None, None),
+      entry("Happy 新年🎍", now, None, None),
+    ];
+    let stats = compute(&entries, now);
+
+    assert_eq!(stats.top_characters[0], CharacterCount { character: "年".to_string(), count: 3 });
+    assert_eq!(stats.top_characters[1], CharacterCount { character: "新".to_string(), count: 3 });
+    assert!(stats.top_characters.iter().all(|c| !["！", " ", "🎍"].contains(&c.character.as_str())));
+    assert!(stats.top_kanji.iter().all(|c| is_kanji(c.character.chars().next().unwrap())));
+    assert!(stats.top_kanji.iter().any(|c| c.character == "謹"));
+    assert!(!stats.top_kanji.iter().any(|c| c.character == "あ" || c.character == "p"));
+  }
+
+  /// 件数の少ない区分はまとめられ、匿名化済みの値も集計できること
+  #[test]
+  fn test_breakdowns() {
+    let now = datetime!(2026-01-03 00:00 UTC);
+    let mut entries = Vec::new();
+    for _ in 0..3 {
+      entries.push(entry("書", now, Some(CHROME_WINDOWS), Some("ja,en-US;q=0.9")));
+    }
+    // 匿名化済み (ブラウザの種類・主言語タグのみ)
+    entries.push(entry("書", now, Some("Safari"), Some("ja")));
+    entries.push(entry("書", now, Some(FIREFOX_LINUX), Some("de-DE")));
+    entries.push(entry("書", now, None, None));
+    let stats = compute(&entries, now);
+
+    assert_eq!(stats.languages, vec![label("ja", 4), label("Other", 2)]);
+    assert_eq!(stats.browsers, vec![label("Chrome", 3), label("Other", 3)]);
+    assert_eq!(stats.operating_systems, vec![label("Windows", 3), label("Other", 3)]);
+  }
+
+  #[test]
+  fn test_empty() {
+    let stats = compute(&[], datetime!(2026-01-03 00:00 UTC));
+    assert_eq!(stats.total_entries, 0);
+    assert!(stats.new_year.daily.iter().all(|d| d.count == 0));
+    assert!(stats.top_characters.is_empty());
+    assert!(stats.languages.is_empty());
+  }
+}
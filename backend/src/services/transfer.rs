@@ -0,0 +1,105 @@
+//! 別のブラウザへ書き初めを引き継ぐための引き継ぎコード
+//!
+//! コードは人が入力しやすいよう、紛らわしい文字を除いた32種類の文字 (Crockford's Base32) で表す。
+//! DBには正規化したコードのハッシュのみを保存し、コードそのものは発行時のレスポンスにしか現れない。
+
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// コードに使う文字 (I, L, O, U を除く)
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// コードの文字数 (50ビット)
+pub const CODE_LENGTH: usize = 10;
+/// 表示時に区切りを入れる位置
+const GROUP_LENGTH: usize = 5;
+
+/// 新しいコードを生成する (`XXXXX-XXXXX` の形式)
+///
+/// 乱数はUUID v4 (OSの乱数生成器) から取り出す。
+/// バージョン・バリアントを表す固定のビットを含まないバイトのみを使う。
+pub fn generate_code() -> String {
+  let bytes = Uuid::new_v4().into_bytes();
+  let random = bytes[..6]
+    .iter()
+    .chain(&bytes[9..11])
+    .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+
+  let mut code = String::with_capacity(CODE_LENGTH + 1);
+  for i in 0..CODE_LENGTH {
+    if i == GROUP_LENGTH {
+      code.push('-');
+    }
+    let index = (random >> (5 * i)) & 0x1f;
+    code.push(ALPHABET[index as usize] as char);
+  }
+  code
+}
+
+/// 入力されたコードを正規化する
+///
+/// 大文字・小文字を区別せず、区切りの `-` と空白は無視する。
+/// 読み間違えやすい文字は同じ値として扱う (O → 0, I・L → 1)。
+pub fn normalize_code(input: &str) -> Result<String, AppError> {
+  let mut code = String::with_capacity(CODE_LENGTH);
+  for c in input.chars() {
+    let c = match c.to_ascii_uppercase() {
+      '-' => continue,
+      c if c.is_whitespace() => continue,
+      'O' => '0',
+      'I' | 'L' => '1',
+      c if c.is_ascii() && ALPHABET.contains(&(c as u8)) => c,
+      _ => return Err(invalid_code()),
+    };
+    code.push(c);
+  }
+  if code.len() != CODE_LENGTH {
+    return Err(invalid_code());
+  }
+  Ok(code)
+}
+
+/// DBに保存するハッシュ (正規化したコードのSHA-256)
+pub fn hash_code(normalized: &str) -> Vec<u8> {
+  Sha256::digest(normalized.as_bytes()).to_vec()
+}
+
+/// 形式が不正・期限切れ・使用済みのコード (どの理由かは区別しない)
+pub fn invalid_code() -> AppError {
+  AppError::Validation("Invalid or expired transfer code".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_generate_code() {
+    let code = generate_code();
+    assert_eq!(code.len(), CODE_LENGTH + 1);
+    assert_eq!(code.as_bytes()[GROUP_LENGTH], b'-');
+    // 生成したコードは正規化しても同じ文字列になる
+    assert_eq!(normalize_code(&code).unwrap(), code.replace('-', ""));
+    assert_ne!(generate_code(), code);
+  }
+
+  #[test]
+  fn test_normalize_code() {
+    assert_eq!(normalize_code("ABCDE-FGHJK").unwrap(), "ABCDEFGHJK");
+    assert_eq!(normalize_code(" abcde fghjk ").unwrap(), "ABCDEFGHJK");
+    // 読み間違えやすい文字
+    assert_eq!(normalize_code("oOiIl-LABCD").unwrap(), "001111ABCD");
+    // 文字数・使えない文字
+    assert!(normalize_code("ABCDE-FGHJ").is_err());
+    assert!(normalize_code("ABCDE-FGHJKM").is_err());
+    assert!(normalize_code("ABCDE-FGHJU").is_err());
+    assert!(normalize_code("ABCDE-FGHJ＋").is_err());
+  }
+
+  #[test]
+  fn test_hash_code() {
+    assert_eq!(hash_code("ABCDEFGHJK").len(), 32);
+    assert_ne!(hash_code("ABCDEFGHJK"), hash_code("ABCDEFGHJM"));
+  }
+}
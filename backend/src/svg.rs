@@ -0,0 +1,108 @@
+//! 書き初めの縦書きSVGを生成するモジュール
+//!
+//! 縦書きの配置 (`tategaki::compose`) を1文字ずつ `<text>` 要素として書き出す。
+//! 表示側のフォントや `writing-mode` の対応状況によらず同じ配置になるよう、文字の位置・回転は全て座標で指定する。
+//! 表示側のフォントに縦書き用の互換文字があるかは分からないため、句読点・括弧は元の文字を移動・回転して描く。
+
+use std::fmt::Write;
+
+use crate::feed::escape_xml;
+use crate::tategaki::{self, Card, Color, Scene, VerticalGlyph};
+
+/// 表示側で使うフォント (フロントエンドの明朝体の指定に合わせ、OGP画像の毛筆フォントを優先する)
+const FONT_FAMILY: &str = "'Yuji Syuku', 'Yu Mincho', '游明朝', 'YuMincho', 'Hiragino Mincho ProN', serif";
+
+/// SVGを生成する
+pub fn render(card: &Card) -> String {
+  render_scene(&tategaki::compose(card, |_| false), card)
+}
+
+/// 配置済みの場面をSVGにする (`card` はタイトル・代替テキストに使う)
+fn render_scene(scene: &Scene, card: &Card) -> String {
+  let mut svg = String::new();
+  let frame = &scene.frame;
+  let frame_offset = frame.inset as f32 + frame.width as f32 / 2.0;
+
+  // String への書き込みは失敗しない
+  let _ = write!(
+    svg,
+    r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" role="img" aria-labelledby="title">"#,
+    w = scene.width,
+    h = scene.height,
+  );
+  let _ = write!(
+    svg,
+    "<title id=\"title\">{}</title>",
+    escape_xml(&format!("{} — {}", card.content, card.user_name))
+  );
+  let _ = write!(
+    svg,
+    r#"<rect width="100%" height="100%" fill="{}"/>"#,
+    hex(scene.background)
+  );
+  let _ = write!(
+    svg,
+    r#"<rect x="{o}" y="{o}" width="{w}" height="{h}" fill="none" stroke="{c}" stroke-width="{sw}"/>"#,
+    o = frame_offset,
+    w = scene.width as f32 - frame_offset * 2.0,
+    h = scene.height as f32 - frame_offset * 2.0,
+    c = hex(frame.color),
+    sw = frame.width,
+  );
+  let _ = write!(
+    svg,
+    r#"<g font-family="{}" text-anchor="middle" dominant-baseline="central" aria-hidden="true">"#,
+    escape_xml(FONT_FAMILY)
+  );
+  for placed in &scene.glyphs {
+    // 文字の枠の中心を基準に描く
+    let (cx, cy) = (placed.x + placed.size / 2.0, placed.y + placed.size / 2.0);
+    let (cx, cy, transform) = match placed.glyph {
+      VerticalGlyph::Upright(_) => (cx, cy, String::new()),
+      VerticalGlyph::Rotated(_) => (cx, cy, format!(r#" transform="rotate(90 {:.1} {:.1})""#, cx, cy)),
+      VerticalGlyph::Shifted(_, dx, dy) => (cx + dx * placed.size, cy + dy * placed.size, String::new()),
+    };
+    let _ = write!(
+      svg,
+      r#"<text x="{:.1}" y="{:.1}" font-size="{:.1}" fill="{}"{}>{}</text>"#,
+      cx,
+      cy,
+      placed.size,
+      hex(placed.color),
+      transform,
+      escape_xml(&placed.glyph.char().to_string()),
+    );
+  }
+  svg.push_str("</g></svg>");
+  svg
+}
+
+pub(crate) fn hex([r, g, b]: Color) -> String {
+  format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_render() {
+    let svg = render(&Card {
+      content: "ラーメン、\n<script>",
+      user_name: "太郎&花子",
+      year: 2026,
+    });
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="630""#));
+    assert!(svg.ends_with("</svg>"));
+    // 長音記号は回転、読点は右上に寄せる
+    assert!(svg.contains(r#"transform="rotate(90"#));
+    assert!(svg.contains(">ー</text>"));
+    assert!(svg.contains(">、</text>"));
+    // 年は朱色
+    assert!(svg.contains(r##"fill="#b7282e">二</text>"##));
+    // ユーザー入力はエスケープする
+    assert!(svg.contains("&lt;"));
+    assert!(svg.contains("&amp;"));
+    assert!(!svg.contains("<script>"));
+  }
+}
@@ -0,0 +1,387 @@
+//! 書き初めの縦書きレイアウト
+//!
+//! 内容を縦書き (右の列から左へ、各列は上から下へ) に配置し、左側に年とユーザー名を添えた
+//! 1200x630 の「場面」(`Scene`) を組み立てる。SVG (`svg.rs`) とOGP画像のPNG (`ogp.rs`) は
+//! どちらもこの場面を描くだけで、配置の規則はここにまとめる。
+//!
+//! 改行・折り返しはフロントエンドのカード (`writing-mode: vertical-rl; white-space: pre-wrap`) に合わせる。
+//! - 改行で列を改め、空行は空の列になる
+//! - 1列に収まらない行は折り返す。句読点・閉じ括弧・長音記号・小書きの仮名は列の先頭に置かず、
+//!   開き括弧は列の末尾に置かない (直前の文字ごと次の列へ送る)
+//!
+//! OpenTypeの縦書き用の字形置換 (`vert`) は使えない前提で、句読点・括弧は縦書き用の互換文字
+//! (U+FE10〜, U+FE30〜) に置き換え、フォントにない場合や長音記号などは字形を回転・移動して描く。
+
+/// 場面の大きさ (OGPの推奨サイズ)
+pub const WIDTH: u32 = 1200;
+pub const HEIGHT: u32 = 630;
+
+const MARGIN: f32 = 60.0;
+/// 内容を描く領域 (左側は署名欄)
+const CONTENT_LEFT: f32 = 260.0;
+const CONTENT_RIGHT: f32 = WIDTH as f32 - MARGIN;
+const CONTENT_TOP: f32 = MARGIN;
+const CONTENT_BOTTOM: f32 = HEIGHT as f32 - MARGIN;
+/// 内容の文字の大きさ (全ての列が収まる最大の大きさを使う)
+const MAX_FONT_SIZE: f32 = 150.0;
+const MIN_FONT_SIZE: f32 = 28.0;
+const FONT_SIZE_STEP: f32 = 4.0;
+/// 折り返さない配置を優先する条件 (折り返した場合の文字の大きさに対する比)
+const UNWRAPPED_MIN_RATIO: f32 = 0.75;
+/// 列の間隔 (文字の大きさに対する比)
+pub const COLUMN_PITCH: f32 = 1.35;
+/// 文字の送り (文字の大きさに対する比)
+pub const CHAR_PITCH: f32 = 1.05;
+/// 署名 (年・ユーザー名) の文字の大きさ
+const SIGNATURE_FONT_SIZE: f32 = 36.0;
+/// 署名の列の左端
+const YEAR_COLUMN_X: f32 = 170.0;
+const NAME_COLUMN_X: f32 = 100.0;
+/// 枠線
+const FRAME_INSET: u32 = 24;
+const FRAME_WIDTH: u32 = 4;
+
+pub const PAPER: Color = [0xf7, 0xf1, 0xe3];
+pub const INK: Color = [0x1a, 0x1a, 0x1a];
+pub const VERMILION: Color = [0xb7, 0x28, 0x2e];
+
+/// 列の先頭に置かない文字 (行頭禁則)
+const NO_START: &str = "、。，．・：；！？）」』】〕》〉｝］ーぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶ…‥々ゝゞヽヾ";
+/// 列の末尾に置かない文字 (行末禁則)
+const NO_END: &str = "（「『【〔《〈｛［(";
+
+/// RGB
+pub type Color = [u8; 3];
+
+/// 描く内容
+pub struct Card<'a> {
+  pub content: &'a str,
+  pub user_name: &'a str,
+  /// 書き初めの年 (例: 2026)
+  pub year: i32,
+}
+
+/// 縦書きの配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnLayout {
+  /// 文字の大きさ (px)
+  pub font_size: f32,
+  /// 右から順の列
+  pub columns: Vec<Vec<char>>,
+}
+
+impl ColumnLayout {
+  /// 最も長い列の文字数
+  pub fn rows(&self) -> usize {
+    self.columns.iter().map(Vec::len).max().unwrap_or(0)
+  }
+}
+
+/// 内容を縦書きの列に分ける
+///
+/// 改行で列を改め、1列に収まらない行は禁則処理をして折り返す。
+/// 全ての列が `width` x `height` に収まる最大の文字の大きさを選ぶ (最小 `MIN_FONT_SIZE`)。
+/// 行を折り返さずに収まる場合は、文字が極端に小さくならない限りそれを優先する。
+pub fn layout(content: &str, width: f32, height: f32) -> ColumnLayout {
+  let lines: Vec<Vec<char>> = content
+    .lines()
+    .map(|line| line.chars().filter(|c| !c.is_control()).collect())
+    .collect();
+
+  let sizes = || {
+    std::iter::successors(Some(MAX_FONT_SIZE), |size| {
+      Some(size - FONT_SIZE_STEP).filter(|&next| next >= MIN_FONT_SIZE)
+    })
+  };
+  let fits = |layout: &ColumnLayout| layout.columns.len() as f32 * layout.font_size * COLUMN_PITCH <= width;
+  let wrap = |font_size: f32| {
+    let rows = ((height / (font_size * CHAR_PITCH)).floor() as usize).max(1);
+    let columns = lines.iter().flat_map(|line| wrap_line(line, rows)).collect();
+    ColumnLayout { font_size, columns }
+  };
+
+  let wrapped = sizes().map(wrap).find(fits).unwrap_or_else(|| wrap(MIN_FONT_SIZE));
+  let unwrapped = sizes()
+    .map(wrap)
+    .find(|layout| layout.columns.len() == lines.len() && fits(layout));
+  match unwrapped {
+    Some(layout) if layout.font_size >= wrapped.font_size * UNWRAPPED_MIN_RATIO => layout,
+    _ => wrapped,
+  }
+}
+
+/// 1行を `rows` 文字ずつの列に折り返す (空行は空の列1つ)
+///
+/// 区切りの前後が禁則に当たる場合は、列が空にならない範囲で区切りを前にずらす。
+fn wrap_line(line: &[char], rows: usize) -> Vec<Vec<char>> {
+  let mut columns = Vec::new();
+  let mut start = 0;
+  while line.len() - start > rows {
+    let mut end = start + rows;
+    while end > start + 1 && (NO_START.contains(line[end]) || NO_END.contains(line[end - 1])) {
+      end -= 1;
+    }
+    columns.push(line[start..end].to_vec());
+    start = end;
+  }
+  columns.push(line[start..].to_vec());
+  columns
+}
+
+/// 縦書きでの字形の描き方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerticalGlyph {
+  /// そのまま描く
+  Upright(char),
+  /// 90度回転して描く (長音記号・ダッシュ・括弧など)
+  Rotated(char),
+  /// 文字の枠に対する比でずらして描く (句読点・小書きの仮名)
+  Shifted(char, f32, f32),
+}
+
+impl VerticalGlyph {
+  pub fn char(self) -> char {
+    match self {
+      VerticalGlyph::Upright(c) | VerticalGlyph::Rotated(c) | VerticalGlyph::Shifted(c, _, _) => c,
+    }
+  }
+}
+
+/// 縦書きでの字形を決める
+/// `has_glyph` はフォントに字形があるか (縦書き用の互換文字がなければ元の文字を回転・移動する)
+pub fn vertical_glyph(c: char, has_glyph: impl Fn(char) -> bool) -> VerticalGlyph {
+  const VERTICAL_FORMS: [(char, char); 25] = [
+    ('、', '︑'),
+    ('。', '︒'),
+    ('，', '︐'),
+    ('：', '︓'),
+    ('；', '︔'),
+    ('！', '︕'),
+    ('？', '︖'),
+    ('…', '︙'),
+    ('‥', '︰'),
+    ('（', '︵'),
+    ('）', '︶'),
+    ('｛', '︷'),
+    ('｝', '︸'),
+    ('〔', '︹'),
+    ('〕', '︺'),
+    ('【', '︻'),
+    ('】', '︼'),
+    ('《', '︽'),
+    ('》', '︾'),
+    ('〈', '︿'),
+    ('〉', '﹀'),
+    ('「', '﹁'),
+    ('」', '﹂'),
+    ('『', '﹃'),
+    ('』', '﹄'),
+  ];
+  const PUNCTUATION: [char; 4] = ['、', '。', '，', '．'];
+  const SMALL_KANA: &str = "ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶ";
+
+  if let Some(&(_, vertical)) = VERTICAL_FORMS.iter().find(|&&(from, _)| from == c) {
+    if has_glyph(vertical) {
+      return VerticalGlyph::Upright(vertical);
+    }
+  }
+  if PUNCTUATION.contains(&c) {
+    // 右上に寄せる
+    return VerticalGlyph::Shifted(c, 0.6, -0.6);
+  }
+  if SMALL_KANA.contains(c) {
+    return VerticalGlyph::Shifted(c, 0.1, -0.1);
+  }
+  match c {
+    'ー' | '〜' | '～' | '－' | '-' | '―' | '—' | '–' | '＝' | '=' | '…' | '‥' | '（' | '）' | '(' | ')'
+    | '｛' | '｝' | '〔' | '〕' | '【' | '】' | '《' | '》' | '〈' | '〉' | '「' | '」' | '『' | '』'
+    | '［' | '］' | '[' | ']' | '：' | '；' => VerticalGlyph::Rotated(c),
+    _ => VerticalGlyph::Upright(c),
+  }
+}
+
+/// 年を漢数字にする (例: 2026 -> "二〇二六年")
+pub fn kanji_year(year: i32) -> String {
+  const DIGITS: [char; 10] = ['〇', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+  let mut s: String = year
+    .to_string()
+    .chars()
+    .map(|c| c.to_digit(10).map_or(c, |d| DIGITS[d as usize]))
+    .collect();
+  s.push('年');
+  s
+}
+
+/// 配置済みの1文字
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedGlyph {
+  pub glyph: VerticalGlyph,
+  /// 文字の枠の左上
+  pub x: f32,
+  pub y: f32,
+  /// 文字の枠の大きさ (= 文字の大きさ)
+  pub size: f32,
+  pub color: Color,
+}
+
+/// 外周から `inset` の位置に描く太さ `width` の枠線
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+  pub inset: u32,
+  pub width: u32,
+  pub color: Color,
+}
+
+/// 描く内容を全て配置したもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+  pub width: u32,
+  pub height: u32,
+  pub background: Color,
+  pub frame: Frame,
+  pub glyphs: Vec<PlacedGlyph>,
+}
+
+/// 内容・署名を配置する
+/// `has_glyph` は描画に使うフォントに字形があるか (フォントが分からない場合は常に `false` を返す)
+pub fn compose(card: &Card, has_glyph: impl Fn(char) -> bool) -> Scene {
+  let mut glyphs = Vec::new();
+  let mut place_column = |column: &[char], x: f32, y: f32, size: f32, color: Color| {
+    for (row, &c) in column.iter().enumerate() {
+      if c.is_whitespace() {
+        continue;
+      }
+      glyphs.push(PlacedGlyph {
+        glyph: vertical_glyph(c, &has_glyph),
+        x,
+        y: y + row as f32 * size * CHAR_PITCH,
+        size,
+        color,
+      });
+    }
+  };
+
+  // 内容: 右の列から、全体を領域の中央に置く
+  let area_width = CONTENT_RIGHT - CONTENT_LEFT;
+  let area_height = CONTENT_BOTTOM - CONTENT_TOP;
+  let layout = layout(card.content, area_width, area_height);
+  let size = layout.font_size;
+  let block_width = layout.columns.len() as f32 * size * COLUMN_PITCH - (COLUMN_PITCH - 1.0) * size;
+  let block_height = layout.rows() as f32 * size * CHAR_PITCH;
+  let right = CONTENT_RIGHT - (area_width - block_width).max(0.0) / 2.0;
+  let top = CONTENT_TOP + (area_height - block_height).max(0.0) / 2.0;
+  for (i, column) in layout.columns.iter().enumerate() {
+    let x = right - size - i as f32 * size * COLUMN_PITCH;
+    place_column(column, x, top, size, INK);
+  }
+
+  // 署名: 年は上揃え、ユーザー名は下揃え
+  let year: Vec<char> = kanji_year(card.year).chars().collect();
+  place_column(&year, YEAR_COLUMN_X, CONTENT_TOP, SIGNATURE_FONT_SIZE, VERMILION);
+  let name: Vec<char> = card.user_name.chars().filter(|c| !c.is_control()).collect();
+  let name_size = SIGNATURE_FONT_SIZE.min(area_height / (name.len().max(1) as f32 * CHAR_PITCH));
+  let name_top = CONTENT_BOTTOM - name.len() as f32 * name_size * CHAR_PITCH;
+  place_column(&name, NAME_COLUMN_X, name_top, name_size, INK);
+
+  Scene {
+    width: WIDTH,
+    height: HEIGHT,
+    background: PAPER,
+    frame: Frame {
+      inset: FRAME_INSET,
+      width: FRAME_WIDTH,
+      color: VERMILION,
+    },
+    glyphs,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 短い内容は大きな文字の1列になること
+  #[test]
+  fn test_layout_short() {
+    let layout = layout("謹賀新年", 880.0, 510.0);
+    assert_eq!(layout.columns, vec![vec!['謹', '賀', '新', '年']]);
+    assert!(layout.font_size >= 100.0);
+  }
+
+  /// 改行で列を改め、長い行は折り返し、全ての列が領域に収まること
+  #[test]
+  fn test_layout_wraps() {
+    let content = "一富士\n\n今年こそ毎日欠かさず運動して健康な体を手に入れたいと思いますよろしく";
+    let layout = layout(content, 880.0, 510.0);
+    assert_eq!(layout.columns[0], vec!['一', '富', '士']);
+    assert!(layout.columns[1].is_empty());
+    assert!(layout.columns.len() >= 3);
+    assert!(layout.columns.len() as f32 * layout.font_size * COLUMN_PITCH <= 880.0);
+    assert!(layout.rows() as f32 * layout.font_size * CHAR_PITCH <= 510.0);
+    let chars: usize = layout.columns.iter().map(Vec::len).sum();
+    assert_eq!(chars, content.chars().filter(|c| *c != '\n').count());
+  }
+
+  /// 句読点・閉じ括弧は列の先頭に、開き括弧は列の末尾に置かないこと
+  #[test]
+  fn test_wrap_line_kinsoku() {
+    let chars = |s: &str| s.chars().collect::<Vec<_>>();
+    let wrap = |s: &str, rows| {
+      wrap_line(&chars(s), rows)
+        .into_iter()
+        .map(|column| column.into_iter().collect::<String>())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(wrap("春夏秋冬", 2), vec!["春夏", "秋冬"]);
+    assert_eq!(wrap("", 2), vec![""]);
+    // 「。」の前の文字ごと次の列へ送る
+    assert_eq!(wrap("一二三。", 3), vec!["一二", "三。"]);
+    assert_eq!(wrap("ラーメン", 1), vec!["ラ", "ー", "メ", "ン"]);
+    assert_eq!(wrap("あいう「え」", 4), vec!["あいう", "「え」"]);
+    assert_eq!(wrap("コーヒー", 2), vec!["コー", "ヒー"]);
+  }
+
+  /// 句読点・括弧・長音記号の縦書き
+  #[test]
+  fn test_vertical_glyph() {
+    let all = |_: char| true;
+    let none = |_: char| false;
+    assert_eq!(vertical_glyph('、', all), VerticalGlyph::Upright('︑'));
+    assert_eq!(vertical_glyph('「', all), VerticalGlyph::Upright('﹁'));
+    // 縦書き用の互換文字がフォントになければ、元の文字を移動・回転する
+    assert_eq!(vertical_glyph('。', none), VerticalGlyph::Shifted('。', 0.6, -0.6));
+    assert_eq!(vertical_glyph('「', none), VerticalGlyph::Rotated('「'));
+    assert_eq!(vertical_glyph('ー', all), VerticalGlyph::Rotated('ー'));
+    assert_eq!(vertical_glyph('ょ', all), VerticalGlyph::Shifted('ょ', 0.1, -0.1));
+    assert_eq!(vertical_glyph('年', all), VerticalGlyph::Upright('年'));
+  }
+
+  #[test]
+  fn test_kanji_year() {
+    assert_eq!(kanji_year(2026), "二〇二六年");
+    assert_eq!(kanji_year(2030), "二〇三〇年");
+  }
+
+  /// 内容は右の列から、署名は左側に配置されること
+  #[test]
+  fn test_compose() {
+    let scene = compose(
+      &Card {
+        content: "謹賀\n新年",
+        user_name: "太 郎",
+        year: 2026,
+      },
+      |_| false,
+    );
+    assert_eq!((scene.width, scene.height), (WIDTH, HEIGHT));
+
+    let find = |c: char| scene.glyphs.iter().find(|g| g.glyph.char() == c).unwrap();
+    assert!(find('謹').x > find('新').x);
+    assert!(find('謹').y < find('賀').y);
+    assert_eq!(find('二').color, VERMILION);
+    assert!(find('郎').x < CONTENT_LEFT);
+    // 空白は配置しない (位置は詰めない)
+    assert!(scene.glyphs.iter().all(|g| !g.glyph.char().is_whitespace()));
+    assert!(scene.glyphs.iter().all(|g| g.x >= 0.0 && g.x + g.size <= WIDTH as f32));
+  }
+}
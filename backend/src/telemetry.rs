@@ -0,0 +1,256 @@
+//! ログの出力とリクエストの追跡
+//!
+//! リクエストごとにリクエストIDを決め (nginxが付けた `X-Request-Id` があればそれを使う)、
+//! そのリクエストの処理中のログを1つのspanにまとめる。
+//! spanにはメソッド・ルート・リクエストID・ユーザーID・ステータス・処理時間を記録する。
+//! リクエストIDはレスポンスの `X-Request-Id` ヘッダーとエラーレスポンスのボディにも付ける。
+//!
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` を設定すると、spanをOTLP (HTTP) でコレクターに送る。
+//! リクエストのspanの下に、サービス (`service.*`) とリポジトリ (`db.*`) のメソッドのspanが入る。
+//! 受け取ったW3Cの `traceparent` があれば、そのトレースの続きとして記録する。
+
+use std::time::Instant;
+
+use axum::{
+  extract::{MatchedPath, Request},
+  http::{HeaderMap, HeaderName, HeaderValue},
+  middleware::Next,
+  response::Response,
+};
+use opentelemetry::{propagation::Extractor, trace::TracerProvider as _};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+  propagation::TraceContextPropagator,
+  trace::{Sampler, SdkTracerProvider},
+  Resource,
+};
+use tracing::{field, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+use uuid::Uuid;
+
+use crate::config::{Config, LogFormat};
+
+/// リクエストIDのヘッダー名
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// 受け付けるリクエストIDの最大長 (これより長いものは新しく発行する)
+const REQUEST_ID_MAX_LEN: usize = 128;
+
+tokio::task_local! {
+  /// 処理中のリクエストのID (エラーレスポンスに付けるため)
+  static REQUEST_ID: String;
+}
+
+/// トレースのエクスポートを終了するためのガード (dropするときに残りのspanを送る)
+pub struct TelemetryGuard {
+  tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+  fn drop(&mut self) {
+    if let Some(provider) = self.tracer_provider.take() {
+      if let Err(e) = provider.shutdown() {
+        eprintln!("Failed to shut down trace exporter: {:?}", e);
+      }
+    }
+  }
+}
+
+/// ログの出力とトレースのエクスポートを初期化する
+/// RUST_LOG環境変数でレベル制御可能 (デフォルトは debug)
+pub fn init(config: &Config) -> TelemetryGuard {
+  let (tracer_provider, export_error) = match config.otlp_endpoint.as_deref().map(|endpoint| tracer_provider(config, endpoint)) {
+    Some(Ok(provider)) => (Some(provider), None),
+    Some(Err(e)) => (None, Some(e)),
+    None => (None, None),
+  };
+
+  let fmt_layer = match config.log_format {
+    LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+    // 1行1オブジェクト。現在のspan (リクエストIDなど) をフィールドに含める
+    LogFormat::Json => tracing_subscriber::fmt::layer()
+      .json()
+      .with_current_span(true)
+      .with_span_list(false)
+      .boxed(),
+  };
+  let otel_layer = tracer_provider
+    .as_ref()
+    .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("server")));
+  tracing_subscriber::registry()
+    .with(fmt_layer)
+    .with(otel_layer)
+    .with(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "server=debug".into()))
+    .init();
+
+  if let Some(e) = export_error {
+    tracing::warn!("Trace export disabled: {}", e);
+  } else if let Some(endpoint) = &config.otlp_endpoint {
+    tracing::info!("Exporting traces to {} (sample ratio: {})", endpoint, config.trace_sample_ratio);
+  }
+  TelemetryGuard { tracer_provider }
+}
+
+/// OTLP (HTTP) でspanを送るプロバイダーを作る
+/// 受け取った `traceparent` の記録の有無に従い、なければ設定した割合で記録する
+fn tracer_provider(config: &Config, endpoint: &str) -> Result<SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+  let exporter = opentelemetry_otlp::SpanExporter::builder()
+    .with_http()
+    .with_endpoint(format!("{}/v1/traces", endpoint))
+    .build()?;
+  opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+  Ok(
+    SdkTracerProvider::builder()
+      .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+        config.trace_sample_ratio,
+      ))))
+      .with_resource(Resource::builder().with_service_name(config.otel_service_name.clone()).build())
+      .with_batch_exporter(exporter)
+      .build(),
+  )
+}
+
+/// 処理中のリクエストのID (リクエストの外ではNone)
+pub fn current_request_id() -> Option<String> {
+  REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// 処理中のリクエストのspanにユーザーIDを記録する (AuthUserの確定時に呼ぶ)
+pub fn record_user_id(user_id: Uuid) {
+  tracing::Span::current().record("user_id", field::display(user_id));
+}
+
+/// リクエストを追跡するミドルウェア (一番外側に追加する)
+pub async fn trace_request(request: Request, next: Next) -> Response {
+  let request_id = incoming_request_id(&request).unwrap_or_else(|| Uuid::new_v4().to_string());
+  let route = request
+    .extensions()
+    .get::<MatchedPath>()
+    .map(|path| path.as_str().to_string())
+    .unwrap_or_else(|| request.uri().path().to_string());
+  let span = tracing::info_span!(
+    "request",
+    method = %request.method(),
+    route = %route,
+    request_id = %request_id,
+    user_id = field::Empty,
+    status = field::Empty,
+    latency_ms = field::Empty,
+    otel.name = %format!("{} {}", request.method(), route),
+    otel.kind = "server",
+    otel.status_code = field::Empty,
+  );
+  // 上流 (nginxの前段のロードバランサーなど) のトレースの続きにする (エクスポートしない場合は何もしない)
+  let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+    propagator.extract(&HeaderExtractor(request.headers()))
+  });
+  let _ = span.set_parent(parent);
+
+  let started = Instant::now();
+  let mut response = REQUEST_ID
+    .scope(request_id.clone(), next.run(request).instrument(span.clone()))
+    .await;
+  let status = response.status().as_u16();
+  span.record("status", status);
+  span.record("latency_ms", started.elapsed().as_millis() as u64);
+  span.in_scope(|| {
+    if response.status().is_server_error() {
+      span.record("otel.status_code", "ERROR");
+      tracing::error!(status, "request failed");
+    } else {
+      tracing::info!(status, "request finished");
+    }
+  });
+
+  if let Ok(value) = HeaderValue::from_str(&request_id) {
+    response
+      .headers_mut()
+      .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+  }
+  response
+}
+
+/// `traceparent` などを読み取るためのヘッダーのラッパー
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+  fn get(&self, key: &str) -> Option<&str> {
+    self.0.get(key).and_then(|value| value.to_str().ok())
+  }
+
+  fn keys(&self) -> Vec<&str> {
+    self.0.keys().map(HeaderName::as_str).collect()
+  }
+}
+
+/// 受け取った `X-Request-Id` (ログを壊さないよう、英数字と `-`・`_`・`.` のみの短いものに限る)
+fn incoming_request_id(request: &Request) -> Option<String> {
+  let value = request.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?.trim();
+  let valid = !value.is_empty()
+    && value.len() <= REQUEST_ID_MAX_LEN
+    && value
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+  valid.then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::body::Body;
+
+  fn request_with_id(id: &str) -> Request {
+    Request::builder()
+      .header(REQUEST_ID_HEADER, id)
+      .body(Body::empty())
+      .unwrap()
+  }
+
+  #[test]
+  fn test_incoming_request_id() {
+    assert_eq!(
+      incoming_request_id(&request_with_id("0f8e2c1d9a7b4e65")).as_deref(),
+      Some("0f8e2c1d9a7b4e65")
+    );
+    assert_eq!(
+      incoming_request_id(&request_with_id("req_1.2-3")).as_deref(),
+      Some("req_1.2-3")
+    );
+    assert!(incoming_request_id(&request_with_id("")).is_none());
+    assert!(incoming_request_id(&request_with_id("with space")).is_none());
+    assert!(incoming_request_id(&request_with_id("\"quoted\"")).is_none());
+    assert!(incoming_request_id(&request_with_id(&"a".repeat(REQUEST_ID_MAX_LEN + 1))).is_none());
+    assert!(incoming_request_id(&Request::new(Body::empty())).is_none());
+  }
+
+  #[test]
+  fn test_extract_traceparent() {
+    use opentelemetry::propagation::TextMapPropagator;
+    use opentelemetry::trace::TraceContextExt;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      "traceparent",
+      HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+    );
+    let context = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+    let span_context = context.span().span_context().clone();
+    assert!(span_context.is_remote());
+    assert!(span_context.is_sampled());
+    assert_eq!(span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+
+    // 不正な値は無視する (新しいトレースになる)
+    headers.insert("traceparent", HeaderValue::from_static("invalid"));
+    let context = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+    assert!(!context.span().span_context().is_valid());
+  }
+
+  #[tokio::test]
+  async fn test_current_request_id() {
+    assert!(current_request_id().is_none());
+    let id = REQUEST_ID
+      .scope("abc".to_string(), async { current_request_id() })
+      .await;
+    assert_eq!(id.as_deref(), Some("abc"));
+  }
+}
@@ -0,0 +1,307 @@
+//! フロントエンド用のTypeScriptの型定義の生成
+//!
+//! OpenAPIのドキュメント (`openapi::ApiDoc`) のスキーマ・入力値の上限・エラーの種類から
+//! `frontend/src/types/generated.ts` を生成する。フロントエンドで型や上限を手で書き写さないため。
+//!
+//! 生成結果はコミットしておき、DTOや上限を変更したら
+//! `cargo run -- export-types ../frontend/src/types/generated.ts` で更新すること (更新しないとテストが失敗する)。
+
+use utoipa::openapi::schema::{Array, ArrayItems, KnownFormat, Object, Schema, SchemaFormat, SchemaType, Type};
+use utoipa::openapi::RefOr;
+use utoipa::OpenApi;
+
+use crate::error::ErrorCode;
+use crate::models::strokes::STROKES_FORMAT_VERSION;
+use crate::openapi::ApiDoc;
+use crate::photo::PHOTO_MAX_BYTES;
+use crate::validation::{
+  CONTENT_MAX_GRAPHEMES, CONTENT_MAX_LINES, STROKES_MAX_BYTES, STROKES_MAX_DIMENSION,
+  STROKES_MAX_DURATION_MS, STROKES_MAX_POINTS, STROKES_MAX_STROKES, STROKES_MAX_STROKE_POINTS,
+  USER_NAME_MAX_GRAPHEMES,
+};
+
+/// 生成するファイルの先頭のコメント
+const HEADER: &str = "// このファイルはバックエンドの型から生成したもの。手で編集しないこと。
+// 更新するには backend で `cargo run -- export-types ../frontend/src/types/generated.ts` を実行する。
+";
+
+/// フロントエンドに渡す入力値の上限 (名前・値・説明)
+fn limits() -> [(&'static str, u64, &'static str); 11] {
+  [
+    ("USER_NAME_MAX_GRAPHEMES", USER_NAME_MAX_GRAPHEMES as u64, "ユーザー名の最大文字数 (書記素クラスタ単位)"),
+    ("CONTENT_MAX_GRAPHEMES", CONTENT_MAX_GRAPHEMES as u64, "書き初め内容の最大文字数 (書記素クラスタ単位)"),
+    ("CONTENT_MAX_LINES", CONTENT_MAX_LINES as u64, "書き初め内容の最大行数"),
+    ("STROKES_FORMAT_VERSION", STROKES_FORMAT_VERSION.into(), "筆跡データの形式のバージョン"),
+    ("STROKES_MAX_DIMENSION", STROKES_MAX_DIMENSION.into(), "筆跡の描画領域の最大の幅・高さ"),
+    ("STROKES_MAX_STROKES", STROKES_MAX_STROKES as u64, "筆跡の最大の画数"),
+    ("STROKES_MAX_STROKE_POINTS", STROKES_MAX_STROKE_POINTS as u64, "1画の最大の点の数"),
+    ("STROKES_MAX_POINTS", STROKES_MAX_POINTS as u64, "筆跡全体の最大の点の数"),
+    ("STROKES_MAX_DURATION_MS", STROKES_MAX_DURATION_MS.into(), "書き始めから書き終わりまでの最大の時間 (ミリ秒)"),
+    ("STROKES_MAX_BYTES", STROKES_MAX_BYTES as u64, "筆跡データ (JSON) の最大バイト数"),
+    ("PHOTO_MAX_BYTES", PHOTO_MAX_BYTES as u64, "添付する写真の最大バイト数"),
+  ]
+}
+
+/// `frontend/src/types/generated.ts` の内容
+pub fn render() -> String {
+  let mut out = String::from(HEADER);
+
+  out.push_str("\n/** 入力値の上限 (バックエンドのバリデーションと同じ値) */\nexport const LIMITS = {\n");
+  for (name, value, description) in limits() {
+    out.push_str(&doc(Some(description), 1));
+    out.push_str(&format!("\t{}: {},\n", name, value));
+  }
+  out.push_str("} as const;\n");
+
+  out.push_str("\n/** エラーのレスポンスの `code` の値 */\nexport const ERROR_CODES = [\n");
+  for code in ErrorCode::ALL {
+    let value = serde_json::to_value(code).expect("ErrorCode must be serializable");
+    out.push_str(&format!("\t{},\n", literal(&value)));
+  }
+  out.push_str("] as const;\n");
+
+  let schemas = ApiDoc::openapi().components.map(|components| components.schemas).unwrap_or_default();
+  for (name, schema) in &schemas {
+    out.push('\n');
+    out.push_str(&doc(description(schema), 0));
+    out.push_str(&declaration(name, schema));
+  }
+  out
+}
+
+/// 1つのスキーマの宣言 (プロパティを持つオブジェクトはinterface、それ以外はtype)
+fn declaration(name: &str, schema: &RefOr<Schema>) -> String {
+  match schema {
+    RefOr::T(Schema::Object(object)) if !object.properties.is_empty() => {
+      format!("export interface {} {}\n", name, object_literal(object, 0))
+    }
+    // 列挙・oneOfは要素ごとに改行する
+    RefOr::T(Schema::Object(Object { enum_values: Some(values), .. })) => {
+      let members = values.iter().map(|value| (None, literal(value))).collect();
+      format!("export type {} ={};\n", name, multiline_union(members))
+    }
+    RefOr::T(Schema::OneOf(one_of)) => {
+      let members = one_of.items.iter().map(|item| (description(item), ts_type(item, 1))).collect();
+      format!("export type {} ={};\n", name, multiline_union(members))
+    }
+    _ => format!("export type {} = {};\n", name, ts_type(schema, 0)),
+  }
+}
+
+/// スキーマをTypeScriptの型に変換する (`indent` はオブジェクトのリテラルを閉じる位置の字下げ)
+fn ts_type(schema: &RefOr<Schema>, indent: usize) -> String {
+  match schema {
+    RefOr::Ref(reference) => ref_name(&reference.ref_location).to_string(),
+    RefOr::T(schema) => schema_type(schema, indent),
+  }
+}
+
+fn schema_type(schema: &Schema, indent: usize) -> String {
+  match schema {
+    Schema::Object(object) => object_type(object, indent),
+    Schema::Array(array) => array_type(array, indent),
+    Schema::OneOf(one_of) => union(one_of.items.iter().map(|item| ts_type(item, indent)).collect()),
+    Schema::AllOf(all_of) => all_of
+      .items
+      .iter()
+      .map(|item| ts_type(item, indent))
+      .collect::<Vec<_>>()
+      .join(" & "),
+    _ => "unknown".to_string(),
+  }
+}
+
+fn object_type(object: &Object, indent: usize) -> String {
+  if let Some(values) = &object.enum_values {
+    return union(values.iter().map(literal).collect());
+  }
+  if !object.properties.is_empty() {
+    return object_literal(object, indent);
+  }
+  let format = object.format.as_ref();
+  match &object.schema_type {
+    SchemaType::Type(ty) => primitive(ty, format).to_string(),
+    SchemaType::Array(types) => union(types.iter().map(|ty| primitive(ty, format).to_string()).collect()),
+    SchemaType::AnyValue => "unknown".to_string(),
+  }
+}
+
+fn primitive(ty: &Type, format: Option<&SchemaFormat>) -> &'static str {
+  match ty {
+    Type::String if matches!(format, Some(SchemaFormat::KnownFormat(KnownFormat::Binary))) => "Blob",
+    Type::String => "string",
+    Type::Integer | Type::Number => "number",
+    Type::Boolean => "boolean",
+    Type::Null => "null",
+    // プロパティを定義していないオブジェクト (WebAuthnのオプションなど)
+    Type::Object => "Record<string, unknown>",
+    Type::Array => "unknown[]",
+  }
+}
+
+fn array_type(array: &Array, indent: usize) -> String {
+  // prefixItemsは要素数の決まった配列 (タプル)
+  if !array.prefix_items.is_empty() {
+    let items: Vec<_> = array.prefix_items.iter().map(|item| schema_type(item, indent)).collect();
+    return format!("[{}]", items.join(", "));
+  }
+  match &array.items {
+    ArrayItems::RefOrSchema(item) => {
+      let item = ts_type(item, indent);
+      if !item.starts_with('{') && (item.contains(" | ") || item.contains(" & ")) {
+        format!("({})[]", item)
+      } else {
+        format!("{}[]", item)
+      }
+    }
+    ArrayItems::False => "never[]".to_string(),
+  }
+}
+
+/// オブジェクトのリテラル (`{ ... }`)。必須でないプロパティは `?` を付ける
+fn object_literal(object: &Object, indent: usize) -> String {
+  let mut out = String::from("{\n");
+  for (name, property) in &object.properties {
+    out.push_str(&doc(description(property), indent + 1));
+    let optional = if object.required.contains(name) { "" } else { "?" };
+    out.push_str(&format!(
+      "{}{}{}: {};\n",
+      tabs(indent + 1),
+      property_name(name),
+      optional,
+      ts_type(property, indent + 1)
+    ));
+  }
+  out.push_str(&tabs(indent));
+  out.push('}');
+  out
+}
+
+/// 1行の合併型 (nullは最後にする)
+fn union(mut members: Vec<String>) -> String {
+  members.sort_by_key(|member| member == "null");
+  members.join(" | ")
+}
+
+/// 要素ごとに改行した合併型 (`=` の後に続ける)
+fn multiline_union(members: Vec<(Option<&str>, String)>) -> String {
+  let mut out = String::new();
+  for (description, member) in members {
+    out.push('\n');
+    out.push_str(doc(description, 1).trim_end_matches('\n'));
+    if description.is_some() {
+      out.push('\n');
+    }
+    out.push_str(&format!("\t| {}", member));
+  }
+  out
+}
+
+/// スキーマの説明 (`Option<T>` のプロパティは `oneOf` の中の参照に説明が付く)
+fn description(schema: &RefOr<Schema>) -> Option<&str> {
+  let description = match schema {
+    RefOr::Ref(reference) => Some(reference.description.as_str()),
+    RefOr::T(Schema::Object(object)) => object.description.as_deref(),
+    RefOr::T(Schema::Array(array)) => array.description.as_deref(),
+    RefOr::T(Schema::AllOf(all_of)) => all_of.description.as_deref(),
+    RefOr::T(Schema::OneOf(one_of)) => one_of
+      .description
+      .as_deref()
+      .or_else(|| one_of.items.iter().find_map(description)),
+    _ => None,
+  };
+  description.filter(|description| !description.is_empty())
+}
+
+/// JSDocのコメント
+fn doc(description: Option<&str>, indent: usize) -> String {
+  let Some(description) = description else {
+    return String::new();
+  };
+  let description = description.replace("*/", "*\\/");
+  let tabs = tabs(indent);
+  if !description.contains('\n') {
+    return format!("{}/** {} */\n", tabs, description);
+  }
+  let mut out = format!("{}/**\n", tabs);
+  for line in description.lines() {
+    if line.is_empty() {
+      out.push_str(&format!("{} *\n", tabs));
+    } else {
+      out.push_str(&format!("{} * {}\n", tabs, line));
+    }
+  }
+  out.push_str(&format!("{} */\n", tabs));
+  out
+}
+
+/// 値のリテラル (文字列はシングルクォート)
+fn literal(value: &serde_json::Value) -> String {
+  match value {
+    serde_json::Value::String(value) => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+    value => value.to_string(),
+  }
+}
+
+/// `#/components/schemas/Name` の `Name`
+fn ref_name(location: &str) -> &str {
+  location.rsplit('/').next().unwrap_or(location)
+}
+
+/// 識別子として使えない名前はクォートする
+fn property_name(name: &str) -> String {
+  let is_identifier = !name.starts_with(|c: char| c.is_ascii_digit())
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+  if is_identifier {
+    name.to_string()
+  } else {
+    literal(&serde_json::Value::String(name.to_string()))
+  }
+}
+
+fn tabs(indent: usize) -> String {
+  "\t".repeat(indent)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// コミットしてある型定義
+  const GENERATED_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../frontend/src/types/generated.ts");
+
+  /// コミットしてある型定義が最新であること
+  #[test]
+  fn test_generated_is_up_to_date() {
+    let committed = std::fs::read_to_string(GENERATED_PATH).unwrap_or_default();
+    assert!(
+      render() == committed,
+      "frontend/src/types/generated.ts is out of date; run `cargo run -- export-types ../frontend/src/types/generated.ts` and commit the result"
+    );
+  }
+
+  /// 上限・エラーの種類がバックエンドの定数と一致すること
+  #[test]
+  fn test_renders_constants() {
+    let generated = render();
+    assert!(generated.contains("\tUSER_NAME_MAX_GRAPHEMES: 20,\n"));
+    assert!(generated.contains("\tCONTENT_MAX_GRAPHEMES: 50,\n"));
+    assert!(generated.contains("\t'not_found',\n"));
+    assert!(generated.contains("\t| 'request_header_fields_too_large'\n"));
+  }
+
+  /// nullable・任意のプロパティ、タプル、合併型・交差型を変換できること
+  #[test]
+  fn test_renders_schemas() {
+    let generated = render();
+    assert!(generated.contains("export type StrokePoint = [number, number, number, number];\n"));
+    assert!(generated.contains("\tstrokes: StrokePoint[][];\n"));
+    assert!(generated.contains("\tphoto: PhotoResponse | null;\n"));
+    assert!(generated.contains("\tstrokes?: Strokes | null;\n"));
+    assert!(generated.contains("\tdevice: string | null;\n"));
+    assert!(generated.contains("\tcode: ErrorCode;\n"));
+    assert!(generated.contains("\toptions: Record<string, unknown>;\n"));
+    assert!(generated.contains("export type SearchHitResponse = CalligraphyResponse & {\n"));
+    assert!(generated.contains("\t| {\n\t\tentry: CalligraphyResponse;\n\t\ttype: 'created';\n\t}\n"));
+  }
+}
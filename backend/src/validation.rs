@@ -0,0 +1,471 @@
+//! 入力値の正規化とバリデーションを行うモジュール
+//!
+//! 文字数は `chars().count()` (コードポイント数) ではなく、
+//! ユーザーが1文字として認識する書記素クラスタ (grapheme cluster) 単位で数える。
+//! 例: 家族の絵文字 "👨‍👩‍👧" や異体字セレクタ付きの漢字 "葛󠄀" はどちらも1文字。
+//!
+//! DBのCHECK制約は書記素クラスタを数えられないため、コードポイント数の上限
+//! (`*_MAX_CHARS`) とNFC正規化済みであることのみを検証する。
+//! このモジュールを通過した値は必ずDBのCHECK制約も通過する。
+
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::AppError;
+use crate::models::strokes::{StrokePoint, Strokes, STROKES_FORMAT_VERSION};
+
+/// ユーザー名の最大文字数 (書記素クラスタ単位)
+pub const USER_NAME_MAX_GRAPHEMES: usize = 20;
+/// 書き初め内容の最大文字数 (書記素クラスタ単位)
+pub const CONTENT_MAX_GRAPHEMES: usize = 50;
+/// 書き初め内容の最大行数
+pub const CONTENT_MAX_LINES: usize = 10;
+
+/// ユーザー名の最大コードポイント数 (DBのCHECK制約と一致させること)
+/// 結合文字を大量に連ねた入力でDBを肥大化させないための上限
+pub const USER_NAME_MAX_CHARS: usize = 160;
+/// 書き初め内容の最大コードポイント数 (DBのCHECK制約と一致させること)
+pub const CONTENT_MAX_CHARS: usize = 400;
+
+/// 筆跡の描画領域の最大の幅・高さ
+pub const STROKES_MAX_DIMENSION: u32 = 2048;
+/// 筆跡の最大の画数
+pub const STROKES_MAX_STROKES: usize = 300;
+/// 1画の最大の点の数
+pub const STROKES_MAX_STROKE_POINTS: usize = 2000;
+/// 筆跡全体の最大の点の数
+pub const STROKES_MAX_POINTS: usize = 8000;
+/// 書き始めから書き終わりまでの最大の時間 (ミリ秒)
+pub const STROKES_MAX_DURATION_MS: u32 = 10 * 60 * 1000;
+/// 筆跡データ (JSON) の最大バイト数 (DBのCHECK制約より小さくすること)
+pub const STROKES_MAX_BYTES: usize = 256 * 1024;
+
+/// バリデーションエラー
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+  /// 空文字 (空白のみを含む)
+  #[error("{field} must not be empty")]
+  Empty { field: &'static str },
+
+  /// 文字数超過 (書記素クラスタ単位)
+  #[error("{field} must be {max} chars or less")]
+  TooLong { field: &'static str, max: usize },
+
+  /// コードポイント数超過 (結合文字の過剰な連結など)
+  #[error("{field} contains too many combining characters")]
+  TooManyCodePoints { field: &'static str },
+
+  /// 行数超過
+  #[error("{field} must be {max} lines or less")]
+  TooManyLines { field: &'static str, max: usize },
+
+  /// 制御文字・双方向テキスト制御文字などの禁止文字
+  #[error("{field} contains a disallowed character (U+{code:04X})")]
+  DisallowedChar { field: &'static str, code: u32 },
+
+  /// 筆跡データが不正 (形式・大きさ・点の数など)
+  #[error("Invalid strokes: {reason}")]
+  InvalidStrokes { reason: String },
+
+  /// クエリパラメーターの値が不正 (数値でない・範囲外・未知の値など)
+  #[error("Invalid query parameter '{field}': {reason}")]
+  InvalidParam { field: &'static str, reason: String },
+}
+
+impl From<ValidationError> for AppError {
+  fn from(e: ValidationError) -> Self {
+    AppError::Validation(e.to_string())
+  }
+}
+
+/// ユーザー名を正規化・検証する
+/// 改行は許可しない
+pub fn normalize_user_name(input: &str) -> Result<String, ValidationError> {
+  normalize(
+    input,
+    Rule {
+      field: "User name",
+      max_graphemes: USER_NAME_MAX_GRAPHEMES,
+      max_chars: USER_NAME_MAX_CHARS,
+      max_lines: 1,
+    },
+  )
+}
+
+/// 書き初め内容を正規化・検証する
+/// 改行は `CONTENT_MAX_LINES` 行まで許可する
+pub fn normalize_content(input: &str) -> Result<String, ValidationError> {
+  normalize(
+    input,
+    Rule {
+      field: "Content",
+      max_graphemes: CONTENT_MAX_GRAPHEMES,
+      max_chars: CONTENT_MAX_CHARS,
+      max_lines: CONTENT_MAX_LINES,
+    },
+  )
+}
+
+/// 検索語を正規化・検証する
+/// 書き初め内容と同じ文字数まで、改行は許可しない
+pub fn normalize_search_query(input: &str) -> Result<String, ValidationError> {
+  normalize(
+    input,
+    Rule {
+      field: "Query",
+      max_graphemes: CONTENT_MAX_GRAPHEMES,
+      max_chars: CONTENT_MAX_CHARS,
+      max_lines: 1,
+    },
+  )
+}
+
+/// 筆跡データを正規化・検証する
+///
+/// 座標は0.1px、筆圧は0.01単位に丸めてから、大きさ・点の数・時刻の順序とJSONのバイト数を検証する。
+pub fn normalize_strokes(input: Strokes) -> Result<Strokes, ValidationError> {
+  let invalid = |reason: String| ValidationError::InvalidStrokes { reason };
+
+  if input.version != STROKES_FORMAT_VERSION {
+    return Err(invalid(format!("unsupported version {}", input.version)));
+  }
+  let dimensions = 1..=STROKES_MAX_DIMENSION;
+  if !dimensions.contains(&input.width) || !dimensions.contains(&input.height) {
+    return Err(invalid(format!(
+      "width and height must be between 1 and {}",
+      STROKES_MAX_DIMENSION
+    )));
+  }
+  if input.strokes.is_empty() || input.strokes.len() > STROKES_MAX_STROKES {
+    return Err(invalid(format!(
+      "must have between 1 and {} strokes",
+      STROKES_MAX_STROKES
+    )));
+  }
+  if input
+    .strokes
+    .iter()
+    .any(|stroke| stroke.is_empty() || stroke.len() > STROKES_MAX_STROKE_POINTS)
+  {
+    return Err(invalid(format!(
+      "each stroke must have between 1 and {} points",
+      STROKES_MAX_STROKE_POINTS
+    )));
+  }
+  if input.point_count() > STROKES_MAX_POINTS {
+    return Err(invalid(format!("must have {} points or less", STROKES_MAX_POINTS)));
+  }
+
+  let (width, height) = (input.width as f32, input.height as f32);
+  let mut last_t = 0;
+  let mut strokes = Vec::with_capacity(input.strokes.len());
+  for stroke in input.strokes {
+    let mut points = Vec::with_capacity(stroke.len());
+    for StrokePoint(x, y, pressure, t) in stroke {
+      // NaN・無限大は範囲の比較で弾かれる
+      if !(0.0..=width).contains(&x) || !(0.0..=height).contains(&y) {
+        return Err(invalid("points must be inside the canvas".to_string()));
+      }
+      if !(0.0..=1.0).contains(&pressure) {
+        return Err(invalid("pressure must be between 0 and 1".to_string()));
+      }
+      if t < last_t || t > STROKES_MAX_DURATION_MS {
+        return Err(invalid(format!(
+          "timestamps must be in order and {} ms or less",
+          STROKES_MAX_DURATION_MS
+        )));
+      }
+      last_t = t;
+      points.push(StrokePoint(round_to(x, 10.0), round_to(y, 10.0), round_to(pressure, 100.0), t));
+    }
+    strokes.push(points);
+  }
+
+  let normalized = Strokes { strokes, ..input };
+  let bytes = serde_json::to_vec(&normalized).map_or(usize::MAX, |json| json.len());
+  if bytes > STROKES_MAX_BYTES {
+    return Err(invalid(format!("must be {} bytes or less", STROKES_MAX_BYTES)));
+  }
+  Ok(normalized)
+}
+
+fn round_to(value: f32, scale: f32) -> f32 {
+  (value * scale).round() / scale
+}
+
+/// 1以上 `max` 以下の整数のクエリパラメーターを解釈する (未指定なら `default`)
+pub fn parse_bounded_int(
+  field: &'static str,
+  value: Option<&str>,
+  default: u32,
+  max: u32,
+) -> Result<u32, ValidationError> {
+  let Some(value) = value else {
+    return Ok(default);
+  };
+  match value.trim().parse::<u32>() {
+    Ok(n) if (1..=max).contains(&n) => Ok(n),
+    _ => Err(ValidationError::InvalidParam {
+      field,
+      reason: format!("must be an integer between 1 and {}", max),
+    }),
+  }
+}
+
+/// ユーザーが認識する文字数 (書記素クラスタ数) を数える
+pub fn grapheme_count(s: &str) -> usize {
+  s.graphemes(true).count()
+}
+
+/// フィールドごとの検証ルール
+struct Rule {
+  field: &'static str,
+  max_graphemes: usize,
+  max_chars: usize,
+  max_lines: usize,
+}
+
+/// 正規化と検証の共通処理
+///
+/// 1. 改行コードを LF に統一
+/// 2. 前後の空白 (全角スペース含む) と各行末の空白を除去
+/// 3. NFC 正規化
+/// 4. 禁止文字・空・行数・文字数のチェック
+fn normalize(input: &str, rule: Rule) -> Result<String, ValidationError> {
+  let trimmed = input
+    .replace("\r\n", "\n")
+    .replace('\r', "\n")
+    .split('\n')
+    .map(str::trim_end)
+    .collect::<Vec<_>>()
+    .join("\n");
+  let normalized: String = trimmed.trim().nfc().collect();
+
+  if let Some(c) = normalized
+    .chars()
+    .find(|&c| is_disallowed(c) || (c == '\n' && rule.max_lines <= 1))
+  {
+    return Err(ValidationError::DisallowedChar {
+      field: rule.field,
+      code: c as u32,
+    });
+  }
+
+  if normalized.is_empty() {
+    return Err(ValidationError::Empty { field: rule.field });
+  }
+  if normalized.split('\n').count() > rule.max_lines {
+    return Err(ValidationError::TooManyLines {
+      field: rule.field,
+      max: rule.max_lines,
+    });
+  }
+  if grapheme_count(&normalized) > rule.max_graphemes {
+    return Err(ValidationError::TooLong {
+      field: rule.field,
+      max: rule.max_graphemes,
+    });
+  }
+  if normalized.chars().count() > rule.max_chars {
+    return Err(ValidationError::TooManyCodePoints { field: rule.field });
+  }
+
+  Ok(normalized)
+}
+
+/// 入力として受け付けない文字か判定する
+/// - 改行以外の制御文字 (C0/C1)
+/// - 双方向テキストの埋め込み・上書き・分離 (Trojan Source対策)
+fn is_disallowed(c: char) -> bool {
+  (c.is_control() && c != '\n')
+    || matches!(
+      c,
+      '\u{061C}' // ARABIC LETTER MARK
+        | '\u{200E}' // LEFT-TO-RIGHT MARK
+        | '\u{200F}' // RIGHT-TO-LEFT MARK
+        | '\u{202A}'..='\u{202E}' // LRE, RLE, PDF, LRO, RLO
+        | '\u{2066}'..='\u{2069}' // LRI, RLI, FSI, PDI
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use proptest::prelude::*;
+  use unicode_normalization::is_nfc;
+
+  /// 書記素クラスタ単位で数えられることのテスト
+  #[test]
+  fn test_grapheme_count() {
+    assert_eq!(grapheme_count("👨‍👩‍👧"), 1);
+    assert_eq!(grapheme_count("葛\u{E0100}"), 1); // 異体字セレクタ付き
+    assert_eq!(grapheme_count("🇯🇵"), 1);
+    assert_eq!(grapheme_count("謹賀新年"), 4);
+  }
+
+  /// 絵文字を20個並べたユーザー名は許可されること
+  #[test]
+  fn test_user_name_counts_graphemes() {
+    let name = "👨‍👩‍👧".repeat(USER_NAME_MAX_GRAPHEMES);
+    assert!(name.chars().count() > USER_NAME_MAX_GRAPHEMES);
+    assert_eq!(normalize_user_name(&name), Ok(name));
+
+    let too_long = "👨‍👩‍👧".repeat(USER_NAME_MAX_GRAPHEMES + 1);
+    assert!(matches!(
+      normalize_user_name(&too_long),
+      Err(ValidationError::TooLong { .. })
+    ));
+  }
+
+  /// 前後の空白除去と空文字の拒否
+  #[test]
+  fn test_trim_and_empty() {
+    assert_eq!(normalize_user_name("　太郎 "), Ok("太郎".to_string()));
+    assert!(matches!(
+      normalize_user_name(" \u{3000} "),
+      Err(ValidationError::Empty { .. })
+    ));
+    assert_eq!(
+      normalize_content("\r\n一富士 \r\n二鷹\n\n"),
+      Ok("一富士\n二鷹".to_string())
+    );
+  }
+
+  /// 改行・行数の制限
+  #[test]
+  fn test_newlines() {
+    assert!(matches!(
+      normalize_user_name("太\n郎"),
+      Err(ValidationError::DisallowedChar { code: 0x0A, .. })
+    ));
+    let lines = ["あ"; CONTENT_MAX_LINES + 1].join("\n");
+    assert!(matches!(
+      normalize_content(&lines),
+      Err(ValidationError::TooManyLines { .. })
+    ));
+  }
+
+  /// 制御文字・双方向テキスト制御文字の拒否
+  #[test]
+  fn test_disallowed_chars() {
+    assert!(normalize_user_name("abc\u{202E}def").is_err());
+    assert!(normalize_content("abc\u{2066}def").is_err());
+    assert!(normalize_content("abc\u{0007}def").is_err());
+    assert!(normalize_content("abc\tdef").is_err());
+  }
+
+  /// NFC正規化されること (濁点の結合文字 → 合成済み文字)
+  #[test]
+  fn test_nfc() {
+    assert_eq!(normalize_content("か\u{3099}"), Ok("が".to_string()));
+  }
+
+  /// コードポイント数上限 (結合文字の過剰な連結)
+  #[test]
+  fn test_too_many_code_points() {
+    let zalgo = format!("a{}", "\u{0301}".repeat(CONTENT_MAX_CHARS + 1));
+    assert!(matches!(
+      normalize_content(&zalgo),
+      Err(ValidationError::TooManyCodePoints { .. })
+    ));
+  }
+
+  /// 数値パラメーターの範囲チェック
+  #[test]
+  fn test_parse_bounded_int() {
+    assert_eq!(parse_bounded_int("page", None, 1, 100), Ok(1));
+    assert_eq!(parse_bounded_int("page", Some("3"), 1, 100), Ok(3));
+    for invalid in ["0", "101", "-1", "abc", ""] {
+      assert!(matches!(
+        parse_bounded_int("page", Some(invalid), 1, 100),
+        Err(ValidationError::InvalidParam { field: "page", .. })
+      ));
+    }
+  }
+
+  fn strokes(points: Vec<Vec<StrokePoint>>) -> Strokes {
+    Strokes {
+      version: STROKES_FORMAT_VERSION,
+      width: 600,
+      height: 800,
+      strokes: points,
+    }
+  }
+
+  /// 筆跡の座標・筆圧は丸められること
+  #[test]
+  fn test_normalize_strokes() {
+    let input = strokes(vec![
+      vec![StrokePoint(10.04, 20.06, 0.123, 0), StrokePoint(11.0, 21.0, 0.5, 16)],
+      vec![StrokePoint(600.0, 800.0, 1.0, 16)],
+    ]);
+    let normalized = normalize_strokes(input).unwrap();
+    assert_eq!(normalized.strokes[0][0], StrokePoint(10.0, 20.1, 0.12, 0));
+    assert_eq!(normalized.point_count(), 3);
+  }
+
+  /// 形式・大きさ・点の数・時刻の順序の検証
+  #[test]
+  fn test_normalize_strokes_invalid() {
+    let point = StrokePoint(1.0, 1.0, 0.5, 0);
+    let invalid = [
+      Strokes {
+        version: 2,
+        ..strokes(vec![vec![point]])
+      },
+      Strokes {
+        width: 0,
+        ..strokes(vec![vec![point]])
+      },
+      Strokes {
+        height: STROKES_MAX_DIMENSION + 1,
+        ..strokes(vec![vec![point]])
+      },
+      strokes(vec![]),
+      strokes(vec![vec![]]),
+      strokes(vec![vec![point]; STROKES_MAX_STROKES + 1]),
+      strokes(vec![vec![point; STROKES_MAX_STROKE_POINTS + 1]]),
+      strokes(vec![vec![point; STROKES_MAX_STROKE_POINTS]; STROKES_MAX_POINTS / STROKES_MAX_STROKE_POINTS + 1]),
+      strokes(vec![vec![StrokePoint(601.0, 1.0, 0.5, 0)]]),
+      strokes(vec![vec![StrokePoint(f32::NAN, 1.0, 0.5, 0)]]),
+      strokes(vec![vec![StrokePoint(1.0, 1.0, 1.5, 0)]]),
+      strokes(vec![vec![StrokePoint(1.0, 1.0, 0.5, 10)], vec![StrokePoint(1.0, 1.0, 0.5, 5)]]),
+      strokes(vec![vec![StrokePoint(1.0, 1.0, 0.5, STROKES_MAX_DURATION_MS + 1)]]),
+    ];
+    for input in invalid {
+      assert!(
+        matches!(normalize_strokes(input.clone()), Err(ValidationError::InvalidStrokes { .. })),
+        "{:?}",
+        input.strokes.first().and_then(|s| s.first())
+      );
+    }
+  }
+
+  proptest! {
+    /// 正規化は冪等であること
+    #[test]
+    fn prop_idempotent(s in "\\PC{0,60}") {
+      if let Ok(once) = normalize_content(&s) {
+        prop_assert_eq!(normalize_content(&once), Ok(once.clone()));
+      }
+    }
+
+    /// 成功した出力はDBのCHECK制約 (NFC, コードポイント数) を満たすこと
+    #[test]
+    fn prop_output_satisfies_db_constraints(s in any::<String>()) {
+      if let Ok(out) = normalize_content(&s) {
+        prop_assert!(is_nfc(&out));
+        prop_assert!(out.chars().count() <= CONTENT_MAX_CHARS);
+        prop_assert!(grapheme_count(&out) <= CONTENT_MAX_GRAPHEMES);
+        prop_assert!(!out.chars().any(is_disallowed));
+        prop_assert_eq!(out.trim(), out.as_str());
+      }
+      if let Ok(out) = normalize_user_name(&s) {
+        prop_assert!(is_nfc(&out));
+        prop_assert!(out.chars().count() <= USER_NAME_MAX_CHARS);
+        prop_assert!(!out.contains('\n'));
+      }
+    }
+  }
+}
@@ -3,10 +3,31 @@ use axum::{
   http::{Request, StatusCode},
 };
 use http_body_util::BodyExt; // for collect
-use server::create_app;
+use server::{config::Config, create_app};
 use sqlx::postgres::PgPoolOptions;
 use tower::ServiceExt; // for oneshot
 
+/// レスポンスのSet-Cookieから、次のリクエストに付けるCookieヘッダーの値を作る (削除されたCookieは除く)
+fn cookies_of(response: &axum::response::Response) -> String {
+  response
+    .headers()
+    .get_all("set-cookie")
+    .iter()
+    .map(|value| value.to_str().unwrap().split(';').next().unwrap().trim().to_string())
+    .filter(|pair| !pair.ends_with('='))
+    .collect::<Vec<_>>()
+    .join("; ")
+}
+
+/// Cookieヘッダーの値に含まれるCSRFトークン (状態を変更するリクエストの `X-CSRF-Token` ヘッダーに付ける)
+fn csrf_token(cookie: &str) -> String {
+  cookie
+    .split(';')
+    .find_map(|pair| pair.trim().strip_prefix("calli_csrf="))
+    .expect("CSRF cookie missing")
+    .to_string()
+}
+
 #[tokio::test]
 async fn test_calligraphy_scenario() {
   // 1. Setup
@@ -18,7 +39,7 @@ async fn test_calligraphy_scenario() {
     .expect("Failed to connect to DB");
 
   // アプリケーションの作成（状態を持つため、リクエストごとにクローンして使う）
-  let app = create_app(pool);
+  let app = create_app(pool, Config::default());
 
   // --- Step 1: 新規作成 (POST) ---
   let response = app
@@ -37,7 +58,7 @@ async fn test_calligraphy_scenario() {
   assert_eq!(response.status(), StatusCode::OK);
 
   // レスポンスからCookieを取得 (認証用)
-  let cookie_header = response.headers().get("set-cookie").unwrap().clone();
+  let cookie_header = cookies_of(&response);
 
   // レスポンスボディからIDを取得
   let body = response.into_body().collect().await.unwrap().to_bytes();
@@ -58,7 +79,7 @@ async fn test_calligraphy_scenario() {
       Request::builder()
         .method("GET")
         .uri("/api/calligraphy/me")
-        .header("Cookie", cookie_header.to_str().unwrap()) // Cookieをセット
+        .header("Cookie", &cookie_header) // Cookieをセット
         .body(Body::empty())
         .unwrap(),
     )
@@ -75,6 +96,348 @@ async fn test_calligraphy_scenario() {
 
   println!("Step 2: Fetched successfully");
 
+  // --- Step 2.5: 条件付きGET (変更がなければ 304 Not Modified) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie_header)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let etag = response.headers()["etag"].clone();
+
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie_header)
+        .header("If-None-Match", etag)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  assert!(body.is_empty());
+
+  println!("Step 2.5: Conditional GET returned 304");
+
+  // --- Step 2.6: 条件を指定した一覧取得 (自分の書き初めのみ) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy?mine_only=true&sort=oldest")
+        .header("Cookie", &cookie_header)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let list_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert_eq!(list_json.as_array().unwrap().len(), 1);
+  assert_eq!(list_json[0]["is_mine"], true);
+
+  // 未知の値は400 (JSONのエラーメッセージ付き)
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy?sort=most_reacted")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let error_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert!(error_json["error"].as_str().unwrap().contains("sort"));
+  assert_eq!(error_json["code"], "validation");
+
+  println!("Step 2.6: Filtered list");
+
+  // --- Step 2.7: 個人データのエクスポート ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/me/export")
+        .header("Cookie", &cookie_header)
+        .header("User-Agent", "ExportTest/1.0")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(response.headers().get("content-disposition").is_some());
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let export_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert_eq!(export_json["calligraphy"]["public_id"], created_json["public_id"]);
+  assert_eq!(export_json["calligraphy"]["content"], "Integration Test Scenario");
+
+  println!("Step 2.7: Exported personal data");
+
+  // --- Step 2.8: 集計 (個々のリクエスト情報は含まれない) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/stats")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let stats_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert!(stats_json["total_entries"].as_u64().unwrap() >= 1);
+  assert_eq!(stats_json["new_year"]["daily"].as_array().unwrap().len(), 14);
+  for field in ["ip_address", "user_agent", "accept_language", "user_id"] {
+    assert!(!String::from_utf8_lossy(&body).contains(field), "{}", field);
+  }
+
+  println!("Step 2.8: Fetched board stats");
+
+  // --- Step 2.9: フィード (Cookieなし, 条件付きGETに対応) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/feed.atom")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(response.headers()["content-type"].to_str().unwrap().starts_with("application/atom+xml"));
+  assert!(response.headers().get("set-cookie").is_none());
+  let feed_etag = response.headers()["etag"].clone();
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let atom = String::from_utf8(body.to_vec()).unwrap();
+  assert!(atom.contains(&format!("<id>urn:uuid:{}</id>", created_json["public_id"].as_str().unwrap())));
+
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/feed.atom")
+        .header("If-None-Match", feed_etag)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+  println!("Step 2.9: Fetched feed");
+
+  // --- Step 2.10: OGP画像 (フォントがなければサイト共通の画像へリダイレクト) ---
+  let public_id = created_json["public_id"].as_str().unwrap();
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri(format!("/api/calligraphy/{}/ogp.png", public_id))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  match response.status() {
+    StatusCode::OK => {
+      assert_eq!(response.headers()["content-type"], "image/png");
+      assert!(response.headers().get("etag").is_some());
+    }
+    StatusCode::TEMPORARY_REDIRECT => assert_eq!(response.headers()["location"], "/ogp.png"),
+    status => panic!("unexpected status: {}", status),
+  }
+
+  for uri in [
+    "/api/calligraphy/not-a-uuid/ogp.png".to_string(),
+    format!("/api/calligraphy/{}/ogp.png", uuid::Uuid::new_v4()),
+  ] {
+    let response = app
+      .clone()
+      .oneshot(Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  }
+
+  println!("Step 2.10: Fetched OGP image");
+
+  // --- Step 2.11: 縦書きのSVG (Cookieなし, 条件付きGETに対応) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri(format!("/api/calligraphy/{}.svg", public_id))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(response.headers()["content-type"].to_str().unwrap().starts_with("image/svg+xml"));
+  assert!(response.headers().get("set-cookie").is_none());
+  let svg_etag = response.headers()["etag"].clone();
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let svg = String::from_utf8(body.to_vec()).unwrap();
+  assert!(svg.starts_with("<svg"));
+  assert!(svg.contains("Integration Test Scenario"));
+
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri(format!("/api/calligraphy/{}.svg", public_id))
+        .header("If-None-Match", svg_etag)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+  for uri in ["/api/calligraphy/not-a-uuid.svg".to_string(), format!("/api/calligraphy/{}", public_id)] {
+    let response = app
+      .clone()
+      .oneshot(Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  }
+
+  println!("Step 2.11: Fetched SVG");
+
+  // --- Step 2.12: 手書きの筆跡 (保存・取得・描画, 省略して更新すると削除) ---
+  let strokes = r#"{ "version": 1, "width": 200, "height": 300, "strokes": [[[10, 10, 0.5, 0], [190, 290.04, 0.8, 120]], [[100, 150, 0.3, 400]]] }"#;
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Content-Type", "application/json")
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
+        .body(Body::from(format!(
+          r#"{{ "user_name": "Test User", "content": "Integration Test Scenario", "strokes": {} }}"#,
+          strokes
+        )))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri(format!("/api/calligraphy/{}/strokes", public_id))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let strokes_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert_eq!(strokes_json["version"], 1);
+  assert_eq!(strokes_json["strokes"][0][1][1].as_f64().unwrap() as f32, 290.0); // 0.1px単位に丸める
+
+  for (path, content_type) in [("strokes.svg", "image/svg+xml"), ("strokes.png", "image/png")] {
+    let response = app
+      .clone()
+      .oneshot(
+        Request::builder()
+          .method("GET")
+          .uri(format!("/api/calligraphy/{}/{}", public_id, path))
+          .body(Body::empty())
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with(content_type));
+  }
+
+  // 不正な筆跡は400
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Content-Type", "application/json")
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
+        .body(Body::from(
+          r#"{ "user_name": "Test User", "content": "Integration Test Scenario", "strokes": { "version": 1, "width": 200, "height": 300, "strokes": [[[500, 10, 0.5, 0]]] } }"#,
+        ))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+  // 筆跡を省略して更新すると筆跡は削除される
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Content-Type", "application/json")
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
+        .body(Body::from(r#"{ "user_name": "Test User", "content": "Integration Test Scenario"}"#))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri(format!("/api/calligraphy/{}/strokes", public_id))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+  println!("Step 2.12: Stored and rendered strokes");
+
   // --- Step 3: 削除 (DELETE) ---
   let response = app
     .clone()
@@ -82,7 +445,8 @@ async fn test_calligraphy_scenario() {
       Request::builder()
         .method("DELETE")
         .uri("/api/calligraphy/me")
-        .header("Cookie", cookie_header.to_str().unwrap())
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
         .body(Body::empty())
         .unwrap(),
     )
@@ -99,7 +463,7 @@ async fn test_calligraphy_scenario() {
       Request::builder()
         .method("GET")
         .uri("/api/calligraphy/me")
-        .header("Cookie", cookie_header.to_str().unwrap())
+        .header("Cookie", &cookie_header)
         .body(Body::empty())
         .unwrap(),
     )
@@ -110,3 +474,1560 @@ async fn test_calligraphy_scenario() {
   assert_eq!(response.status(), StatusCode::NOT_FOUND);
   println!("Step 4: Confirmed deletion (404)");
 }
+
+/// multipart/form-data のボディを組み立てる ((項目名, ファイル名, 内容) の列)
+fn multipart_body(boundary: &str, fields: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+  let mut body = Vec::new();
+  for (name, file_name, content) in fields {
+    body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+    match file_name {
+      Some(file_name) => body.extend_from_slice(
+        format!(
+          "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+          name, file_name
+        )
+        .as_bytes(),
+      ),
+      None => body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes()),
+    }
+    body.extend_from_slice(content);
+    body.extend_from_slice(b"\r\n");
+  }
+  body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+  body
+}
+
+#[tokio::test]
+async fn test_photo_upload() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let photo_dir = std::env::temp_dir().join(format!("photo-upload-test-{}", uuid::Uuid::new_v4().simple()));
+  let config = Config {
+    photo_dir: Some(photo_dir.to_string_lossy().into_owned()),
+    ..Config::default()
+  };
+  let app = create_app(pool, config);
+
+  let mut png = Vec::new();
+  image::DynamicImage::ImageRgb8(image::RgbImage::new(64, 48))
+    .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+    .unwrap();
+  let boundary = "calligraphy-boundary";
+  let post = |body: Vec<u8>, cookie: Option<&str>| {
+    let mut request = Request::builder()
+      .method("POST")
+      .uri("/api/calligraphy")
+      .header("Content-Type", format!("multipart/form-data; boundary={}", boundary));
+    if let Some(cookie) = cookie {
+      request = request.header("Cookie", cookie).header("X-CSRF-Token", csrf_token(cookie));
+    }
+    request.body(Body::from(body)).unwrap()
+  };
+  let get = |uri: &str| Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap();
+
+  // --- Step 1: 写真を添付して投稿 (multipart/form-data) ---
+  let response = app
+    .clone()
+    .oneshot(post(
+      multipart_body(
+        boundary,
+        &[
+          ("user_name", None, "写真の人".as_bytes()),
+          ("content", None, "書き初め".as_bytes()),
+          ("photo", Some("kakizome.png"), &png),
+        ],
+      ),
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let cookie = cookies_of(&response);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert_eq!(created["content"], "書き初め");
+  assert_eq!(created["photo"]["width"], 64);
+  assert_eq!(created["photo"]["height"], 48);
+  let photo_url = created["photo"]["url"].as_str().unwrap().to_string();
+  assert!(photo_url.starts_with("/api/photos/") && photo_url.ends_with(".jpg"));
+  println!("Step 1: Uploaded photo");
+
+  // --- Step 2: 写真の取得 (内容が変わらないため長期間キャッシュできる) ---
+  let response = app.clone().oneshot(get(&photo_url)).await.unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()["content-type"], "image/jpeg");
+  assert!(response.headers()["cache-control"].to_str().unwrap().contains("immutable"));
+  let etag = response.headers()["etag"].clone();
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  assert_eq!(&body[..3], &[0xFF, 0xD8, 0xFF]);
+
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri(&photo_url)
+        .header("If-None-Match", etag)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+  let thumbnail_url = created["photo"]["thumbnail_url"].as_str().unwrap();
+  let response = app.clone().oneshot(get(thumbnail_url)).await.unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let response = app.clone().oneshot(get("/api/photos/not-a-photo.jpg")).await.unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  println!("Step 2: Fetched photo");
+
+  // --- Step 3: 不正な写真・項目 ---
+  let response = app
+    .clone()
+    .oneshot(post(
+      multipart_body(
+        boundary,
+        &[
+          ("user_name", None, "写真の人".as_bytes()),
+          ("content", None, "書き初め".as_bytes()),
+          ("photo", Some("kakizome.gif"), b"GIF89a...."),
+        ],
+      ),
+      Some(&cookie),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+  let response = app
+    .clone()
+    .oneshot(post(
+      multipart_body(
+        boundary,
+        &[("user_name", None, "写真の人".as_bytes()), ("unknown", None, b"?")],
+      ),
+      Some(&cookie),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  println!("Step 3: Rejected invalid uploads");
+
+  // --- Step 4: 削除すると写真も配信されなくなる ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("DELETE")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie)
+        .header("X-CSRF-Token", csrf_token(&cookie))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  let response = app.clone().oneshot(get(&photo_url)).await.unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  println!("Step 4: Deleted photo");
+
+  std::fs::remove_dir_all(&photo_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_calligraphy_stream() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let app = create_app(pool, Config::default());
+
+  // --- Step 1: ストリームの購読開始 (Cookieも発行される) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/stream")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()["content-type"], "text/event-stream");
+  let cookie_header = cookies_of(&response);
+  let mut body = response.into_body();
+
+  // --- Step 2: 同じCookieで投稿 ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Content-Type", "application/json")
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
+        .body(Body::from(r#"{ "user_name": "Stream User", "content": "SSE Test"}"#))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+
+  // --- Step 3: createdイベントを受信 (自分の投稿なので is_mine: true) ---
+  let frame = next_event(&mut body).await;
+  assert!(frame.contains("event: created"), "unexpected frame: {frame}");
+  assert!(frame.contains("\"content\":\"SSE Test\""));
+  assert!(frame.contains("\"is_mine\":true"));
+
+  // --- Step 4: 削除するとdeletedイベントを受信 ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("DELETE")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+  let frame = next_event(&mut body).await;
+  assert!(frame.contains("event: deleted"), "unexpected frame: {frame}");
+  assert!(frame.contains("\"public_id\""));
+}
+
+/// SSEのボディから次のイベント (keep-aliveのコメント以外) を読み出す
+async fn next_event(body: &mut Body) -> String {
+  loop {
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(5), body.frame())
+      .await
+      .expect("Timed out waiting for SSE event")
+      .expect("Stream ended")
+      .unwrap();
+    if let Ok(data) = frame.into_data() {
+      let text = String::from_utf8(data.to_vec()).unwrap();
+      if !text.starts_with(':') {
+        return text;
+      }
+    }
+  }
+}
+
+#[tokio::test]
+async fn test_live_board_websocket() {
+  use futures_util::{SinkExt, StreamExt};
+  use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let app = create_app(pool, Config::default());
+
+  // WebSocketはoneshotでは扱えないため、実際にサーバーを起動する
+  let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let addr = listener.local_addr().unwrap();
+  let server = app.clone();
+  tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+
+  // 接続時に発行されたセッションのCookieも返す (その後の投稿を同じユーザーにする)
+  let connect = || async move {
+    let mut request = format!("ws://{addr}/api/ws").into_client_request().unwrap();
+    request.headers_mut().insert("origin", "http://localhost".parse().unwrap());
+    let (socket, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+    let cookie = response
+      .headers()
+      .get_all("set-cookie")
+      .iter()
+      .map(|value| value.to_str().unwrap().split(';').next().unwrap().to_string())
+      .collect::<Vec<_>>()
+      .join("; ");
+    assert!(cookie.contains("calli_session="));
+    (socket, cookie)
+  };
+  // 条件を満たすJSONメッセージが届くまで読み進める
+  async fn wait_for<S>(socket: &mut S, pred: impl Fn(&serde_json::Value) -> bool) -> serde_json::Value
+  where
+    S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+  {
+    loop {
+      let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+        .await
+        .expect("Timed out waiting for message")
+        .expect("Connection closed")
+        .unwrap();
+      if let Message::Text(text) = message {
+        let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+        if pred(&json) {
+          return json;
+        }
+      }
+    }
+  }
+
+  // --- Step 0: 許可していないオリジン・Originなしのハンドシェイクはセッションを発行せずに拒否される ---
+  for origin in [Some("https://evil.example"), None] {
+    let mut request = format!("ws://{addr}/api/ws").into_client_request().unwrap();
+    if let Some(origin) = origin {
+      request.headers_mut().insert("origin", origin.parse().unwrap());
+    }
+    match tokio_tungstenite::connect_async(request).await {
+      Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response.headers().get("set-cookie").is_none());
+      }
+      other => panic!("unexpected: {:?}", other.map(|(_, response)| response.status())),
+    }
+  }
+
+  // --- Step 1: 接続すると在室人数が届く ---
+  let (mut socket_a, cookie_a) = connect().await;
+  wait_for(&mut socket_a, |m| m["type"] == "presence" && m["online"] == 1).await;
+
+  let (mut socket_b, _) = connect().await;
+  wait_for(&mut socket_a, |m| m["type"] == "presence" && m["online"] == 2).await;
+
+  // --- Step 2: 入力中の通知 ---
+  socket_b
+    .send(Message::Text(r#"{"type":"typing","active":true}"#.into()))
+    .await
+    .unwrap();
+  wait_for(&mut socket_a, |m| m["type"] == "presence" && m["typing"] == 1).await;
+
+  // --- Step 3: 投稿が配信され、is_mine は接続ごとに計算される ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Content-Type", "application/json")
+        .header("Cookie", &cookie_a)
+        .header("X-CSRF-Token", csrf_token(&cookie_a))
+        .body(Body::from(r#"{ "user_name": "WS User", "content": "WebSocket Test"}"#))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+
+  let created = wait_for(&mut socket_a, |m| m["type"] == "created").await;
+  assert_eq!(created["entry"]["content"], "WebSocket Test");
+  assert_eq!(created["entry"]["is_mine"], true);
+  let created = wait_for(&mut socket_b, |m| m["type"] == "created").await;
+  assert_eq!(created["entry"]["is_mine"], false);
+
+  // 後始末
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("DELETE")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie_a)
+        .header("X-CSRF-Token", csrf_token(&cookie_a))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+  // --- Step 4: メッセージを送りすぎると切断される (バイナリのフレームも数える) ---
+  for _ in 0..30 {
+    if socket_b
+      .send(Message::Binary(vec![0; 16]))
+      .await
+      .is_err()
+    {
+      break;
+    }
+  }
+  let close_frame = loop {
+    match tokio::time::timeout(std::time::Duration::from_secs(5), socket_b.next())
+      .await
+      .expect("Timed out waiting for close")
+    {
+      Some(Ok(Message::Close(frame))) => break frame,
+      Some(Ok(_)) => continue,
+      other => panic!("unexpected: {other:?}"),
+    }
+  };
+  assert_eq!(u16::from(close_frame.unwrap().code), 1008);
+
+  // B が切断されると在室人数が1に戻る
+  wait_for(&mut socket_a, |m| m["type"] == "presence" && m["online"] == 1).await;
+}
+
+#[tokio::test]
+async fn test_identity_transfer() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let app = create_app(pool, Config::default());
+
+  let request = |method: &str, uri: &str, cookie: Option<&str>, body: Option<String>| {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(cookie) = cookie {
+      builder = builder.header("Cookie", cookie);
+      if method != "GET" {
+        builder = builder.header("X-CSRF-Token", csrf_token(cookie));
+      }
+    }
+    match body {
+      Some(body) => builder
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  };
+  let claim_body = |code: &str| Some(serde_json::json!({ "code": code }).to_string());
+
+  // --- Step 1: 元のブラウザで投稿し、引き継ぎコードを発行 ---
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/calligraphy",
+      None,
+      Some(r#"{ "user_name": "引き継ぎ", "content": "機種変更" }"#.to_string()),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let old_cookie = cookies_of(&response);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/identity/transfer-code", Some(&old_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()["cache-control"], "no-store");
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let issued: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  let code = issued["code"].as_str().unwrap().to_string();
+  assert_eq!(code.len(), 11);
+
+  // 書き初めのないブラウザでは発行できない
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/identity/transfer-code", None, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+  // --- Step 2: 新しいブラウザでコードを使う (小文字でも受け付ける) ---
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/identity/claim", None, claim_body(&code.to_lowercase())))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let new_cookie = cookies_of(&response);
+  assert_ne!(new_cookie, old_cookie);
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let claimed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert_eq!(claimed["public_id"], created["public_id"]);
+  assert_eq!(claimed["is_mine"], true);
+
+  // --- Step 3: 新しいCookieで自分の書き初めとして取得でき、元のCookieでは取得できない ---
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", Some(&new_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", Some(&old_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+  // --- Step 4: 使用済みのコードは使えない ---
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/identity/claim", None, claim_body(&code)))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+  // 既に書き初めのあるブラウザには引き継がない
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/identity/transfer-code", Some(&new_cookie), None))
+    .await
+    .unwrap();
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let issued: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/identity/claim",
+      Some(&new_cookie),
+      claim_body(issued["code"].as_str().unwrap()),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::CONFLICT);
+
+  // Cleanup
+  let response = app
+    .clone()
+    .oneshot(request("DELETE", "/api/calligraphy/me", Some(&new_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_passkey_login() {
+  use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+  use webauthn_rs::prelude::Url;
+
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  // 公開URL (`http://localhost`) がRPのオリジンになる
+  let config = Config::default();
+  let origin = Url::parse(&config.public_base_url).unwrap();
+  let app = create_app(pool, config);
+
+  let request = |method: &str, uri: &str, cookie: Option<&str>, body: Option<serde_json::Value>| {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(cookie) = cookie {
+      builder = builder.header("Cookie", cookie);
+      if method != "GET" {
+        builder = builder.header("X-CSRF-Token", csrf_token(cookie));
+      }
+    }
+    match body {
+      Some(body) => builder
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  };
+  let json = |response: axum::response::Response| async move {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+  };
+  // ハードウェアの代わりにソフトウェアの認証器を使う (ユーザー検証は済んだものとする)
+  let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+  // --- Step 1: 投稿したブラウザでパスキーを登録 ---
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/calligraphy",
+      None,
+      Some(serde_json::json!({ "user_name": "パスキー", "content": "鍵" })),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let user_cookie = cookies_of(&response);
+  let created = json(response).await;
+
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/register/start",
+      Some(&user_cookie),
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let started = json(response).await;
+  let credential = authenticator
+    .do_registration(
+      origin.clone(),
+      serde_json::from_value(started["options"].clone()).unwrap(),
+    )
+    .expect("Software authenticator failed to register");
+
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/register/finish",
+      Some(&user_cookie),
+      Some(serde_json::json!({ "ceremony_id": started["ceremony_id"], "credential": credential })),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::CREATED);
+  let passkey_id = json(response).await["id"].as_str().unwrap().to_string();
+
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/passkeys", Some(&user_cookie), None))
+    .await
+    .unwrap();
+  let list = json(response).await;
+  assert_eq!(list.as_array().unwrap().len(), 1);
+  assert_eq!(list[0]["id"], passkey_id.as_str());
+  assert!(list[0]["last_used_at"].is_null());
+
+  // 別のブラウザが開始した登録は完了できない
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/register/start",
+      Some(&user_cookie),
+      None,
+    ))
+    .await
+    .unwrap();
+  let other = json(response).await;
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/register/finish",
+      None,
+      Some(serde_json::json!({ "ceremony_id": other["ceremony_id"], "credential": credential })),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+  // --- Step 2: Cookieのない別のブラウザでパスキーでログイン ---
+  let login_start = serde_json::json!({ "public_id": created["public_id"] });
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/login/start",
+      None,
+      Some(login_start.clone()),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let started = json(response).await;
+  let real_credentials = started["options"]["publicKey"]["allowCredentials"].clone();
+  assert_eq!(real_credentials.as_array().unwrap().len(), 1);
+  let assertion = authenticator
+    .do_authentication(
+      origin.clone(),
+      serde_json::from_value(started["options"].clone()).unwrap(),
+    )
+    .expect("Software authenticator failed to authenticate");
+  let login_finish = serde_json::json!({ "ceremony_id": started["ceremony_id"], "credential": assertion });
+
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/login/finish",
+      None,
+      Some(login_finish.clone()),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let session_cookie = cookies_of(&response);
+  assert!(session_cookie.contains("calli_session="), "Session cookie should be set");
+  let logged_in = json(response).await;
+  assert_eq!(logged_in["public_id"], created["public_id"]);
+  assert_eq!(logged_in["is_mine"], true);
+
+  // 同じ認証の結果は2回使えない
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/passkeys/login/finish", None, Some(login_finish)))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+  // --- Step 3: セッションのCookieだけで自分の書き初めとして扱われる ---
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", Some(&session_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(json(response).await["public_id"], created["public_id"]);
+
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/passkeys", Some(&session_cookie), None))
+    .await
+    .unwrap();
+  assert!(!json(response).await[0]["last_used_at"].is_null());
+
+  // --- Step 4: ログアウトするとセッションは使えない ---
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/passkeys/logout", Some(&session_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", Some(&session_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+  // --- Step 5: パスキーを削除するとログインできない (開始は偽の資格情報IDで同じ形の200を返す) ---
+  let response = app
+    .clone()
+    .oneshot(request(
+      "DELETE",
+      &format!("/api/passkeys/{}", passkey_id),
+      Some(&user_cookie),
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  let login_start_without_passkeys = || {
+    let app = app.clone();
+    let login_start = login_start.clone();
+    async move {
+      let response = app
+        .oneshot(request("POST", "/api/passkeys/login/start", None, Some(login_start)))
+        .await
+        .unwrap();
+      assert_eq!(response.status(), StatusCode::OK);
+      let started = json(response).await;
+      assert!(started["ceremony_id"].is_string());
+      started["options"]["publicKey"]["allowCredentials"].clone()
+    }
+  };
+  let decoys = login_start_without_passkeys().await;
+  assert!(!decoys.as_array().unwrap().is_empty());
+  assert_ne!(decoys, real_credentials);
+
+  // --- Step 6: 書き初めを削除するとパスキーも削除される ---
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/register/start",
+      Some(&user_cookie),
+      None,
+    ))
+    .await
+    .unwrap();
+  let started = json(response).await;
+  let credential = authenticator
+    .do_registration(
+      origin.clone(),
+      serde_json::from_value(started["options"].clone()).unwrap(),
+    )
+    .expect("Software authenticator failed to register");
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/passkeys/register/finish",
+      Some(&user_cookie),
+      Some(serde_json::json!({ "ceremony_id": started["ceremony_id"], "credential": credential })),
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::CREATED);
+
+  let response = app
+    .clone()
+    .oneshot(request("DELETE", "/api/calligraphy/me", Some(&user_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/passkeys", Some(&user_cookie), None))
+    .await
+    .unwrap();
+  assert_eq!(json(response).await, serde_json::json!([]));
+  // 削除後も開始の応答は変わらない (同じ偽の資格情報ID)
+  assert_eq!(login_start_without_passkeys().await, decoys);
+}
+
+#[tokio::test]
+async fn test_sessions() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let app = create_app(pool.clone(), Config::default());
+
+  const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0";
+  const SAFARI: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Mobile/15E148 Safari/604.1";
+  let request = |method: &str, uri: &str, cookie: &str, user_agent: &str, body: Option<&str>| {
+    let mut builder = Request::builder()
+      .method(method)
+      .uri(uri)
+      .header("Cookie", cookie)
+      .header("User-Agent", user_agent);
+    // 以前のユーザーIDのCookieだけの場合はCSRFトークンを持たない
+    if method != "GET" && cookie.contains("calli_csrf=") {
+      builder = builder.header("X-CSRF-Token", csrf_token(cookie));
+    }
+    match body {
+      Some(body) => builder
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap(),
+      None => builder.body(Body::empty()).unwrap(),
+    }
+  };
+  // セッションを発行した場合、そのブラウザのCookie (セッションとCSRFトークン)
+  let session_cookie = |response: &axum::response::Response| {
+    let cookie = cookies_of(response);
+    cookie.contains("calli_session=").then_some(cookie)
+  };
+  let json = |response: axum::response::Response| async move {
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+  };
+
+  // --- Step 1: 以前のユーザーIDのCookieは、書き初めを持つユーザーなら一度だけセッションに移行される ---
+  // (セッションを導入する前に投稿したユーザー)
+  let user_id = uuid::Uuid::new_v4();
+  sqlx::query("INSERT INTO calligraphy (user_id, user_name, content) VALUES ($1, 'セッション', '端末')")
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+  let legacy = format!("calli_user_id={user_id}");
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", &legacy, SAFARI, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let phone = session_cookie(&response).expect("Session cookie should be issued");
+  assert!(response
+    .headers()
+    .get_all("set-cookie")
+    .iter()
+    .any(|value| value.to_str().unwrap().starts_with("calli_user_id=;")));
+  let created = json(response).await;
+
+  // 移行済みのユーザーIDのCookie (別の端末にコピーされたCookie) からはセッションを作れない
+  // (書き込みでは新しいユーザーとして扱われる)
+  let response = app
+    .clone()
+    .oneshot(request("POST", "/api/identity/transfer-code", &legacy, FIREFOX, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", &legacy, FIREFOX, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  assert!(session_cookie(&response).is_none());
+
+  // 書き初めのないユーザーIDのCookieからもセッションを作れない
+  let response = app
+    .clone()
+    .oneshot(request(
+      "GET",
+      "/api/calligraphy/me",
+      &format!("calli_user_id={}", uuid::Uuid::new_v4()),
+      FIREFOX,
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  assert!(session_cookie(&response).is_none());
+
+  // 同じユーザーの別の端末 (パスキーでログインした端末など)
+  let laptop_token = server::services::sessions::generate_token();
+  sqlx::query(
+    "INSERT INTO user_session (token_hash, user_id, device, expires_at) VALUES ($1, $2, 'Firefox / Linux', NOW() + INTERVAL '1 year')",
+  )
+  .bind(server::services::sessions::hash_token(&laptop_token))
+  .bind(user_id)
+  .execute(&pool)
+  .await
+  .unwrap();
+  let laptop = format!(
+    "calli_session={}; calli_csrf={}",
+    laptop_token,
+    server::csrf::derive_token(&laptop_token)
+  );
+
+  // 有効なセッションでは、セッションのCookieを発行し直さない
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", &laptop, FIREFOX, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(session_cookie(&response).is_none());
+
+  // --- Step 2: セッションの一覧 ---
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/sessions", &laptop, FIREFOX, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()["cache-control"], "no-store");
+  let sessions = json(response).await;
+  let sessions = sessions.as_array().unwrap();
+  assert_eq!(sessions.len(), 2);
+  let current: Vec<_> = sessions.iter().filter(|s| s["current"] == true).collect();
+  assert_eq!(current.len(), 1);
+  assert_eq!(current[0]["device"], "Firefox / Linux");
+  let other = sessions.iter().find(|s| s["current"] == false).unwrap();
+  assert_eq!(other["device"], "Safari / iOS");
+  assert!(other.get("token_hash").is_none());
+
+  // --- Step 3: 他の端末をログアウトさせる ---
+  let response = app
+    .clone()
+    .oneshot(request(
+      "DELETE",
+      &format!("/api/sessions/{}", other["id"].as_str().unwrap()),
+      &laptop,
+      FIREFOX,
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  assert!(response.headers().get("set-cookie").is_none());
+
+  // ログアウトさせられた端末は、新しいユーザーとして扱われる (読み込みだけではセッションを発行しない)
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", &phone, SAFARI, None))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  assert!(session_cookie(&response).is_none());
+
+  // 他のユーザーのセッションは削除できない (書き込みのため、新しいユーザーのセッションが発行される)
+  let response = app
+    .clone()
+    .oneshot(request(
+      "DELETE",
+      &format!("/api/sessions/{}", current[0]["id"].as_str().unwrap()),
+      &phone,
+      SAFARI,
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  let phone = session_cookie(&response).expect("A new session should be issued");
+
+  // --- Step 4: 管理者が全セッションを無効にすると、以前のCookieからも書き初めを操作できない ---
+  let revoked = server::run_revoke_sessions(pool.clone(), created["public_id"].as_str().unwrap().parse().unwrap())
+    .await
+    .unwrap();
+  assert_eq!(revoked, 1);
+  for cookie in [&laptop, &legacy] {
+    let response = app
+      .clone()
+      .oneshot(request("DELETE", "/api/calligraphy/me", cookie, FIREFOX, None))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+  }
+  // 書き初めそのものは残る
+  let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM calligraphy WHERE public_id = $1")
+    .bind(created["public_id"].as_str().unwrap().parse::<uuid::Uuid>().unwrap())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+  assert_eq!(remaining, 1);
+
+  // --- Step 5: このブラウザのセッションを削除すると、Cookieも削除される ---
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/sessions", &phone, SAFARI, None))
+    .await
+    .unwrap();
+  let sessions = json(response).await;
+  let response = app
+    .clone()
+    .oneshot(request(
+      "DELETE",
+      &format!("/api/sessions/{}", sessions[0]["id"].as_str().unwrap()),
+      &phone,
+      SAFARI,
+      None,
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  assert!(response
+    .headers()
+    .get_all("set-cookie")
+    .iter()
+    .any(|value| value.to_str().unwrap().starts_with("calli_session=;")));
+
+  // Cleanup (書き初めは誰も知らないユーザーIDに移っている)
+  sqlx::query("DELETE FROM calligraphy WHERE public_id = $1")
+    .bind(created["public_id"].as_str().unwrap().parse::<uuid::Uuid>().unwrap())
+    .execute(&pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_csrf_protection() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  // 公開URL (`http://localhost`) に加えて、管理画面のオリジンを許可する
+  let config = Config {
+    csrf_trusted_origins: vec!["https://admin.kakizome.example".to_string()],
+    ..Config::default()
+  };
+  let app = create_app(pool, config);
+
+  let request = |method: &str, uri: &str, headers: &[(&str, &str)]| {
+    let mut builder = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+      builder = builder.header(*name, *value);
+    }
+    if method == "POST" {
+      builder
+        .header("Content-Type", "application/json")
+        .body(Body::from(r#"{ "user_name": "CSRF", "content": "偽造" }"#))
+        .unwrap()
+    } else {
+      builder.body(Body::empty()).unwrap()
+    }
+  };
+
+  // --- Step 1: 別のサイトからの投稿は拒否する (Cookieがなくても) ---
+  for headers in [
+    &[("Origin", "https://evil.example")][..],
+    &[("Origin", "https://evil.example"), ("Sec-Fetch-Site", "cross-site")][..],
+    &[("Origin", "null")][..],
+    &[("Sec-Fetch-Site", "cross-site")][..],
+  ] {
+    let response = app
+      .clone()
+      .oneshot(request("POST", "/api/calligraphy", headers))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN, "{:?}", headers);
+    assert!(response.headers().get("set-cookie").is_none());
+  }
+
+  // 同じオリジンからの投稿では、セッションとCSRFトークンのCookieを発行する
+  let response = app
+    .clone()
+    .oneshot(request(
+      "POST",
+      "/api/calligraphy",
+      &[("Origin", "http://localhost"), ("Sec-Fetch-Site", "same-origin")],
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let csrf_cookie = response
+    .headers()
+    .get_all("set-cookie")
+    .iter()
+    .map(|value| value.to_str().unwrap())
+    .find(|value| value.starts_with("calli_csrf="))
+    .expect("CSRF cookie should be set");
+  assert!(!csrf_cookie.contains("HttpOnly"));
+  let cookie = cookies_of(&response);
+  let token = csrf_token(&cookie);
+  println!("Step 1: Rejected cross-site requests");
+
+  // --- Step 2: Cookieを送る場合はCSRFトークンが必要 ---
+  for headers in [
+    vec![("Cookie", cookie.as_str())],
+    vec![("Cookie", cookie.as_str()), ("X-CSRF-Token", "0123456789abcdef")],
+    // 別のサイトからはトークンが正しくても拒否する
+    vec![("Cookie", cookie.as_str()), ("X-CSRF-Token", token.as_str()), ("Origin", "https://evil.example")],
+  ] {
+    let response = app
+      .clone()
+      .oneshot(request("DELETE", "/api/calligraphy/me", &headers))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN, "{:?}", headers);
+  }
+
+  // Cookieを書き換えても、セッションに対応しないトークンは使えない
+  let session = cookie.split("; ").find(|pair| pair.starts_with("calli_session=")).unwrap();
+  let forged = format!("{}; calli_csrf=forged", session);
+  let response = app
+    .clone()
+    .oneshot(request(
+      "DELETE",
+      "/api/calligraphy/me",
+      &[("Cookie", forged.as_str()), ("X-CSRF-Token", "forged")],
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::FORBIDDEN);
+  println!("Step 2: Rejected requests without a valid token");
+
+  // --- Step 3: トークンのCookieがないセッションには、GETのレスポンスで発行する ---
+  let response = app
+    .clone()
+    .oneshot(request("GET", "/api/calligraphy/me", &[("Cookie", session)]))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(csrf_token(&cookies_of(&response)), token);
+  println!("Step 3: Reissued the CSRF cookie");
+
+  // --- Step 4: 許可したオリジンからは、トークンを付ければ操作できる ---
+  let response = app
+    .clone()
+    .oneshot(request(
+      "DELETE",
+      "/api/calligraphy/me",
+      &[
+        ("Cookie", cookie.as_str()),
+        ("X-CSRF-Token", token.as_str()),
+        ("Origin", "https://admin.kakizome.example"),
+        ("Sec-Fetch-Site", "same-site"),
+      ],
+    ))
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  println!("Step 4: Accepted a trusted origin");
+}
+
+#[tokio::test]
+async fn test_cors() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let frontend = "http://localhost:5173";
+
+  // --- Step 1: 既定ではCORSのヘッダーを付けない ---
+  let app = create_app(pool.clone(), Config::default());
+  let response = app
+    .oneshot(
+      Request::builder()
+        .uri("/api/calligraphy")
+        .header("Origin", frontend)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(response.headers().get("access-control-allow-origin").is_none());
+  println!("Step 1: No CORS headers by default");
+
+  // --- Step 2: 許可したオリジンのプリフライトに応答する ---
+  let config = Config {
+    cors_allowed_origins: vec![frontend.to_string()],
+    ..Config::default()
+  };
+  let app = create_app(pool, config);
+  let preflight = |origin: &str| {
+    Request::builder()
+      .method("OPTIONS")
+      .uri("/api/calligraphy/me")
+      .header("Origin", origin)
+      .header("Access-Control-Request-Method", "DELETE")
+      .header("Access-Control-Request-Headers", "content-type,x-csrf-token")
+      .body(Body::empty())
+      .unwrap()
+  };
+  let response = app.clone().oneshot(preflight(frontend)).await.unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let headers = response.headers();
+  assert_eq!(headers["access-control-allow-origin"], frontend);
+  assert_eq!(headers["access-control-allow-credentials"], "true");
+  assert_eq!(headers["access-control-max-age"], "600");
+  assert!(headers["access-control-allow-methods"].to_str().unwrap().contains("DELETE"));
+  assert!(headers["access-control-allow-headers"].to_str().unwrap().contains("x-csrf-token"));
+
+  let response = app.clone().oneshot(preflight("https://evil.example")).await.unwrap();
+  assert!(response.headers().get("access-control-allow-origin").is_none());
+  println!("Step 2: Answered preflight requests");
+
+  // --- Step 3: 許可したオリジンからの投稿を受け付け、CSRFトークンをヘッダーで渡す ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Origin", frontend)
+        .header("Sec-Fetch-Site", "same-site")
+        .header("Content-Type", "application/json")
+        .body(Body::from(r#"{ "user_name": "CORS", "content": "越境" }"#))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert_eq!(response.headers()["access-control-allow-origin"], frontend);
+  assert!(response.headers()["access-control-expose-headers"]
+    .to_str()
+    .unwrap()
+    .contains("x-csrf-token"));
+  let cookie = cookies_of(&response);
+  let token = response.headers()["x-csrf-token"].to_str().unwrap().to_string();
+  assert_eq!(token, csrf_token(&cookie));
+  println!("Step 3: Accepted a post from the allowed origin");
+
+  // --- Step 4: ヘッダーで受け取ったトークンで削除できる ---
+  let response = app
+    .oneshot(
+      Request::builder()
+        .method("DELETE")
+        .uri("/api/calligraphy/me")
+        .header("Origin", frontend)
+        .header("Cookie", cookie.as_str())
+        .header("X-CSRF-Token", token.as_str())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+  assert_eq!(response.headers()["access-control-allow-origin"], frontend);
+  println!("Step 4: Deleted with the token from the response header");
+}
+
+#[tokio::test]
+async fn test_request_hardening() {
+  use futures_util::StreamExt;
+
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let config = Config {
+    body_max_bytes: 1024,
+    request_body_timeout_secs: 1,
+    max_header_bytes: 4 * 1024,
+    ..Config::default()
+  };
+  let app = create_app(pool, config);
+
+  // --- Step 1: APIのレスポンスにセキュリティ関連のヘッダーを付ける ---
+  let response = app
+    .clone()
+    .oneshot(Request::builder().uri("/api/calligraphy").body(Body::empty()).unwrap())
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let headers = response.headers();
+  assert_eq!(headers["x-content-type-options"], "nosniff");
+  assert_eq!(headers["x-frame-options"], "DENY");
+  assert!(headers["content-security-policy"].to_str().unwrap().contains("frame-ancestors 'none'"));
+  assert!(headers.get("referrer-policy").is_some());
+  println!("Step 1: Added security headers");
+
+  // --- Step 2: 大きすぎるヘッダーは431 ---
+  let large = "a".repeat(8 * 1024);
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .uri("/api/calligraphy")
+        .header("X-Padding", large.as_str())
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE);
+  assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+  println!("Step 2: Rejected large headers");
+
+  // --- Step 3: 上限を超えるボディは413 (書き初めの投稿以外) ---
+  let body = format!(r#"{{ "code": "{}" }}"#, "0".repeat(2048));
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/identity/claim")
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+  println!("Step 3: Rejected a large body");
+
+  // --- Step 4: ボディを送り終えないリクエストは408 ---
+  let stalled = futures_util::stream::once(async { Ok::<_, std::io::Error>("{ \"code\": ") })
+    .chain(futures_util::stream::pending());
+  let response = app
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/identity/claim")
+        .header("Content-Type", "application/json")
+        .body(Body::from_stream(stalled))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+  println!("Step 4: Timed out a stalled body");
+}
+
+#[tokio::test]
+async fn test_request_id() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let app = create_app(pool, Config::default());
+
+  // --- Step 1: リクエストIDがなければ発行する ---
+  let response = app
+    .clone()
+    .oneshot(Request::builder().uri("/api/calligraphy").body(Body::empty()).unwrap())
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let issued = response.headers()["x-request-id"].to_str().unwrap();
+  assert!(uuid::Uuid::parse_str(issued).is_ok());
+  println!("Step 1: Issued a request id");
+
+  // --- Step 2: nginxが付けたリクエストIDを使い、エラーレスポンスにも付ける ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .uri("/api/calligraphy?sort=unknown")
+        .header("X-Request-Id", "3f6c0a9e5b1d4c2e8f7a6b5c4d3e2f1a")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+  assert_eq!(response.headers()["x-request-id"], "3f6c0a9e5b1d4c2e8f7a6b5c4d3e2f1a");
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let error_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  assert_eq!(error_json["request_id"], "3f6c0a9e5b1d4c2e8f7a6b5c4d3e2f1a");
+  println!("Step 2: Echoed the incoming request id");
+
+  // --- Step 3: ヘッダーやログを壊す値は使わない ---
+  let response = app
+    .oneshot(
+      Request::builder()
+        .uri("/api/calligraphy")
+        .header("X-Request-Id", "bad id")
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_ne!(response.headers()["x-request-id"], "bad id");
+  println!("Step 3: Replaced an invalid request id");
+}
+
+#[tokio::test]
+async fn test_session_issued_lazily() {
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let app = create_app(pool.clone(), Config::default());
+
+  // --- Step 1: Cookieなしの読み込みではセッションを発行しない ---
+  let response = app
+    .clone()
+    .oneshot(Request::builder().method("GET").uri("/api/calligraphy").body(Body::empty()).unwrap())
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(response.headers().get("set-cookie").is_none());
+
+  // --- Step 2: 最初の書き込みでセッションを発行する (一度も使われていないため有効期限は短い) ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("POST")
+        .uri("/api/calligraphy")
+        .header("Content-Type", "application/json")
+        .body(Body::from(r#"{ "user_name": "遅延発行", "content": "初書き" }"#))
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  let cookie_header = cookies_of(&response);
+  assert!(cookie_header.contains("calli_session="));
+  let body = response.into_body().collect().await.unwrap().to_bytes();
+  let created: serde_json::Value = serde_json::from_slice(&body).unwrap();
+  let public_id = uuid::Uuid::parse_str(created["public_id"].as_str().unwrap()).unwrap();
+
+  let expires_at = || async {
+    sqlx::query_scalar::<_, time::OffsetDateTime>(
+      "SELECT s.expires_at FROM user_session s JOIN calligraphy c ON c.user_id = s.user_id WHERE c.public_id = $1",
+    )
+    .bind(public_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap()
+  };
+  assert!(expires_at().await < time::OffsetDateTime::now_utc() + time::Duration::days(2));
+
+  // --- Step 3: 同じセッションで次にアクセスすると通常の有効期限に延長する ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("GET")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie_header)
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::OK);
+  assert!(cookies_of(&response).contains("calli_session="));
+  assert!(expires_at().await > time::OffsetDateTime::now_utc() + time::Duration::days(300));
+
+  // --- 後片付け ---
+  let response = app
+    .clone()
+    .oneshot(
+      Request::builder()
+        .method("DELETE")
+        .uri("/api/calligraphy/me")
+        .header("Cookie", &cookie_header)
+        .header("X-CSRF-Token", csrf_token(&cookie_header))
+        .body(Body::empty())
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+  assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_public_assets_beyond_board_limit() {
+  use server::repositories::db_repository::{BoardRepository, CalligraphyRepository};
+
+  let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+  let pool = PgPoolOptions::new()
+    .max_connections(1)
+    .connect(&database_url)
+    .await
+    .expect("Failed to connect to DB");
+  let repository = CalligraphyRepository::new(pool.clone());
+  let app = create_app(pool.clone(), Config::default());
+
+  // --- 一覧 (新しい100件) に含まれない古い書き初めを作る ---
+  let strokes = serde_json::from_str(
+    r#"{ "version": 1, "width": 200, "height": 300, "strokes": [[[10, 10, 0.5, 0], [190, 290, 0.8, 120]]] }"#,
+  )
+  .unwrap();
+  let oldest_id = uuid::Uuid::new_v4();
+  let oldest = repository
+    .create(oldest_id, "Old User".to_string(), "Oldest Entry".to_string(), None, None, None, Some(strokes), None)
+    .await
+    .unwrap();
+  let mut user_ids = vec![oldest_id];
+  for i in 0..100 {
+    let user_id = uuid::Uuid::new_v4();
+    repository
+      .create(user_id, format!("Filler {}", i), "Filler".to_string(), None, None, None, None, None)
+      .await
+      .unwrap();
+    user_ids.push(user_id);
+  }
+  // 並行する他のテストの一覧に影響しないよう、作成日時を過去にずらす (古い書き初めが最も古い)
+  sqlx::query("UPDATE calligraphy SET created_at = created_at - INTERVAL '10 years' WHERE user_id = ANY($1)")
+    .bind(&user_ids)
+    .execute(&pool)
+    .await
+    .unwrap();
+  sqlx::query("UPDATE calligraphy SET created_at = created_at - INTERVAL '1 day' WHERE user_id = $1")
+    .bind(oldest_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+  let board = repository.find_all().await.unwrap();
+  assert!(board.iter().all(|entry| entry.public_id != oldest.public_id));
+
+  // --- 一覧にない書き初めのSVG・筆跡・OGP画像も取得できる ---
+  for (uri, statuses) in [
+    (format!("/api/calligraphy/{}.svg", oldest.public_id), vec![StatusCode::OK]),
+    (format!("/api/calligraphy/{}/strokes", oldest.public_id), vec![StatusCode::OK]),
+    (format!("/api/calligraphy/{}/strokes.svg", oldest.public_id), vec![StatusCode::OK]),
+    (
+      format!("/api/calligraphy/{}/ogp.png", oldest.public_id),
+      vec![StatusCode::OK, StatusCode::TEMPORARY_REDIRECT],
+    ),
+  ] {
+    let response = app
+      .clone()
+      .oneshot(Request::builder().method("GET").uri(&uri).body(Body::empty()).unwrap())
+      .await
+      .unwrap();
+    assert!(statuses.contains(&response.status()), "{} returned {}", uri, response.status());
+  }
+
+  // --- 後片付け ---
+  for user_id in user_ids {
+    repository.delete(user_id).await.unwrap();
+  }
+}
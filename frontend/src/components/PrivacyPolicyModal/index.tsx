@@ -1,4 +1,7 @@
-import { useEffect } from 'react';
+import { useEffect, useState } from 'react';
+import { API_CONFIG } from '../../constants';
+import { privacyApi } from '../../lib/api';
+import type { RetentionPolicy } from '../../types/privacy';
 import './PrivacyPolicyModal.css';
 
 interface PrivacyPolicyModalProps {
@@ -21,6 +24,16 @@ export const PrivacyPolicyModal = ({ isOpen, onClose }: PrivacyPolicyModalProps)
 		return () => window.removeEventListener('keydown', handleEsc);
 	}, [isOpen, onClose]);
 
+	// 保持期間を表示するため、開いたときに保持ポリシーを取得する
+	const [retention, setRetention] = useState<RetentionPolicy | null>(null);
+	useEffect(() => {
+		if (!isOpen) return;
+		privacyApi
+			.retention()
+			.then(setRetention)
+			.catch(() => setRetention(null));
+	}, [isOpen]);
+
 	// モーダルが開いているときはbodyのスクロールを無効化
 	useEffect(() => {
 		if (isOpen) {
@@ -81,6 +94,25 @@ export const PrivacyPolicyModal = ({ isOpen, onClose }: PrivacyPolicyModalProps)
 						<p>
 							取得した情報は適切に管理し、法令に基づく場合を除き、利用者の同意なく第三者に提供することはありません。
 						</p>
+						{retention?.enabled && (
+							<p>
+								IPアドレス・ブラウザ情報は、書き初めの最終更新から{retention.anonymize_after_days}日が経過すると、
+								個人を特定できない形（IPアドレスは上位{retention.ipv4_prefix}ビット、ブラウザ情報は種類のみ）に匿名化します。
+							</p>
+						)}
+					</section>
+
+					<section className="privacy-section">
+						<h3>4. 保存している情報の確認</h3>
+						<p>
+							当サイトがあなたについて保存している情報（書き初めの内容、IPアドレス、ブラウザ情報など）は、
+							以下からJSONファイルとしてダウンロードできます。
+						</p>
+						<p>
+							<a href={`${API_CONFIG.BASE_URL}/calligraphy/me/export`} download>
+								保存している情報をダウンロード
+							</a>
+						</p>
 					</section>
 				</div>
 			</div>
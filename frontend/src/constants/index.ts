@@ -2,6 +2,8 @@
  * アプリケーション全体で使用する定数
  */
 
+import { LIMITS } from '../types/generated';
+
 // API設定
 export const API_CONFIG = {
 	BASE_URL: '/api',
@@ -9,10 +11,10 @@ export const API_CONFIG = {
 	REFETCH_INTERVAL: 60 * 1000, // 60秒ごとに自動更新
 } as const;
 
-// フォームバリデーション
+// フォームバリデーション (上限はバックエンドから生成した値を使う)
 export const FORM_LIMITS = {
-	USER_NAME_MAX_LENGTH: 20,
-	CONTENT_MAX_LENGTH: 50,
+	USER_NAME_MAX_LENGTH: LIMITS.USER_NAME_MAX_GRAPHEMES,
+	CONTENT_MAX_LENGTH: LIMITS.CONTENT_MAX_GRAPHEMES,
 	CONTENT_CENTER_THRESHOLD: 3, // 3行以下で中央揃え
 } as const;
 
@@ -1,21 +1,46 @@
 import type { Calligraphy, CreateCalligraphyRequest } from '../types/calligraphy';
+import type { RetentionPolicy } from '../types/privacy';
+import type { BoardStats } from '../types/stats';
 import { API_CONFIG } from '../constants';
 
+/**
+ * レスポンスの X-CSRF-Token ヘッダーで受け取ったトークン
+ * (別のオリジンのバックエンドを使う場合はCookieを読めないため)
+ */
+let responseCsrfToken: string | undefined;
+
+/**
+ * CSRFトークン (サーバーがセッションと一緒に発行するCookie `calli_csrf` の値)
+ */
+function csrfToken(): string | undefined {
+	return (
+		document.cookie
+			.split('; ')
+			.find((pair) => pair.startsWith('calli_csrf='))
+			?.slice('calli_csrf='.length) ?? responseCsrfToken
+	);
+}
+
 /**
  * 共通Fetchラッパー
  * - credentials: 'include' を自動付与 (Cookie送信用)
+ * - GET以外のリクエストには X-CSRF-Token を自動付与
  * - エラーハンドリングの統一
  */
 async function client<T>(path: string, options?: RequestInit): Promise<T> {
+	const method = (options?.method ?? 'GET').toUpperCase();
+	const token = method === 'GET' ? undefined : csrfToken();
 	const response = await fetch(`${API_CONFIG.BASE_URL}${path}`, {
 		...options,
 		headers: {
 			'Content-Type': 'application/json',
+			...(token ? { 'X-CSRF-Token': token } : {}),
 			...options?.headers,
 		},
 		// CORS環境下でCookieを送信するために必須
 		credentials: 'include',
 	});
+	responseCsrfToken = response.headers.get('X-CSRF-Token') ?? responseCsrfToken;
 
 	if (!response.ok) {
 		const errorData = await response.json().catch(() => ({ error: 'Unknown error' }));
@@ -57,3 +82,22 @@ export const calligraphyApi = {
 		}),
 };
 
+/**
+ * プライバシー関連API
+ */
+export const privacyApi = {
+	/**
+	 * 収集した情報の保持ポリシーを取得
+	 */
+	retention: () => client<RetentionPolicy>('/privacy/retention'),
+};
+
+/**
+ * 集計API
+ */
+export const statsApi = {
+	/**
+	 * ボード全体の集計を取得
+	 */
+	get: () => client<BoardStats>('/stats'),
+};
@@ -1,37 +1,65 @@
+/**
+ * APIの型定義
+ * バックエンドから生成した型 (generated.ts) を使い、手で書き写さない
+ */
+import type {
+	CalligraphyResponse,
+	ErrorResponse,
+	FinishAuthenticationRequest as GeneratedFinishAuthenticationRequest,
+	FinishRegistrationRequest as GeneratedFinishRegistrationRequest,
+	StartAuthenticationResponse as GeneratedStartAuthenticationResponse,
+	StartRegistrationResponse as GeneratedStartRegistrationResponse,
+} from './generated';
+
+export type {
+	ClaimRequest,
+	CreateCalligraphyRequest,
+	ErrorCode,
+	PasskeyResponse,
+	PhotoResponse,
+	SessionResponse,
+	StartAuthenticationRequest,
+	StrokePoint,
+	Strokes,
+	TransferCodeResponse,
+} from './generated';
+
 /**
  * 書き初めデータの型定義
  */
-export interface Calligraphy {
-	user_name: string;
-	content: string;
-	created_at: string;
-	updated_at: string;
-	is_mine: boolean;
-}
+export type Calligraphy = CalligraphyResponse;
 
 /**
- * 書き初め作成リクエストの型定義
+ * パスキーの登録の開始のレスポンスの型定義
+ * options は navigator.credentials.create() に渡す
  */
-export interface CreateCalligraphyRequest {
-	user_name: string;
-	content: string;
-}
+export type StartRegistrationResponse = Omit<GeneratedStartRegistrationResponse, 'options'> & {
+	options: { publicKey: PublicKeyCredentialCreationOptionsJSON };
+};
 
 /**
- * APIエラーの型定義
+ * パスキーの登録の完了のリクエストの型定義
  */
-export interface ApiError {
-	error: string;
-	code?: string;
-	details?: Record<string, unknown>;
-}
+export type FinishRegistrationRequest = Omit<GeneratedFinishRegistrationRequest, 'credential'> & {
+	credential: RegistrationResponseJSON;
+};
 
 /**
- * APIエラーレスポンスの型定義
+ * パスキーでのログインの開始のレスポンスの型定義
+ * options は navigator.credentials.get() に渡す
  */
-export interface ApiErrorResponse {
-	error: string;
-	code?: string;
-	details?: Record<string, unknown>;
-}
+export type StartAuthenticationResponse = Omit<GeneratedStartAuthenticationResponse, 'options'> & {
+	options: { publicKey: PublicKeyCredentialRequestOptionsJSON };
+};
 
+/**
+ * パスキーでのログインの完了のリクエストの型定義
+ */
+export type FinishAuthenticationRequest = Omit<GeneratedFinishAuthenticationRequest, 'credential'> & {
+	credential: AuthenticationResponseJSON;
+};
+
+/**
+ * APIエラーレスポンスの型定義
+ */
+export type ApiErrorResponse = ErrorResponse;
@@ -0,0 +1,426 @@
+// このファイルはバックエンドの型から生成したもの。手で編集しないこと。
+// 更新するには backend で `cargo run -- export-types ../frontend/src/types/generated.ts` を実行する。
+
+/** 入力値の上限 (バックエンドのバリデーションと同じ値) */
+export const LIMITS = {
+	/** ユーザー名の最大文字数 (書記素クラスタ単位) */
+	USER_NAME_MAX_GRAPHEMES: 20,
+	/** 書き初め内容の最大文字数 (書記素クラスタ単位) */
+	CONTENT_MAX_GRAPHEMES: 50,
+	/** 書き初め内容の最大行数 */
+	CONTENT_MAX_LINES: 10,
+	/** 筆跡データの形式のバージョン */
+	STROKES_FORMAT_VERSION: 1,
+	/** 筆跡の描画領域の最大の幅・高さ */
+	STROKES_MAX_DIMENSION: 2048,
+	/** 筆跡の最大の画数 */
+	STROKES_MAX_STROKES: 300,
+	/** 1画の最大の点の数 */
+	STROKES_MAX_STROKE_POINTS: 2000,
+	/** 筆跡全体の最大の点の数 */
+	STROKES_MAX_POINTS: 8000,
+	/** 書き始めから書き終わりまでの最大の時間 (ミリ秒) */
+	STROKES_MAX_DURATION_MS: 600000,
+	/** 筆跡データ (JSON) の最大バイト数 */
+	STROKES_MAX_BYTES: 262144,
+	/** 添付する写真の最大バイト数 */
+	PHOTO_MAX_BYTES: 10485760,
+} as const;
+
+/** エラーのレスポンスの `code` の値 */
+export const ERROR_CODES = [
+	'validation',
+	'forbidden',
+	'not_found',
+	'conflict',
+	'payload_too_large',
+	'unsupported_media_type',
+	'request_timeout',
+	'request_header_fields_too_large',
+	'too_many_requests',
+	'service_unavailable',
+	'internal',
+] as const;
+
+/**
+ * ボード全体の集計結果 (`GET /api/stats`)
+ *
+ * 個々の行のリクエスト情報 (User-Agent・Accept-Language) は含めず、件数の集計のみを返す。
+ */
+export interface BoardStats {
+	/** 書き初めの総数 */
+	total_entries: number;
+	/** 年末年始の日別の投稿数 */
+	new_year: NewYearActivity;
+	/** 内容に多く使われている文字 (英数字・かな・漢字) */
+	top_characters: CharacterCount[];
+	/** 内容に多く使われている漢字 */
+	top_kanji: CharacterCount[];
+	/** 言語別の件数 (Accept-Languageの主言語タグ) */
+	languages: LabelCount[];
+	/** ブラウザ別の件数 */
+	browsers: LabelCount[];
+	/** OS別の件数 */
+	operating_systems: LabelCount[];
+}
+
+/** イベント配信用のDTO (user_idなどの内部情報は含めない) */
+export type CalligraphyEventResponse =
+	| CalligraphyResponse
+	| {
+		public_id: string;
+		is_mine: boolean;
+	};
+
+/** 書き初めの投稿・更新のmultipart/form-data (ドキュメント用。実際の解析は `CalligraphyForm` で行う) */
+export interface CalligraphyFormData {
+	/** JSONの `user_name` と同じ */
+	user_name: string;
+	/** JSONの `content` と同じ */
+	content: string;
+	/** 筆跡データのJSON文字列 */
+	strokes?: string | null;
+	/** 写真ファイル (JPEG / PNG / WebP、10MiBまで)。空のファイルは添付なしとして扱う */
+	photo?: Blob | null;
+}
+
+/**
+ * calligraphyテーブルの1行
+ * 通常のレスポンスでは返さない情報収集用の列も含め、全ての列を持つ
+ */
+export interface CalligraphyRecord {
+	public_id: string;
+	user_name: string;
+	content: string;
+	/** 内容の文字数 (書記素クラスタ単位) */
+	content_length: number;
+	/** IPアドレス (匿名化後はネットワークアドレス, 例: "192.0.2.0/24") */
+	ip_address: string | null;
+	user_agent: string | null;
+	accept_language: string | null;
+	created_at: string;
+	updated_at: string;
+	/** IPアドレス等を匿名化した日時 (未匿名化ならnull) */
+	anonymized_at: string | null;
+	/** 手書きの筆跡データ (なければnull) */
+	strokes: Strokes | null;
+	/** 添付した写真のファイル名・大きさ (なければnull) */
+	photo: Photo | null;
+}
+
+/** APIレスポンス用のDTO */
+export interface CalligraphyResponse {
+	/** 公開用ID */
+	public_id: string;
+	user_name: string;
+	content: string;
+	/** 添付した写真 (なければnull) */
+	photo: PhotoResponse | null;
+	created_at: string;
+	updated_at: string;
+	/** リクエストしたユーザー自身の書き初めか */
+	is_mine: boolean;
+}
+
+export interface CharacterCount {
+	character: string;
+	count: number;
+}
+
+/** 引き継ぎコードを使うリクエストのボディ */
+export interface ClaimRequest {
+	code: string;
+}
+
+/** クライアントから受け取るメッセージ */
+export type ClientMessage =
+	/** 書き初めを入力中かどうか (入力中は数秒おきに送り直す) */
+	| {
+		active: boolean;
+		type: 'typing';
+	}
+	/** アプリケーションレベルの死活確認 (pongを返す) */
+	| {
+		type: 'ping';
+	};
+
+/** フロントから受け取る書き初め作成・更新用のリクエストボディ */
+export interface CreateCalligraphyRequest {
+	/** ユーザー名 (最大20文字) */
+	user_name: string;
+	/** 書き初め内容 (最大50文字・10行) */
+	content: string;
+	/** 手書きの筆跡データ (任意) */
+	strokes?: Strokes | null;
+}
+
+export interface DailyCount {
+	/** 日付 (日本時間) */
+	date: string;
+	count: number;
+}
+
+/** エラーの種類 (クライアントが分岐に使う、メッセージより変わりにくい値) */
+export type ErrorCode =
+	| 'validation'
+	| 'forbidden'
+	| 'not_found'
+	| 'conflict'
+	| 'payload_too_large'
+	| 'unsupported_media_type'
+	| 'request_timeout'
+	| 'request_header_fields_too_large'
+	| 'too_many_requests'
+	| 'service_unavailable'
+	| 'internal';
+
+/** エラーレスポンスのボディ */
+export interface ErrorResponse {
+	/** エラーメッセージ */
+	error: string;
+	/** エラーの種類 */
+	code: ErrorCode;
+	/** リクエストID (`X-Request-Id` と同じ値。サーバーのログと突き合わせるため) */
+	request_id?: string | null;
+}
+
+/** 認証の完了のリクエストボディ */
+export interface FinishAuthenticationRequest {
+	ceremony_id: string;
+	/** `navigator.credentials.get()` の結果 (バイナリはbase64url) */
+	credential: Record<string, unknown>;
+}
+
+/** 登録の完了のリクエストボディ */
+export interface FinishRegistrationRequest {
+	ceremony_id: string;
+	/** `navigator.credentials.create()` の結果 (バイナリはbase64url) */
+	credential: Record<string, unknown>;
+}
+
+/** フィールドごとのハイライト */
+export interface Highlights {
+	user_name: Segment[];
+	content: Segment[];
+}
+
+export interface LabelCount {
+	label: string;
+	count: number;
+}
+
+/** 年末年始 (日本時間) の日別の投稿数 */
+export interface NewYearActivity {
+	/** 集計期間の初日 (この日を含む) */
+	from: string;
+	/** 集計期間の最終日 (この日を含む) */
+	to: string;
+	/** 期間内の全ての日の件数 (投稿がない日は0件) */
+	daily: DailyCount[];
+}
+
+/** passkey_credentialテーブルの1行 */
+export interface PasskeyCredentialRecord {
+	id: string;
+	/** 認証器が発行した資格情報ID (Base64URL) */
+	credential_id: string;
+	/** 公開鍵・署名カウンタ等 (webauthn-rs の Passkey) */
+	passkey: Record<string, unknown>;
+	created_at: string;
+	/** 最後にログインに使った日時 (未使用ならnull) */
+	last_used_at: string | null;
+}
+
+/** APIレスポンス用のパスキーの情報 */
+export interface PasskeyResponse {
+	id: string;
+	created_at: string;
+	last_used_at: string | null;
+}
+
+/** 個人データのエクスポート (保存している全ての情報) */
+export interface PersonalDataExport {
+	format_version: number;
+	exported_at: string;
+	/** ユーザーID (セッションが指すID。以前はCookie `calli_user_id` に保存していた値) */
+	user_id: string;
+	/** 書き初め (投稿していなければnull) */
+	calligraphy: CalligraphyRecord | null;
+	/** ブラウザのセッション (期限切れで未削除のものも含む) */
+	sessions: SessionRecord[];
+	/** 登録したパスキー */
+	passkeys: PasskeyCredentialRecord[];
+}
+
+/**
+ * 書き初めに添付した写真 (DBにはJSONとして保存する)
+ *
+ * ファイル名 (`key`) は再エンコードした画像の内容のハッシュ (SHA-256) と拡張子で、内容が変わらない限り同じ名前になる。
+ */
+export interface Photo {
+	/** 写真のファイル名 (例: `3a7b...e1.jpg`) */
+	key: string;
+	width: number;
+	height: number;
+	/** サムネイルのファイル名 */
+	thumbnail_key: string;
+	thumbnail_width: number;
+	thumbnail_height: number;
+}
+
+/** APIレスポンス用の写真の情報 */
+export interface PhotoResponse {
+	url: string;
+	width: number;
+	height: number;
+	thumbnail_url: string;
+	thumbnail_width: number;
+	thumbnail_height: number;
+}
+
+/** 収集したリクエスト情報の保持ポリシー (プライバシーポリシーからの参照用に公開する) */
+export interface RetentionPolicy {
+	/** 匿名化ジョブが有効か */
+	enabled: boolean;
+	/** 最終更新からこの日数が経過した行を匿名化する */
+	anonymize_after_days: number;
+	/** 匿名化後に残すIPv4アドレスのプレフィックス長 */
+	ipv4_prefix: number;
+	/** 匿名化後に残すIPv6アドレスのプレフィックス長 */
+	ipv6_prefix: number;
+}
+
+/** 検索結果1件のレスポンス (通常の書き初めのレスポンスにハイライトを加えたもの) */
+export type SearchHitResponse = CalligraphyResponse & {
+	highlights: Highlights;
+};
+
+/** 検索APIのレスポンス */
+export interface SearchResponse {
+	items: SearchHitResponse[];
+	page: number;
+	per_page: number;
+	/** 次のページがあるか */
+	has_more: boolean;
+}
+
+/** ハイライト用の区間 */
+export interface Segment {
+	text: string;
+	/** 検索語に一致した区間か */
+	matched: boolean;
+}
+
+/** クライアントへ送るメッセージ */
+export type ServerMessage =
+	/** 新規投稿 */
+	| {
+		entry: CalligraphyResponse;
+		type: 'created';
+	}
+	/** 投稿の更新 */
+	| {
+		entry: CalligraphyResponse;
+		type: 'updated';
+	}
+	/** 投稿の削除 */
+	| {
+		public_id: string;
+		is_mine: boolean;
+		type: 'deleted';
+	}
+	/** 取りこぼしがあったため一覧の再取得が必要 */
+	| {
+		type: 'reset';
+	}
+	/** 在室人数・入力中の人数 */
+	| {
+		online: number;
+		typing: number;
+		type: 'presence';
+	}
+	/** pingへの応答 */
+	| {
+		type: 'pong';
+	}
+	/** 不正なメッセージなどのエラー通知 */
+	| {
+		message: string;
+		type: 'error';
+	};
+
+/**
+ * user_sessionテーブルの1行
+ * セッショントークンのハッシュはCookieの値を推測する手掛かりになるため含めない
+ */
+export interface SessionRecord {
+	id: string;
+	/** ブラウザとOSの種類 (例: "Firefox / Linux") */
+	device: string | null;
+	created_at: string;
+	last_seen_at: string;
+	expires_at: string;
+}
+
+/** APIレスポンス用のセッションの情報 */
+export interface SessionResponse {
+	id: string;
+	device: string | null;
+	created_at: string;
+	last_seen_at: string;
+	expires_at: string;
+	/** このリクエストのセッションか */
+	current: boolean;
+}
+
+/** 認証の開始のリクエストボディ (ログインする書き初めの公開用ID) */
+export interface StartAuthenticationRequest {
+	public_id: string;
+}
+
+/** 認証の開始のレスポンス (`options` は `navigator.credentials.get()` にそのまま渡す) */
+export interface StartAuthenticationResponse {
+	ceremony_id: string;
+	/** WebAuthnの `PublicKeyCredentialRequestOptions` (`{ "publicKey": ... }`) */
+	options: Record<string, unknown>;
+}
+
+/** 登録の開始のレスポンス (`options` は `navigator.credentials.create()` にそのまま渡す) */
+export interface StartRegistrationResponse {
+	ceremony_id: string;
+	/** WebAuthnの `PublicKeyCredentialCreationOptions` (`{ "publicKey": ... }`) */
+	options: Record<string, unknown>;
+}
+
+/** 筆跡の1点 `[x, y, 筆圧 (0.0〜1.0), 書き始めからの経過時間 (ミリ秒)]` */
+export type StrokePoint = [number, number, number, number];
+
+/**
+ * 手書きの筆跡データ
+ *
+ * ```json
+ * { "version": 1, "width": 600, "height": 800, "strokes": [[[120.5, 88, 0.42, 0], [121, 90.5, 0.5, 16]]] }
+ * ```
+ *
+ * 座標は左上を原点とする `width` x `height` の描画領域上の値。
+ */
+export interface Strokes {
+	/** 形式のバージョン (現在は1) */
+	version: number;
+	/** 描画領域の大きさ */
+	width: number;
+	height: number;
+	/**
+	 * 書いた順の画 (1画は筆を下ろしてから離すまでの点の列)
+	 * 点は `[x, y, 筆圧, 書き始めからの経過時間 (ミリ秒)]`
+	 */
+	strokes: StrokePoint[][];
+}
+
+/** 発行した引き継ぎコード (レスポンス用) */
+export interface TransferCodeResponse {
+	/** 引き継ぎ先のブラウザで入力するコード (`XXXXX-XXXXX`) */
+	code: string;
+	/** 有効期限 (これを過ぎたコードは使えない) */
+	expires_at: string;
+}
@@ -0,0 +1,4 @@
+/**
+ * 収集した情報 (IPアドレス等) の保持ポリシーの型定義 (バックエンドから生成した型)
+ */
+export type { RetentionPolicy } from './generated';
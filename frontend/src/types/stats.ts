@@ -0,0 +1,4 @@
+/**
+ * ボードの集計 (GET /api/stats) の型定義 (バックエンドから生成した型)
+ */
+export type { BoardStats, CharacterCount, DailyCount, LabelCount, NewYearActivity } from './generated';
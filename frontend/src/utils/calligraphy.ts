@@ -24,10 +24,10 @@ export const findMyCalligraphy = (list: Calligraphy[]): Calligraphy | undefined
 
 /**
  * 書き初めカードの一意IDを生成
- * user_name + created_at + updated_at の組み合わせで一意性を保証
+ * 公開用ID + updated_at の組み合わせで、更新時にカードを再描画させる
  */
 export const generateCardId = (calligraphy: Calligraphy): string => {
-	return `${calligraphy.user_name}-${calligraphy.created_at}-${calligraphy.updated_at}`;
+	return `${calligraphy.public_id}-${calligraphy.updated_at}`;
 };
 
 /**
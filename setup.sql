@@ -1,10 +1,97 @@
+-- 全文検索 (トライグラム) 用の拡張
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- pg_trgm は LC_CTYPE で文字を判定するため、UTF-8のロケールでないと日本語の類似度が0になる
+-- (テスト用のDBも含め、createdb --encoding=UTF8 --locale=C.UTF-8 で作成する)
+DO $$
+BEGIN
+	IF current_setting('lc_ctype') NOT ILIKE '%utf%8' THEN
+		RAISE WARNING 'lc_ctype is %, not a UTF-8 locale: search similarity will ignore non-ASCII text', current_setting('lc_ctype');
+	END IF;
+END $$;
+
 CREATE TABLE IF NOT EXISTS calligraphy (
-	user_id UUID PRIMARY KEY,                   								-- UUID型 (主キー)
-	user_name text NOT NULL DEFAULT '' CHECK (char_length(user_name) <= 20),	-- ユーザー名
-	content TEXT NOT NULL DEFAULT '' CHECK (char_length(content) <= 50), 			-- 書き初めの内容
+	user_id UUID PRIMARY KEY,                   								-- UUID型 (主キー) Cookieの値なので外部に公開しない
+	public_id UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),				-- 公開用ID (イベント配信やURLで使用)
+	user_name text NOT NULL DEFAULT '' CHECK (char_length(user_name) <= 160 AND user_name IS NFC NORMALIZED),	-- ユーザー名 (20書記素はアプリ側で検証)
+	content TEXT NOT NULL DEFAULT '' CHECK (char_length(content) <= 400 AND content IS NFC NORMALIZED), 	-- 書き初めの内容 (50書記素はアプリ側で検証)
+	content_length SMALLINT NOT NULL DEFAULT 0,							-- 内容の文字数 (書記素クラスタ単位, アプリ側で算出)
 	ip_address INET,                            							-- IPアドレス
 	user_agent TEXT,                                							-- ユーザーエージェント
 	accept_language VARCHAR(255),                     				-- Accept-Language ヘッダー
 	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,       				-- 作成日時 (タイムゾーン付き)
-	updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL        				-- 更新日時
-);
\ No newline at end of file
+	updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,       				-- 更新日時
+	anonymized_at TIMESTAMPTZ,                           				-- IPアドレス等を匿名化した日時 (更新時にNULLに戻る)
+	strokes JSONB CHECK (strokes IS NULL OR (jsonb_typeof(strokes) = 'object' AND pg_column_size(strokes) <= 1048576)),	-- 手書きの筆跡データ (画数・点の数はアプリ側で検証)
+	photo JSONB CHECK (photo IS NULL OR jsonb_typeof(photo) = 'object')	-- 添付した写真 (ファイル名・大きさ, ファイルはアプリ側のストレージに保存)
+);
+
+-- ボード全体の状態 (1行のみ)
+-- 削除は max(updated_at) に現れないため、最終削除日時をトリガーで記録する (Last-Modified の算出に使用)
+CREATE TABLE IF NOT EXISTS calligraphy_board (
+	id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),		-- 1行に制限するためのキー
+	last_deleted_at TIMESTAMPTZ									-- 最終削除日時
+);
+INSERT INTO calligraphy_board (id) VALUES (TRUE) ON CONFLICT DO NOTHING;
+
+CREATE OR REPLACE FUNCTION calligraphy_touch_board() RETURNS trigger AS $$
+BEGIN
+	UPDATE calligraphy_board SET last_deleted_at = NOW();
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER calligraphy_after_delete
+	AFTER DELETE ON calligraphy
+	FOR EACH STATEMENT EXECUTE FUNCTION calligraphy_touch_board();
+
+-- 全文検索用のトライグラムインデックス (ILIKE '%...%' と類似度検索 % の両方で使用)
+CREATE INDEX IF NOT EXISTS calligraphy_user_name_trgm_idx ON calligraphy USING gin (user_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS calligraphy_content_trgm_idx ON calligraphy USING gin (content gin_trgm_ops);
+
+-- 一覧の文字数での絞り込み用
+CREATE INDEX IF NOT EXISTS calligraphy_content_length_idx ON calligraphy (content_length);
+
+-- 匿名化ジョブが対象の行を探すためのインデックス (未匿名化の行のみ)
+CREATE INDEX IF NOT EXISTS calligraphy_unanonymized_idx ON calligraphy (updated_at) WHERE anonymized_at IS NULL;
+
+-- 別のブラウザへ書き初めを引き継ぐための引き継ぎコード (1ユーザーにつき1つ, 使用時に削除)
+CREATE TABLE IF NOT EXISTS transfer_code (
+	user_id UUID PRIMARY KEY REFERENCES calligraphy (user_id) ON DELETE CASCADE ON UPDATE CASCADE,	-- 引き継ぎ元のユーザー
+	code_hash BYTEA NOT NULL UNIQUE,								-- 正規化したコードのSHA-256 (コードそのものは保存しない)
+	expires_at TIMESTAMPTZ NOT NULL,								-- 有効期限
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL			-- 発行日時
+);
+
+-- パスキー (WebAuthn) の資格情報 (書き初めのユーザーに紐づく)
+CREATE TABLE IF NOT EXISTS passkey_credential (
+	credential_id BYTEA PRIMARY KEY,									-- 認証器が発行した資格情報ID
+	id UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),					-- 公開用ID (一覧・削除で使用)
+	user_id UUID NOT NULL REFERENCES calligraphy (user_id) ON DELETE CASCADE ON UPDATE CASCADE,	-- 持ち主
+	passkey JSONB NOT NULL,													-- 公開鍵・署名カウンタ等 (webauthn-rs の Passkey)
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,					-- 登録日時
+	last_used_at TIMESTAMPTZ													-- 最後にログインに使った日時
+);
+CREATE INDEX IF NOT EXISTS passkey_credential_user_id_idx ON passkey_credential (user_id);
+
+-- パスキーの登録・認証の途中の状態 (開始から完了までの間だけ保持し、完了時に削除する)
+CREATE TABLE IF NOT EXISTS passkey_ceremony (
+	id UUID PRIMARY KEY,
+	kind TEXT NOT NULL CHECK (kind IN ('registration', 'authentication')),	-- 登録か認証か
+	user_id UUID NOT NULL,														-- 登録するユーザー / 認証されるユーザー
+	state JSONB NOT NULL,														-- チャレンジ等 (webauthn-rs の状態)
+	expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- ブラウザごとのセッション (Cookie `calli_session`, パスキーでのログインも含む)
+CREATE TABLE IF NOT EXISTS user_session (
+	id UUID PRIMARY KEY DEFAULT gen_random_uuid(),						-- 公開用ID (一覧・削除で使用)
+	token_hash BYTEA NOT NULL UNIQUE,										-- セッショントークンのSHA-256 (トークンそのものは保存しない)
+	user_id UUID NOT NULL,														-- セッションのユーザー (書き初めがなくてもよい)
+	device TEXT,																	-- ブラウザとOSの種類 (例: "Firefox / Linux")
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+	last_seen_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,					-- 最後に使われた日時 (数分単位)
+	expires_at TIMESTAMPTZ NOT NULL											-- 有効期限 (使われるたびに延長)
+);
+CREATE INDEX IF NOT EXISTS user_session_user_id_idx ON user_session (user_id);
+CREATE INDEX IF NOT EXISTS user_session_expires_at_idx ON user_session (expires_at);	-- 期限切れのセッションの掃除用
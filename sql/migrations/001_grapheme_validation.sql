@@ -0,0 +1,19 @@
+-- 既存DB向けマイグレーション: 書記素クラスタ単位のバリデーション対応
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/001_grapheme_validation.sql
+--
+-- 文字数 (書記素クラスタ) はアプリ側 (backend/src/validation.rs) で検証する。
+-- DB側ではコードポイント数の上限とNFC正規化済みであることのみを保証する。
+-- 上限値は validation.rs の USER_NAME_MAX_CHARS / CONTENT_MAX_CHARS と一致させること。
+
+BEGIN;
+
+ALTER TABLE calligraphy DROP CONSTRAINT IF EXISTS calligraphy_user_name_check;
+ALTER TABLE calligraphy ADD CONSTRAINT calligraphy_user_name_check
+	CHECK (char_length(user_name) <= 160 AND user_name IS NFC NORMALIZED);
+
+ALTER TABLE calligraphy DROP CONSTRAINT IF EXISTS calligraphy_content_check;
+ALTER TABLE calligraphy ADD CONSTRAINT calligraphy_content_check
+	CHECK (char_length(content) <= 400 AND content IS NFC NORMALIZED);
+
+COMMIT;
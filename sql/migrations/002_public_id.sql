@@ -0,0 +1,13 @@
+-- 既存DB向けマイグレーション: 公開用IDの追加
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/002_public_id.sql
+--
+-- user_id は Cookie の値そのもの (認証情報) なので外部に出せない。
+-- 削除イベントの配信など、エントリを外部から識別するための公開用IDを追加する。
+
+BEGIN;
+
+ALTER TABLE calligraphy ADD COLUMN IF NOT EXISTS public_id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE calligraphy ADD CONSTRAINT calligraphy_public_id_key UNIQUE (public_id);
+
+COMMIT;
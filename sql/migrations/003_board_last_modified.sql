@@ -0,0 +1,26 @@
+-- 既存DB向けマイグレーション: 条件付きGET (Last-Modified) 用の最終削除日時
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/003_board_last_modified.sql
+
+BEGIN;
+
+-- ボード全体の状態 (1行のみ)
+-- 削除は max(updated_at) に現れないため、最終削除日時をトリガーで記録する (Last-Modified の算出に使用)
+CREATE TABLE IF NOT EXISTS calligraphy_board (
+	id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),		-- 1行に制限するためのキー
+	last_deleted_at TIMESTAMPTZ									-- 最終削除日時
+);
+INSERT INTO calligraphy_board (id) VALUES (TRUE) ON CONFLICT DO NOTHING;
+
+CREATE OR REPLACE FUNCTION calligraphy_touch_board() RETURNS trigger AS $$
+BEGIN
+	UPDATE calligraphy_board SET last_deleted_at = NOW();
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER calligraphy_after_delete
+	AFTER DELETE ON calligraphy
+	FOR EACH STATEMENT EXECUTE FUNCTION calligraphy_touch_board();
+
+COMMIT;
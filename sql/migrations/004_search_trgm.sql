@@ -0,0 +1,13 @@
+-- 既存DB向けマイグレーション: 全文検索 (pg_trgm) 用の拡張とインデックス
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/004_search_trgm.sql
+
+BEGIN;
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- 全文検索用のトライグラムインデックス (ILIKE '%...%' と類似度検索 % の両方で使用)
+CREATE INDEX IF NOT EXISTS calligraphy_user_name_trgm_idx ON calligraphy USING gin (user_name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS calligraphy_content_trgm_idx ON calligraphy USING gin (content gin_trgm_ops);
+
+COMMIT;
@@ -0,0 +1,15 @@
+-- 既存DB向けマイグレーション: 一覧の文字数での絞り込み用に、内容の文字数 (書記素クラスタ単位) を保持する
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/005_content_length.sql
+
+BEGIN;
+
+ALTER TABLE calligraphy ADD COLUMN IF NOT EXISTS content_length SMALLINT NOT NULL DEFAULT 0;
+
+-- DBは書記素クラスタを数えられないため、既存行はコードポイント数で近似する
+-- (絵文字などを含む行は、次に更新されたときにアプリが正しい値に置き換える)
+UPDATE calligraphy SET content_length = char_length(content);
+
+CREATE INDEX IF NOT EXISTS calligraphy_content_length_idx ON calligraphy (content_length);
+
+COMMIT;
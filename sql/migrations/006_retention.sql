@@ -0,0 +1,12 @@
+-- 既存DB向けマイグレーション: 収集したリクエスト情報 (IPアドレス等) の匿名化日時
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/006_retention.sql
+
+BEGIN;
+
+ALTER TABLE calligraphy ADD COLUMN IF NOT EXISTS anonymized_at TIMESTAMPTZ;
+
+-- 匿名化ジョブが対象の行を探すためのインデックス (未匿名化の行のみ)
+CREATE INDEX IF NOT EXISTS calligraphy_unanonymized_idx ON calligraphy (updated_at) WHERE anonymized_at IS NULL;
+
+COMMIT;
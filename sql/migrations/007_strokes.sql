@@ -0,0 +1,10 @@
+-- 既存DB向けマイグレーション: 手書きの筆跡データ
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/007_strokes.sql
+
+BEGIN;
+
+ALTER TABLE calligraphy ADD COLUMN IF NOT EXISTS strokes JSONB
+	CHECK (strokes IS NULL OR (jsonb_typeof(strokes) = 'object' AND pg_column_size(strokes) <= 1048576));
+
+COMMIT;
@@ -0,0 +1,10 @@
+-- 既存DB向けマイグレーション: 書き初めに添付する写真
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/008_photos.sql
+
+BEGIN;
+
+ALTER TABLE calligraphy ADD COLUMN IF NOT EXISTS photo JSONB
+	CHECK (photo IS NULL OR jsonb_typeof(photo) = 'object');
+
+COMMIT;
@@ -0,0 +1,15 @@
+-- 既存DB向けマイグレーション: 別のブラウザへ書き初めを引き継ぐための引き継ぎコード
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/009_transfer_code.sql
+
+BEGIN;
+
+-- 1ユーザーにつき1つ, 使用時に削除
+CREATE TABLE IF NOT EXISTS transfer_code (
+	user_id UUID PRIMARY KEY REFERENCES calligraphy (user_id) ON DELETE CASCADE ON UPDATE CASCADE,	-- 引き継ぎ元のユーザー
+	code_hash BYTEA NOT NULL UNIQUE,								-- 正規化したコードのSHA-256 (コードそのものは保存しない)
+	expires_at TIMESTAMPTZ NOT NULL,								-- 有効期限
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL			-- 発行日時
+);
+
+COMMIT;
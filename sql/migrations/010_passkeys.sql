@@ -0,0 +1,35 @@
+-- 既存DB向けマイグレーション: パスキー (WebAuthn) でのログイン
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/010_passkeys.sql
+
+BEGIN;
+
+-- パスキー (WebAuthn) の資格情報 (書き初めのユーザーに紐づく)
+CREATE TABLE IF NOT EXISTS passkey_credential (
+	credential_id BYTEA PRIMARY KEY,									-- 認証器が発行した資格情報ID
+	id UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),					-- 公開用ID (一覧・削除で使用)
+	user_id UUID NOT NULL REFERENCES calligraphy (user_id) ON DELETE CASCADE ON UPDATE CASCADE,	-- 持ち主
+	passkey JSONB NOT NULL,													-- 公開鍵・署名カウンタ等 (webauthn-rs の Passkey)
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,					-- 登録日時
+	last_used_at TIMESTAMPTZ													-- 最後にログインに使った日時
+);
+CREATE INDEX IF NOT EXISTS passkey_credential_user_id_idx ON passkey_credential (user_id);
+
+-- パスキーの登録・認証の途中の状態 (開始から完了までの間だけ保持し、完了時に削除する)
+CREATE TABLE IF NOT EXISTS passkey_ceremony (
+	id UUID PRIMARY KEY,
+	kind TEXT NOT NULL CHECK (kind IN ('registration', 'authentication')),	-- 登録か認証か
+	user_id UUID NOT NULL,														-- 登録するユーザー / 認証されるユーザー
+	state JSONB NOT NULL,														-- チャレンジ等 (webauthn-rs の状態)
+	expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- パスキーでログインしたブラウザのセッション (Cookie `calli_session`)
+CREATE TABLE IF NOT EXISTS login_session (
+	token_hash BYTEA PRIMARY KEY,												-- セッショントークンのSHA-256 (トークンそのものは保存しない)
+	user_id UUID NOT NULL,														-- ログインしたユーザー
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+	expires_at TIMESTAMPTZ NOT NULL
+);
+
+COMMIT;
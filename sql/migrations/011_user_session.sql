@@ -0,0 +1,31 @@
+-- 既存DB向けマイグレーション: サーバー側のセッション (Cookie `calli_session`)
+-- 新規構築時は setup.sql に反映済みのため不要
+-- docker exec -i puranemone_db psql -U <user> -d <db> < sql/migrations/011_user_session.sql
+--
+-- ユーザーIDのCookie (`calli_user_id`) は、次のアクセス時にアプリがセッションへ移行する。
+
+BEGIN;
+
+-- ブラウザごとのセッション (パスキーでのログインも含む)
+CREATE TABLE IF NOT EXISTS user_session (
+	id UUID PRIMARY KEY DEFAULT gen_random_uuid(),						-- 公開用ID (一覧・削除で使用)
+	token_hash BYTEA NOT NULL UNIQUE,										-- セッショントークンのSHA-256 (トークンそのものは保存しない)
+	user_id UUID NOT NULL,														-- セッションのユーザー (書き初めがなくてもよい)
+	device TEXT,																	-- ブラウザとOSの種類 (例: "Firefox / Linux")
+	created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+	last_seen_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,					-- 最後に使われた日時 (数分単位)
+	expires_at TIMESTAMPTZ NOT NULL											-- 有効期限 (使われるたびに延長)
+);
+CREATE INDEX IF NOT EXISTS user_session_user_id_idx ON user_session (user_id);
+CREATE INDEX IF NOT EXISTS user_session_expires_at_idx ON user_session (expires_at);	-- 期限切れのセッションの掃除用
+
+-- パスキーでログインしたセッションを引き継ぐ
+INSERT INTO user_session (token_hash, user_id, created_at, last_seen_at, expires_at)
+SELECT token_hash, user_id, created_at, created_at, expires_at
+FROM login_session
+WHERE expires_at > NOW()
+ON CONFLICT (token_hash) DO NOTHING;
+
+DROP TABLE IF EXISTS login_session;
+
+COMMIT;